@@ -1,6 +1,6 @@
 //! 测试转换：cargo run --example convert_test
 
-use bili2mp4::cache::{scan, VideoInfo};
+use bili2mp4::cache::scan;
 use bili2mp4::config::{load_config, resolve_mp4box_path};
 use bili2mp4::convert::{convert_one, ConvertError};
 use bili2mp4::filemgr::ConflictStrategy;
@@ -20,7 +20,7 @@ fn main() {
         return;
     }
 
-    let config = load_config();
+    let config = load_config().expect("读取配置失败");
     let mp4box = resolve_mp4box_path(&config);
     println!("MP4Box: {}", mp4box);
 
@@ -43,9 +43,7 @@ fn main() {
         &cancel,
     ) {
         Ok(path) => println!("\n✓ 完成: {}", path.display()),
-        Err(ConvertError::Mp4BoxNotFound) => {
-            println!("\n✗ MP4Box 未找到，请运行: brew install gpac");
-        }
+        Err(e @ ConvertError::ToolNotFound { .. }) => println!("\n✗ {}", e),
         Err(e) => println!("\n✗ 失败: {}", e),
     }
 }
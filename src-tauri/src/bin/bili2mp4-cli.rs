@@ -0,0 +1,1112 @@
+//! 命令行工具
+//! 运行: cargo run --bin bili2mp4-cli -- <命令> [参数]
+//!
+//! 命令:
+//!   doctor                              检查 MP4Box / ffmpeg 路径、版本与封装能力
+//!   convert <缓存目录>... [--out 目录] [--profile 方案] [--force] [--allow-incomplete] [--merge]
+//!           [--start 时间] [--end 时间] [--transcode h264|hevc|aac [--crf N] [--vbitrate K] [--abitrate K]]
+//!           [--container mp4|fmp4|mkv|mov] [--split-size 大小|fat32] [--split-duration 时间]
+//!                                       扫描并转换目录下全部视频（先检查磁盘空间，跳过重复与未下载完成的缓存），
+//!                                       --merge 把同一视频的多个分 P 合并为一个带章节的 MP4，
+//!                                       --start / --end 按关键帧无损截取，
+//!                                       --transcode 按预设用 ffmpeg 重新编码，--container 选择输出容器，
+//!                                       --split-size / --split-duration 把超出上限的输出在关键帧处无损分段
+//!   scan <缓存目录>... [--no-index] [--report 文件] [--sort 排序] [--limit 数量]
+//!                                       列出缓存视频、无法识别的缓存目录及相对上次扫描的变化
+//!   duplicates <缓存目录>...             列出重复缓存及按策略保留的一份
+//!   cleanup <缓存目录>... [--out 目录] [--profile 方案] [--dry-run]
+//!                                       把已转换且校验通过的源缓存移到回收站或隔离目录
+//!   catalog <缓存目录>... [--file 文件] [--format csv|jsonl|html] [--out 目录] [--profile 方案]
+//!                                       导出缓存清单（标题、UP 主、清晰度、时长、大小、转换状态等）
+//!   thumbnail <缓存目录或输出文件>... [--kind poster|frame|列x行]
+//!                                       生成缩略图或带时间标注的联系表，打印图片路径
+//!
+//!   convert、scan、duplicates、cleanup、catalog、thumbnail 均可用 --where <表达式> 筛选视频，
+//!   如 --where 'quality>=1080P and uploader~"某UP" and size<2GB'
+//!   profile list | export <方案> <文件> | import <文件>
+//!                                       管理转换方案
+//!   config show [--effective] | path | preview
+//!                                       查看配置、各项来源及将执行的转换命令
+//!
+//! 全局选项（放在命令之前）:
+//!   --config <文件>  --portable  以及每个配置项对应的 --<配置项>，如 --output-dir、--conflict-strategy
+
+use bili2mp4::cache::{
+    dropped_duplicates, find_duplicates, scan_checked, scan_indexed, write_diagnostics, DuplicatePolicy, ScanReport,
+    VideoInfo,
+};
+use bili2mp4::catalog::{build_catalog, export_catalog, write_catalog, CatalogFormat, ConvertStatus};
+use bili2mp4::config::{
+    cli_flag_name, env_var_name, export_profile, fields, get_config_path, import_profile, load_config, load_layered,
+    resolve_ffmpeg_path, save_config, set_config_path, set_portable, AppConfig, ConfigError,
+    DEFAULT_PROFILE,
+};
+use bili2mp4::convert::{
+    convert_transcode, convert_trimmed, convert_with_fallback, format_bytes, merge_parts, merged_video, parse_time,
+    plan_transcode, preflight, split_output, target_path, Attempt, Backend, Container, ConvertProgress, ConvertTools,
+    SpaceStatus, SplitLimit, TranscodeSettings, TrimRange, FAT32_MAX_BYTES,
+};
+use bili2mp4::filemgr::{
+    append_cleanup_log, cleanup_source, copy_sidecars, write_library_files, CleanupMode, OutputLayout,
+};
+use bili2mp4::query::{parse_size, select, Filter, SortKey};
+use bili2mp4::thumbnail::{default_thumbnail_dir, thumbnail, ThumbnailKind, ThumbnailSource};
+use bili2mp4::toolchain::{doctor, ToolInfo};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+
+const USAGE: &str = "用法: bili2mp4-cli <命令> [参数]
+
+命令:
+  doctor                              检查 MP4Box / ffmpeg 路径、版本与封装能力
+  convert <缓存目录>... [--out 目录] [--profile 方案] [--force] [--allow-incomplete] [--merge]
+          [--start 时间] [--end 时间] [--transcode h264|hevc|aac [--crf N] [--vbitrate K] [--abitrate K]]
+          [--container mp4|fmp4|mkv|mov] [--split-size 大小|fat32] [--split-duration 时间]
+                                      扫描并转换目录下全部视频，按 duplicate_policy
+                                      跳过重复缓存；磁盘空间不足时拒绝开始，
+                                      --force 强制开始；未下载完成的缓存默认跳过，
+                                      --allow-incomplete 仍然转换；--merge 把同一 BV 号
+                                      的多个分 P 无损合并为一个 MP4，每个分 P 一个章节；
+                                      --start / --end（如 1:02:03.5）只截取该时间段，
+                                      起止对齐到关键帧，文件名后附实际范围；
+                                      --transcode 用 ffmpeg 重新编码（h264 兼容、
+                                      hevc 小体积、aac 只转音频），--crf 质量、
+                                      --vbitrate / --abitrate 码率 (kbps)；未指定时
+                                      按方案的 transcode 设置决定是否转码；
+                                      --container 覆盖方案的输出容器（fmp4 为分片
+                                      MP4，mkv / mov 只能由 ffmpeg 写出）；
+                                      --split-size（如 2GB，fat32 为 4GB 上限）/
+                                      --split-duration 覆盖方案的分段上限，超出的
+                                      输出在关键帧处无损切成 标题-part1.mp4 等
+  scan <缓存目录>... [--no-index] [--report 文件] [--sort 排序] [--limit 数量]
+                                      列出缓存视频及无法识别的缓存目录 (✗) 与原因；
+                                      默认使用扫描索引，并显示相对上次扫描的新增 (+)、
+                                      移除 (-) 与变化 (~)，--no-index 完整重新解析且
+                                      不更新索引，--report 把无法识别的目录导出为 JSON；
+                                      --sort 如 \"size desc, title\"，--limit 只显示前 N 个
+  duplicates <缓存目录>...             列出重复缓存，✓ 为按策略保留的一份
+  cleanup <缓存目录>... [--out 目录] [--profile 方案] [--dry-run]
+                                      把已转换且校验通过的源缓存移到回收站或隔离
+                                      目录（按配置 cleanup），--dry-run 只列出
+  catalog <缓存目录>... [--file 文件] [--format csv|jsonl|html] [--out 目录] [--profile 方案]
+                                      导出缓存清单；格式默认按文件扩展名，未指定
+                                      --file 时以 CSV 写到标准输出；能确定输出目录
+                                      （--out 或方案中的 output_dir）时检查是否已转换
+  thumbnail <缓存目录或输出文件>... [--kind poster|frame|列x行]
+                                      生成缩略图并打印图片路径：poster（默认）优先
+                                      用缓存封面，frame 从视频截取关键帧，如 4x3 为
+                                      带时间标注的联系表；需要 ffmpeg，结果缓存在
+                                      配置目录的 thumbnails/
+  convert、scan、duplicates、cleanup、catalog、thumbnail 可加 --where <表达式> 只处理匹配的视频：
+    字段 title uploader bvid dir quality page pages cid size cached_at state
+    运算符 = != > >= < <= ~（包含）!~（不包含），用 and / or / not 与括号组合
+    例: --where 'quality>=1080P and uploader~\"某UP\" and cached_at>2025-01-01 and size<2GB'
+  profile list                        列出转换方案
+  profile export <方案> <文件>        导出方案到文件
+  profile import <文件>               从文件导入方案（同名替换）
+  config show                         显示配置文件内容
+  config show --effective             显示合并后的配置及每项来源
+  config path                         显示配置文件路径
+  config preview                      校验配置并显示各后端将执行的命令
+
+全局选项（放在命令之前）:
+  --config <文件>                     使用指定的配置文件
+  --portable                          便携模式：配置保存在程序同目录的 bili2mp4.json
+  --<配置项> <值>                     覆盖单个配置项，如 --output-dir、--conflict-strategy
+
+优先级: 命令行 > 环境变量 BILI2MP4_<配置项>（如 BILI2MP4_OUTPUT_DIR）> 配置文件 > 默认值";
+
+/// 命令行覆盖的配置项：(配置项, 值)
+type Overrides = Vec<(String, String)>;
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (overrides, args) = match take_global_options(args) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            std::process::exit(2);
+        }
+    };
+    let code = match args.first().map(String::as_str) {
+        Some("doctor") => cmd_doctor(&overrides),
+        Some("convert") => cmd_convert(&args[1..], &overrides),
+        Some("scan") => cmd_scan(&args[1..], &overrides),
+        Some("duplicates") => cmd_duplicates(&args[1..], &overrides),
+        Some("cleanup") => cmd_cleanup(&args[1..], &overrides),
+        Some("catalog") => cmd_catalog(&args[1..], &overrides),
+        Some("thumbnail") => cmd_thumbnail(&args[1..], &overrides),
+        Some("profile") => cmd_profile(&args[1..], &overrides),
+        Some("config") => cmd_config(&args[1..], &overrides),
+        Some("-h") | Some("--help") | None => {
+            println!("{}", USAGE);
+            0
+        }
+        Some(other) => {
+            eprintln!("未知命令: {}\n\n{}", other, USAGE);
+            2
+        }
+    };
+    std::process::exit(code);
+}
+
+/// 取出命令之前的全局选项并应用 --config / --portable，返回配置项覆盖与命令及其参数；
+/// 命令之后的参数原样交给子命令，不会被当作全局选项
+fn take_global_options(args: Vec<String>) -> Result<(Overrides, Vec<String>), String> {
+    let fields = fields();
+    let mut overrides = Vec::new();
+    let mut iter = args.into_iter();
+    while let Some(arg) = iter.next() {
+        let mut value = |name: &str| iter.next().ok_or_else(|| format!("{} 缺少参数值", name));
+        if arg == "--config" {
+            set_config_path(value(&arg)?.into());
+        } else if arg == "--portable" {
+            set_portable(true);
+        } else if let Some(field) = fields.iter().find(|f| cli_flag_name(f) == arg) {
+            overrides.push((field.clone(), value(&arg)?));
+        } else {
+            return Ok((overrides, std::iter::once(arg).chain(iter).collect()));
+        }
+    }
+    Ok((overrides, Vec::new()))
+}
+
+fn cmd_doctor(overrides: &Overrides) -> i32 {
+    let config = match load_layered(overrides) {
+        Ok(layered) => {
+            for warning in &layered.warnings {
+                eprintln!("警告: {}", warning);
+            }
+            layered.config
+        }
+        Err(e) => {
+            eprintln!("警告: {}，按默认设置检查\n", e);
+            AppConfig::default()
+        }
+    };
+    let infos = doctor(&config);
+    for info in &infos {
+        print_tool(info);
+    }
+    // MP4Box 与 ffmpeg 至少一个可用即可转换
+    if infos.iter().any(|i| i.runs) {
+        0
+    } else {
+        eprintln!("\n错误: 未找到可用的 MP4Box 或 ffmpeg");
+        1
+    }
+}
+
+fn print_tool(info: &ToolInfo) {
+    let mark = |v: Option<bool>| match v {
+        Some(true) => "✓",
+        Some(false) => "✗",
+        None => "?",
+    };
+    println!("{}", info.tool.label());
+    println!("  路径: {}", info.path);
+    if !info.runs {
+        println!("  状态: ✗ 无法运行 ({})", info.error.as_deref().unwrap_or("未知错误"));
+        return;
+    }
+    println!("  版本: {}", info.version.as_deref().unwrap_or("未知"));
+    let caps = &info.capabilities;
+    println!(
+        "  封装能力: HEVC {}  AV1 {}  FLAC {}",
+        mark(caps.hevc),
+        mark(caps.av1),
+        mark(caps.flac)
+    );
+}
+
+/// 解析后的参数：位置参数、`--name value` 形式的选项与不带值的开关
+struct ParsedArgs<'a> {
+    positional: Vec<&'a str>,
+    options: Vec<(&'a str, &'a str)>,
+    flags: Vec<&'a str>,
+}
+
+impl<'a> ParsedArgs<'a> {
+    /// `names` 为接受的选项名，`flags` 为接受的开关，其余以 `--` 开头的参数视为错误
+    fn parse(args: &'a [String], names: &[&'a str], flags: &[&'a str]) -> Result<Self, String> {
+        let mut parsed = Self { positional: Vec::new(), options: Vec::new(), flags: Vec::new() };
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            if let Some(name) = names.iter().find(|n| *n == arg) {
+                let value = iter.next().ok_or_else(|| format!("{} 缺少参数值", name))?;
+                parsed.options.push((*name, value.as_str()));
+            } else if let Some(flag) = flags.iter().find(|f| *f == arg) {
+                parsed.flags.push(flag);
+            } else if arg.starts_with("--") {
+                return Err(format!("未知选项: {}", arg));
+            } else {
+                parsed.positional.push(arg.as_str());
+            }
+        }
+        Ok(parsed)
+    }
+
+    /// 选项的值，重复出现时取最后一次
+    fn get(&self, name: &str) -> Option<&'a str> {
+        self.options.iter().rev().find(|(n, _)| *n == name).map(|(_, v)| *v)
+    }
+
+    fn has(&self, flag: &str) -> bool {
+        self.flags.contains(&flag)
+    }
+}
+
+/// `--where` 筛选表达式
+fn parse_where(args: &ParsedArgs) -> Result<Option<Filter>, String> {
+    args.get("--where").map(Filter::parse).transpose().map_err(|e| format!("--where: {}", e))
+}
+
+/// 依次增量扫描多个缓存目录，返回每个视频所在的扫描根目录与视频信息；指定 `filter` 时只保留匹配项
+fn scan_dirs(
+    dirs: &[&str],
+    max_depth: usize,
+    filter: Option<&Filter>,
+) -> Result<(Vec<PathBuf>, Vec<VideoInfo>), String> {
+    let mut roots = Vec::new();
+    let mut videos = Vec::new();
+    for dir in dirs {
+        let mut found = scan_indexed(Path::new(dir), max_depth).map_err(|e| e.to_string())?.videos;
+        if let Some(filter) = filter {
+            found.retain(|v| filter.matches(v));
+        }
+        roots.extend(std::iter::repeat_n(PathBuf::from(dir), found.len()));
+        videos.extend(found);
+    }
+    Ok((roots, videos))
+}
+
+/// 按策略去掉重复缓存并逐个打印
+fn skip_duplicates(
+    roots: Vec<PathBuf>,
+    videos: Vec<VideoInfo>,
+    policy: DuplicatePolicy,
+) -> (Vec<PathBuf>, Vec<VideoInfo>) {
+    let dropped = dropped_duplicates(&videos, policy);
+    for &i in &dropped {
+        let v = &videos[i];
+        println!("跳过重复缓存: {} [{}] {}", v.title, v.quality, v.cache_dir.display());
+    }
+    roots
+        .into_iter()
+        .zip(videos)
+        .enumerate()
+        .filter(|(i, _)| !dropped.contains(i))
+        .map(|(_, pair)| pair)
+        .unzip()
+}
+
+/// 去掉未下载完成的缓存并逐个打印；`allow` 时只提示、仍然转换
+fn skip_incomplete(roots: Vec<PathBuf>, videos: Vec<VideoInfo>, allow: bool) -> (Vec<PathBuf>, Vec<VideoInfo>) {
+    roots
+        .into_iter()
+        .zip(videos)
+        .filter(|(_, v)| match v.download.detail() {
+            Some(detail) if allow => {
+                eprintln!("警告: {} 未下载完成（{}），--allow-incomplete 仍然转换", v.title, detail);
+                true
+            }
+            Some(detail) => {
+                println!("跳过未下载完成的缓存: {}（{}）{}", v.title, detail, v.cache_dir.display());
+                false
+            }
+            None => true,
+        })
+        .unzip()
+}
+
+/// 列出缓存视频；使用索引时先打印与上次扫描的差异
+fn cmd_scan(args: &[String], overrides: &Overrides) -> i32 {
+    let args = match ParsedArgs::parse(args, &["--report", "--where", "--sort", "--limit"], &["--no-index"]) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            return 2;
+        }
+    };
+    if args.positional.is_empty() {
+        eprintln!("需要至少一个缓存目录\n\n{}", USAGE);
+        return 2;
+    }
+    let max_depth = match load_layered(overrides) {
+        Ok(l) => l.config.scan_max_depth,
+        Err(e) => {
+            eprintln!("错误: {}", e);
+            return 1;
+        }
+    };
+    let sort = SortKey::parse_list(args.get("--sort").unwrap_or_default()).map_err(|e| format!("--sort: {}", e));
+    let limit = args.get("--limit").map(|l| l.parse::<usize>().map_err(|_| format!("--limit 应为整数: {}", l)));
+    let (filter, sort, limit) = match (parse_where(&args), sort, limit.transpose()) {
+        (Ok(f), Ok(s), Ok(l)) => (f, s, l),
+        (Err(e), _, _) | (_, Err(e), _) | (_, _, Err(e)) => {
+            eprintln!("{}", e);
+            return 2;
+        }
+    };
+    let mut total = 0;
+    let mut all_rejected = Vec::new();
+    for dir in &args.positional {
+        let path = Path::new(dir);
+        let report = if args.has("--no-index") {
+            scan_checked(path, max_depth)
+                .map(|(videos, rejected)| ScanReport { videos, rejected, first_scan: true, ..Default::default() })
+        } else {
+            scan_indexed(path, max_depth)
+        };
+        let report = match report {
+            Ok(r) => r,
+            Err(e) => {
+                eprintln!("扫描失败: {}: {}", dir, e);
+                return 1;
+            }
+        };
+        println!("{}", dir);
+        let page = select(&report.videos, filter.as_ref(), &sort, 0, limit);
+        for v in &page.items {
+            let state = v.download.detail().map(|d| format!("  ⚠ 未下载完成: {}", d)).unwrap_or_default();
+            println!(
+                "  P{} {} [{}] {}  {}{}",
+                v.page,
+                v.title,
+                v.quality,
+                format_bytes(v.size_bytes),
+                v.cache_dir.display(),
+                state
+            );
+        }
+        if page.items.len() < page.total {
+            println!("  …… 另有 {} 个匹配项未显示", page.total - page.items.len());
+        }
+        if !report.first_scan {
+            let changes = [("+", &report.added), ("-", &report.removed), ("~", &report.changed)];
+            for (mark, list) in changes {
+                for v in list {
+                    println!("  {} {} [{}] {}", mark, v.title, v.quality, v.cache_dir.display());
+                }
+            }
+            println!(
+                "  新增 {}，移除 {}，变化 {}，未变化 {}",
+                report.added.len(),
+                report.removed.len(),
+                report.changed.len(),
+                report.reused
+            );
+        }
+        for r in &report.rejected {
+            println!("  ✗ [{}] {}  {}", r.reason.label(), r.cache_dir, r.detail);
+        }
+        total += page.total;
+        all_rejected.extend(report.rejected);
+    }
+    println!("\n共 {} 个视频，{} 个缓存目录无法识别", total, all_rejected.len());
+    if let Some(file) = args.get("--report") {
+        let roots: Vec<&Path> = args.positional.iter().map(Path::new).collect();
+        if let Err(e) = write_diagnostics(Path::new(file), &roots, total, &all_rejected) {
+            eprintln!("写入诊断报告失败: {}", e);
+            return 1;
+        }
+        println!("诊断报告已写入 {}", file);
+    }
+    0
+}
+
+/// 列出重复缓存，每组首行为按策略保留的一份
+fn cmd_duplicates(args: &[String], overrides: &Overrides) -> i32 {
+    let (args, filter) = match ParsedArgs::parse(args, &["--where"], &[]).and_then(|a| parse_where(&a).map(|f| (a, f))) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            return 2;
+        }
+    };
+    if args.positional.is_empty() {
+        eprintln!("{}", USAGE);
+        return 2;
+    }
+    let config = match load_layered(overrides) {
+        Ok(l) => l.config,
+        Err(e) => {
+            eprintln!("错误: {}", e);
+            return 1;
+        }
+    };
+    let videos = match scan_dirs(&args.positional, config.scan_max_depth, filter.as_ref()) {
+        Ok((_, v)) => v,
+        Err(e) => {
+            eprintln!("扫描失败: {}", e);
+            return 1;
+        }
+    };
+    let groups = find_duplicates(&videos, config.duplicate_policy);
+    for group in &groups {
+        println!("{}（{} 份）", group.key, group.members.len());
+        for &i in &group.members {
+            let v = &videos[i];
+            let mark = if group.keep.contains(&i) { "✓" } else { " " };
+            println!(
+                "  {} {}  {}  {}  {}",
+                mark,
+                v.quality,
+                v.cached_at.as_deref().unwrap_or("-"),
+                format_bytes(v.size_bytes),
+                v.cache_dir.display()
+            );
+        }
+    }
+    println!("\n共 {} 个视频，{} 组重复", videos.len(), groups.len());
+    0
+}
+
+fn cmd_convert(args: &[String], overrides: &Overrides) -> i32 {
+    let args = match ParsedArgs::parse(
+        args,
+        &[
+            "--out",
+            "--profile",
+            "--where",
+            "--start",
+            "--end",
+            "--transcode",
+            "--crf",
+            "--vbitrate",
+            "--abitrate",
+            "--container",
+            "--split-size",
+            "--split-duration",
+        ],
+        &["--force", "--allow-incomplete", "--merge"],
+    ) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            return 2;
+        }
+    };
+    if args.positional.is_empty() {
+        eprintln!("需要至少一个缓存目录\n\n{}", USAGE);
+        return 2;
+    }
+
+    let config = match load_layered(overrides) {
+        Ok(l) => l.config,
+        Err(e) => {
+            eprintln!("错误: {}", e);
+            return 1;
+        }
+    };
+    let mut profile = match config.profile(args.get("--profile")) {
+        Ok(p) => p,
+        Err(e) => {
+            eprintln!("错误: {}", e);
+            return 1;
+        }
+    };
+    if let Some(name) = args.get("--container") {
+        match parse_container(name) {
+            Ok(c) => profile.container = c,
+            Err(e) => {
+                eprintln!("错误: {}", e);
+                return 2;
+            }
+        }
+    }
+    if let Err(e) = parse_split(&args, &mut profile.split) {
+        eprintln!("错误: {}", e);
+        return 2;
+    }
+    let issues = profile.issues();
+    if !issues.is_empty() {
+        eprintln!("错误: {}", issues.join("；"));
+        return 2;
+    }
+    let Some(out_dir) = config.output_dir_for(&profile, args.get("--out")) else {
+        eprintln!("错误: 未指定输出目录（--out 或方案中的 output_dir）");
+        return 2;
+    };
+
+    let trim = match TrimRange::parse(args.get("--start"), args.get("--end")) {
+        Ok(t) => t,
+        Err(e) => {
+            eprintln!("错误: {}", e);
+            return 2;
+        }
+    };
+    if trim.is_some() && args.has("--merge") {
+        eprintln!("错误: --merge 不能与 --start / --end 同时使用");
+        return 2;
+    }
+    let transcode = match parse_transcode(&args) {
+        Ok(t) => t,
+        Err(e) => {
+            eprintln!("错误: {}", e);
+            return 2;
+        }
+    };
+    if transcode.is_some() && args.has("--merge") {
+        eprintln!("错误: --merge 只做无损合并，不能与 --transcode 同时使用");
+        return 2;
+    }
+    let filter = match parse_where(&args) {
+        Ok(f) => f,
+        Err(e) => {
+            eprintln!("{}", e);
+            return 2;
+        }
+    };
+    let (roots, videos) = match scan_dirs(&args.positional, config.scan_max_depth, filter.as_ref()) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("扫描失败: {}", e);
+            return 1;
+        }
+    };
+    let (roots, videos) = skip_duplicates(roots, videos, config.duplicate_policy);
+    let (roots, videos) = skip_incomplete(roots, videos, args.has("--allow-incomplete"));
+    println!(
+        "方案: {}，共 {} 个视频，输出 {} 到 {}",
+        profile.name,
+        videos.len(),
+        profile.container.label(),
+        out_dir
+    );
+    let check = preflight(&videos, Path::new(&out_dir), &profile);
+    match check.status {
+        SpaceStatus::Ok => println!("{}", check.message),
+        SpaceStatus::Low => eprintln!("警告: {}", check.message),
+        SpaceStatus::Insufficient if args.has("--force") => eprintln!("警告: {}（--force，仍然开始）", check.message),
+        SpaceStatus::Insufficient => {
+            eprintln!("错误: {}\n如确认空间足够，可加 --force 强制开始", check.message);
+            return 1;
+        }
+    }
+    if let Err(e) = std::fs::create_dir_all(&out_dir) {
+        eprintln!("错误: 无法创建输出目录: {}", e);
+        return 1;
+    }
+
+    let tools = ConvertTools::from_config(&config);
+    let cancel = AtomicBool::new(false);
+    let jobs = group_jobs(&videos, args.has("--merge"));
+    let mut failed = 0;
+    let mut split = 0;
+    for (i, job) in jobs.iter().enumerate() {
+        let video = &videos[job[0]];
+        let planned = (job.len() == 1).then(|| plan_transcode(video, &profile.transcode, transcode.as_ref())).flatten();
+        let transcoded = planned.is_some();
+        let result = if job.len() > 1 {
+            let parts: Vec<VideoInfo> = job.iter().map(|&j| videos[j].clone()).collect();
+            println!("[{}/{}] 合并 {} 个分 P: {}", i + 1, jobs.len(), parts.len(), video.title);
+            merge_parts(&parts, Path::new(&out_dir), &tools, &profile, |_| {}, print_attempt, &cancel)
+        } else if let Some((settings, reason)) = planned {
+            println!("[{}/{}] {}（转码 {}: {}）", i + 1, jobs.len(), video.title, settings.label(), reason);
+            convert_transcode(video, &settings, trim.as_ref(), Path::new(&out_dir), &tools, &profile, print_progress, &cancel)
+                .map(|(path, actual)| {
+                    match actual {
+                        Some(actual) => println!("  实际范围 {}", actual.label()),
+                        None if trim.is_some() => println!("  未读到关键帧，起止由 ffmpeg 定位"),
+                        None => {}
+                    }
+                    (path, Backend::Ffmpeg)
+                })
+        } else if let Some(range) = &trim {
+            println!("[{}/{}] {}（截取 {}）", i + 1, jobs.len(), video.title, range.label());
+            convert_trimmed(video, range, Path::new(&out_dir), &tools, &profile, |_| {}, print_attempt, &cancel).map(
+                |(path, backend, actual)| {
+                    match actual {
+                        Some(actual) => println!("  实际范围 {}（已对齐到关键帧）", actual.label()),
+                        None => println!("  未读到关键帧，起止由后端定位"),
+                    }
+                    (path, backend)
+                },
+            )
+        } else {
+            println!("[{}/{}] {}", i + 1, jobs.len(), video.title);
+            convert_with_fallback(video, Path::new(&out_dir), &tools, &profile, |_| {}, print_attempt, &cancel)
+        };
+        // 分段后原输出已删除，附属文件与媒体库文件按第一段命名
+        let result = result.and_then(|(path, backend)| {
+            let parts = if profile.split.is_set() {
+                split_output(&path, &profile.split, &tools, &profile, print_attempt, &cancel)?
+            } else {
+                vec![path]
+            };
+            Ok((parts, backend))
+        });
+        match result {
+            Ok((parts, backend)) => {
+                let path = &parts[0];
+                if parts.len() > 1 {
+                    println!("  ✓ {}: 已分为 {} 段", backend.label(), parts.len());
+                    for part in &parts {
+                        println!("    {}", part.display());
+                    }
+                    split += 1;
+                } else {
+                    println!("  ✓ {}: {}", backend.label(), path.display());
+                }
+                let merged;
+                let video = if job.len() > 1 {
+                    let parts: Vec<&VideoInfo> = job.iter().map(|&j| &videos[j]).collect();
+                    merged = merged_video(&parts);
+                    &merged
+                } else {
+                    video
+                };
+                if let Err(e) = copy_sidecars(video, path, &profile.sidecars) {
+                    println!("  附属文件复制失败: {}", e);
+                }
+                if profile.layout == OutputLayout::MediaServer {
+                    if let Err(e) = write_library_files(video, path) {
+                        println!("  媒体库文件写入失败: {}", e);
+                    }
+                }
+                if config.cleanup != CleanupMode::Off {
+                    if job.len() > 1 || trim.is_some() || transcoded || parts.len() > 1 {
+                        println!("  合并、截取、转码或分段的输出不自动清理源缓存");
+                    } else {
+                        clean_source(&config, video, path, &roots[job[0]], false);
+                    }
+                }
+            }
+            Err(e) => {
+                println!("  ✗ {}", e);
+                failed += 1;
+            }
+        }
+    }
+    if split > 0 {
+        println!("\n{} 个输出已分段", split);
+    }
+    if failed > 0 {
+        eprintln!("\n{} 个视频转换失败", failed);
+        1
+    } else {
+        0
+    }
+}
+
+/// `--container` 的取值，与配置文件中的写法相同
+fn parse_container(name: &str) -> Result<Container, String> {
+    serde_json::from_value(serde_json::Value::String(name.to_ascii_lowercase()))
+        .map_err(|_| format!("未知的容器: {}（可用: mp4、fmp4、mkv、mov）", name))
+}
+
+/// `--split-size`（大小或 fat32）与 `--split-duration`，覆盖方案中对应的分段上限
+fn parse_split(args: &ParsedArgs, limit: &mut SplitLimit) -> Result<(), String> {
+    if let Some(size) = args.get("--split-size") {
+        let bytes = if size.eq_ignore_ascii_case("fat32") { Some(FAT32_MAX_BYTES) } else { parse_size(size) };
+        limit.max_bytes = Some(bytes.ok_or_else(|| format!("无法识别的大小: {}（如 2GB、700MB、fat32）", size))?);
+    }
+    if let Some(duration) = args.get("--split-duration") {
+        let ms = parse_time(duration).ok_or_else(|| format!("无法识别的时间: {}", duration))?;
+        if ms % 1000 != 0 {
+            return Err(format!("分段时长须为整秒: {}", duration));
+        }
+        limit.max_seconds = Some(ms / 1000);
+    }
+    Ok(())
+}
+
+/// 转码进度在同一行刷新，完成时换行；ffmpeg 开始输出前不显示
+fn print_progress(progress: ConvertProgress) {
+    use std::io::Write;
+    if progress.percent == 0 {
+        return;
+    }
+    print!("\r  {:>3}%", progress.percent);
+    if progress.percent >= 100 {
+        println!();
+    }
+    let _ = std::io::stdout().flush();
+}
+
+/// `--transcode` 及 `--crf`、`--vbitrate`、`--abitrate`；后三者须与 `--transcode` 同时使用
+fn parse_transcode(args: &ParsedArgs) -> Result<Option<TranscodeSettings>, String> {
+    fn number<T: std::str::FromStr>(args: &ParsedArgs, name: &str) -> Result<Option<T>, String> {
+        args.get(name).map(|v| v.parse().map_err(|_| format!("{} 需要数字: {}", name, v))).transpose()
+    }
+    let Some(preset) = args.get("--transcode") else {
+        if ["--crf", "--vbitrate", "--abitrate"].iter().any(|name| args.get(name).is_some()) {
+            return Err("--crf、--vbitrate、--abitrate 需要与 --transcode 同时使用".to_string());
+        }
+        return Ok(None);
+    };
+    let settings = TranscodeSettings {
+        preset: preset.parse()?,
+        crf: number(args, "--crf")?,
+        video_kbps: number(args, "--vbitrate")?,
+        audio_kbps: number(args, "--abitrate")?,
+    };
+    settings.validate()?;
+    Ok(Some(settings))
+}
+
+fn print_attempt(attempt: Attempt) {
+    match attempt {
+        Attempt::Started(_) => {}
+        Attempt::Failed(b, e) => println!("  {} 失败: {}", b.label(), e),
+        Attempt::Skipped(b, reason) => println!("  跳过 {}: {}", b.label(), reason),
+    }
+}
+
+/// 把视频分为转换任务，每个任务为视频下标；合并时同一 BV 号的多个分 P 为一个任务，
+/// 任务按其在命令行中首次出现的顺序排列，任务内按分 P 排序
+fn group_jobs(videos: &[VideoInfo], merge: bool) -> Vec<Vec<usize>> {
+    let mut jobs: Vec<Vec<usize>> = Vec::new();
+    for (i, video) in videos.iter().enumerate() {
+        let group = video.bvid.as_ref().filter(|_| merge).and_then(|bvid| {
+            jobs.iter_mut().find(|job| videos[job[0]].bvid.as_ref() == Some(bvid))
+        });
+        match group {
+            Some(job) => job.push(i),
+            None => jobs.push(vec![i]),
+        }
+    }
+    // 合并输出的缓存目录、封面与简介取自 P1，不取决于命令行顺序
+    for job in &mut jobs {
+        job.sort_by_key(|&i| videos[i].page);
+    }
+    jobs
+}
+
+/// 清理单个视频的源缓存并打印结果，成功时返回 true
+fn clean_source(config: &AppConfig, video: &VideoInfo, output: &Path, root: &Path, dry_run: bool) -> bool {
+    let quarantine = config.quarantine_dir.as_deref().map(Path::new);
+    match cleanup_source(video, output, root, config.cleanup, quarantine, dry_run) {
+        Ok(record) => {
+            let action = match &record.destination {
+                Some(dest) => format!("移到 {}", dest),
+                None => "移到回收站".to_string(),
+            };
+            let prefix = if dry_run { "将" } else { "已" };
+            println!("  {}{}: {}（{} 字节）", prefix, action, record.cache_dir, record.bytes);
+            if !dry_run {
+                if let Err(e) = append_cleanup_log(&record) {
+                    println!("  清理记录写入失败: {}", e);
+                }
+            }
+            true
+        }
+        Err(e) => {
+            println!("  未清理: {}", e);
+            false
+        }
+    }
+}
+
+/// 清理已转换视频的源缓存；只处理 `<缓存目录>` 之内且输出校验通过的视频
+fn cmd_cleanup(args: &[String], overrides: &Overrides) -> i32 {
+    let args = match ParsedArgs::parse(args, &["--out", "--profile", "--where"], &["--dry-run"]) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            return 2;
+        }
+    };
+    if args.positional.is_empty() {
+        eprintln!("需要至少一个缓存目录\n\n{}", USAGE);
+        return 2;
+    }
+    let config = match load_layered(overrides) {
+        Ok(l) => l.config,
+        Err(e) => {
+            eprintln!("错误: {}", e);
+            return 1;
+        }
+    };
+    if config.cleanup == CleanupMode::Off {
+        eprintln!("错误: 未启用源文件清理，请设置 cleanup 为 trash 或 quarantine（如 --cleanup trash）");
+        return 2;
+    }
+    let profile = match config.profile(args.get("--profile")) {
+        Ok(p) => p,
+        Err(e) => {
+            eprintln!("错误: {}", e);
+            return 1;
+        }
+    };
+    let Some(out_dir) = config.output_dir_for(&profile, args.get("--out")) else {
+        eprintln!("错误: 未指定输出目录（--out 或方案中的 output_dir）");
+        return 2;
+    };
+    let filter = match parse_where(&args) {
+        Ok(f) => f,
+        Err(e) => {
+            eprintln!("{}", e);
+            return 2;
+        }
+    };
+    let (roots, videos) = match scan_dirs(&args.positional, config.scan_max_depth, filter.as_ref()) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("扫描失败: {}", e);
+            return 1;
+        }
+    };
+
+    let dry_run = args.has("--dry-run");
+    let mut cleaned = 0;
+    for (video, root) in videos.iter().zip(&roots) {
+        println!("{}", video.title);
+        match target_path(video, Path::new(&out_dir), &profile) {
+            Ok(output) => cleaned += clean_source(&config, video, &output, root, dry_run) as usize,
+            Err(e) => println!("  未清理: {}", e),
+        }
+    }
+    let verb = if dry_run { "可清理" } else { "已清理" };
+    println!("\n共 {} 个视频，{} {} 个", videos.len(), verb, cleaned);
+    0
+}
+
+/// 导出缓存清单；未指定 `--file` 时写到标准输出
+fn cmd_catalog(args: &[String], overrides: &Overrides) -> i32 {
+    let args = match ParsedArgs::parse(args, &["--file", "--format", "--out", "--profile", "--where"], &[]) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            return 2;
+        }
+    };
+    if args.positional.is_empty() {
+        eprintln!("需要至少一个缓存目录\n\n{}", USAGE);
+        return 2;
+    }
+    let file = args.get("--file").map(Path::new);
+    let format = match (args.get("--format"), file) {
+        (Some(f), _) => f.parse(),
+        (None, Some(path)) => CatalogFormat::from_path(path),
+        (None, None) => Ok(CatalogFormat::Csv),
+    };
+    let (format, filter) = match (format, parse_where(&args)) {
+        (Ok(format), Ok(filter)) => (format, filter),
+        (Err(e), _) => {
+            eprintln!("{}", e);
+            return 2;
+        }
+        (_, Err(e)) => {
+            eprintln!("{}", e);
+            return 2;
+        }
+    };
+    let config = match load_layered(overrides) {
+        Ok(l) => l.config,
+        Err(e) => {
+            eprintln!("错误: {}", e);
+            return 1;
+        }
+    };
+    let profile = match config.profile(args.get("--profile")) {
+        Ok(p) => p,
+        Err(e) => {
+            eprintln!("错误: {}", e);
+            return 1;
+        }
+    };
+    let out_dir = config.output_dir_for(&profile, args.get("--out")).map(PathBuf::from);
+    let videos = match scan_dirs(&args.positional, config.scan_max_depth, filter.as_ref()) {
+        Ok((_, v)) => v,
+        Err(e) => {
+            eprintln!("扫描失败: {}", e);
+            return 1;
+        }
+    };
+    let entries = build_catalog(&videos, out_dir.as_deref().map(|d| (d, &profile)));
+    let result = match file {
+        Some(path) => export_catalog(path, &entries, format),
+        None => write_catalog(&mut std::io::stdout().lock(), &entries, format),
+    };
+    if let Err(e) = result {
+        eprintln!("错误: {}", e);
+        return 1;
+    }
+    if let Some(path) = file {
+        let converted = entries.iter().filter(|e| e.status == ConvertStatus::Converted).count();
+        match out_dir {
+            Some(_) => println!("已导出 {} 个视频（已转换 {} 个）到 {}", entries.len(), converted, path.display()),
+            None => println!("已导出 {} 个视频到 {}（未指定输出目录，未检查转换状态）", entries.len(), path.display()),
+        }
+    }
+    0
+}
+
+fn cmd_thumbnail(args: &[String], overrides: &Overrides) -> i32 {
+    let args = match ParsedArgs::parse(args, &["--kind", "--where"], &[]) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            return 2;
+        }
+    };
+    if args.positional.is_empty() {
+        eprintln!("需要至少一个缓存目录或输出文件\n\n{}", USAGE);
+        return 2;
+    }
+    let kind = match args.get("--kind").map(ThumbnailKind::parse).transpose() {
+        Ok(k) => k.unwrap_or(ThumbnailKind::Poster),
+        Err(e) => {
+            eprintln!("错误: {}", e);
+            return 2;
+        }
+    };
+    let filter = match parse_where(&args) {
+        Ok(f) => f,
+        Err(e) => {
+            eprintln!("{}", e);
+            return 2;
+        }
+    };
+    let config = match load_layered(overrides) {
+        Ok(l) => l.config,
+        Err(e) => {
+            eprintln!("错误: {}", e);
+            return 1;
+        }
+    };
+    let Some(cache_dir) = default_thumbnail_dir() else {
+        eprintln!("错误: 无法确定缩略图缓存目录");
+        return 1;
+    };
+    // 文件按已转换的输出处理，目录按缓存目录扫描
+    let (files, dirs): (Vec<&str>, Vec<&str>) = args.positional.iter().partition(|p| Path::new(p).is_file());
+    let videos = if dirs.is_empty() {
+        Vec::new()
+    } else {
+        match scan_dirs(&dirs, config.scan_max_depth, filter.as_ref()) {
+            Ok((_, v)) => v,
+            Err(e) => {
+                eprintln!("扫描失败: {}", e);
+                return 1;
+            }
+        }
+    };
+    let ffmpeg = resolve_ffmpeg_path(&config);
+    let sources = files
+        .iter()
+        .map(|f| (f.to_string(), ThumbnailSource::Output(Path::new(f))))
+        .chain(videos.iter().map(|v| (v.title.clone(), ThumbnailSource::Cache(v))));
+    let mut failed = 0;
+    for (name, source) in sources {
+        match thumbnail(source, kind, &ffmpeg, &cache_dir) {
+            Ok(t) if t.from_cover => println!("{}: {}（封面）", name, t.path.display()),
+            Ok(t) => println!("{}: {}", name, t.path.display()),
+            Err(e) => {
+                eprintln!("{}: ✗ {}", name, e);
+                failed += 1;
+            }
+        }
+    }
+    if failed > 0 {
+        1
+    } else {
+        0
+    }
+}
+
+fn cmd_profile(args: &[String], overrides: &Overrides) -> i32 {
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    // 导入只改写配置文件本身，不把命令行与环境变量的覆盖一并保存
+    let loaded = match args[..] {
+        ["import", _] => load_config(),
+        _ => load_layered(overrides).map(|layered| {
+            for warning in &layered.warnings {
+                eprintln!("警告: {}", warning);
+            }
+            layered.config
+        }),
+    };
+    let mut config = match loaded {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("错误: {}", e);
+            return 1;
+        }
+    };
+    let result = match args[..] {
+        ["list"] => {
+            if !config.profiles.iter().any(|p| p.name == DEFAULT_PROFILE) {
+                println!("{}（由顶层配置生成）", DEFAULT_PROFILE);
+            }
+            for p in &config.profiles {
+                let backends: Vec<&str> = p.backends.iter().map(|b| b.label()).collect();
+                println!("{}  模板: {}  后端: {}", p.name, p.naming_template, backends.join(" → "));
+            }
+            Ok(())
+        }
+        ["export", name, file] => config
+            .profile(Some(name))
+            .and_then(|p| export_profile(&p, Path::new(file))),
+        ["import", file] => import_profile(Path::new(file)).and_then(|p| {
+            println!("已导入方案: {}", p.name);
+            config.upsert_profile(p);
+            save_config(&config)
+        }),
+        _ => {
+            eprintln!("{}", USAGE);
+            return 2;
+        }
+    };
+    match result {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("错误: {}", e);
+            1
+        }
+    }
+}
+
+fn cmd_config(args: &[String], overrides: &Overrides) -> i32 {
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let result = match args[..] {
+        ["path"] => {
+            match get_config_path() {
+                Some(p) => println!("{}", p.display()),
+                None => println!("（无法确定配置目录）"),
+            }
+            Ok(())
+        }
+        ["show"] => load_config().and_then(|c| {
+            println!("{}", serde_json::to_string_pretty(&c)?);
+            Ok(())
+        }),
+        ["show", "--effective"] => show_effective(overrides),
+        ["preview"] => load_layered(overrides).and_then(|l| {
+            l.config.validate()?;
+            let container = l.config.profile(None)?.container;
+            for p in ConvertTools::from_config(&l.config).preview_all(container) {
+                println!("{}:\n  {}", p.backend.label(), p.command);
+            }
+            Ok(())
+        }),
+        _ => {
+            eprintln!("{}", USAGE);
+            return 2;
+        }
+    };
+    match result {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("错误: {}", e);
+            1
+        }
+    }
+}
+
+/// 逐项打印合并后的取值与来源
+fn show_effective(overrides: &Overrides) -> Result<(), ConfigError> {
+    let layered = load_layered(overrides)?;
+    let values = serde_json::to_value(&layered.config)?;
+    for (field, source) in &layered.sources {
+        let value = values.get(field).map(|v| v.to_string()).unwrap_or_default();
+        println!("{} = {}", field, value);
+        println!("    来源: {}（可用 {} 或 {} 覆盖）", source, env_var_name(field), cli_flag_name(field));
+    }
+    for warning in &layered.warnings {
+        eprintln!("警告: {}", warning);
+    }
+    Ok(())
+}
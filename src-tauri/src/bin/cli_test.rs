@@ -44,7 +44,7 @@ fn main() {
         println!("  ...");
     }
 
-    let config = bili2mp4::config::load_config();
+    let config = bili2mp4::config::load_config().expect("读取配置失败");
     let mp4box = bili2mp4::config::resolve_mp4box_path(&config);
     println!("\n[2] MP4Box 路径: {}", mp4box);
 
@@ -52,7 +52,7 @@ fn main() {
     std::fs::create_dir_all(out_path).expect("创建输出目录失败");
 
     let cancel = std::sync::atomic::AtomicBool::new(false);
-    let strategy = config.conflict_strategy();
+    let strategy = config.conflict_strategy;
 
     println!("\n[3] 转换第一个视频...");
     let video = &videos[0];
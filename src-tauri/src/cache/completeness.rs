@@ -0,0 +1,189 @@
+//! 下载完整性检测：结合 entry.json 的下载进度字段、m4s 实际大小与分片结构，判断缓存是否已下载完成
+
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// 部分客户端在 m4s 开头写入的 9 字节 0x30 填充
+const PADDING: [u8; 9] = [0x30; 9];
+
+/// 分片 MP4 开头可能出现的顶层 box，首个 box 不在其中时不按 MP4 检查
+const LEADING_BOXES: [&[u8; 4]; 6] = [b"ftyp", b"styp", b"sidx", b"moov", b"free", b"skip"];
+
+/// 缓存的下载状态；未完成时附带原因
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "state", content = "detail", rename_all = "snake_case")]
+pub enum DownloadState {
+    #[default]
+    Complete,
+    /// 客户端记录为未下载完成
+    Downloading(String),
+    /// 客户端记录为已完成，但文件小于记录值或分片不完整
+    Truncated(String),
+}
+
+impl DownloadState {
+    pub fn is_complete(&self) -> bool {
+        matches!(self, Self::Complete)
+    }
+
+    /// 未完成的原因
+    pub fn detail(&self) -> Option<&str> {
+        match self {
+            Self::Complete => None,
+            Self::Downloading(d) | Self::Truncated(d) => Some(d),
+        }
+    }
+}
+
+/// entry.json 中的下载进度字段
+#[derive(Debug, Default, Deserialize)]
+pub(super) struct DownloadFields {
+    #[serde(default)]
+    is_completed: Option<bool>,
+    #[serde(default)]
+    downloaded_bytes: Option<u64>,
+    #[serde(default)]
+    total_bytes: Option<u64>,
+    #[serde(default)]
+    total_time_milli: Option<u64>,
+}
+
+impl DownloadFields {
+    /// entry.json 记录的时长（毫秒）
+    pub(super) fn duration_ms(&self) -> Option<u64> {
+        self.total_time_milli.filter(|&ms| ms > 0)
+    }
+}
+
+/// 判断下载状态：先看客户端记录的进度，再核对 m4s 总大小，最后检查分片是否被截断
+pub(super) fn check(fields: &DownloadFields, video: &Path, audio: &Path) -> DownloadState {
+    let size = |p: &Path| std::fs::metadata(p).map(|m| m.len()).unwrap_or(0);
+    let actual = size(video) + size(audio);
+    let total = fields.total_bytes.filter(|&t| t > 0);
+
+    if fields.is_completed == Some(false) {
+        let progress = match (fields.downloaded_bytes, total) {
+            (Some(done), Some(total)) => format!("（已下载 {} / {} 字节）", done, total),
+            _ => String::new(),
+        };
+        return DownloadState::Downloading(format!("客户端标记为未下载完成{}", progress));
+    }
+    if let (Some(done), Some(total)) = (fields.downloaded_bytes, total) {
+        if done < total {
+            return DownloadState::Downloading(format!("已下载 {} / {} 字节", done, total));
+        }
+    }
+    // 允许 1% 误差，避免客户端记录口径不同导致误报
+    if let Some(total) = total {
+        if actual + actual / 100 < total {
+            return DownloadState::Truncated(format!("m4s 共 {} 字节，小于记录的 {} 字节", actual, total));
+        }
+    }
+    for (label, path) in [("视频", video), ("音频", audio)] {
+        if let Some(problem) = truncated_box(path) {
+            let duration = fields
+                .duration_ms()
+                .map(|ms| format!("，应有时长 {}:{:02}", ms / 60_000, ms / 1000 % 60))
+                .unwrap_or_default();
+            return DownloadState::Truncated(format!("{}文件{}{}", label, problem, duration));
+        }
+    }
+    DownloadState::Complete
+}
+
+/// 逐个读取顶层 box 头，检查最后一个分片是否越过文件末尾；不是 MP4 结构或无法读取时不判断
+fn truncated_box(path: &Path) -> Option<String> {
+    let mut f = File::open(path).ok()?;
+    let len = f.metadata().ok()?.len();
+    let mut header = [0u8; 9];
+    let mut pos = match f.read_exact(&mut header) {
+        Ok(()) if header == PADDING => PADDING.len() as u64,
+        _ => 0,
+    };
+    let start = pos;
+    while pos < len {
+        if len - pos < 8 {
+            return (pos > start).then(|| format!("末尾有 {} 字节残缺数据", len - pos));
+        }
+        let mut head = [0u8; 8];
+        f.seek(SeekFrom::Start(pos)).ok()?;
+        f.read_exact(&mut head).ok()?;
+        let kind = &head[4..8];
+        if pos == start && !LEADING_BOXES.iter().any(|b| &b[..] == kind) {
+            // 不是 MP4 结构，交给转换后端处理
+            return None;
+        }
+        if !kind.iter().all(u8::is_ascii_graphic) {
+            return Some(format!("在 {} 字节处数据损坏", pos));
+        }
+        let size = match u32::from_be_bytes([head[0], head[1], head[2], head[3]]) {
+            0 => len - pos,
+            1 => {
+                let mut large = [0u8; 8];
+                f.read_exact(&mut large).ok()?;
+                u64::from_be_bytes(large)
+            }
+            n => n as u64,
+        };
+        if size < 8 {
+            return Some(format!("在 {} 字节处数据损坏", pos));
+        }
+        // 损坏的 largesize 可能接近 u64::MAX，用剩余长度比较以免溢出
+        let remaining = len - pos;
+        if size > remaining {
+            return Some(format!(
+                "被截断：{} 缺少 {} 字节",
+                String::from_utf8_lossy(kind),
+                size - remaining
+            ));
+        }
+        pos += size;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::convert::codec::tests::mp4_box;
+
+    #[test]
+    fn test_download_state() {
+        let tmp = std::env::temp_dir().join("bili2mp4_completeness_test");
+        std::fs::create_dir_all(&tmp).unwrap();
+        let video = tmp.join("video.m4s");
+        let audio = tmp.join("audio.m4s");
+        let fragments = [mp4_box(b"ftyp", &[0; 8]), mp4_box(b"moof", &[0; 16]), mp4_box(b"mdat", &[0; 100])].concat();
+        std::fs::write(&video, &fragments).unwrap();
+        std::fs::write(&audio, [&PADDING[..], &fragments].concat()).unwrap();
+        let total = std::fs::metadata(&video).unwrap().len() + std::fs::metadata(&audio).unwrap().len();
+
+        let fields = |is_completed, downloaded: u64| DownloadFields {
+            is_completed,
+            downloaded_bytes: Some(downloaded),
+            total_bytes: Some(total),
+            total_time_milli: Some(90_000),
+        };
+        assert_eq!(check(&fields(Some(true), total), &video, &audio), DownloadState::Complete);
+        assert_eq!(check(&DownloadFields::default(), &video, &audio), DownloadState::Complete);
+        assert!(matches!(check(&fields(Some(false), 10), &video, &audio), DownloadState::Downloading(_)));
+        assert!(matches!(check(&fields(None, 10), &video, &audio), DownloadState::Downloading(_)));
+
+        // 最后一个 mdat 只写了一半
+        std::fs::write(&video, &fragments[..fragments.len() - 50]).unwrap();
+        let state = check(&DownloadFields::default(), &video, &audio);
+        assert_eq!(state, DownloadState::Truncated("视频文件被截断：mdat 缺少 50 字节".into()));
+        assert!(matches!(check(&fields(Some(true), total), &video, &audio), DownloadState::Truncated(_)));
+
+        // largesize 接近 u64::MAX 时不溢出
+        let mut huge = mp4_box(b"ftyp", &[0; 8]);
+        huge.extend([0, 0, 0, 1, b'm', b'd', b'a', b't']);
+        huge.extend((u64::MAX - 4).to_be_bytes());
+        std::fs::write(&video, &huge).unwrap();
+        let state = check(&DownloadFields::default(), &video, &audio);
+        assert!(matches!(state, DownloadState::Truncated(ref d) if d.starts_with("视频文件被截断：mdat")), "{:?}", state);
+        std::fs::remove_dir_all(&tmp).ok();
+    }
+}
@@ -0,0 +1,112 @@
+//! 扫描结果之外的元数据（简介、发布日期、标签等），生成媒体库 NFO 时按需从缓存 JSON 读取
+
+use super::parser::VideoInfo;
+use super::scanner::META_FILES;
+use chrono::{DateTime, NaiveDate};
+use serde_json::Value;
+
+/// 缓存 JSON 中与媒体库相关的字段；各客户端字段不一，缺失的为空
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MediaDetails {
+    /// 整个视频的标题；多 P 视频中 [`VideoInfo::title`] 为分 P 标题
+    pub video_title: Option<String>,
+    pub description: Option<String>,
+    pub uploader: Option<String>,
+    /// 发布日期；没有时为空，不以缓存时间代替
+    pub published: Option<NaiveDate>,
+    pub tags: Vec<String>,
+    /// 分区名，如「知识」
+    pub genre: Option<String>,
+}
+
+/// 读取视频所在缓存目录的元数据文件；无法读取时只保留扫描结果中已有的字段
+pub fn read_details(video: &VideoInfo) -> MediaDetails {
+    let json = META_FILES
+        .iter()
+        .map(|name| video.cache_dir.join(name))
+        .find(|p| p.is_file())
+        .and_then(|p| std::fs::read_to_string(p).ok())
+        .and_then(|s| serde_json::from_str::<Value>(&s).ok())
+        .unwrap_or_default();
+    let text = |keys: &[&str]| {
+        keys.iter()
+            .filter_map(|k| json.get(*k)?.as_str())
+            .map(str::trim)
+            .find(|s| !s.is_empty())
+            .map(String::from)
+    };
+    MediaDetails {
+        video_title: text(&["title"]),
+        description: text(&["description", "desc", "intro"]),
+        uploader: video.uploader.clone().or_else(|| text(&["owner_name", "ownerName", "uname"])),
+        published: ["pubdate", "pub_date", "ctime"].iter().find_map(|k| json.get(*k).and_then(date)),
+        tags: tags(json.get("tags")),
+        genre: text(&["tname", "typename", "type_name"]),
+    }
+}
+
+/// 时间戳（秒或毫秒）或 `YYYY-MM-DD` 开头的字符串
+fn date(value: &Value) -> Option<NaiveDate> {
+    match value {
+        Value::Number(n) => {
+            let n = n.as_i64().filter(|&n| n > 0)?;
+            let secs = if n > 100_000_000_000 { n / 1000 } else { n };
+            DateTime::from_timestamp(secs, 0).map(|t| t.date_naive())
+        }
+        Value::String(s) => NaiveDate::parse_from_str(s.get(..10)?, "%Y-%m-%d").ok(),
+        _ => None,
+    }
+}
+
+/// 字符串数组、带 `tag_name` / `name` 的对象数组，或逗号分隔的字符串
+fn tags(value: Option<&Value>) -> Vec<String> {
+    let names: Vec<String> = match value {
+        Some(Value::Array(items)) => items
+            .iter()
+            .filter_map(|t| match t {
+                Value::String(s) => Some(s.clone()),
+                Value::Object(o) => o.get("tag_name").or(o.get("name"))?.as_str().map(String::from),
+                _ => None,
+            })
+            .collect(),
+        Some(Value::String(s)) => s.split([',', '，']).map(String::from).collect(),
+        _ => Vec::new(),
+    };
+    let mut tags: Vec<String> = Vec::new();
+    for name in names.iter().map(|s| s.trim()).filter(|s| !s.is_empty()) {
+        if !tags.iter().any(|t| t == name) {
+            tags.push(name.to_string());
+        }
+    }
+    tags
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_details() {
+        let tmp = std::env::temp_dir().join("bili2mp4_details_test");
+        std::fs::create_dir_all(&tmp).unwrap();
+        std::fs::write(
+            tmp.join("entry.json"),
+            r#"{"title":"合集标题","desc":" 简介 ","owner_name":"UP","pubdate":1700000000,
+                "tags":["教程",{"tag_name":"编程"},"教程"],"tname":"知识","page_data":{"part":"第一集"}}"#,
+        )
+        .unwrap();
+        let video = VideoInfo { total_pages: 2, ..VideoInfo::fixture(&tmp, "第一集") };
+        let details = read_details(&video);
+        assert_eq!(details.video_title.as_deref(), Some("合集标题"));
+        assert_eq!(details.description.as_deref(), Some("简介"));
+        assert_eq!(details.uploader.as_deref(), Some("UP"));
+        assert_eq!(details.published, NaiveDate::from_ymd_opt(2023, 11, 14));
+        assert_eq!(details.tags, ["教程", "编程"]);
+        assert_eq!(details.genre.as_deref(), Some("知识"));
+
+        assert_eq!(date(&Value::from(1_700_000_000_000i64)), NaiveDate::from_ymd_opt(2023, 11, 14));
+        assert_eq!(date(&Value::from("2024-05-06 12:00:00")), NaiveDate::from_ymd_opt(2024, 5, 6));
+        assert_eq!(read_details(&VideoInfo { cache_dir: tmp.join("missing"), ..video }), MediaDetails::default());
+        std::fs::remove_dir_all(&tmp).ok();
+    }
+}
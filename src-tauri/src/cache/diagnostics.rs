@@ -0,0 +1,150 @@
+//! 扫描诊断：记录无法识别的缓存目录及原因，供界面与 CLI 展示，并可导出用于问题反馈
+
+use super::parser::ParseError;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// 缓存目录被跳过的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RejectReason {
+    /// 元数据文件不是合法 JSON
+    JsonError,
+    MissingAudio,
+    /// 缺少视频文件（或音视频都缺）
+    MissingVideo,
+    /// 目录中有 m4s 但没有元数据，或元数据无法定位媒体文件
+    UnknownLayout,
+    /// 元数据或 m4s 为 0 字节，通常是下载中断
+    ZeroByteFile,
+    /// 文件无法读取（权限等）
+    Unreadable,
+}
+
+impl RejectReason {
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::JsonError => "JSON 错误",
+            Self::MissingAudio => "缺少音频",
+            Self::MissingVideo => "缺少视频",
+            Self::UnknownLayout => "无法识别的结构",
+            Self::ZeroByteFile => "空文件",
+            Self::Unreadable => "无法读取",
+        }
+    }
+}
+
+impl From<&ParseError> for RejectReason {
+    fn from(e: &ParseError) -> Self {
+        match e {
+            ParseError::Io(_) => Self::Unreadable,
+            ParseError::Json(_) => Self::JsonError,
+            ParseError::MissingMedia | ParseError::MissingVideo => Self::MissingVideo,
+            ParseError::MissingAudio => Self::MissingAudio,
+            ParseError::UnknownLayout => Self::UnknownLayout,
+            ParseError::EmptyFile(_) => Self::ZeroByteFile,
+        }
+    }
+}
+
+/// 一个被跳过的缓存目录
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Rejected {
+    pub cache_dir: String,
+    /// 解析失败的元数据文件；目录中没有元数据时为空
+    pub meta_file: Option<String>,
+    pub reason: RejectReason,
+    /// 具体错误信息
+    pub detail: String,
+}
+
+impl Rejected {
+    pub(super) fn from_error(meta: &Path, err: &ParseError) -> Self {
+        Self {
+            cache_dir: meta.parent().unwrap_or(meta).display().to_string(),
+            meta_file: Some(meta.display().to_string()),
+            reason: err.into(),
+            detail: err.to_string(),
+        }
+    }
+
+    pub(super) fn unknown_layout(dir: &Path) -> Self {
+        Self {
+            cache_dir: dir.display().to_string(),
+            meta_file: None,
+            reason: RejectReason::UnknownLayout,
+            detail: "目录中有 m4s 文件，但没有 entry.json 或 videoInfo.json".to_string(),
+        }
+    }
+}
+
+/// 导出的诊断报告
+#[derive(Debug, Serialize)]
+struct DiagnosticsFile<'a> {
+    app_version: &'static str,
+    os: &'static str,
+    generated_at: String,
+    roots: Vec<String>,
+    videos: usize,
+    rejected: &'a [Rejected],
+}
+
+/// 把扫描目录、识别出的视频数量与跳过项写成 JSON 诊断报告
+pub fn write_diagnostics(path: &Path, roots: &[&Path], videos: usize, rejected: &[Rejected]) -> std::io::Result<()> {
+    let file = DiagnosticsFile {
+        app_version: env!("CARGO_PKG_VERSION"),
+        os: std::env::consts::OS,
+        generated_at: chrono::Local::now().to_rfc3339(),
+        roots: roots.iter().map(|r| r.display().to_string()).collect(),
+        videos,
+        rejected,
+    };
+    std::fs::write(path, serde_json::to_vec_pretty(&file)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::scan_checked;
+
+    #[test]
+    fn test_reject_reasons() {
+        let tmp = std::env::temp_dir().join("bili2mp4_diagnostics_test");
+        std::fs::remove_dir_all(&tmp).ok();
+        let entry = r#"{"title":"t","page_data":{"page":1}}"#;
+        let cache = |name: &str, meta: &str, files: &[(&str, &[u8])]| {
+            let dir = tmp.join(name);
+            std::fs::create_dir_all(dir.join("80")).unwrap();
+            if !meta.is_empty() {
+                std::fs::write(dir.join("entry.json"), meta).unwrap();
+            }
+            for (file, data) in files {
+                std::fs::write(dir.join("80").join(file), data).unwrap();
+            }
+        };
+        cache("ok", entry, &[("video.m4s", b"v"), ("audio.m4s", b"a")]);
+        cache("json", "{title", &[("video.m4s", b"v"), ("audio.m4s", b"a")]);
+        cache("no_audio", entry, &[("video.m4s", b"v")]);
+        cache("no_video", entry, &[("audio.m4s", b"a")]);
+        cache("no_meta", "", &[("video.m4s", b"v"), ("audio.m4s", b"a")]);
+        cache("empty", entry, &[("video.m4s", b""), ("audio.m4s", b"a")]);
+
+        let (videos, rejected) = scan_checked(&tmp, 6).unwrap();
+        assert_eq!(videos.len(), 1);
+        let reasons: Vec<(String, RejectReason)> = rejected
+            .iter()
+            .map(|r| (Path::new(&r.cache_dir).file_name().unwrap().to_string_lossy().into_owned(), r.reason))
+            .collect();
+        assert_eq!(
+            reasons,
+            vec![
+                ("empty".into(), RejectReason::ZeroByteFile),
+                ("json".into(), RejectReason::JsonError),
+                ("no_audio".into(), RejectReason::MissingAudio),
+                ("no_meta".into(), RejectReason::UnknownLayout),
+                ("no_video".into(), RejectReason::MissingVideo),
+            ]
+        );
+        std::fs::remove_dir_all(&tmp).ok();
+    }
+}
@@ -0,0 +1,142 @@
+//! 重复缓存检测：同一 BV 号的同一分 P 以不同清晰度、或在多个缓存目录中各存一份
+
+use super::parser::VideoInfo;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// 重复缓存的保留策略
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DuplicatePolicy {
+    /// 保留清晰度最高的一份，清晰度相同时取较大的文件
+    #[default]
+    KeepHighest,
+    /// 保留缓存时间最新的一份
+    KeepNewest,
+    /// 全部保留
+    KeepAll,
+}
+
+/// 一组重复的缓存
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DuplicateGroup {
+    /// 分组键，如 `BV1xx411c7mD/p2` 或 `cid:123456`
+    pub key: String,
+    /// 组内视频在输入中的下标，按策略优先顺序排列
+    pub members: Vec<usize>,
+    /// 按策略保留的下标
+    pub keep: Vec<usize>,
+}
+
+/// 分组键：有 BV 号时用 BV 号 + 分 P，否则用 cid；两者都没有时不参与去重
+fn group_key(video: &VideoInfo) -> Option<String> {
+    match (&video.bvid, video.cid) {
+        (Some(bvid), _) if !bvid.is_empty() => Some(format!("{}/p{}", bvid, video.page)),
+        (_, Some(cid)) => Some(format!("cid:{}", cid)),
+        _ => None,
+    }
+}
+
+/// 清晰度排序值：取标签中的分辨率数字，`+` 与 `60` 帧略高；无法识别时为 0
+pub fn quality_rank(label: &str) -> u32 {
+    let digits: String = label.chars().take_while(|c| c.is_ascii_digit()).collect();
+    let height: u32 = digits.parse().unwrap_or(0);
+    let rest = &label[digits.len()..];
+    let bonus = if rest.contains('+') {
+        2
+    } else if rest.contains("60") {
+        1
+    } else {
+        0
+    };
+    height * 10 + bonus
+}
+
+/// 找出全部重复组（成员不少于两个），按首次出现的顺序返回
+pub fn find_duplicates(items: &[VideoInfo], policy: DuplicatePolicy) -> Vec<DuplicateGroup> {
+    let mut order = Vec::new();
+    let mut groups: HashMap<String, Vec<usize>> = HashMap::new();
+    for (i, video) in items.iter().enumerate() {
+        if let Some(key) = group_key(video) {
+            let members = groups.entry(key.clone()).or_default();
+            if members.is_empty() {
+                order.push(key);
+            }
+            members.push(i);
+        }
+    }
+    order
+        .into_iter()
+        .filter_map(|key| {
+            let mut members = groups.remove(&key)?;
+            if members.len() < 2 {
+                return None;
+            }
+            let highest = |i: &usize| (quality_rank(&items[*i].quality), items[*i].size_bytes);
+            match policy {
+                DuplicatePolicy::KeepNewest => {
+                    members.sort_by_key(|i| std::cmp::Reverse((items[*i].cached_at.clone(), highest(i))))
+                }
+                _ => members.sort_by_key(|i| std::cmp::Reverse(highest(i))),
+            }
+            let keep = match policy {
+                DuplicatePolicy::KeepAll => members.clone(),
+                _ => members[..1].to_vec(),
+            };
+            Some(DuplicateGroup { key, members, keep })
+        })
+        .collect()
+}
+
+/// 按策略应跳过的重复项下标（升序）
+pub fn dropped_duplicates(items: &[VideoInfo], policy: DuplicatePolicy) -> Vec<usize> {
+    let mut dropped: Vec<usize> = find_duplicates(items, policy)
+        .into_iter()
+        .flat_map(|g| g.members.into_iter().filter(move |i| !g.keep.contains(i)))
+        .collect();
+    dropped.sort_unstable();
+    dropped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn video(bvid: Option<&str>, cid: Option<u64>, quality: &str, cached_at: &str) -> VideoInfo {
+        VideoInfo {
+            quality: quality.into(),
+            size_bytes: 100,
+            cached_at: Some(cached_at.into()),
+            bvid: bvid.map(String::from),
+            cid,
+            ..VideoInfo::fixture(Path::new("/c"), "t")
+        }
+    }
+
+    #[test]
+    fn test_policies() {
+        let items = vec![
+            video(Some("BV1"), None, "720P", "2024-05-01"),
+            video(None, Some(9), "480P", "2024-01-01"),
+            video(Some("BV1"), None, "1080P", "2024-03-01"),
+            video(None, Some(9), "480P", "2024-02-01"),
+            video(Some("BV2"), None, "1080P", "2024-03-01"),
+            video(None, None, "1080P", "2024-03-01"),
+        ];
+        let groups = find_duplicates(&items, DuplicatePolicy::KeepHighest);
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0], DuplicateGroup { key: "BV1/p1".into(), members: vec![2, 0], keep: vec![2] });
+        assert_eq!(groups[1].key, "cid:9");
+
+        let newest = find_duplicates(&items, DuplicatePolicy::KeepNewest);
+        assert_eq!(newest[0].keep, vec![0]);
+        assert_eq!(newest[1].keep, vec![3]);
+
+        assert_eq!(dropped_duplicates(&items, DuplicatePolicy::KeepHighest), vec![0, 3]);
+        assert!(dropped_duplicates(&items, DuplicatePolicy::KeepAll).is_empty());
+        assert!(quality_rank("1080P+") > quality_rank("1080P60"));
+        assert!(quality_rank("1080P60") > quality_rank("1080P"));
+        assert!(quality_rank("720P") > quality_rank("未知"));
+    }
+}
@@ -0,0 +1,226 @@
+//! 持久化扫描索引：按元数据文件的路径、修改时间与大小判断缓存是否变化，未变化的目录不再解析
+//!
+//! 索引只是加速用的缓存，缺失、损坏或版本不符时按空索引处理
+
+use super::diagnostics::Rejected;
+use super::parser::VideoInfo;
+use super::scanner::{fill_total_pages, find_meta_files, parse_meta, ScanError};
+use crate::config::config_dir;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::UNIX_EPOCH;
+
+/// 索引文件名，位于配置文件同目录
+const INDEX_FILE: &str = "scan-index.json";
+const INDEX_VERSION: u32 = 3;
+
+/// 元数据文件的大小与修改时间
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+struct Fingerprint {
+    size: u64,
+    mtime_secs: u64,
+    mtime_nanos: u32,
+}
+
+impl Fingerprint {
+    fn of(path: &Path) -> Option<Self> {
+        let meta = std::fs::metadata(path).ok()?;
+        let mtime = meta.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+        Some(Self {
+            size: meta.len(),
+            mtime_secs: mtime.as_secs(),
+            mtime_nanos: mtime.subsec_nanos(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct IndexEntry {
+    fingerprint: Fingerprint,
+    video: VideoInfo,
+}
+
+impl IndexEntry {
+    /// 元数据文件未变，且音视频文件仍在、总大小与上次一致
+    fn is_fresh(&self, fingerprint: Fingerprint) -> bool {
+        let size = |p: &Path| std::fs::metadata(p).map(|m| m.len()).ok();
+        self.fingerprint == fingerprint
+            && matches!(
+                (size(&self.video.video_path), size(&self.video.audio_path)),
+                (Some(v), Some(a)) if v + a == self.video.size_bytes
+            )
+    }
+}
+
+/// 各扫描目录的索引：扫描目录 → 元数据文件路径 → 解析结果
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ScanIndex {
+    version: u32,
+    roots: BTreeMap<String, BTreeMap<String, IndexEntry>>,
+}
+
+impl Default for ScanIndex {
+    fn default() -> Self {
+        Self { version: INDEX_VERSION, roots: BTreeMap::new() }
+    }
+}
+
+impl ScanIndex {
+    /// 默认索引位置
+    pub fn default_path() -> Option<PathBuf> {
+        config_dir().map(|d| d.join(INDEX_FILE))
+    }
+
+    /// 读取索引；文件缺失、损坏或版本不符时返回空索引
+    pub fn load(path: &Path) -> Self {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|s| serde_json::from_str::<Self>(&s).ok())
+            .filter(|index| index.version == INDEX_VERSION)
+            .unwrap_or_default()
+    }
+
+    /// 先写临时文件再替换，避免中断时留下半个索引
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_vec(self)?)?;
+        std::fs::rename(&tmp, path)
+    }
+}
+
+/// 增量扫描结果
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ScanReport {
+    /// 当前全部视频，按缓存目录排序
+    pub videos: Vec<VideoInfo>,
+    /// 上次扫描后新增的视频
+    pub added: Vec<VideoInfo>,
+    /// 上次扫描后消失（或无法再解析）的视频
+    pub removed: Vec<VideoInfo>,
+    /// 元数据或音视频文件有变化、重新解析的视频
+    pub changed: Vec<VideoInfo>,
+    /// 无法识别而跳过的缓存目录，按目录排序
+    pub rejected: Vec<Rejected>,
+    /// 未变化、直接复用索引的数量
+    pub reused: usize,
+    /// 索引中没有该目录的记录，此时全部视频都计为新增
+    pub first_scan: bool,
+}
+
+/// 使用默认位置的索引增量扫描；进程内串行读写索引，保存失败不影响扫描结果
+pub fn scan_indexed(dir: &Path, max_depth: usize) -> Result<ScanReport, ScanError> {
+    static LOCK: Mutex<()> = Mutex::new(());
+    let _guard = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let path = ScanIndex::default_path();
+    let mut index = path.as_deref().map(ScanIndex::load).unwrap_or_default();
+    let report = scan_incremental(dir, max_depth, &mut index)?;
+    if let Some(path) = path {
+        let _ = index.save(&path);
+    }
+    Ok(report)
+}
+
+/// 扫描 `dir` 并与索引中上次的结果比较，同时用本次结果更新索引
+pub fn scan_incremental(dir: &Path, max_depth: usize, index: &mut ScanIndex) -> Result<ScanReport, ScanError> {
+    let found = find_meta_files(dir, max_depth)?;
+    let root = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf()).display().to_string();
+    let previous = index.roots.remove(&root);
+    let first_scan = previous.is_none();
+    let mut previous = previous.unwrap_or_default();
+
+    // (元数据路径, 条目, 是否复用)；解析失败的不进入索引，下次重新解析
+    let results: Vec<Result<(String, IndexEntry, bool), Rejected>> = found
+        .metas
+        .par_iter()
+        .filter_map(|meta| {
+            let key = meta.display().to_string();
+            let fingerprint = Fingerprint::of(meta)?;
+            if let Some(old) = previous.get(&key).filter(|e| e.is_fresh(fingerprint)) {
+                return Some(Ok((key, old.clone(), true)));
+            }
+            Some(match parse_meta(meta) {
+                Ok(video) => Ok((key, IndexEntry { fingerprint, video }, false)),
+                Err(e) => Err(Rejected::from_error(meta, &e)),
+            })
+        })
+        .collect();
+
+    let mut report = ScanReport { first_scan, rejected: found.rejected(), ..Default::default() };
+    let mut current = BTreeMap::new();
+    for result in results {
+        let (key, entry, reused) = match result {
+            Ok(r) => r,
+            Err(rejected) => {
+                report.rejected.push(rejected);
+                continue;
+            }
+        };
+        let existed = previous.remove(&key).is_some();
+        if reused {
+            report.reused += 1;
+        } else if existed {
+            report.changed.push(entry.video.clone());
+        } else {
+            report.added.push(entry.video.clone());
+        }
+        report.videos.push(entry.video.clone());
+        current.insert(key, entry);
+    }
+    report.removed = previous.into_values().map(|e| e.video).collect();
+    report.videos.sort_by(|a, b| a.cache_dir.cmp(&b.cache_dir));
+    fill_total_pages(&mut report.videos);
+    report.rejected.sort_by(|a, b| a.cache_dir.cmp(&b.cache_dir));
+    index.roots.insert(root, current);
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_cache(dir: &Path, title: &str) {
+        std::fs::create_dir_all(dir.join("80")).unwrap();
+        std::fs::write(dir.join("80/video.m4s"), b"video").unwrap();
+        std::fs::write(dir.join("80/audio.m4s"), b"audio").unwrap();
+        let entry = format!(r#"{{"title":"{}","page_data":{{"page":1}}}}"#, title);
+        std::fs::write(dir.join("entry.json"), entry).unwrap();
+    }
+
+    #[test]
+    fn test_incremental_diff_and_persistence() {
+        let tmp = std::env::temp_dir().join("bili2mp4_index_test");
+        std::fs::remove_dir_all(&tmp).ok();
+        let root = tmp.join("download");
+        write_cache(&root.join("a"), "A");
+        write_cache(&root.join("b"), "B");
+        let index_path = tmp.join("scan-index.json");
+
+        let mut index = ScanIndex::load(&index_path);
+        let first = scan_incremental(&root, 6, &mut index).unwrap();
+        assert!(first.first_scan);
+        assert_eq!(first.added.len(), 2);
+        index.save(&index_path).unwrap();
+
+        let mut index = ScanIndex::load(&index_path);
+        let same = scan_incremental(&root, 6, &mut index).unwrap();
+        assert!(!same.first_scan);
+        assert_eq!((same.reused, same.added.len(), same.changed.len()), (2, 0, 0));
+
+        std::fs::remove_dir_all(root.join("a")).unwrap();
+        write_cache(&root.join("c"), "C");
+        std::fs::write(root.join("b/80/audio.m4s"), b"longer audio").unwrap();
+        let diff = scan_incremental(&root, 6, &mut index).unwrap();
+        assert_eq!(diff.added[0].title, "C");
+        assert_eq!(diff.removed[0].title, "A");
+        assert_eq!(diff.changed[0].title, "B");
+        assert_eq!(diff.reused, 0);
+        assert_eq!(diff.videos.len(), 2);
+        std::fs::remove_dir_all(&tmp).ok();
+    }
+}
@@ -1,5 +1,15 @@
+mod completeness;
+mod details;
+mod diagnostics;
+mod duplicates;
+mod index;
 mod parser;
 pub mod scanner;
 
+pub use completeness::DownloadState;
+pub use details::{read_details, MediaDetails};
+pub use diagnostics::{write_diagnostics, RejectReason, Rejected};
+pub use duplicates::{dropped_duplicates, find_duplicates, quality_rank, DuplicateGroup, DuplicatePolicy};
+pub use index::{scan_incremental, scan_indexed, ScanIndex, ScanReport};
 pub use parser::VideoInfo;
-pub use scanner::scan;
+pub use scanner::{scan, scan_checked, scan_with_depth, DEFAULT_MAX_DEPTH, META_FILES};
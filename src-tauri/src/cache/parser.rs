@@ -1,6 +1,7 @@
 //! 解析 B 站缓存 entry.json
 //! 支持多种 entry.json 结构变体
 
+use super::completeness::{self, DownloadFields, DownloadState};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
@@ -23,15 +24,30 @@ pub struct VideoInfo {
     pub size_bytes: u64,
     /// 缓存时间
     pub cached_at: Option<String>,
+    /// 时长（毫秒），元数据中没有时为空
+    #[serde(default)]
+    pub duration_ms: Option<u64>,
+    /// BV 号
+    #[serde(default)]
+    pub bvid: Option<String>,
+    /// 分 P 的 cid，同一视频不同清晰度的缓存相同
+    #[serde(default)]
+    pub cid: Option<u64>,
+    /// UP 主名称
+    #[serde(default)]
+    pub uploader: Option<String>,
     /// video.m4s 绝对路径
     #[serde(serialize_with = "path_to_string", deserialize_with = "string_to_path")]
     pub video_path: PathBuf,
     /// audio.m4s 绝对路径
     #[serde(serialize_with = "path_to_string", deserialize_with = "string_to_path")]
     pub audio_path: PathBuf,
+    /// 下载状态，未完成的缓存默认不转换
+    #[serde(default)]
+    pub download: DownloadState,
 }
 
-fn path_to_string<S>(path: &PathBuf, s: S) -> Result<S::Ok, S::Error>
+fn path_to_string<S>(path: &Path, s: S) -> Result<S::Ok, S::Error>
 where
     S: serde::Serializer,
 {
@@ -46,9 +62,32 @@ where
     Ok(PathBuf::from(st))
 }
 
+#[cfg(test)]
+impl VideoInfo {
+    /// 测试用视频：单 P、1080P，m4s 位于 `cache_dir` 下，其余字段为空，按需用结构体更新语法覆盖
+    pub(crate) fn fixture(cache_dir: &Path, title: &str) -> Self {
+        VideoInfo {
+            cache_dir: cache_dir.to_path_buf(),
+            title: title.to_string(),
+            quality: "1080P".to_string(),
+            page: 1,
+            total_pages: 1,
+            size_bytes: 0,
+            cached_at: None,
+            duration_ms: None,
+            bvid: None,
+            cid: None,
+            uploader: None,
+            video_path: cache_dir.join("video.m4s"),
+            audio_path: cache_dir.join("audio.m4s"),
+            download: Default::default(),
+        }
+    }
+}
+
 /// 解析 videoInfo.json（新版 B 站 macOS 客户端）
 pub fn parse_video_info(info_path: &Path) -> Result<VideoInfo, ParseError> {
-    let content = std::fs::read_to_string(info_path)?;
+    let content = read_meta(info_path)?;
     let info: VideoInfoJson = serde_json::from_str(&content)?;
 
     let cache_dir = info_path
@@ -57,6 +96,7 @@ pub fn parse_video_info(info_path: &Path) -> Result<VideoInfo, ParseError> {
         .to_path_buf();
 
     let (video_path, audio_path) = find_m4s_files_by_id(&cache_dir, info.item_id)?;
+    ensure_not_empty([&video_path, &audio_path])?;
 
     let title = info
         .tab_name
@@ -87,6 +127,11 @@ pub fn parse_video_info(info_path: &Path) -> Result<VideoInfo, ParseError> {
         total_pages,
         size_bytes,
         cached_at,
+        duration_ms: info.duration.filter(|&d| d > 0).map(|d| d * 1000),
+        download: completeness::check(&DownloadFields::default(), &video_path, &audio_path),
+        bvid: info.bvid,
+        cid: info.cid,
+        uploader: info.owner_name,
         video_path,
         audio_path,
     })
@@ -104,6 +149,15 @@ struct VideoInfoJson {
     p: Option<u32>,
     #[serde(default)]
     qn: Option<u32>,
+    #[serde(default)]
+    bvid: Option<String>,
+    #[serde(default)]
+    cid: Option<u64>,
+    #[serde(default, alias = "ownerName", alias = "uname")]
+    owner_name: Option<String>,
+    /// 时长（秒）
+    #[serde(default)]
+    duration: Option<u64>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -117,18 +171,23 @@ struct EntryJson {
     #[serde(default)]
     quality: Option<u32>,
     #[serde(default)]
-    #[allow(dead_code)]
     bvid: Option<String>,
     #[serde(default)]
+    owner_name: Option<String>,
+    #[serde(default)]
     #[allow(dead_code)]
     avid: Option<i64>,
     #[serde(default)]
     #[allow(dead_code)]
     tid: Option<u32>,
+    #[serde(flatten)]
+    download: DownloadFields,
 }
 
 #[derive(Debug, Deserialize)]
 struct PageData {
+    #[serde(default)]
+    cid: Option<u64>,
     #[serde(default)]
     page: Option<u32>,
     #[serde(default)]
@@ -139,17 +198,44 @@ struct PageData {
 
 #[derive(Debug, Error)]
 pub enum ParseError {
-    #[error("无法读取 entry.json: {0}")]
+    #[error("无法读取文件: {0}")]
     Io(#[from] std::io::Error),
     #[error("JSON 解析失败: {0}")]
     Json(#[from] serde_json::Error),
-    #[error("缺少视频或音频文件")]
+    #[error("缺少视频与音频文件")]
     MissingMedia,
+    #[error("缺少视频文件")]
+    MissingVideo,
+    #[error("缺少音频文件")]
+    MissingAudio,
+    #[error("无法识别的缓存结构")]
+    UnknownLayout,
+    #[error("文件为空: {0}")]
+    EmptyFile(PathBuf),
+}
+
+/// 读取元数据文件；空文件单独报告，而不是作为 JSON 错误
+fn read_meta(path: &Path) -> Result<String, ParseError> {
+    let content = std::fs::read_to_string(path)?;
+    if content.trim().is_empty() {
+        return Err(ParseError::EmptyFile(path.to_path_buf()));
+    }
+    Ok(content)
+}
+
+/// 下载中断时常留下 0 字节的 m4s
+fn ensure_not_empty(paths: [&Path; 2]) -> Result<(), ParseError> {
+    for p in paths {
+        if std::fs::metadata(p)?.len() == 0 {
+            return Err(ParseError::EmptyFile(p.to_path_buf()));
+        }
+    }
+    Ok(())
 }
 
 /// 解析 entry.json，结合目录内 m4s 文件返回 VideoInfo
 pub fn parse_entry(entry_path: &Path) -> Result<VideoInfo, ParseError> {
-    let content = std::fs::read_to_string(entry_path)?;
+    let content = read_meta(entry_path)?;
     let entry: EntryJson = serde_json::from_str(&content)?;
 
     let cache_dir = entry_path
@@ -158,6 +244,7 @@ pub fn parse_entry(entry_path: &Path) -> Result<VideoInfo, ParseError> {
         .to_path_buf();
 
     let (video_path, audio_path) = find_m4s_files(&cache_dir)?;
+    ensure_not_empty([&video_path, &audio_path])?;
 
     let title = entry
         .page_data
@@ -193,6 +280,11 @@ pub fn parse_entry(entry_path: &Path) -> Result<VideoInfo, ParseError> {
         total_pages,
         size_bytes,
         cached_at,
+        duration_ms: entry.download.duration_ms(),
+        download: completeness::check(&entry.download, &video_path, &audio_path),
+        bvid: entry.bvid,
+        cid: entry.page_data.as_ref().and_then(|p| p.cid),
+        uploader: entry.owner_name,
         video_path,
         audio_path,
     })
@@ -206,7 +298,7 @@ fn find_m4s_files_by_id(cache_dir: &Path, item_id: Option<u64>) -> Result<(PathB
             .file_name()
             .and_then(|n| n.to_str())
             .and_then(|s| s.parse().ok())
-    }).ok_or(ParseError::MissingMedia)?;
+    }).ok_or(ParseError::UnknownLayout)?;
 
     let prefix = format!("{}-1-", item_id);
     let mut m4s_files: Vec<_> = std::fs::read_dir(cache_dir)?
@@ -234,10 +326,14 @@ fn find_m4s_files_by_id(cache_dir: &Path, item_id: Option<u64>) -> Result<(PathB
             .and_then(|s| s.parse::<u64>().ok())
             .unwrap_or(0)
     };
-    m4s_files.sort_by(|a, b| extract_code(b).cmp(&extract_code(a)));
-
-    if m4s_files.len() < 2 {
-        return Err(ParseError::MissingMedia);
+    m4s_files.sort_by_key(|e| std::cmp::Reverse(extract_code(e)));
+
+    match m4s_files.len() {
+        0 => return Err(ParseError::MissingMedia),
+        // 音频流编号为 302xx（如 30280、杜比 30250、Hi-Res 30251）
+        1 if (30200..30300).contains(&extract_code(&m4s_files[0])) => return Err(ParseError::MissingVideo),
+        1 => return Err(ParseError::MissingAudio),
+        _ => {}
     }
 
     // 较大的 codec 为视频，较小的为音频
@@ -253,6 +349,8 @@ fn find_m4s_files_by_id(cache_dir: &Path, item_id: Option<u64>) -> Result<(PathB
 fn find_m4s_files(cache_dir: &Path) -> Result<(PathBuf, PathBuf), ParseError> {
     let mut video_path = None;
     let mut audio_path = None;
+    // 只有其中一个文件的目录，用于区分缺少视频还是音频
+    let mut partial = None;
 
     let subdirs = ["64", "80", "32", "16", "112", "116", "120", ""];
     for sub in subdirs {
@@ -266,15 +364,20 @@ fn find_m4s_files(cache_dir: &Path) -> Result<(PathBuf, PathBuf), ParseError> {
         }
         let v = base.join("video.m4s");
         let a = base.join("audio.m4s");
-        if v.exists() && a.exists() {
-            video_path = Some(v);
-            audio_path = Some(a);
-            break;
+        match (v.exists(), a.exists()) {
+            (true, true) => {
+                video_path = Some(v);
+                audio_path = Some(a);
+                break;
+            }
+            (true, false) => partial = partial.or(Some(ParseError::MissingAudio)),
+            (false, true) => partial = partial.or(Some(ParseError::MissingVideo)),
+            (false, false) => {}
         }
     }
 
     if video_path.is_none() || audio_path.is_none() {
-        return Err(ParseError::MissingMedia);
+        return Err(partial.unwrap_or(ParseError::MissingMedia));
     }
 
     Ok((video_path.unwrap(), audio_path.unwrap()))
@@ -339,13 +442,20 @@ mod tests {
         fs::create_dir_all(&sub).ok();
         fs::write(sub.join("video.m4s"), b"x").ok();
         fs::write(sub.join("audio.m4s"), b"x").ok();
-        let entry = r#"{"title":"测试","page_data":{"page":1,"part":"Part1"},"type_tag":"s_1080p"}"#;
+        let entry = r#"{"title":"测试","page_data":{"cid":123456,"page":1,"part":"Part1"},"type_tag":"s_1080p"}"#;
         fs::write(tmp.join("entry.json"), entry).ok();
         let r = parse_entry(&tmp.join("entry.json"));
         assert!(r.is_ok(), "{:?}", r.err());
         let v = r.unwrap();
         assert_eq!(v.title, "Part1");
         assert_eq!(v.quality, "1080P");
+        assert_eq!(v.cid, Some(123456));
+        assert!(v.download.is_complete());
+
+        let downloading = r#"{"title":"测试","is_completed":false,"downloaded_bytes":1,"total_bytes":2}"#;
+        fs::write(tmp.join("entry.json"), downloading).ok();
+        let v = parse_entry(&tmp.join("entry.json")).unwrap();
+        assert!(matches!(v.download, DownloadState::Downloading(_)));
         fs::remove_dir_all(&tmp).ok();
     }
 }
@@ -1,9 +1,11 @@
 //! 扫描 B 站缓存目录，定位 entry.json 并解析
 
-use super::parser::{parse_entry, parse_video_info, VideoInfo};
-use std::path::PathBuf;
+use super::diagnostics::Rejected;
+use super::parser::{parse_entry, parse_video_info, ParseError, VideoInfo};
+use rayon::prelude::*;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use thiserror::Error;
-use walkdir::WalkDir;
 
 #[derive(Debug, Error)]
 pub enum ScanError {
@@ -13,7 +15,9 @@ pub enum ScanError {
     Parse(#[from] super::parser::ParseError),
 }
 
-/// 默认 B 站缓存路径（macOS / Windows）
+/// 默认 B 站缓存路径（macOS / Windows / Linux）
+///
+/// Linux 无官方客户端，查找 Wine 中运行的 Windows 客户端与已挂载的安卓设备，仅返回存在的目录
 pub fn default_cache_paths() -> Vec<PathBuf> {
     #[cfg(target_os = "macos")]
     {
@@ -49,50 +53,285 @@ pub fn default_cache_paths() -> Vec<PathBuf> {
         paths
     }
 
-    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    #[cfg(target_os = "linux")]
+    {
+        let mut paths = Vec::new();
+        for prefix in wine_prefixes() {
+            for user in subdirs(&prefix.join("drive_c/users")) {
+                paths.push(user.join("AppData/Local/bilibili/download"));
+            }
+        }
+        for root in android_mount_roots() {
+            for app in ANDROID_PACKAGES {
+                paths.push(root.join("Android/data").join(app).join("download"));
+            }
+        }
+        paths.retain(|p| p.exists());
+        paths
+    }
+
+    #[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "linux")))]
     {
-        let _ = PathBuf::new();
         Vec::new()
     }
 }
 
+/// B 站安卓客户端包名（普通版 / HD 版 / 概念版）
+#[cfg(target_os = "linux")]
+const ANDROID_PACKAGES: &[&str] = &["tv.danmaku.bili", "tv.danmaku.bilibilihd", "com.bilibili.app.blue"];
+
+#[cfg(target_os = "linux")]
+fn subdirs(dir: &Path) -> Vec<PathBuf> {
+    std::fs::read_dir(dir)
+        .map(|d| {
+            d.filter_map(|e| e.ok())
+                .map(|e| e.path())
+                .filter(|p| p.is_dir())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Wine 前缀：$WINEPREFIX、~/.wine，以及 wineprefixes / Bottles 管理的前缀
+#[cfg(target_os = "linux")]
+fn wine_prefixes() -> Vec<PathBuf> {
+    let mut prefixes: Vec<PathBuf> = std::env::var_os("WINEPREFIX").map(PathBuf::from).into_iter().collect();
+    if let Some(home) = dirs::home_dir() {
+        prefixes.push(home.join(".wine"));
+        prefixes.extend(subdirs(&home.join(".local/share/wineprefixes")));
+        prefixes.extend(subdirs(&home.join(".local/share/bottles/bottles")));
+        prefixes.extend(subdirs(&home.join(".var/app/com.usebottles.bottles/data/bottles/bottles")));
+    }
+    prefixes
+}
+
+/// 已挂载的安卓设备存储根目录：gvfs MTP 挂载点，以及 /media、/run/media 下的 U 盘式挂载
+#[cfg(target_os = "linux")]
+fn android_mount_roots() -> Vec<PathBuf> {
+    let mut roots = Vec::new();
+    if let Some(runtime) = std::env::var_os("XDG_RUNTIME_DIR") {
+        for device in subdirs(&PathBuf::from(runtime).join("gvfs")) {
+            let is_mtp = device
+                .file_name()
+                .and_then(|n| n.to_str())
+                .map(|n| n.starts_with("mtp:"))
+                .unwrap_or(false);
+            if is_mtp {
+                // MTP 设备下一级为存储卷，如「内部共享存储空间」
+                roots.extend(subdirs(&device));
+            }
+        }
+    }
+    let user = std::env::var("USER")
+        .ok()
+        .or_else(|| dirs::home_dir().and_then(|h| h.file_name().and_then(|n| n.to_str()).map(String::from)));
+    if let Some(user) = user {
+        for base in ["/media", "/run/media"] {
+            roots.extend(subdirs(&PathBuf::from(base).join(&user)));
+        }
+    }
+    roots
+}
+
+/// 默认扫描深度：元数据文件相对扫描目录的最大层级
+pub const DEFAULT_MAX_DEPTH: usize = 6;
+
+/// 缓存目录中的元数据文件，同一目录有多个时优先 entry.json
+pub const META_FILES: [&str; 3] = ["entry.json", "videoInfo.json", ".videoInfo"];
+
 /// 扫描指定目录，返回所有可解析的视频
-pub fn scan(dir: &std::path::Path) -> Result<Vec<VideoInfo>, ScanError> {
+pub fn scan(dir: &Path) -> Result<Vec<VideoInfo>, ScanError> {
+    scan_with_depth(dir, DEFAULT_MAX_DEPTH)
+}
+
+/// 按指定深度扫描，结果按缓存目录排序
+pub fn scan_with_depth(dir: &Path, max_depth: usize) -> Result<Vec<VideoInfo>, ScanError> {
+    scan_checked(dir, max_depth).map(|(videos, _)| videos)
+}
+
+/// 按指定深度扫描，同时返回无法识别的缓存目录及原因；两者均按目录排序
+pub fn scan_checked(dir: &Path, max_depth: usize) -> Result<(Vec<VideoInfo>, Vec<Rejected>), ScanError> {
+    let found = find_meta_files(dir, max_depth)?;
+    let results: Vec<Result<VideoInfo, Rejected>> = found
+        .metas
+        .par_iter()
+        .map(|p| parse_meta(p).map_err(|e| Rejected::from_error(p, &e)))
+        .collect();
+    let mut videos = Vec::new();
+    let mut rejected = found.rejected();
+    for r in results {
+        match r {
+            Ok(v) => videos.push(v),
+            Err(r) => rejected.push(r),
+        }
+    }
+    videos.sort_by(|a, b| a.cache_dir.cmp(&b.cache_dir));
+    fill_total_pages(&mut videos);
+    rejected.sort_by(|a, b| a.cache_dir.cmp(&b.cache_dir));
+    Ok((videos, rejected))
+}
+
+/// 补全未知的总 P 数：元数据只记录当前分 P 序号时，解析结果的总 P 数不大于分 P 序号，
+/// 此时取同一 BV 号下已缓存的最大分 P 序号；已知的总 P 数保持不变
+pub(super) fn fill_total_pages(videos: &mut [VideoInfo]) {
+    let mut max_page: HashMap<String, u32> = HashMap::new();
+    for v in videos.iter() {
+        if let Some(bvid) = &v.bvid {
+            let max = max_page.entry(bvid.clone()).or_default();
+            *max = (*max).max(v.page);
+        }
+    }
+    for v in videos.iter_mut().filter(|v| v.total_pages <= v.page) {
+        if let Some(&max) = v.bvid.as_ref().and_then(|b| max_page.get(b)) {
+            v.total_pages = max.max(v.page);
+        }
+    }
+}
+
+/// 解析单个元数据文件
+pub(super) fn parse_meta(path: &Path) -> Result<VideoInfo, ParseError> {
+    if path.file_name().is_some_and(|n| n == "entry.json") {
+        parse_entry(path)
+    } else {
+        parse_video_info(path)
+    }
+}
+
+/// 遍历结果
+pub(super) struct MetaFiles {
+    /// 每个缓存目录的元数据文件
+    pub metas: Vec<PathBuf>,
+    /// 有 m4s 却没有元数据文件的目录
+    pub unknown: Vec<PathBuf>,
+}
+
+impl MetaFiles {
+    pub fn rejected(&self) -> Vec<Rejected> {
+        self.unknown.iter().map(|d| Rejected::unknown_layout(d)).collect()
+    }
+}
+
+/// 并行逐层遍历目录（不跟随符号链接）；有元数据文件的缓存目录仍继续向下查找嵌套的缓存，
+/// 其直接子目录（如安卓的清晰度目录）中的 m4s 属于该缓存，不报告为无法识别
+pub(super) fn find_meta_files(dir: &Path, max_depth: usize) -> Result<MetaFiles, ScanError> {
     if !dir.exists() {
         return Err(ScanError::DirNotFound(dir.to_path_buf()));
     }
+    let mut found = MetaFiles { metas: Vec::new(), unknown: Vec::new() };
+    // (目录, 上一级是否有元数据文件)
+    let mut level = vec![(dir.to_path_buf(), false)];
+    // 第 depth 层目录中的文件位于 depth + 1 层
+    for depth in 0..max_depth {
+        let results: Vec<(PathBuf, bool, Level)> =
+            level.par_iter().map(|(d, owned)| (d.clone(), *owned, read_level(d))).collect();
+        level = Vec::new();
+        for (path, owned, result) in results {
+            let has_meta = result.meta.is_some();
+            if let Some(meta) = result.meta {
+                found.metas.push(meta);
+            } else if result.has_m4s && !owned {
+                found.unknown.push(cache_dir_of(&path, dir));
+            }
+            if depth + 1 < max_depth {
+                level.extend(result.subdirs.into_iter().map(|d| (d, has_meta)));
+            }
+        }
+        if level.is_empty() {
+            break;
+        }
+    }
+    found.metas.sort();
+    found.unknown.sort();
+    found.unknown.dedup();
+    Ok(found)
+}
 
-    let mut videos = Vec::new();
-    let mut seen_dirs = std::collections::HashSet::new();
+/// 安卓缓存的 m4s 位于清晰度子目录（如 `80/`），报告时取其上一级的缓存目录
+fn cache_dir_of(dir: &Path, root: &Path) -> PathBuf {
+    let is_quality_dir = dir
+        .file_name()
+        .and_then(|n| n.to_str())
+        .is_some_and(|n| n.len() <= 3 && n.chars().all(|c| c.is_ascii_digit()));
+    match dir.parent() {
+        Some(parent) if is_quality_dir && dir != root => parent.to_path_buf(),
+        _ => dir.to_path_buf(),
+    }
+}
 
-    for entry in WalkDir::new(dir)
-        .max_depth(6)
-        .follow_links(false)
-        .into_iter()
-        .filter_map(|e| e.ok())
-    {
-        let p = entry.path();
-        let is_entry = p.file_name().map(|n| n == "entry.json").unwrap_or(false);
-        let is_video_info = p.file_name().map(|n| n == "videoInfo.json" || n == ".videoInfo").unwrap_or(false);
-
-        if is_entry {
-            if let Some(parent) = p.parent() {
-                if seen_dirs.insert(parent.to_path_buf()) {
-                    if let Ok(v) = parse_entry(p) {
-                        videos.push(v);
-                    }
-                }
-            }
-        } else if is_video_info {
-            if let Some(parent) = p.parent() {
-                if seen_dirs.insert(parent.to_path_buf()) {
-                    if let Ok(v) = parse_video_info(p) {
-                        videos.push(v);
-                    }
+/// 一个目录的读取结果
+struct Level {
+    meta: Option<PathBuf>,
+    subdirs: Vec<PathBuf>,
+    has_m4s: bool,
+}
+
+/// 读取一个目录：其中的元数据文件（如有）、子目录及是否直接包含 m4s；无法读取的目录视为空
+fn read_level(dir: &Path) -> Level {
+    let mut level = Level { meta: None, subdirs: Vec::new(), has_m4s: false };
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return level;
+    };
+    let mut meta_rank = usize::MAX;
+    for entry in entries.filter_map(|e| e.ok()) {
+        let Ok(file_type) = entry.file_type() else { continue };
+        if file_type.is_dir() {
+            level.subdirs.push(entry.path());
+        } else if file_type.is_file() {
+            let name = entry.file_name();
+            if let Some(rank) = META_FILES.iter().position(|m| name == *m) {
+                if rank < meta_rank {
+                    meta_rank = rank;
+                    level.meta = Some(entry.path());
                 }
+            } else if Path::new(&name).extension().is_some_and(|e| e == "m4s") {
+                level.has_m4s = true;
             }
         }
     }
+    level
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    Ok(videos)
+    #[test]
+    fn test_scan_depth_and_meta_preference() {
+        let tmp = std::env::temp_dir().join("bili2mp4_scanner_test");
+        std::fs::remove_dir_all(&tmp).ok();
+        let shallow = tmp.join("a");
+        let deep = tmp.join("b/c/d");
+        for dir in [&shallow, &deep] {
+            std::fs::create_dir_all(dir.join("80")).unwrap();
+            std::fs::write(dir.join("80/video.m4s"), b"v").unwrap();
+            std::fs::write(dir.join("80/audio.m4s"), b"a").unwrap();
+            std::fs::write(dir.join("entry.json"), r#"{"title":"t","page_data":{"page":1}}"#).unwrap();
+        }
+        std::fs::write(shallow.join("videoInfo.json"), "{}").unwrap();
+        std::fs::write(deep.join("entry.json"), r#"{"title":"t","bvid":"BV1","page_data":{"page":3}}"#).unwrap();
+        std::fs::write(shallow.join("entry.json"), r#"{"title":"t","bvid":"BV1","page_data":{"page":1}}"#).unwrap();
+
+        let metas = find_meta_files(&tmp, DEFAULT_MAX_DEPTH).unwrap();
+        assert_eq!(metas.metas, vec![shallow.join("entry.json"), deep.join("entry.json")]);
+        assert!(metas.unknown.is_empty());
+        // b/c/d/entry.json 位于第 4 层
+        assert_eq!(scan_with_depth(&tmp, 3).unwrap().len(), 1);
+        let videos = scan_with_depth(&tmp, 4).unwrap();
+        assert_eq!(videos.len(), 2);
+        // 同一 BV 号缓存了 P1 与 P3，总 P 数至少为 3
+        assert!(videos.iter().all(|v| v.total_pages == 3));
+        let mut known = videos.clone();
+        known[0].total_pages = 8;
+        fill_total_pages(&mut known);
+        assert_eq!(known.iter().map(|v| v.total_pages).collect::<Vec<_>>(), [8, 3]);
+        assert!(matches!(scan(&tmp.join("missing")), Err(ScanError::DirNotFound(_))));
+
+        // 缓存目录之内嵌套的缓存同样找到
+        let nested = shallow.join("80/extra");
+        std::fs::create_dir_all(&nested).unwrap();
+        std::fs::write(nested.join("entry.json"), "{}").unwrap();
+        let metas = find_meta_files(&tmp, DEFAULT_MAX_DEPTH).unwrap();
+        assert!(metas.metas.contains(&nested.join("entry.json")));
+        std::fs::remove_dir_all(&tmp).ok();
+    }
 }
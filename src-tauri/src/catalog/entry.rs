@@ -0,0 +1,140 @@
+//! 清单条目：由扫描结果生成，附带转换状态与封面位置
+
+use crate::cache::VideoInfo;
+use crate::config::Profile;
+use crate::convert::target_path;
+use crate::filemgr::find_cover;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+/// 转换状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConvertStatus {
+    /// 输出目录中已有按方案命名的文件
+    Converted,
+    NotConverted,
+    /// 缓存未下载完成，尚不能转换
+    Incomplete,
+    /// 未指定输出目录，无法判断
+    Unchecked,
+}
+
+impl ConvertStatus {
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Converted => "已转换",
+            Self::NotConverted => "未转换",
+            Self::Incomplete => "未下载完成",
+            Self::Unchecked => "未检查",
+        }
+    }
+}
+
+/// 清单中的一行
+#[derive(Debug, Clone, Serialize)]
+pub struct CatalogEntry {
+    pub title: String,
+    pub uploader: Option<String>,
+    pub bvid: Option<String>,
+    pub page: u32,
+    pub total_pages: u32,
+    pub quality: String,
+    pub duration_ms: Option<u64>,
+    pub size_bytes: u64,
+    pub cached_at: Option<String>,
+    pub status: ConvertStatus,
+    /// 已转换时的输出文件
+    pub output: Option<String>,
+    pub cache_dir: String,
+    /// 封面图，仅用于 HTML 清单
+    #[serde(skip)]
+    pub cover: Option<PathBuf>,
+}
+
+/// 按扫描结果生成清单；指定 `output` 时按方案的命名规则检查输出目录中是否已有转换结果
+pub fn build_catalog(videos: &[VideoInfo], output: Option<(&Path, &Profile)>) -> Vec<CatalogEntry> {
+    videos
+        .iter()
+        .map(|v| {
+            let converted = output
+                .and_then(|(dir, profile)| target_path(v, dir, profile).ok())
+                .filter(|p| p.is_file());
+            let status = match (&converted, output) {
+                (Some(_), _) => ConvertStatus::Converted,
+                _ if !v.download.is_complete() => ConvertStatus::Incomplete,
+                (None, Some(_)) => ConvertStatus::NotConverted,
+                (None, None) => ConvertStatus::Unchecked,
+            };
+            CatalogEntry {
+                title: v.title.clone(),
+                uploader: v.uploader.clone(),
+                bvid: v.bvid.clone(),
+                page: v.page,
+                total_pages: v.total_pages,
+                quality: v.quality.clone(),
+                duration_ms: v.duration_ms,
+                size_bytes: v.size_bytes,
+                cached_at: v.cached_at.clone(),
+                status,
+                output: converted.map(|p| p.display().to_string()),
+                cache_dir: v.cache_dir.display().to_string(),
+                cover: find_cover(&v.cache_dir),
+            }
+        })
+        .collect()
+}
+
+/// 时长显示为 `m:ss` 或 `h:mm:ss`
+pub fn format_duration(ms: u64) -> String {
+    let secs = ms / 1000;
+    let (h, m, s) = (secs / 3600, secs / 60 % 60, secs % 60);
+    if h > 0 {
+        format!("{}:{:02}:{:02}", h, m, s)
+    } else {
+        format!("{}:{:02}", m, s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::DownloadState;
+
+    #[test]
+    fn test_build_catalog_status() {
+        let tmp = std::env::temp_dir().join("bili2mp4_catalog_entry_test");
+        std::fs::remove_dir_all(&tmp).ok();
+        let cache = tmp.join("cache/1");
+        let out = tmp.join("out");
+        std::fs::create_dir_all(&cache).unwrap();
+        std::fs::create_dir_all(&out).unwrap();
+        std::fs::write(tmp.join("cache/cover.jpg"), b"jpg").unwrap();
+        let video = |title: &str, download| VideoInfo {
+            size_bytes: 10,
+            duration_ms: Some(3_723_000),
+            download,
+            ..VideoInfo::fixture(&cache, title)
+        };
+        let videos = [
+            video("已转换", DownloadState::Complete),
+            video("未转换", DownloadState::Complete),
+            video("下载中", DownloadState::Downloading(String::new())),
+        ];
+        std::fs::write(out.join("已转换.mp4"), b"mp4").unwrap();
+
+        let profile = Profile::default();
+        let entries = build_catalog(&videos, Some((&out, &profile)));
+        let status: Vec<_> = entries.iter().map(|e| e.status).collect();
+        assert_eq!(
+            status,
+            [ConvertStatus::Converted, ConvertStatus::NotConverted, ConvertStatus::Incomplete]
+        );
+        assert_eq!(entries[0].output.as_deref(), Some(out.join("已转换.mp4").display().to_string().as_str()));
+        assert_eq!(entries[0].cover, Some(tmp.join("cache/cover.jpg")));
+        assert_eq!(build_catalog(&videos[..1], None)[0].status, ConvertStatus::Unchecked);
+        assert_eq!(format_duration(3_723_000), "1:02:03");
+        assert_eq!(format_duration(65_000), "1:05");
+        std::fs::remove_dir_all(&tmp).ok();
+    }
+}
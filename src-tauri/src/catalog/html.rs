@@ -0,0 +1,114 @@
+//! 独立的 HTML 清单：封面内嵌为 data URI，点击表头排序，不依赖外部资源
+
+use super::entry::{format_duration, CatalogEntry, ConvertStatus};
+use crate::convert::format_bytes;
+use crate::thumbnail::image_data_uri;
+use crate::util::escape_markup;
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// 超过该大小的封面不内嵌，避免页面过大
+const MAX_COVER_BYTES: u64 = 2 * 1024 * 1024;
+
+const STYLE: &str = "body{font-family:-apple-system,'PingFang SC','Microsoft YaHei',sans-serif;margin:24px;color:#222}
+h1{font-size:20px;margin:0 0 4px}
+.summary{color:#666;font-size:13px;margin-bottom:16px}
+table{border-collapse:collapse;width:100%;font-size:13px}
+th,td{padding:6px 8px;border-bottom:1px solid #eee;text-align:left;vertical-align:middle}
+th{cursor:pointer;user-select:none;background:#fafafa;position:sticky;top:0}
+th.asc::after{content:' ▲'}th.desc::after{content:' ▼'}
+td.num{text-align:right;white-space:nowrap}
+img{width:96px;height:54px;object-fit:cover;border-radius:4px;background:#eee}
+.converted{color:#27ae60}.not_converted{color:#888}.incomplete{color:#e67e22}.unchecked{color:#aaa}";
+
+/// 封面按出现顺序编号，多个分 P 共用一张封面时只内嵌一次
+const SCRIPT: &str = "document.querySelectorAll('img[data-cover]').forEach(img => { img.src = COVERS[img.dataset.cover]; });
+document.querySelectorAll('th').forEach((th, col) => th.addEventListener('click', () => {
+  const tbody = document.querySelector('tbody');
+  const desc = th.classList.contains('asc');
+  document.querySelectorAll('th').forEach(h => h.classList.remove('asc', 'desc'));
+  th.classList.add(desc ? 'desc' : 'asc');
+  const key = tr => tr.children[col].dataset.sort ?? tr.children[col].textContent;
+  const rows = [...tbody.rows].sort((a, b) => {
+    const x = key(a), y = key(b);
+    const cmp = x !== '' && y !== '' && !isNaN(x) && !isNaN(y) ? x - y : x.localeCompare(y, 'zh');
+    return desc ? -cmp : cmp;
+  });
+  tbody.append(...rows);
+}));";
+
+pub(super) fn write_html(w: &mut impl Write, entries: &[CatalogEntry]) -> std::io::Result<()> {
+    let mut covers: Vec<String> = Vec::new();
+    let mut cover_ids: HashMap<&PathBuf, Option<usize>> = HashMap::new();
+    let total: u64 = entries.iter().map(|e| e.size_bytes).sum();
+
+    writeln!(w, "<!DOCTYPE html>\n<html lang=\"zh-CN\">\n<head>\n<meta charset=\"utf-8\">")?;
+    writeln!(w, "<title>Bili2MP4 缓存清单</title>\n<style>\n{}\n</style>\n</head>\n<body>", STYLE)?;
+    writeln!(w, "<h1>Bili2MP4 缓存清单</h1>")?;
+    writeln!(
+        w,
+        "<div class=\"summary\">共 {} 个视频，{}，生成于 {}</div>",
+        entries.len(),
+        format_bytes(total),
+        chrono::Local::now().format("%Y-%m-%d %H:%M")
+    )?;
+    writeln!(w, "<table>\n<thead><tr>")?;
+    for th in ["封面", "标题", "UP 主", "BV 号", "分 P", "清晰度", "时长", "大小", "缓存时间", "状态"] {
+        write!(w, "<th>{}</th>", th)?;
+    }
+    writeln!(w, "</tr></thead>\n<tbody>")?;
+    for e in entries {
+        let cover = e.cover.as_ref().and_then(|path| {
+            *cover_ids.entry(path).or_insert_with(|| {
+                let uri = data_uri(path)?;
+                covers.push(uri);
+                Some(covers.len() - 1)
+            })
+        });
+        write!(w, "<tr>")?;
+        match cover {
+            Some(id) => write!(w, "<td data-sort=\"\"><img data-cover=\"{}\" alt=\"\"></td>", id)?,
+            None => write!(w, "<td data-sort=\"\"></td>")?,
+        }
+        write!(w, "<td title=\"{}\">{}</td>", escape_markup(&e.cache_dir), escape_markup(&e.title))?;
+        write!(w, "<td>{}</td>", escape_markup(e.uploader.as_deref().unwrap_or_default()))?;
+        write!(w, "<td>{}</td>", escape_markup(e.bvid.as_deref().unwrap_or_default()))?;
+        write!(w, "<td class=\"num\" data-sort=\"{}\">{}/{}</td>", e.page, e.page, e.total_pages)?;
+        write!(w, "<td>{}</td>", escape_markup(&e.quality))?;
+        match e.duration_ms {
+            Some(ms) => write!(w, "<td class=\"num\" data-sort=\"{}\">{}</td>", ms, format_duration(ms))?,
+            None => write!(w, "<td class=\"num\" data-sort=\"\"></td>")?,
+        }
+        write!(w, "<td class=\"num\" data-sort=\"{}\">{}</td>", e.size_bytes, format_bytes(e.size_bytes))?;
+        write!(w, "<td>{}</td>", escape_markup(e.cached_at.as_deref().unwrap_or_default()))?;
+        let status = status_class(e.status);
+        match &e.output {
+            Some(out) => write!(w, "<td class=\"{}\" title=\"{}\">{}</td>", status, escape_markup(out), e.status.label())?,
+            None => write!(w, "<td class=\"{}\">{}</td>", status, e.status.label())?,
+        }
+        writeln!(w, "</tr>")?;
+    }
+    writeln!(w, "</tbody>\n</table>")?;
+    // data URI 只含 base64 字符与固定前缀，按 JSON 写出即为合法的脚本字面量
+    let covers = serde_json::to_string(&covers).map_err(std::io::Error::other)?;
+    writeln!(w, "<script>\nconst COVERS = {};\n{}\n</script>", covers, SCRIPT)?;
+    writeln!(w, "</body>\n</html>")
+}
+
+fn status_class(status: ConvertStatus) -> &'static str {
+    match status {
+        ConvertStatus::Converted => "converted",
+        ConvertStatus::NotConverted => "not_converted",
+        ConvertStatus::Incomplete => "incomplete",
+        ConvertStatus::Unchecked => "unchecked",
+    }
+}
+
+/// 读取封面为 data URI；过大或无法读取时跳过
+fn data_uri(path: &Path) -> Option<String> {
+    if std::fs::metadata(path).ok()?.len() > MAX_COVER_BYTES {
+        return None;
+    }
+    image_data_uri(path).ok()
+}
@@ -0,0 +1,6 @@
+mod entry;
+mod html;
+mod writer;
+
+pub use entry::{build_catalog, format_duration, CatalogEntry, ConvertStatus};
+pub use writer::{export_catalog, write_catalog, CatalogError, CatalogFormat};
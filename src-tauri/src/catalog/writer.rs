@@ -0,0 +1,200 @@
+//! 清单导出：CSV、JSON Lines 与独立的 HTML 页面
+
+use super::entry::{format_duration, CatalogEntry};
+use super::html::write_html;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+use thiserror::Error;
+
+/// CSV 列名，除 `duration` 外与 JSON Lines 的字段名一致：CSV 写格式化的时长（如 `1:05`），
+/// JSON Lines 写毫秒数 `duration_ms`
+const CSV_HEADER: [&str; 12] = [
+    "title",
+    "uploader",
+    "bvid",
+    "page",
+    "total_pages",
+    "quality",
+    "duration",
+    "size_bytes",
+    "cached_at",
+    "status",
+    "output",
+    "cache_dir",
+];
+
+#[derive(Debug, Error)]
+pub enum CatalogError {
+    #[error("写入清单失败: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("JSON 序列化失败: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("未知的清单格式: {0}（可用: csv、jsonl、html）")]
+    UnknownFormat(String),
+}
+
+/// 清单格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CatalogFormat {
+    Csv,
+    Jsonl,
+    Html,
+}
+
+impl FromStr for CatalogFormat {
+    type Err = CatalogError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "csv" => Ok(Self::Csv),
+            "jsonl" | "ndjson" => Ok(Self::Jsonl),
+            "html" | "htm" => Ok(Self::Html),
+            _ => Err(CatalogError::UnknownFormat(s.to_string())),
+        }
+    }
+}
+
+impl CatalogFormat {
+    /// 按文件扩展名推断格式
+    pub fn from_path(path: &Path) -> Result<Self, CatalogError> {
+        let ext = path.extension().and_then(|e| e.to_str()).unwrap_or_default();
+        ext.parse()
+    }
+}
+
+/// 按格式写出清单
+pub fn write_catalog(w: &mut impl Write, entries: &[CatalogEntry], format: CatalogFormat) -> Result<(), CatalogError> {
+    match format {
+        CatalogFormat::Csv => write_csv(w, entries)?,
+        CatalogFormat::Jsonl => {
+            for entry in entries {
+                serde_json::to_writer(&mut *w, entry)?;
+                writeln!(w)?;
+            }
+        }
+        CatalogFormat::Html => write_html(w, entries)?,
+    }
+    Ok(())
+}
+
+/// 写出清单文件，先写临时文件再替换，避免留下写了一半的清单
+pub fn export_catalog(path: &Path, entries: &[CatalogEntry], format: CatalogFormat) -> Result<(), CatalogError> {
+    let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
+    tmp_name.push(".tmp");
+    let tmp = path.with_file_name(tmp_name);
+    let mut w = BufWriter::new(std::fs::File::create(&tmp)?);
+    write_catalog(&mut w, entries, format)?;
+    w.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+/// 带 UTF-8 BOM，便于表格软件正确识别中文
+fn write_csv(w: &mut impl Write, entries: &[CatalogEntry]) -> std::io::Result<()> {
+    w.write_all("\u{feff}".as_bytes())?;
+    writeln!(w, "{}", CSV_HEADER.join(","))?;
+    for e in entries {
+        let fields = [
+            e.title.clone(),
+            e.uploader.clone().unwrap_or_default(),
+            e.bvid.clone().unwrap_or_default(),
+            e.page.to_string(),
+            e.total_pages.to_string(),
+            e.quality.clone(),
+            e.duration_ms.map(format_duration).unwrap_or_default(),
+            e.size_bytes.to_string(),
+            e.cached_at.clone().unwrap_or_default(),
+            e.status.label().to_string(),
+            e.output.clone().unwrap_or_default(),
+            e.cache_dir.clone(),
+        ];
+        let row: Vec<String> = fields.iter().map(|f| csv_field(f)).collect();
+        writeln!(w, "{}", row.join(","))?;
+    }
+    Ok(())
+}
+
+/// 表格软件会把以这些字符开头的单元格当作公式执行
+const FORMULA_PREFIXES: [char; 6] = ['=', '+', '-', '@', '\t', '\r'];
+
+/// 可能被当作公式的字段前加单引号；含逗号、引号或换行的字段加引号，内部引号加倍
+fn csv_field(value: &str) -> String {
+    let value = if value.starts_with(FORMULA_PREFIXES) {
+        format!("'{}", value)
+    } else {
+        value.to_string()
+    };
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::catalog::ConvertStatus;
+
+    fn entry(title: &str) -> CatalogEntry {
+        CatalogEntry {
+            title: title.into(),
+            uploader: Some("UP".into()),
+            bvid: Some("BV1xx411c7mD".into()),
+            page: 1,
+            total_pages: 2,
+            quality: "1080P".into(),
+            duration_ms: Some(65_000),
+            size_bytes: 2048,
+            cached_at: Some("2025-01-02".into()),
+            status: ConvertStatus::NotConverted,
+            output: None,
+            cache_dir: "/cache/1".into(),
+            cover: None,
+        }
+    }
+
+    fn render(entries: &[CatalogEntry], format: CatalogFormat) -> String {
+        let mut buf = Vec::new();
+        write_catalog(&mut buf, entries, format).unwrap();
+        String::from_utf8(buf).unwrap()
+    }
+
+    #[test]
+    fn test_catalog_formats() {
+        let tmp = std::env::temp_dir().join("bili2mp4_catalog_writer_test");
+        std::fs::create_dir_all(&tmp).unwrap();
+        std::fs::write(tmp.join("cover.jpg"), b"abcd").unwrap();
+        let mut entries = [entry("a, \"b\""), entry("<script>"), entry("=HYPERLINK(\"x\")")];
+        for e in &mut entries {
+            e.cover = Some(tmp.join("cover.jpg"));
+        }
+
+        let csv = render(&entries, CatalogFormat::Csv);
+        let lines: Vec<&str> = csv.trim_start_matches('\u{feff}').lines().collect();
+        assert_eq!(lines[0], CSV_HEADER.join(","));
+        assert_eq!(lines[1], "\"a, \"\"b\"\"\",UP,BV1xx411c7mD,1,2,1080P,1:05,2048,2025-01-02,未转换,,/cache/1");
+        assert!(lines[3].starts_with("\"'=HYPERLINK(\"\"x\"\")\",UP,"));
+        assert_eq!(csv_field("-1"), "'-1");
+
+        let jsonl = render(&entries, CatalogFormat::Jsonl);
+        let rows: Vec<serde_json::Value> = jsonl.lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[0]["status"], "not_converted");
+        assert_eq!(rows[1]["duration_ms"], 65_000);
+        assert!(rows[0].get("cover").is_none());
+
+        let html = render(&entries, CatalogFormat::Html);
+        assert!(html.contains("&lt;script&gt;"));
+        assert!(!html.contains("<td><script>"));
+        // 两个分 P 共用的封面只内嵌一次
+        assert_eq!(html.matches("data:image/jpeg;base64,YWJjZA==").count(), 1);
+        assert_eq!(html.matches("data-cover=\"0\"").count(), 3);
+        assert!(html.contains("const COVERS = [\"data:image/jpeg;base64,YWJjZA==\"];"));
+
+        assert_eq!(CatalogFormat::from_path(Path::new("a/清单.HTML")).unwrap(), CatalogFormat::Html);
+        assert!(matches!(CatalogFormat::from_path(Path::new("a.txt")), Err(CatalogError::UnknownFormat(_))));
+        std::fs::remove_dir_all(&tmp).ok();
+    }
+}
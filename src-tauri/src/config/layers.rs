@@ -0,0 +1,189 @@
+//! 分层配置：默认值 < 配置文件 < BILI2MP4_* 环境变量 < 命令行参数
+//!
+//! 覆盖层只影响本次运行，保存配置时只写配置文件层
+
+use super::location::get_config_path;
+use super::preferences::{read_config_value, AppConfig, ConfigError};
+use serde_json::{Map, Value};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// 配置项取值的来源
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigSource {
+    Default,
+    File(PathBuf),
+    /// 环境变量名
+    Env(String),
+    /// 命令行参数名
+    Cli(String),
+}
+
+impl fmt::Display for ConfigSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigSource::Default => write!(f, "默认值"),
+            ConfigSource::File(p) => write!(f, "配置文件 {}", p.display()),
+            ConfigSource::Env(name) => write!(f, "环境变量 {}", name),
+            ConfigSource::Cli(flag) => write!(f, "命令行 {}", flag),
+        }
+    }
+}
+
+/// 合并后的配置及每一项的来源（按配置项名排序）
+#[derive(Debug, Clone)]
+pub struct LayeredConfig {
+    pub config: AppConfig,
+    pub sources: Vec<(String, ConfigSource)>,
+    /// 迁移时丢弃的取值及 [`AppConfig::warnings`]，需提示用户
+    pub warnings: Vec<String>,
+}
+
+/// 可覆盖的配置项，即配置文件中除 version 外的全部字段
+pub fn fields() -> Vec<String> {
+    match serde_json::to_value(AppConfig::default()) {
+        Ok(Value::Object(obj)) => obj.keys().filter(|k| *k != "version").cloned().collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// 配置项对应的环境变量，如 output_dir → BILI2MP4_OUTPUT_DIR
+pub fn env_var_name(field: &str) -> String {
+    format!("BILI2MP4_{}", field.to_ascii_uppercase())
+}
+
+/// 配置项对应的命令行参数，如 output_dir → --output-dir
+pub fn cli_flag_name(field: &str) -> String {
+    format!("--{}", field.replace('_', "-"))
+}
+
+/// 读取当前配置文件并叠加环境变量与命令行覆盖；`cli` 为 (配置项, 值)
+pub fn load_layered(cli: &[(String, String)]) -> Result<LayeredConfig, ConfigError> {
+    load_layers(get_config_path().as_deref(), |name| std::env::var(name).ok(), cli)
+}
+
+pub(super) fn load_layers(
+    path: Option<&Path>,
+    env: impl Fn(&str) -> Option<String>,
+    cli: &[(String, String)],
+) -> Result<LayeredConfig, ConfigError> {
+    let fields = fields();
+    let mut sources: Vec<(String, ConfigSource)> =
+        fields.iter().map(|f| (f.clone(), ConfigSource::Default)).collect();
+    let mut set_source = |field: &str, source: ConfigSource| {
+        if let Some(entry) = sources.iter_mut().find(|(f, _)| f == field) {
+            entry.1 = source;
+        }
+    };
+
+    let (mut obj, mut warnings) = match path.map(read_config_value).transpose()?.flatten() {
+        Some((Value::Object(obj), warnings)) => (obj, warnings),
+        _ => (Map::new(), Vec::new()),
+    };
+    if let Some(path) = path {
+        for field in fields.iter().filter(|f| obj.contains_key(*f)) {
+            set_source(field, ConfigSource::File(path.to_path_buf()));
+        }
+    }
+
+    for field in &fields {
+        let name = env_var_name(field);
+        if let Some(raw) = env(&name) {
+            apply(&mut obj, field, &raw, &format!("环境变量 {}", name))?;
+            set_source(field, ConfigSource::Env(name));
+        }
+    }
+
+    for (field, raw) in cli {
+        let flag = cli_flag_name(field);
+        if !fields.contains(field) {
+            return Err(ConfigError::Override {
+                origin: format!("命令行 {}", flag),
+                error: "未知配置项".to_string(),
+            });
+        }
+        apply(&mut obj, field, raw, &format!("命令行 {}", flag))?;
+        set_source(field, ConfigSource::Cli(flag));
+    }
+
+    let config: AppConfig = serde_json::from_value(Value::Object(obj))?;
+    warnings.extend(config.warnings());
+    Ok(LayeredConfig { config, sources, warnings })
+}
+
+/// 写入一项覆盖：空字符串清空该项；先按字符串解析，不合法时再按 JSON 解析（数组、布尔等）
+fn apply(obj: &mut Map<String, Value>, field: &str, raw: &str, origin: &str) -> Result<(), ConfigError> {
+    let mut candidates = Vec::new();
+    if raw.is_empty() {
+        candidates.push(Value::Null);
+    } else {
+        candidates.push(Value::String(raw.to_string()));
+        if let Ok(json) = serde_json::from_str::<Value>(raw) {
+            candidates.push(json);
+        }
+    }
+    let mut last_err = None;
+    for value in candidates {
+        let mut trial = obj.clone();
+        trial.insert(field.to_string(), value);
+        match serde_json::from_value::<AppConfig>(Value::Object(trial.clone())) {
+            Ok(_) => {
+                *obj = trial;
+                return Ok(());
+            }
+            Err(e) => last_err = Some(e),
+        }
+    }
+    Err(ConfigError::Override {
+        origin: origin.to_string(),
+        error: last_err.map(|e| e.to_string()).unwrap_or_default(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filemgr::ConflictStrategy;
+    use crate::hooks::CompleteAction;
+
+    #[test]
+    fn test_layer_precedence_and_sources() {
+        let tmp = std::env::temp_dir().join("bili2mp4_layers_test");
+        std::fs::create_dir_all(&tmp).unwrap();
+        let path = tmp.join("config.json");
+        std::fs::write(
+            &path,
+            r#"{"version": 2, "output_dir": "/file/out", "conflict_strategy": "skip", "hook_command": "echo hi"}"#,
+        )
+        .unwrap();
+
+        let env = |name: &str| match name {
+            "BILI2MP4_OUTPUT_DIR" => Some("/env/out".to_string()),
+            "BILI2MP4_ON_COMPLETE" => Some("notify".to_string()),
+            "BILI2MP4_HOOK_COMMAND" => Some(String::new()),
+            _ => None,
+        };
+        let cli = vec![("output_dir".to_string(), "/cli/out".to_string())];
+        let layered = load_layers(Some(&path), env, &cli).unwrap();
+        let c = &layered.config;
+        assert_eq!(c.output_dir.as_deref(), Some("/cli/out"));
+        assert_eq!(c.on_complete, CompleteAction::Notify);
+        assert_eq!(c.conflict_strategy, ConflictStrategy::Skip);
+        assert_eq!(c.hook_command, None);
+
+        let source = |f: &str| layered.sources.iter().find(|(k, _)| k == f).unwrap().1.clone();
+        assert_eq!(source("output_dir"), ConfigSource::Cli("--output-dir".into()));
+        assert_eq!(source("on_complete"), ConfigSource::Env("BILI2MP4_ON_COMPLETE".into()));
+        assert_eq!(source("conflict_strategy"), ConfigSource::File(path.clone()));
+        assert_eq!(source("mp4box_path"), ConfigSource::Default);
+
+        let profiles = vec![("profiles".to_string(), r#"[{"name": "phone"}]"#.to_string())];
+        let layered = load_layers(Some(&path), |_| None, &profiles).unwrap();
+        assert_eq!(layered.config.profiles[0].name, "phone");
+
+        let bad = |name: &str| (name == "BILI2MP4_CONFLICT_STRATEGY").then(|| "explode".to_string());
+        let err = load_layers(Some(&path), bad, &[]).unwrap_err();
+        assert!(err.to_string().contains("BILI2MP4_CONFLICT_STRATEGY"));
+        std::fs::remove_dir_all(&tmp).ok();
+    }
+}
@@ -0,0 +1,56 @@
+//! 配置文件位置：命令行指定、环境变量、便携模式与默认目录
+
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+/// 便携模式下配置文件名，与可执行文件放在同一目录
+pub const PORTABLE_FILE: &str = "bili2mp4.json";
+
+static PATH_OVERRIDE: OnceLock<PathBuf> = OnceLock::new();
+static PORTABLE: OnceLock<bool> = OnceLock::new();
+
+/// 指定配置文件路径（命令行 `--config`），进程内只能设置一次
+pub fn set_config_path(path: PathBuf) {
+    let _ = PATH_OVERRIDE.set(path);
+}
+
+/// 启用便携模式（命令行 `--portable`），进程内只能设置一次
+pub fn set_portable(enabled: bool) {
+    let _ = PORTABLE.set(enabled);
+}
+
+/// 可执行文件同目录下的便携配置路径
+pub fn portable_config_path() -> Option<PathBuf> {
+    let exe = std::env::current_exe().ok()?;
+    Some(exe.parent()?.join(PORTABLE_FILE))
+}
+
+/// 是否为便携模式：显式开启、设置了 BILI2MP4_PORTABLE，或程序同目录已有便携配置文件
+fn is_portable() -> bool {
+    if let Some(&enabled) = PORTABLE.get() {
+        return enabled;
+    }
+    if std::env::var("BILI2MP4_PORTABLE").is_ok_and(|v| !v.is_empty() && v != "0") {
+        return true;
+    }
+    portable_config_path().is_some_and(|p| p.is_file())
+}
+
+/// 配置文件路径，优先级：`--config` > BILI2MP4_CONFIG > 便携模式 > ~/.config/bili2mp4/config.json
+pub fn get_config_path() -> Option<PathBuf> {
+    if let Some(p) = PATH_OVERRIDE.get() {
+        return Some(p.clone());
+    }
+    if let Some(p) = std::env::var_os("BILI2MP4_CONFIG").filter(|v| !v.is_empty()) {
+        return Some(PathBuf::from(p));
+    }
+    if is_portable() {
+        return portable_config_path();
+    }
+    dirs::config_dir().map(|p| p.join("bili2mp4").join("config.json"))
+}
+
+/// 配置文件所在目录，清理记录、扫描索引等运行数据也存放于此
+pub fn config_dir() -> Option<PathBuf> {
+    get_config_path()?.parent().map(|p| p.to_path_buf())
+}
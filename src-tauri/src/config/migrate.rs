@@ -0,0 +1,78 @@
+//! 配置文件版本迁移：逐版本升级原始 JSON，再交给 serde 严格解析
+
+use super::preferences::{ConfigError, CONFIG_VERSION};
+use crate::filemgr::ConflictStrategy;
+use crate::hooks::{CompleteAction, CompleteScope};
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+
+/// 将任意旧版本配置升级到 [`CONFIG_VERSION`]；无 `version` 字段视为 v1。
+/// 同时返回迁移中被丢弃的取值，供界面提示
+pub fn migrate(mut value: Value) -> Result<(Value, Vec<String>), ConfigError> {
+    let mut warnings = Vec::new();
+    let Some(obj) = value.as_object_mut() else {
+        return Ok((value, warnings));
+    };
+    let mut version = obj.get("version").and_then(Value::as_u64).unwrap_or(1) as u32;
+    if version > CONFIG_VERSION {
+        return Err(ConfigError::UnsupportedVersion(version));
+    }
+    while version < CONFIG_VERSION {
+        match version {
+            1 => v1_to_v2(obj, &mut warnings),
+            _ => unreachable!("缺少 v{} 的迁移", version),
+        }
+        version += 1;
+    }
+    obj.insert("version".into(), Value::from(CONFIG_VERSION));
+    Ok((value, warnings))
+}
+
+/// v1 的枚举选项是可为 null 的字符串，无法识别时按默认值处理；v2 改为严格枚举
+fn v1_to_v2(obj: &mut Map<String, Value>, warnings: &mut Vec<String>) {
+    keep_if_valid::<CompleteAction>(obj, "on_complete", warnings);
+    keep_if_valid::<CompleteScope>(obj, "on_complete_scope", warnings);
+    keep_if_valid::<ConflictStrategy>(obj, "conflict_strategy", warnings);
+    if obj.get("hook_command").and_then(Value::as_str).is_some_and(|s| s.trim().is_empty()) {
+        obj.remove("hook_command");
+    }
+}
+
+/// 取值无法解析为 `T` 时移除该字段，由默认值补齐；非 null 的取值被丢弃时记录警告
+fn keep_if_valid<T: DeserializeOwned>(obj: &mut Map<String, Value>, key: &str, warnings: &mut Vec<String>) {
+    let Some(value) = obj.get(key) else { return };
+    if serde_json::from_value::<T>(value.clone()).is_ok() {
+        return;
+    }
+    if !value.is_null() {
+        warnings.push(format!("{} 的取值 {} 无法识别，已改用默认值", key, value));
+    }
+    obj.remove(key);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::AppConfig;
+
+    #[test]
+    fn test_migrate_v1() {
+        let v1 = serde_json::json!({
+            "output_dir": "/Users/me/Movies",
+            "on_complete": "notify",
+            "on_complete_scope": null,
+            "hook_command": "  ",
+            "conflict_strategy": "whatever",
+            "mp4box_path": null
+        });
+        let (value, warnings) = migrate(v1).unwrap();
+        assert_eq!(warnings, vec![r#"conflict_strategy 的取值 "whatever" 无法识别，已改用默认值"#]);
+        let config: AppConfig = serde_json::from_value(value).unwrap();
+        assert_eq!(config.version, CONFIG_VERSION);
+        assert_eq!(config.output_dir.as_deref(), Some("/Users/me/Movies"));
+        assert_eq!(config.on_complete, CompleteAction::Notify);
+        assert_eq!(config.on_complete_scope, CompleteScope::Batch);
+        assert_eq!(config.conflict_strategy, ConflictStrategy::Rename);
+        assert_eq!(config.hook_command, None);
+    }
+}
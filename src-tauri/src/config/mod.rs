@@ -1,3 +1,13 @@
+mod layers;
+mod location;
+mod migrate;
 mod preferences;
+mod profile;
 
-pub use preferences::{load_config, resolve_ffmpeg_path, resolve_mp4box_path, save_config, AppConfig};
+pub use layers::{cli_flag_name, env_var_name, fields, load_layered, ConfigSource, LayeredConfig};
+pub use location::{config_dir, get_config_path, portable_config_path, set_config_path, set_portable, PORTABLE_FILE};
+pub use preferences::{
+    load_config, load_config_from, resolve_ffmpeg_path, resolve_mp4box_path, save_config, save_config_to, AppConfig,
+    ConfigError, CONFIG_VERSION,
+};
+pub use profile::{export_profile, import_profile, Profile, DEFAULT_PROFILE};
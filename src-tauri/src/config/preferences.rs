@@ -1,60 +1,239 @@
 //! 偏好配置持久化
 
-use crate::filemgr::ConflictStrategy;
+use super::location::get_config_path;
+use super::migrate::migrate;
+use super::profile::{Profile, DEFAULT_PROFILE};
+use crate::cache::{DuplicatePolicy, DEFAULT_MAX_DEPTH};
+use crate::convert::{Container, ExtraArgs};
+use crate::filemgr::{CleanupMode, ConflictStrategy};
+use crate::hooks::{CompleteAction, CompleteScope};
+use crate::toolchain::find_in_path;
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
 
-#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+/// 当前配置文件版本；v1 为无 `version` 字段、选项均为自由字符串的旧格式
+pub const CONFIG_VERSION: u32 = 2;
+
+/// 扫描深度上限，避免误选根目录时遍历整个磁盘
+const MAX_SCAN_DEPTH: usize = 32;
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("读写配置文件失败: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("配置文件格式错误: {0}")]
+    Parse(#[from] serde_json::Error),
+    #[error("配置文件版本 {0} 高于当前支持的版本 {CONFIG_VERSION}，请升级应用")]
+    UnsupportedVersion(u32),
+    #[error("配置无效: {}", .0.join("；"))]
+    Invalid(Vec<String>),
+    #[error("未找到转换方案: {0}")]
+    UnknownProfile(String),
+    #[error("{origin} 的取值无效: {error}")]
+    Override { origin: String, error: String },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct AppConfig {
+    /// 配置文件版本
+    pub version: u32,
     /// 默认输出目录
     pub output_dir: Option<String>,
-    /// 完成后操作：none | open_folder | open_and_play | notify
-    pub on_complete: Option<String>,
+    /// 完成后操作
+    pub on_complete: CompleteAction,
+    /// 完成后操作的时机：整批结束或每个任务成功后
+    pub on_complete_scope: CompleteScope,
+    /// 每个任务结束后执行的自定义命令，任务信息通过 BILI2MP4_* 环境变量与 stdin JSON 传入
+    pub hook_command: Option<String>,
     /// 冲突策略
-    pub conflict_strategy: Option<String>,
+    pub conflict_strategy: ConflictStrategy,
+    /// 未指定方案时的输出容器
+    pub container: Container,
     /// MP4Box 自定义路径（若未捆绑）
     pub mp4box_path: Option<String>,
+    /// ffmpeg 自定义路径
+    pub ffmpeg_path: Option<String>,
+    /// 各后端的附加参数
+    pub extra_args: ExtraArgs,
+    /// 扫描缓存目录的最大深度（元数据文件相对扫描目录的层级）
+    pub scan_max_depth: usize,
+    /// 同一视频同一分 P 有多份缓存时，全选与命令行转换保留哪一份
+    pub duplicate_policy: DuplicatePolicy,
+    /// 转换成功且校验通过后如何处理源缓存目录，默认不处理
+    pub cleanup: CleanupMode,
+    /// `cleanup` 为 quarantine 时源缓存目录移入的目录
+    pub quarantine_dir: Option<String>,
+    /// 命名的转换方案
+    pub profiles: Vec<Profile>,
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        Self {
+            version: CONFIG_VERSION,
+            output_dir: None,
+            on_complete: CompleteAction::default(),
+            on_complete_scope: CompleteScope::default(),
+            hook_command: None,
+            conflict_strategy: ConflictStrategy::default(),
+            container: Container::default(),
+            mp4box_path: None,
+            ffmpeg_path: None,
+            extra_args: ExtraArgs::default(),
+            scan_max_depth: DEFAULT_MAX_DEPTH,
+            duplicate_policy: DuplicatePolicy::default(),
+            cleanup: CleanupMode::default(),
+            quarantine_dir: None,
+            profiles: Vec::new(),
+        }
+    }
 }
 
 impl AppConfig {
-    pub fn conflict_strategy(&self) -> ConflictStrategy {
-        match self.conflict_strategy.as_deref() {
-            Some("overwrite") => ConflictStrategy::Overwrite,
-            Some("skip") => ConflictStrategy::Skip,
-            _ => ConflictStrategy::Rename,
+    /// 非空的钩子命令
+    pub fn hook_command(&self) -> Option<&str> {
+        self.hook_command.as_deref().map(str::trim).filter(|s| !s.is_empty())
+    }
+
+    /// 按名称取转换方案；未指定或为 default 且未自定义时，由顶层的输出目录、冲突策略与容器生成
+    pub fn profile(&self, name: Option<&str>) -> Result<Profile, ConfigError> {
+        let name = name.unwrap_or(DEFAULT_PROFILE);
+        if let Some(p) = self.profiles.iter().find(|p| p.name == name) {
+            return Ok(p.clone());
+        }
+        if name != DEFAULT_PROFILE {
+            return Err(ConfigError::UnknownProfile(name.to_string()));
+        }
+        Ok(Profile {
+            output_dir: self.output_dir.clone(),
+            conflict_strategy: self.conflict_strategy,
+            container: self.container,
+            ..Default::default()
+        })
+    }
+
+    /// 输出目录：调用方指定的目录（界面所选或 `--out`）优先，其次为方案中的目录，最后为顶层的默认输出目录
+    pub fn output_dir_for(&self, profile: &Profile, requested: Option<&str>) -> Option<String> {
+        requested
+            .map(str::trim)
+            .filter(|d| !d.is_empty())
+            .map(String::from)
+            .or_else(|| profile.output_dir.clone())
+            .or_else(|| self.output_dir.clone())
+    }
+
+    /// 新增方案，同名时替换
+    pub fn upsert_profile(&mut self, profile: Profile) {
+        match self.profiles.iter_mut().find(|p| p.name == profile.name) {
+            Some(existing) => *existing = profile,
+            None => self.profiles.push(profile),
+        }
+    }
+
+    /// 不妨碍保存、但需提示的问题：自定义的工具路径不存在（工具可能暂未安装或位于移动硬盘），
+    /// 转换时会改用自动查找的路径
+    pub fn warnings(&self) -> Vec<String> {
+        [("MP4Box", &self.mp4box_path), ("ffmpeg", &self.ffmpeg_path)]
+            .into_iter()
+            .filter_map(|(tool, path)| {
+                let path = path.as_deref()?;
+                (!Path::new(path).is_file()).then(|| format!("{} 路径不存在，将自动查找: {}", tool, path))
+            })
+            .collect()
+    }
+
+    /// 检查取值是否可用，返回全部问题而非首个
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let mut issues = Vec::new();
+        if self.version != CONFIG_VERSION {
+            issues.push(format!("版本应为 {}，实际为 {}", CONFIG_VERSION, self.version));
+        }
+        if let Some(dir) = &self.output_dir {
+            if !Path::new(dir).is_absolute() {
+                issues.push(format!("输出目录必须是绝对路径: {:?}", dir));
+            }
+        }
+        if !(1..=MAX_SCAN_DEPTH).contains(&self.scan_max_depth) {
+            issues.push(format!("扫描深度应在 1 到 {} 之间: {}", MAX_SCAN_DEPTH, self.scan_max_depth));
+        }
+        issues.extend(self.extra_args.issues());
+        match &self.quarantine_dir {
+            Some(dir) if !Path::new(dir).is_absolute() => {
+                issues.push(format!("隔离目录必须是绝对路径: {:?}", dir));
+            }
+            None if self.cleanup == CleanupMode::Quarantine => {
+                issues.push("清理方式为 quarantine 时需设置隔离目录 quarantine_dir".to_string());
+            }
+            _ => {}
+        }
+        for (i, profile) in self.profiles.iter().enumerate() {
+            if self.profiles[..i].iter().any(|p| p.name == profile.name) {
+                issues.push(format!("方案名重复: {}", profile.name));
+            }
+            issues.extend(profile.issues());
+        }
+        if issues.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError::Invalid(issues))
         }
     }
 }
 
-/// 配置文件路径：~/.config/bili2mp4/config.json
-pub fn get_config_path() -> Option<PathBuf> {
-    dirs::config_dir().map(|p| p.join("bili2mp4").join("config.json"))
+/// 读取配置；文件不存在时返回默认值，格式错误时返回错误而不是默认值，避免下次保存覆盖用户设置
+pub fn load_config() -> Result<AppConfig, ConfigError> {
+    match get_config_path() {
+        Some(path) => load_config_from(&path),
+        None => Ok(AppConfig::default()),
+    }
+}
+
+/// 从指定文件读取配置，旧版本自动迁移到当前版本（仅在内存中，下次保存时写回）
+pub fn load_config_from(path: &Path) -> Result<AppConfig, ConfigError> {
+    match read_config_value(path)? {
+        Some((value, _)) => Ok(serde_json::from_value(value)?),
+        None => Ok(AppConfig::default()),
+    }
 }
 
-pub fn load_config() -> AppConfig {
-    let path = match get_config_path() {
-        Some(p) => p,
-        None => return AppConfig::default(),
-    };
+/// 读取并迁移配置文件的原始 JSON，另返回迁移警告；文件不存在时返回 None
+pub(super) fn read_config_value(path: &Path) -> Result<Option<(serde_json::Value, Vec<String>)>, ConfigError> {
     if !path.exists() {
-        return AppConfig::default();
+        return Ok(None);
     }
-    match std::fs::read_to_string(&path) {
-        Ok(s) => serde_json::from_str(&s).unwrap_or_default(),
-        Err(_) => AppConfig::default(),
+    let text = std::fs::read_to_string(path)?;
+    let value: serde_json::Value = serde_json::from_str(&text)?;
+    Ok(Some(migrate(value)?))
+}
+
+pub fn save_config(config: &AppConfig) -> Result<(), ConfigError> {
+    match get_config_path() {
+        Some(path) => save_config_to(&path, config),
+        None => Ok(()),
     }
 }
 
-pub fn save_config(config: &AppConfig) -> std::io::Result<()> {
-    let path = match get_config_path() {
-        Some(p) => p,
-        None => return Ok(()),
-    };
+/// 校验后原子写入：先写同目录临时文件并落盘，旧文件备份为 `.bak`，再重命名替换
+pub fn save_config_to(path: &Path, config: &AppConfig) -> Result<(), ConfigError> {
+    config.validate()?;
     if let Some(parent) = path.parent() {
         std::fs::create_dir_all(parent)?;
     }
     let s = serde_json::to_string_pretty(config)?;
-    std::fs::write(path, s)
+    let tmp = path.with_extension("json.tmp");
+    {
+        use std::io::Write;
+        let mut f = std::fs::File::create(&tmp)?;
+        f.write_all(s.as_bytes())?;
+        f.sync_all()?;
+    }
+    if path.exists() {
+        std::fs::copy(path, path.with_extension("json.bak"))?;
+    }
+    std::fs::rename(&tmp, path)?;
+    Ok(())
 }
 
 /// Homebrew 常见路径（GUI .app 启动时 PATH 不含这些，需显式查找）
@@ -95,93 +274,155 @@ fn find_in_win_paths(mp4box: bool) -> Option<String> {
     None
 }
 
-/// 检测 MP4Box 路径：优先配置，其次 which/where，再平台路径，最后默认名称
+/// Linux 下 gpac / ffmpeg 可能所在目录：
+/// AppImage / Snap / Flatpak 打包时随应用捆绑的工具、应用同目录、系统目录、Snap 与 Flatpak 导出目录
+#[cfg(target_os = "linux")]
+fn linux_tool_dirs() -> Vec<PathBuf> {
+    let mut candidates = Vec::new();
+    for var in ["APPDIR", "SNAP"] {
+        if let Some(root) = std::env::var_os(var) {
+            candidates.push(PathBuf::from(root).join("usr/bin"));
+        }
+    }
+    candidates.push(PathBuf::from("/app/bin"));
+    if let Some(exe_dir) = std::env::current_exe().ok().and_then(|p| p.parent().map(PathBuf::from)) {
+        candidates.push(exe_dir);
+    }
+    if let Some(home) = dirs::home_dir() {
+        candidates.push(home.join(".local/bin"));
+        candidates.push(home.join(".local/share/flatpak/exports/bin"));
+    }
+    for base in ["/usr/local/bin", "/usr/bin", "/snap/bin", "/var/lib/flatpak/exports/bin"] {
+        candidates.push(PathBuf::from(base));
+    }
+    candidates
+}
+
+/// 按目录优先、名称次之查找；`names` 含 Snap 等打包方式下的别名
+#[cfg(target_os = "linux")]
+fn find_in_linux_paths(names: &[&str]) -> Option<String> {
+    for base in linux_tool_dirs() {
+        for name in names {
+            let path = base.join(name);
+            if crate::toolchain::is_executable(&path) {
+                return path.to_str().map(String::from);
+            }
+        }
+    }
+    None
+}
+
+/// 检测 MP4Box 路径：优先配置，其次 PATH，再平台路径，最后默认名称
 pub fn resolve_mp4box_path(config: &AppConfig) -> String {
     if let Some(ref p) = config.mp4box_path {
         if std::path::Path::new(p).exists() {
             return p.clone();
         }
     }
-    if let Ok(path) = which_mp4box() {
+    if let Some(path) = find_in_path("MP4Box") {
         return path;
     }
     #[cfg(target_os = "macos")]
     return find_in_brew_paths("MP4Box").unwrap_or_else(|| "MP4Box".to_string());
     #[cfg(target_os = "windows")]
     return find_in_win_paths(true).unwrap_or_else(|| "MP4Box.exe".to_string());
-    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    #[cfg(target_os = "linux")]
+    return find_in_linux_paths(&["MP4Box", "gpac.MP4Box", "gpac.mp4box"]).unwrap_or_else(|| "MP4Box".to_string());
+    #[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "linux")))]
     "MP4Box".to_string()
 }
 
-/// 检测 ffmpeg 路径（兜底转换用）
-pub fn resolve_ffmpeg_path() -> String {
-    if let Ok(path) = which_ffmpeg() {
+/// 检测 ffmpeg 路径（兜底转换用）：优先配置，其次 PATH，再平台路径，最后默认名称
+pub fn resolve_ffmpeg_path(config: &AppConfig) -> String {
+    if let Some(ref p) = config.ffmpeg_path {
+        if std::path::Path::new(p).exists() {
+            return p.clone();
+        }
+    }
+    if let Some(path) = find_in_path("ffmpeg") {
         return path;
     }
     #[cfg(target_os = "macos")]
     return find_in_brew_paths("ffmpeg").unwrap_or_else(|| "ffmpeg".to_string());
     #[cfg(target_os = "windows")]
     return find_in_win_paths(false).unwrap_or_else(|| "ffmpeg.exe".to_string());
-    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    #[cfg(target_os = "linux")]
+    return find_in_linux_paths(&["ffmpeg", "ffmpeg.ffmpeg"]).unwrap_or_else(|| "ffmpeg".to_string());
+    #[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "linux")))]
     "ffmpeg".to_string()
 }
 
-#[cfg(unix)]
-fn which_mp4box() -> Result<String, std::io::Error> {
-    let output = std::process::Command::new("which").arg("MP4Box").output()?;
-    if output.status.success() {
-        let s = String::from_utf8_lossy(&output.stdout).trim().to_string();
-        if !s.is_empty() {
-            return Ok(s);
-        }
-    }
-    Err(std::io::Error::new(
-        std::io::ErrorKind::NotFound,
-        "MP4Box not found",
-    ))
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-#[cfg(windows)]
-fn which_mp4box() -> Result<String, std::io::Error> {
-    let output = std::process::Command::new("where").arg("MP4Box").output()?;
-    if output.status.success() {
-        let s = String::from_utf8_lossy(&output.stdout).lines().next().unwrap_or("").trim().to_string();
-        if !s.is_empty() {
-            return Ok(s);
-        }
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(name);
+        std::fs::remove_dir_all(&dir).ok();
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
     }
-    Err(std::io::Error::new(
-        std::io::ErrorKind::NotFound,
-        "MP4Box not found",
-    ))
-}
 
-#[cfg(unix)]
-fn which_ffmpeg() -> Result<String, std::io::Error> {
-    let output = std::process::Command::new("which").arg("ffmpeg").output()?;
-    if output.status.success() {
-        let s = String::from_utf8_lossy(&output.stdout).trim().to_string();
-        if !s.is_empty() {
-            return Ok(s);
-        }
+    #[test]
+    fn test_load_malformed_is_error() {
+        let dir = temp_dir("bili2mp4_config_malformed");
+        let path = dir.join("config.json");
+        std::fs::write(&path, "{ not json").unwrap();
+        assert!(matches!(load_config_from(&path), Err(ConfigError::Parse(_))));
+        std::fs::write(&path, r#"{"version": 2, "conflict_strategy": "explode"}"#).unwrap();
+        assert!(matches!(load_config_from(&path), Err(ConfigError::Parse(_))));
+        std::fs::write(&path, r#"{"version": 99}"#).unwrap();
+        assert!(matches!(load_config_from(&path), Err(ConfigError::UnsupportedVersion(99))));
+        std::fs::remove_dir_all(&dir).ok();
     }
-    Err(std::io::Error::new(
-        std::io::ErrorKind::NotFound,
-        "ffmpeg not found",
-    ))
-}
 
-#[cfg(windows)]
-fn which_ffmpeg() -> Result<String, std::io::Error> {
-    let output = std::process::Command::new("where").arg("ffmpeg").output()?;
-    if output.status.success() {
-        let s = String::from_utf8_lossy(&output.stdout).lines().next().unwrap_or("").trim().to_string();
-        if !s.is_empty() {
-            return Ok(s);
-        }
+    #[test]
+    fn test_save_is_atomic_with_backup() {
+        let dir = temp_dir("bili2mp4_config_save");
+        let path = dir.join("config.json");
+        let mut config = AppConfig {
+            output_dir: Some(dir.to_string_lossy().into_owned()),
+            ..Default::default()
+        };
+        save_config_to(&path, &config).unwrap();
+        assert!(!path.with_extension("json.bak").exists());
+
+        config.conflict_strategy = ConflictStrategy::Skip;
+        config.container = Container::Mkv;
+        save_config_to(&path, &config).unwrap();
+        let backup = load_config_from(&path.with_extension("json.bak")).unwrap();
+        assert_eq!(backup.conflict_strategy, ConflictStrategy::Rename);
+        assert_eq!(load_config_from(&path).unwrap().conflict_strategy, ConflictStrategy::Skip);
+        assert!(!path.with_extension("json.tmp").exists());
+
+        config.upsert_profile(Profile {
+            name: "phone".into(),
+            backends: vec![crate::convert::Backend::Ffmpeg],
+            ..Default::default()
+        });
+        save_config_to(&path, &config).unwrap();
+        let loaded = load_config_from(&path).unwrap();
+        assert_eq!(loaded.profile(Some("phone")).unwrap().backends.len(), 1);
+        assert_eq!(loaded.profile(None).unwrap().conflict_strategy, ConflictStrategy::Skip);
+        assert_eq!(loaded.profile(None).unwrap().container, Container::Mkv);
+        assert!(matches!(loaded.profile(Some("tv")), Err(ConfigError::UnknownProfile(_))));
+
+        // 调用方指定的目录优先，其次为方案中的目录，最后为顶层的输出目录
+        let mut nas = Profile { name: "nas".into(), output_dir: Some("/nas".into()), ..Default::default() };
+        assert_eq!(config.output_dir_for(&nas, Some("/gui")).as_deref(), Some("/gui"));
+        assert_eq!(config.output_dir_for(&nas, Some(" ")).as_deref(), Some("/nas"));
+        assert_eq!(config.output_dir_for(&nas, None).as_deref(), Some("/nas"));
+        nas.output_dir = None;
+        assert_eq!(config.output_dir_for(&nas, None), config.output_dir);
+
+        // 工具路径不存在只提示，不阻止保存
+        config.mp4box_path = Some(dir.join("MP4Box").to_string_lossy().into_owned());
+        save_config_to(&path, &config).unwrap();
+        assert_eq!(config.warnings().len(), 1);
+
+        config.output_dir = Some("relative/out".into());
+        assert!(matches!(save_config_to(&path, &config), Err(ConfigError::Invalid(_))));
+        assert_eq!(load_config_from(&path).unwrap().conflict_strategy, ConflictStrategy::Skip);
+        std::fs::remove_dir_all(&dir).ok();
     }
-    Err(std::io::Error::new(
-        std::io::ErrorKind::NotFound,
-        "ffmpeg not found",
-    ))
 }
@@ -0,0 +1,152 @@
+//! 转换方案：输出目录、文件名模板、冲突策略、后端顺序、容器、附属文件、转码与分段设置的命名组合
+
+use super::preferences::{ConfigError, CONFIG_VERSION};
+use crate::convert::{Backend, Container, SplitLimit, TranscodePolicy, DEFAULT_CHAIN};
+use crate::filemgr::{check_template, ConflictStrategy, OutputLayout, Sidecars};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// 未指定方案时使用的名称，对应配置顶层的 output_dir / conflict_strategy / container
+pub const DEFAULT_PROFILE: &str = "default";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Profile {
+    pub name: String,
+    /// 输出目录；调用方传入的目录优先，见 [`AppConfig::output_dir_for`]
+    ///
+    /// [`AppConfig::output_dir_for`]: super::AppConfig::output_dir_for
+    pub output_dir: Option<String>,
+    /// 文件名模板，`/` 分隔子目录，占位符见 [`crate::filemgr::TEMPLATE_FIELDS`]
+    pub naming_template: String,
+    pub conflict_strategy: ConflictStrategy,
+    /// 后端尝试顺序
+    pub backends: Vec<Backend>,
+    pub container: Container,
+    pub sidecars: Sidecars,
+    /// 输出目录结构；为媒体服务器结构时不使用文件名模板
+    pub layout: OutputLayout,
+    /// 何时及如何转码；默认只做无损封装
+    pub transcode: TranscodePolicy,
+    /// 输出超过大小或时长上限时无损分段；默认不分段
+    pub split: SplitLimit,
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Self {
+            name: DEFAULT_PROFILE.to_string(),
+            output_dir: None,
+            naming_template: "{title}".to_string(),
+            conflict_strategy: ConflictStrategy::default(),
+            backends: DEFAULT_CHAIN.to_vec(),
+            container: Container::default(),
+            sidecars: Sidecars::default(),
+            layout: OutputLayout::default(),
+            transcode: TranscodePolicy::default(),
+            split: SplitLimit::default(),
+        }
+    }
+}
+
+impl Profile {
+    /// 校验问题列表，消息带方案名前缀
+    pub fn issues(&self) -> Vec<String> {
+        let mut issues = Vec::new();
+        let prefix = format!("方案 {:?}", self.name);
+        if self.name.trim().is_empty() {
+            issues.push("方案名不能为空".to_string());
+        }
+        if let Some(dir) = &self.output_dir {
+            if !Path::new(dir).is_absolute() {
+                issues.push(format!("{}: 输出目录必须是绝对路径: {:?}", prefix, dir));
+            }
+        }
+        if let Err(e) = check_template(&self.naming_template) {
+            issues.push(format!("{}: {}", prefix, e));
+        }
+        if self.backends.is_empty() {
+            issues.push(format!("{}: 至少需要一个转换后端", prefix));
+        }
+        for (i, b) in self.backends.iter().enumerate() {
+            if self.backends[..i].contains(b) {
+                issues.push(format!("{}: 后端 {} 重复", prefix, b.label()));
+            }
+        }
+        if !self.backends.is_empty() && !self.backends.iter().any(|&b| self.container.written_by(b)) {
+            issues.push(format!("{}: 所选后端都无法写出 {}，需要 ffmpeg", prefix, self.container.label()));
+        }
+        for issue in self.transcode.issues().into_iter().chain(self.split.issues()) {
+            issues.push(format!("{}: {}", prefix, issue));
+        }
+        if self.split.is_set() && !self.container.splittable() {
+            issues.push(format!("{}: {} 输出不支持分段", prefix, self.container.label()));
+        }
+        issues
+    }
+}
+
+/// 导出文件格式：方案字段外加版本号，便于日后迁移
+#[derive(Serialize, Deserialize)]
+struct ProfileFile {
+    version: u32,
+    #[serde(flatten)]
+    profile: Profile,
+}
+
+pub fn export_profile(profile: &Profile, path: &Path) -> Result<(), ConfigError> {
+    let file = ProfileFile {
+        version: CONFIG_VERSION,
+        profile: profile.clone(),
+    };
+    std::fs::write(path, serde_json::to_string_pretty(&file)?)?;
+    Ok(())
+}
+
+/// 读取并校验导出的方案文件
+pub fn import_profile(path: &Path) -> Result<Profile, ConfigError> {
+    let file: ProfileFile = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+    if file.version > CONFIG_VERSION {
+        return Err(ConfigError::UnsupportedVersion(file.version));
+    }
+    let issues = file.profile.issues();
+    if !issues.is_empty() {
+        return Err(ConfigError::Invalid(issues));
+    }
+    Ok(file.profile)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_export_import_roundtrip() {
+        let tmp = std::env::temp_dir().join("bili2mp4_profile_test");
+        std::fs::create_dir_all(&tmp).unwrap();
+        let path = tmp.join("phone.json");
+        let phone = Profile {
+            name: "phone".into(),
+            naming_template: "{bvid}-{page}".into(),
+            conflict_strategy: ConflictStrategy::Skip,
+            backends: vec![Backend::Ffmpeg],
+            container: Container::Mkv,
+            sidecars: Sidecars { danmaku: false, cover: true },
+            ..Default::default()
+        };
+        export_profile(&phone, &path).unwrap();
+        assert_eq!(import_profile(&path).unwrap(), phone);
+
+        std::fs::write(&path, r#"{"version": 2, "name": "bad", "naming_template": "{oops}", "backends": []}"#).unwrap();
+        match import_profile(&path) {
+            Err(ConfigError::Invalid(issues)) => assert_eq!(issues.len(), 2),
+            other => panic!("unexpected: {:?}", other),
+        }
+        let mov = Profile { backends: vec![Backend::Mp4Box], container: Container::Mov, ..phone };
+        assert!(mov.issues()[0].contains("无法写出 MOV"));
+        let split = SplitLimit { max_bytes: Some(crate::convert::FAT32_MAX_BYTES), max_seconds: None };
+        let mkv = Profile { split, backends: vec![Backend::Ffmpeg], container: Container::Mkv, ..mov };
+        assert_eq!(mkv.issues(), vec!["方案 \"phone\": MKV 输出不支持分段".to_string()]);
+        std::fs::remove_dir_all(&tmp).ok();
+    }
+}
@@ -0,0 +1,476 @@
+//! 外部工具命令行参数：固定参数、用户附加参数的校验与插入，以及命令预览
+
+use super::pipeline::{Backend, Container};
+use super::transcode::{TranscodePreset, TranscodeSettings};
+use super::trim::TrimRange;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// 各后端的附加参数，每个元素为一个独立参数（不经 shell 解析）
+///
+/// MP4Box 插入在 `-new` 之前，ffmpeg 插入在输出路径之前（可覆盖默认的 -movflags）
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExtraArgs {
+    pub mp4box: Vec<String>,
+    pub mp4box_raw: Vec<String>,
+    pub ffmpeg: Vec<String>,
+}
+
+/// 由程序管理、不允许通过附加参数修改的选项（输入、输出、覆盖、格式、流选择与拆分）
+const MP4BOX_RESERVED: [&str; 4] = ["-add", "-cat", "-new", "-out"];
+const MP4BOX_RESERVED_PREFIX: &str = "-split";
+const FFMPEG_RESERVED: [&str; 5] = ["-i", "-y", "-n", "-f", "-map"];
+/// 不带取值的常用 ffmpeg 选项，其后的非选项参数会被 ffmpeg 当作额外的输出文件
+const FFMPEG_FLAGS: [&str; 7] = ["-an", "-vn", "-sn", "-dn", "-shortest", "-hide_banner", "-nostdin"];
+
+impl ExtraArgs {
+    pub fn for_backend(&self, backend: Backend) -> &[String] {
+        match backend {
+            Backend::Mp4Box => &self.mp4box,
+            Backend::Mp4BoxRaw => &self.mp4box_raw,
+            Backend::Ffmpeg => &self.ffmpeg,
+        }
+    }
+
+    /// 校验问题列表
+    pub fn issues(&self) -> Vec<String> {
+        let mut issues = Vec::new();
+        for backend in [Backend::Mp4Box, Backend::Mp4BoxRaw, Backend::Ffmpeg] {
+            let args = self.for_backend(backend);
+            if let Some(first) = args.first() {
+                if !first.starts_with('-') {
+                    issues.push(format!("{} 附加参数须以选项开头: {:?}", backend.label(), first));
+                }
+            }
+            for (i, arg) in args.iter().enumerate() {
+                if arg.is_empty() || arg.contains('\0') {
+                    issues.push(format!("{} 附加参数不能为空或含 NUL", backend.label()));
+                } else if is_reserved(backend, arg) {
+                    issues.push(format!("{} 附加参数不允许 {}（由程序管理）", backend.label(), arg));
+                } else if backend == Backend::Ffmpeg && i > 0 && is_extra_output(&args[i - 1], arg) {
+                    issues.push(format!("{} 附加参数不允许额外的输出文件: {:?}", backend.label(), arg));
+                }
+            }
+        }
+        issues
+    }
+}
+
+fn is_reserved(backend: Backend, arg: &str) -> bool {
+    match backend {
+        Backend::Mp4Box | Backend::Mp4BoxRaw => {
+            MP4BOX_RESERVED.contains(&arg) || arg.starts_with(MP4BOX_RESERVED_PREFIX)
+        }
+        Backend::Ffmpeg => FFMPEG_RESERVED.contains(&arg),
+    }
+}
+
+/// ffmpeg 的选项最多带一个取值，紧跟在取值或无值选项之后的非选项参数即为额外的输出文件
+fn is_extra_output(prev: &str, arg: &str) -> bool {
+    !arg.starts_with('-') && (!prev.starts_with('-') || FFMPEG_FLAGS.contains(&prev))
+}
+
+/// ffmpeg 按容器设置的输出选项：MP4 / MOV 把 moov 移到开头，分片 MP4 按关键帧分片，MKV 无需设置
+fn ffmpeg_container_args(container: Container) -> Vec<String> {
+    let flags = match container {
+        Container::Mp4 | Container::Mov => "+faststart",
+        Container::Fmp4 => "+frag_keyframe+empty_moov+default_base_moof",
+        Container::Mkv => return Vec::new(),
+    };
+    vec!["-movflags".to_string(), flags.to_string()]
+}
+
+/// MP4Box 参数：`-add v#video -add a#audio [-frag 1000] [附加] -new out -itags tool=Bili2MP4`，
+/// 分片 MP4 每秒一个分片
+pub fn mp4box_args(
+    video: &Path,
+    audio: &Path,
+    output: &Path,
+    container: Container,
+    raw: bool,
+    extra: &[String],
+) -> Vec<String> {
+    let suffix = if raw { ":raw" } else { "" };
+    let mut args = vec![
+        "-add".to_string(),
+        format!("{}#video{}", video.to_string_lossy(), suffix),
+        "-add".to_string(),
+        format!("{}#audio{}", audio.to_string_lossy(), suffix),
+    ];
+    if container == Container::Fmp4 {
+        args.extend(["-frag".to_string(), "1000".to_string()]);
+    }
+    args.extend(extra.iter().cloned());
+    args.extend([
+        "-new".to_string(),
+        output.to_string_lossy().into_owned(),
+        "-itags".to_string(),
+        "tool=Bili2MP4".to_string(),
+    ]);
+    args
+}
+
+/// ffmpeg 参数：`-y -i v -i a -c copy [容器选项] [附加] out`，容器由输出扩展名决定
+pub fn ffmpeg_args(video: &Path, audio: &Path, output: &Path, container: Container, extra: &[String]) -> Vec<String> {
+    let mut args: Vec<String> = ["-y", "-i"].map(String::from).to_vec();
+    args.push(video.to_string_lossy().into_owned());
+    args.push("-i".to_string());
+    args.push(audio.to_string_lossy().into_owned());
+    args.extend(["-c", "copy"].map(String::from));
+    args.extend(ffmpeg_container_args(container));
+    args.extend(extra.iter().cloned());
+    args.push(output.to_string_lossy().into_owned());
+    args
+}
+
+/// MP4Box 拼接参数：`-add 第一段 -cat 第二段 ... -chap 章节文件 -new out -itags tool=Bili2MP4`
+pub fn mp4box_concat_args(parts: &[PathBuf], chapters: &Path, output: &Path) -> Vec<String> {
+    let mut args = Vec::new();
+    for (i, part) in parts.iter().enumerate() {
+        args.push(if i == 0 { "-add" } else { "-cat" }.to_string());
+        args.push(part.to_string_lossy().into_owned());
+    }
+    args.extend([
+        "-chap".to_string(),
+        chapters.to_string_lossy().into_owned(),
+        "-new".to_string(),
+        output.to_string_lossy().into_owned(),
+        "-itags".to_string(),
+        "tool=Bili2MP4".to_string(),
+    ]);
+    args
+}
+
+/// ffmpeg 拼接参数：concat 列表为输入，章节取自 ffmetadata 文件
+pub fn ffmpeg_concat_args(list: &Path, metadata: &Path, output: &Path, container: Container) -> Vec<String> {
+    let mut args: Vec<String> = ["-y", "-f", "concat", "-safe", "0", "-i"].map(String::from).to_vec();
+    args.push(list.to_string_lossy().into_owned());
+    args.push("-i".to_string());
+    args.push(metadata.to_string_lossy().into_owned());
+    args.extend(
+        ["-map", "0", "-map_metadata", "1", "-map_chapters", "1", "-c", "copy"].map(String::from),
+    );
+    args.extend(ffmpeg_container_args(container));
+    args.push(output.to_string_lossy().into_owned());
+    args
+}
+
+/// 毫秒转为命令行使用的秒数，如 `61.500`
+fn seconds(ms: u64) -> String {
+    format!("{}.{:03}", ms / 1000, ms % 1000)
+}
+
+/// MP4Box 截取参数：`-splitx 开始:结束 in -out out`，结束为空时截到末尾
+pub fn mp4box_cut_args(input: &Path, start_ms: u64, end_ms: Option<u64>, output: &Path) -> Vec<String> {
+    let end = end_ms.map(seconds).unwrap_or_else(|| "end".to_string());
+    vec![
+        "-splitx".to_string(),
+        format!("{}:{}", seconds(start_ms), end),
+        input.to_string_lossy().into_owned(),
+        "-out".to_string(),
+        output.to_string_lossy().into_owned(),
+    ]
+}
+
+/// ffmpeg 截取参数：`-ss`/`-to` 作为输入选项按关键帧定位，`-c copy` 不重新编码
+pub fn ffmpeg_cut_args(
+    input: &Path,
+    start_ms: u64,
+    end_ms: Option<u64>,
+    output: &Path,
+    container: Container,
+) -> Vec<String> {
+    let mut args = vec!["-y".to_string(), "-ss".to_string(), seconds(start_ms)];
+    if let Some(end) = end_ms {
+        args.extend(["-to".to_string(), seconds(end)]);
+    }
+    args.extend(["-i".to_string(), input.to_string_lossy().into_owned()]);
+    args.extend(
+        ["-map", "0", "-c", "copy", "-avoid_negative_ts", "make_zero"].map(String::from),
+    );
+    args.extend(ffmpeg_container_args(container));
+    args.push(output.to_string_lossy().into_owned());
+    args
+}
+
+/// 视频、音频两路输入；截取时 `-ss`/`-to` 作为各自的输入选项
+fn ffmpeg_inputs(video: &Path, audio: &Path, range: Option<&TrimRange>) -> Vec<String> {
+    let mut args = Vec::new();
+    for input in [video, audio] {
+        if let Some(range) = range {
+            args.extend(["-ss".to_string(), seconds(range.start_ms)]);
+            if let Some(end) = range.end_ms {
+                args.extend(["-to".to_string(), seconds(end)]);
+            }
+        }
+        args.extend(["-i".to_string(), input.to_string_lossy().into_owned()]);
+    }
+    args
+}
+
+/// ffmpeg 直接从源文件截取：两路输入按范围定位，`-c copy` 不重新编码，不需要中间文件
+pub fn ffmpeg_trim_args(
+    video: &Path,
+    audio: &Path,
+    range: &TrimRange,
+    output: &Path,
+    container: Container,
+    extra: &[String],
+) -> Vec<String> {
+    let mut args = vec!["-y".to_string()];
+    args.extend(ffmpeg_inputs(video, audio, Some(range)));
+    args.extend(
+        ["-map", "0:v:0", "-map", "1:a:0", "-c", "copy", "-avoid_negative_ts", "make_zero"].map(String::from),
+    );
+    args.extend(ffmpeg_container_args(container));
+    args.extend(extra.iter().cloned());
+    args.push(output.to_string_lossy().into_owned());
+    args
+}
+
+/// ffmpeg 转码参数：按预设选择编码器，`-progress pipe:1` 输出进度；截取时 `-ss`/`-to` 作为两路输入的选项；
+/// `copy_audio` 时音频已是 AAC，直接复制
+#[allow(clippy::too_many_arguments)]
+pub fn ffmpeg_transcode_args(
+    video: &Path,
+    audio: &Path,
+    output: &Path,
+    container: Container,
+    settings: &TranscodeSettings,
+    range: Option<&TrimRange>,
+    copy_audio: bool,
+    extra: &[String],
+) -> Vec<String> {
+    let mut args: Vec<String> = ["-y", "-nostats", "-progress", "pipe:1"].map(String::from).to_vec();
+    args.extend(ffmpeg_inputs(video, audio, range));
+    args.extend(["-map", "0:v:0", "-map", "1:a:0"].map(String::from));
+    let video_args: &[&str] = match settings.preset {
+        TranscodePreset::H264Compat => &["-c:v", "libx264", "-preset", "medium", "-profile:v", "high", "-pix_fmt", "yuv420p"],
+        TranscodePreset::SmallHevc => &["-c:v", "libx265", "-preset", "medium"],
+        TranscodePreset::AudioAac => &["-c:v", "copy"],
+    };
+    args.extend(video_args.iter().map(|a| a.to_string()));
+    // Apple 设备只播放标记为 hvc1 的 HEVC；MKV 不使用 fourcc
+    if settings.preset == TranscodePreset::SmallHevc && container != Container::Mkv {
+        args.extend(["-tag:v", "hvc1"].map(String::from));
+    }
+    if settings.preset.encodes_video() {
+        match (settings.video_kbps, settings.crf.or(settings.preset.default_crf())) {
+            (Some(kbps), _) => args.extend(["-b:v".to_string(), format!("{}k", kbps)]),
+            (None, Some(crf)) => args.extend(["-crf".to_string(), crf.to_string()]),
+            (None, None) => {}
+        }
+    }
+    if copy_audio {
+        args.extend(["-c:a", "copy"].map(String::from));
+    } else {
+        let audio_kbps = settings.audio_kbps.unwrap_or(TranscodeSettings::DEFAULT_AUDIO_KBPS);
+        args.extend(["-c:a".to_string(), "aac".to_string(), "-b:a".to_string(), format!("{}k", audio_kbps)]);
+    }
+    args.extend(ffmpeg_container_args(container));
+    args.extend(extra.iter().cloned());
+    args.push(output.to_string_lossy().into_owned());
+    args
+}
+
+/// 某后端将执行的完整参数
+pub fn backend_args(
+    backend: Backend,
+    video: &Path,
+    audio: &Path,
+    output: &Path,
+    container: Container,
+    extra: &ExtraArgs,
+) -> Vec<String> {
+    let extra = extra.for_backend(backend);
+    match backend {
+        Backend::Mp4Box => mp4box_args(video, audio, output, container, false, extra),
+        Backend::Mp4BoxRaw => mp4box_args(video, audio, output, container, true, extra),
+        Backend::Ffmpeg => ffmpeg_args(video, audio, output, container, extra),
+    }
+}
+
+/// 某后端的命令预览
+#[derive(Debug, Clone, Serialize)]
+pub struct CommandPreview {
+    pub backend: Backend,
+    pub command: String,
+}
+
+/// 拼接为可读的命令行，含空白或引号的参数按当前平台的 shell 规则加引号，仅用于展示
+pub fn command_line(program: &str, args: &[String]) -> String {
+    std::iter::once(program)
+        .chain(args.iter().map(String::as_str))
+        .map(quote)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn is_plain(arg: &str, safe: &str) -> bool {
+    !arg.is_empty() && arg.chars().all(|c| c.is_alphanumeric() || safe.contains(c) || !c.is_ascii())
+}
+
+/// POSIX shell：单引号包裹，内部的单引号写作 `'\''`
+#[cfg(not(windows))]
+fn quote(arg: &str) -> String {
+    if is_plain(arg, "-_./:#+=,@%") {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', r"'\''"))
+    }
+}
+
+/// Windows：双引号包裹，按 CommandLineToArgvW 的规则转义引号及其前面的反斜杠
+#[cfg(windows)]
+fn quote(arg: &str) -> String {
+    if is_plain(arg, r"-_./\:#+=,@") {
+        return arg.to_string();
+    }
+    let mut quoted = String::from('"');
+    let mut backslashes = 0;
+    for c in arg.chars() {
+        match c {
+            '\\' => backslashes += 1,
+            '"' => {
+                quoted.push_str(&"\\".repeat(backslashes * 2 + 1));
+                quoted.push(c);
+                backslashes = 0;
+            }
+            _ => {
+                quoted.push_str(&"\\".repeat(backslashes));
+                quoted.push(c);
+                backslashes = 0;
+            }
+        }
+    }
+    quoted.push_str(&"\\".repeat(backslashes * 2));
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extra_args_inserted_before_output() {
+        let extra = ExtraArgs {
+            mp4box: vec!["-brand".into(), "mp42".into()],
+            ffmpeg: vec!["-map_metadata".into(), "-1".into()],
+            ..Default::default()
+        };
+        let (v, a, o) = (Path::new("/c/video.m4s"), Path::new("/c/audio.m4s"), Path::new("/out/a b.mp4"));
+        let args = backend_args(Backend::Mp4Box, v, a, o, Container::Mp4, &extra);
+        assert_eq!(
+            command_line("MP4Box", &args),
+            format!(
+                "MP4Box -add /c/video.m4s#video -add /c/audio.m4s#audio -brand mp42 -new {} -itags tool=Bili2MP4",
+                quote("/out/a b.mp4")
+            )
+        );
+        let args = backend_args(Backend::Ffmpeg, v, a, o, Container::Mp4, &extra);
+        assert_eq!(&args[args.len() - 3..], ["-map_metadata", "-1", "/out/a b.mp4"]);
+        assert!(extra.issues().is_empty());
+    }
+
+    #[test]
+    fn test_container_args() {
+        let (v, a) = (Path::new("/c/video.m4s"), Path::new("/c/audio.m4s"));
+        let extra = ExtraArgs::default();
+        let args = backend_args(Backend::Mp4Box, v, a, Path::new("/out/a.mp4"), Container::Fmp4, &extra);
+        assert!(command_line("MP4Box", &args).contains("#audio -frag 1000 -new /out/a.mp4"));
+        let args = backend_args(Backend::Ffmpeg, v, a, Path::new("/out/a.mp4"), Container::Fmp4, &extra);
+        assert!(command_line("ffmpeg", &args).ends_with("-movflags +frag_keyframe+empty_moov+default_base_moof /out/a.mp4"));
+        let args = backend_args(Backend::Ffmpeg, v, a, Path::new("/out/a.mkv"), Container::Mkv, &extra);
+        assert!(command_line("ffmpeg", &args).ends_with("-c copy /out/a.mkv"));
+
+        let hevc = TranscodeSettings { preset: TranscodePreset::SmallHevc, ..Default::default() };
+        let args = ffmpeg_transcode_args(v, a, Path::new("/out/a.mkv"), Container::Mkv, &hevc, None, false, &[]);
+        assert!(!args.iter().any(|a| a == "hvc1" || a == "-movflags"));
+    }
+
+    #[test]
+    fn test_reserved_args_rejected() {
+        let extra = ExtraArgs {
+            mp4box: vec!["-new".into(), "/tmp/x.mp4".into()],
+            mp4box_raw: vec!["-splits".into(), "100".into()],
+            ffmpeg: vec!["out.mkv".into(), "".into()],
+        };
+        assert_eq!(extra.issues().len(), 4);
+
+        let extra = ExtraArgs {
+            ffmpeg: vec!["-f".into(), "matroska".into(), "-map".into(), "0:s".into()],
+            ..Default::default()
+        };
+        assert_eq!(extra.issues().len(), 2);
+        let extra = ExtraArgs {
+            ffmpeg: vec!["-metadata".into(), "title=a".into(), "extra.mp4".into(), "-an".into(), "b.mp4".into()],
+            ..Default::default()
+        };
+        assert_eq!(extra.issues().len(), 2);
+        let extra = ExtraArgs {
+            ffmpeg: vec!["-map_metadata".into(), "-1".into(), "-an".into(), "-metadata".into(), "a=b".into()],
+            ..Default::default()
+        };
+        assert!(extra.issues().is_empty());
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_quote_posix() {
+        assert_eq!(quote("/out/a b.mp4"), "'/out/a b.mp4'");
+        assert_eq!(quote("it's"), r"'it'\''s'");
+        assert_eq!(quote(""), "''");
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_quote_windows() {
+        assert_eq!(quote(r"C:\out\a.mp4"), r"C:\out\a.mp4");
+        assert_eq!(quote(r"C:\out\a b.mp4"), r#""C:\out\a b.mp4""#);
+        assert_eq!(quote(r#"say "hi""#), r#""say \"hi\"""#);
+        assert_eq!(quote(r"C:\my dir\"), r#""C:\my dir\\""#);
+        assert_eq!(quote(""), r#""""#);
+    }
+
+    #[test]
+    fn test_transcode_args() {
+        let (v, a, o) = (Path::new("/c/video.m4s"), Path::new("/c/audio.m4s"), Path::new("/out/a.mp4"));
+        let hevc = TranscodeSettings { preset: TranscodePreset::SmallHevc, crf: Some(26), ..Default::default() };
+        assert_eq!(
+            command_line("ffmpeg", &ffmpeg_transcode_args(v, a, o, Container::Mp4, &hevc, None, false, &[])),
+            "ffmpeg -y -nostats -progress pipe:1 -i /c/video.m4s -i /c/audio.m4s -map 0:v:0 -map 1:a:0 \
+             -c:v libx265 -preset medium -tag:v hvc1 -crf 26 -c:a aac -b:a 192k -movflags +faststart /out/a.mp4"
+        );
+        let range = TrimRange { start_ms: 5_000, end_ms: Some(9_000) };
+        let aac = TranscodeSettings { preset: TranscodePreset::AudioAac, audio_kbps: Some(128), ..Default::default() };
+        let args = ffmpeg_transcode_args(v, a, o, Container::Mp4, &aac, Some(&range), false, &[]);
+        assert_eq!(args.iter().filter(|a| *a == "-ss").count(), 2);
+        assert!(command_line("ffmpeg", &args).contains("-c:v copy -c:a aac -b:a 128k"));
+        let extra = ["-threads".to_string(), "2".to_string()];
+        let args = ffmpeg_transcode_args(v, a, o, Container::Mp4, &hevc, None, true, &extra);
+        assert!(command_line("ffmpeg", &args).ends_with("-crf 26 -c:a copy -movflags +faststart -threads 2 /out/a.mp4"));
+    }
+
+    #[test]
+    fn test_cut_args() {
+        let (i, o) = (Path::new("/t/full.mp4"), Path::new("/out/a.mp4"));
+        assert_eq!(
+            command_line("MP4Box", &mp4box_cut_args(i, 61_500, None, o)),
+            "MP4Box -splitx 61.500:end /t/full.mp4 -out /out/a.mp4"
+        );
+        assert_eq!(
+            command_line("ffmpeg", &ffmpeg_cut_args(i, 0, Some(180_040), o, Container::Mp4)),
+            "ffmpeg -y -ss 0.000 -to 180.040 -i /t/full.mp4 -map 0 -c copy -avoid_negative_ts make_zero \
+             -movflags +faststart /out/a.mp4"
+        );
+        let (v, a) = (Path::new("/c/video.m4s"), Path::new("/c/audio.m4s"));
+        let range = TrimRange { start_ms: 5_000, end_ms: None };
+        assert_eq!(
+            command_line("ffmpeg", &ffmpeg_trim_args(v, a, &range, o, Container::Mkv, &["-metadata".into(), "a=b".into()])),
+            "ffmpeg -y -ss 5.000 -i /c/video.m4s -ss 5.000 -i /c/audio.m4s -map 0:v:0 -map 1:a:0 -c copy \
+             -avoid_negative_ts make_zero -metadata a=b /out/a.mp4"
+        );
+    }
+}
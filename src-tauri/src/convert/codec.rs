@@ -0,0 +1,659 @@
+//! 读取 m4s 初始化段（moov/trak/.../stsd）识别编码格式，及 sidx / moof 中的关键帧位置，无需外部工具
+
+use super::matroska::{self, is_matroska};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// moov、moof 等 box 的读取上限，超出视为异常文件
+const MAX_BOX_SIZE: u64 = 16 * 1024 * 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Codec {
+    Avc,
+    Hevc,
+    Av1,
+    Aac,
+    Flac,
+    Ac3,
+    Eac3,
+    Opus,
+    /// 其他 sample entry，保留原始 fourcc
+    Other(String),
+}
+
+impl Codec {
+    fn from_fourcc(fourcc: &[u8]) -> Self {
+        match fourcc {
+            b"avc1" | b"avc3" => Codec::Avc,
+            b"hev1" | b"hvc1" => Codec::Hevc,
+            b"av01" => Codec::Av1,
+            b"mp4a" => Codec::Aac,
+            b"fLaC" => Codec::Flac,
+            b"ac-3" => Codec::Ac3,
+            b"ec-3" => Codec::Eac3,
+            b"Opus" => Codec::Opus,
+            other => Codec::Other(String::from_utf8_lossy(other).into_owned()),
+        }
+    }
+
+    pub fn is_video(&self) -> bool {
+        matches!(self, Codec::Avc | Codec::Hevc | Codec::Av1)
+    }
+
+    pub fn label(&self) -> &str {
+        match self {
+            Codec::Avc => "H.264",
+            Codec::Hevc => "HEVC",
+            Codec::Av1 => "AV1",
+            Codec::Aac => "AAC",
+            Codec::Flac => "FLAC",
+            Codec::Ac3 => "AC-3",
+            Codec::Eac3 => "E-AC-3",
+            Codec::Opus => "Opus",
+            Codec::Other(s) => s,
+        }
+    }
+}
+
+/// 识别 m4s（或 MP4）首个轨道的编码；自动跳过 9 字节 0x30 填充
+///
+/// 无 moov 或 stsd 时返回 `Ok(None)`
+pub fn probe_codec(path: &Path) -> std::io::Result<Option<Codec>> {
+    Ok(probe_sample_entry(path)?.map(|entry| entry.codec))
+}
+
+/// 首个轨道的 sample entry，用于判断多个文件能否无损拼接
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SampleEntry {
+    pub codec: Codec,
+    /// 完整的 sample entry，含 avcC / hvcC / esds 等解码配置
+    raw: Vec<u8>,
+}
+
+impl SampleEntry {
+    fn is_video(&self) -> bool {
+        self.codec.is_video()
+    }
+
+    fn read_u16(&self, offset: usize) -> Option<u16> {
+        Some(u16::from_be_bytes(self.raw.get(offset..offset + 2)?.try_into().ok()?))
+    }
+
+    /// 视频为「宽x高」，音频为「声道数 / 采样率」
+    fn format(&self) -> Option<String> {
+        if self.is_video() {
+            Some(format!("{}x{}", self.read_u16(32)?, self.read_u16(34)?))
+        } else if matches!(self.codec, Codec::Other(_)) {
+            None
+        } else {
+            // 采样率为 16.16 定点数，取整数部分
+            Some(format!("{} 声道 / {} Hz", self.read_u16(24)?, self.read_u16(32)?))
+        }
+    }
+
+    /// sample entry 固定字段之后子 box 的起点：视频为 86；音频为 36，QuickTime v1 / v2 另有 16 / 36 字节
+    fn children_offset(&self) -> usize {
+        if self.is_video() {
+            return 86;
+        }
+        match self.read_u16(16) {
+            Some(1) => 52,
+            Some(2) => 72,
+            _ => 36,
+        }
+    }
+
+    fn child(&self, kind: &[u8; 4]) -> Option<&[u8]> {
+        let children = self.raw.get(self.children_offset()..)?;
+        child_boxes(children).find(|(k, _)| k == kind).map(|(_, body)| body)
+    }
+
+    /// 决定能否拼接的解码配置，不含 esds 码率、btrt 等随分 P 变化的字段；
+    /// 未知编码无从区分，取完整的 sample entry
+    fn decoder_config(&self) -> Option<Vec<u8>> {
+        match self.codec {
+            Codec::Avc => self.child(b"avcC").map(<[u8]>::to_vec),
+            Codec::Hevc => self.child(b"hvcC").map(<[u8]>::to_vec),
+            Codec::Av1 => self.child(b"av1C").map(<[u8]>::to_vec),
+            Codec::Opus => self.child(b"dOps").map(<[u8]>::to_vec),
+            Codec::Aac => audio_specific_config(self.child(b"esds")?).map(<[u8]>::to_vec),
+            // STREAMINFO 中的采样率、声道数与位深，跳过总样本数与 MD5
+            Codec::Flac => {
+                let info = self.child(b"dfLa")?.get(18..22)?;
+                Some(vec![info[0], info[1], info[2], info[3] & 0xf0])
+            }
+            // 跳过 bit_rate_code
+            Codec::Ac3 => {
+                let dac3 = self.child(b"dac3")?;
+                Some(vec![*dac3.first()?, dac3.get(1)? & 0xfc])
+            }
+            // 跳过 data_rate
+            Codec::Eac3 => {
+                let dec3 = self.child(b"dec3")?;
+                Some([&[dec3.get(1)? & 0x07], dec3.get(2..)?].concat())
+            }
+            Codec::Other(_) => Some(self.raw.clone()),
+        }
+    }
+
+    /// 与另一条轨道的差异说明；可无损拼接时为空
+    ///
+    /// 只比较编码、宽高或声道数 / 采样率，以及解码配置（avcC、AudioSpecificConfig 等）
+    pub fn difference(&self, other: &SampleEntry) -> Option<String> {
+        if self.codec != other.codec {
+            return Some(format!("编码 {} 与 {}", self.codec.label(), other.codec.label()));
+        }
+        match (self.format(), other.format()) {
+            (Some(a), Some(b)) if a != b => return Some(format!("{} 与 {}", a, b)),
+            _ => {}
+        }
+        (self.decoder_config() != other.decoder_config())
+            .then(|| format!("{} 解码参数（如 SPS/PPS、码流配置）", self.codec.label()))
+    }
+}
+
+/// esds 中的 AudioSpecificConfig：ES_Descriptor (0x03) / DecoderConfigDescriptor (0x04) /
+/// DecoderSpecificInfo (0x05)
+fn audio_specific_config(esds: &[u8]) -> Option<&[u8]> {
+    let es = descriptor(esds.get(4..)?, 0x03)?;
+    let flags = *es.get(2)?;
+    let mut pos = 3;
+    if flags & 0x80 != 0 {
+        pos += 2;
+    }
+    if flags & 0x40 != 0 {
+        pos += 1 + *es.get(pos)? as usize;
+    }
+    if flags & 0x20 != 0 {
+        pos += 2;
+    }
+    let config = descriptor(es.get(pos..)?, 0x04)?;
+    // objectTypeIndication、streamType、bufferSizeDB、maxBitrate、avgBitrate 共 13 字节
+    descriptor(config.get(13..)?, 0x05)
+}
+
+/// 读取开头指定标签的 MPEG-4 描述符内容；长度为每字节 7 位的可变长编码
+fn descriptor(data: &[u8], tag: u8) -> Option<&[u8]> {
+    if *data.first()? != tag {
+        return None;
+    }
+    let mut len = 0usize;
+    let mut pos = 1;
+    loop {
+        let byte = *data.get(pos)?;
+        len = len << 7 | (byte & 0x7f) as usize;
+        pos += 1;
+        if byte & 0x80 == 0 || pos > 4 {
+            break;
+        }
+    }
+    data.get(pos..pos + len)
+}
+
+/// 读取 m4s（或 MP4）首个轨道的 sample entry；无 moov 或 stsd 时返回 `Ok(None)`
+pub fn probe_sample_entry(path: &Path) -> std::io::Result<Option<SampleEntry>> {
+    Ok(read_moov(path)?.and_then(|body| {
+        let raw = find_sample_entry(&body, &[b"trak", b"mdia", b"minf", b"stbl", b"stsd"])?;
+        Some(SampleEntry { codec: Codec::from_fourcc(raw.get(4..8)?), raw: raw.to_vec() })
+    }))
+}
+
+/// 读取 mvhd 中的时长（毫秒）；无 moov 或时长未知时返回 `Ok(None)`
+pub fn probe_duration_ms(path: &Path) -> std::io::Result<Option<u64>> {
+    Ok(read_moov(path)?.and_then(|body| {
+        let (_, mvhd) = child_boxes(&body).find(|(kind, _)| kind == b"mvhd")?;
+        let (timescale, duration) = match mvhd.first()? {
+            1 => (
+                u32::from_be_bytes(mvhd.get(20..24)?.try_into().ok()?) as u64,
+                u64::from_be_bytes(mvhd.get(24..32)?.try_into().ok()?),
+            ),
+            _ => (
+                u32::from_be_bytes(mvhd.get(12..16)?.try_into().ok()?) as u64,
+                u32::from_be_bytes(mvhd.get(16..20)?.try_into().ok()?) as u64,
+            ),
+        };
+        (timescale > 0 && duration > 0).then(|| duration * 1000 / timescale)
+    }))
+}
+
+/// 统计轨道数：MP4 / MOV 为 moov 中的 trak，MKV 为 Tracks 中的 TrackEntry；
+/// 缺少轨道信息时返回 `Ok(None)`
+pub fn count_tracks(path: &Path) -> std::io::Result<Option<usize>> {
+    if is_matroska(path) {
+        return matroska::count_tracks(path);
+    }
+    Ok(read_moov(path)?.map(|body| child_boxes(&body).filter(|(kind, _)| kind == b"trak").count()))
+}
+
+/// 视频轨道的关键帧时间（毫秒，升序），取自 sidx 的分段起点、各 moof 的首个样本，
+/// 或普通 MP4 的 stss 同步样本表；无法确定时返回 `Ok(None)`
+pub fn probe_keyframes(path: &Path) -> std::io::Result<Option<Vec<u64>>> {
+    let mut timescale = None;
+    let mut from_sidx = Vec::new();
+    let mut from_moof = Vec::new();
+    let mut from_stss = Vec::new();
+    visit_top_level(path, &[b"moov", b"sidx", b"moof"], |kind, body| {
+        match &kind {
+            b"moov" => {
+                timescale = media_timescale(&body);
+                from_stss = stss_keyframes(&body).unwrap_or_default();
+            }
+            b"sidx" => from_sidx.extend(sidx_keyframes(&body).unwrap_or_default()),
+            _ => {
+                if let (Some(ts), Some(time)) = (timescale, moof_keyframe(&body)) {
+                    from_moof.push(time * 1000 / ts);
+                }
+            }
+        }
+        // sidx 已列出全部分段时不再逐个读取 moof
+        from_sidx.is_empty()
+    })?;
+    let mut times = match (from_sidx.is_empty(), from_moof.is_empty()) {
+        (false, _) => from_sidx,
+        (true, false) => from_moof,
+        (true, true) => from_stss,
+    };
+    times.sort_unstable();
+    times.dedup();
+    Ok((!times.is_empty()).then_some(times))
+}
+
+/// 首个 trak/mdia/mdhd 中的时间刻度
+fn media_timescale(moov: &[u8]) -> Option<u64> {
+    track_timescale(find_box(moov, &[b"trak"])?)
+}
+
+fn track_timescale(trak: &[u8]) -> Option<u64> {
+    let mdhd = find_box(trak, &[b"mdia", b"mdhd"])?;
+    let at = if mdhd.first()? == &1 { 20 } else { 12 };
+    let timescale = read_u32(mdhd, at)? as u64;
+    (timescale > 0).then_some(timescale)
+}
+
+/// 普通 MP4 中视频轨道（hdlr 为 vide）同步样本的解码时间，由 stts 累加得到；
+/// 没有 stss 时每个样本都是同步样本
+fn stss_keyframes(moov: &[u8]) -> Option<Vec<u64>> {
+    let trak = child_boxes(moov)
+        .filter(|(kind, _)| kind == b"trak")
+        .map(|(_, body)| body)
+        .find(|trak| find_box(trak, &[b"mdia", b"hdlr"]).and_then(|h| h.get(8..12)) == Some(b"vide"))?;
+    let timescale = track_timescale(trak)?;
+    let stbl = find_box(trak, &[b"mdia", b"minf", b"stbl"])?;
+    let table = |body: &[u8], width: usize| -> Option<Vec<u32>> {
+        let count = read_u32(body, 4)? as usize;
+        (0..count * width).map(|i| read_u32(body, 8 + i * 4)).collect()
+    };
+    let stts = table(find_box(stbl, &[b"stts"])?, 2)?;
+    let sync = match find_box(stbl, &[b"stss"]) {
+        Some(stss) => Some(table(stss, 1)?),
+        None => None,
+    };
+
+    let mut times = Vec::new();
+    let (mut sample, mut time) = (1u32, 0u64);
+    let mut next_sync = sync.as_deref().map(|s| s.iter().peekable());
+    for entry in stts.chunks_exact(2) {
+        for _ in 0..entry[0] {
+            let is_sync = match &mut next_sync {
+                Some(iter) => iter.next_if_eq(&&sample).is_some(),
+                None => true,
+            };
+            if is_sync {
+                times.push(time * 1000 / timescale);
+            }
+            time += entry[1] as u64;
+            sample += 1;
+        }
+    }
+    Some(times)
+}
+
+/// sidx 中以 SAP（可随机访问点）开始的分段起点
+fn sidx_keyframes(sidx: &[u8]) -> Option<Vec<u64>> {
+    let timescale = read_u32(sidx, 8)? as u64;
+    let (mut time, mut pos) = match sidx.first()? {
+        1 => (read_u64(sidx, 12)?, 28),
+        _ => (read_u32(sidx, 12)? as u64, 20),
+    };
+    if timescale == 0 {
+        return None;
+    }
+    let count = u16::from_be_bytes(sidx.get(pos + 2..pos + 4)?.try_into().ok()?) as usize;
+    pos += 4;
+    let mut times = Vec::new();
+    for _ in 0..count {
+        let reference = sidx.get(pos..pos + 12)?;
+        let duration = read_u32(reference, 4)? as u64;
+        // 引用类型为 0（媒体）且 starts_with_SAP
+        if reference[0] & 0x80 == 0 && reference[8] & 0x80 != 0 {
+            times.push(time * 1000 / timescale);
+        }
+        time += duration;
+        pos += 12;
+    }
+    Some(times)
+}
+
+/// moof 首个样本为同步样本时返回其解码时间（轨道时间刻度）
+fn moof_keyframe(moof: &[u8]) -> Option<u64> {
+    let traf = find_box(moof, &[b"traf"])?;
+    let tfdt = find_box(traf, &[b"tfdt"])?;
+    let time = if tfdt.first()? == &1 { read_u64(tfdt, 4)? } else { read_u32(tfdt, 4)? as u64 };
+
+    let tfhd = find_box(traf, &[b"tfhd"])?;
+    let flags = read_u32(tfhd, 0)? & 0xff_ffff;
+    // track_ID 之后依次为可选的 base_data_offset、sample_description_index、默认时长、默认大小、默认标志
+    let offset = 8 + [(0x1, 8), (0x2, 4), (0x8, 4), (0x10, 4)]
+        .iter()
+        .filter(|(bit, _)| flags & bit != 0)
+        .map(|(_, len)| len)
+        .sum::<usize>();
+    let default_flags = if flags & 0x20 != 0 { read_u32(tfhd, offset) } else { None };
+
+    let first_flags = find_box(traf, &[b"trun"]).and_then(|trun| {
+        let flags = read_u32(trun, 0)? & 0xff_ffff;
+        let mut pos = if flags & 0x1 != 0 { 12 } else { 8 };
+        if flags & 0x4 != 0 {
+            return read_u32(trun, pos);
+        }
+        if flags & 0x400 != 0 {
+            pos += [0x100, 0x200].iter().filter(|&&bit| flags & bit != 0).count() * 4;
+            return read_u32(trun, pos);
+        }
+        None
+    });
+    // sample_is_non_sync_sample 位
+    let is_sync = first_flags.or(default_flags).is_none_or(|f| f & 0x1_0000 == 0);
+    is_sync.then_some(time)
+}
+
+fn read_u32(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn read_u64(data: &[u8], at: usize) -> Option<u64> {
+    Some(u64::from_be_bytes(data.get(at..at + 8)?.try_into().ok()?))
+}
+
+/// 读取顶层 moov 的内容（不含 box 头）；moov 可位于 mdat 之后
+fn read_moov(path: &Path) -> std::io::Result<Option<Vec<u8>>> {
+    let mut moov = None;
+    visit_top_level(path, &[b"moov"], |_, body| {
+        moov = Some(body);
+        false
+    })?;
+    Ok(moov)
+}
+
+/// 依次遍历顶层 box，把 `kinds` 中类型的内容（不含 box 头）交给 `visit`，返回 false 时停止；
+/// 自动跳过 9 字节 0x30 填充，遇到异常或超过读取上限的 box 也停止
+fn visit_top_level(
+    path: &Path,
+    kinds: &[&[u8; 4]],
+    mut visit: impl FnMut([u8; 4], Vec<u8>) -> bool,
+) -> std::io::Result<()> {
+    let mut f = File::open(path)?;
+    let mut head = [0u8; 9];
+    let n = f.read(&mut head)?;
+    let start = if n == 9 && head == [0x30; 9] { 9 } else { 0 };
+    let len = f.metadata()?.len();
+
+    let mut pos = start;
+    while pos + 8 <= len {
+        f.seek(SeekFrom::Start(pos))?;
+        let mut header = [0u8; 16];
+        f.read_exact(&mut header[..8])?;
+        let mut size = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as u64;
+        let mut header_len = 8;
+        if size == 1 {
+            f.read_exact(&mut header[8..16])?;
+            size = u64::from_be_bytes(header[8..16].try_into().unwrap_or([0; 8]));
+            header_len = 16;
+        } else if size == 0 {
+            size = len - pos;
+        }
+        if size < header_len {
+            return Ok(());
+        }
+        let kind: [u8; 4] = [header[4], header[5], header[6], header[7]];
+        if kinds.contains(&&kind) {
+            let body_len = size - header_len;
+            if body_len > MAX_BOX_SIZE || pos + size > len {
+                return Ok(());
+            }
+            let mut body = vec![0u8; body_len as usize];
+            f.read_exact(&mut body)?;
+            if !visit(kind, body) {
+                return Ok(());
+            }
+        }
+        pos += size;
+    }
+    Ok(())
+}
+
+/// 遍历一层子 box，返回 (类型, 内容)；遇到越界的 box 即停止
+fn child_boxes(data: &[u8]) -> impl Iterator<Item = ([u8; 4], &[u8])> {
+    let mut pos = 0usize;
+    std::iter::from_fn(move || {
+        let header = data.get(pos..pos + 8)?;
+        let size = u32::from_be_bytes(header[..4].try_into().ok()?) as usize;
+        let kind: [u8; 4] = header[4..8].try_into().ok()?;
+        let (header_len, size) = match size {
+            0 => (8, data.len() - pos),
+            1 => (16, u64::from_be_bytes(data.get(pos + 8..pos + 16)?.try_into().ok()?) as usize),
+            n => (8, n),
+        };
+        if size < header_len || pos.checked_add(size)? > data.len() {
+            return None;
+        }
+        let body = &data[pos + header_len..pos + size];
+        pos += size;
+        Some((kind, body))
+    })
+}
+
+/// 依次进入 `path` 指定的子 box，返回最后一层的内容
+fn find_box<'a>(data: &'a [u8], path: &[&[u8; 4]]) -> Option<&'a [u8]> {
+    let (target, rest) = path.split_first()?;
+    child_boxes(data)
+        .filter(|(kind, _)| kind == *target)
+        .find_map(|(_, body)| if rest.is_empty() { Some(body) } else { find_box(body, rest) })
+}
+
+/// 依次进入 `path` 指定的子 box，最终在 stsd 中取首个完整的 sample entry（含 box 头）
+fn find_sample_entry<'a>(data: &'a [u8], path: &[&[u8; 4]]) -> Option<&'a [u8]> {
+    let (target, rest) = path.split_first()?;
+    for (kind, body) in child_boxes(data) {
+        if kind != **target {
+            continue;
+        }
+        if rest.is_empty() {
+            // stsd: version/flags(4) + entry_count(4)，随后为 sample entry 的 size(4) + type(4)
+            let size = u32::from_be_bytes(body.get(8..12)?.try_into().ok()?) as usize;
+            return body.get(8..8 + size.max(8)).or_else(|| body.get(8..16));
+        }
+        if let Some(found) = find_sample_entry(body, rest) {
+            return Some(found);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    pub(crate) fn mp4_box(kind: &[u8; 4], body: &[u8]) -> Vec<u8> {
+        let mut v = ((body.len() + 8) as u32).to_be_bytes().to_vec();
+        v.extend_from_slice(kind);
+        v.extend_from_slice(body);
+        v
+    }
+
+    /// 构造仅含单轨 stsd 的最小初始化段
+    pub(crate) fn init_segment(fourcc: &[u8; 4]) -> Vec<u8> {
+        init_segment_with(fourcc, &[0u8; 16], &[0u8; 100])
+    }
+
+    /// 指定 sample entry 内容与 mvhd 内容的初始化段
+    pub(crate) fn init_segment_with(fourcc: &[u8; 4], entry: &[u8], mvhd: &[u8]) -> Vec<u8> {
+        let mut stsd = vec![0, 0, 0, 0, 0, 0, 0, 1];
+        stsd.extend(mp4_box(fourcc, entry));
+        let stbl = mp4_box(b"stbl", &mp4_box(b"stsd", &stsd));
+        let minf = mp4_box(b"minf", &stbl);
+        let mdia = mp4_box(b"mdia", &[mp4_box(b"mdhd", &[0u8; 24]), mp4_box(b"hdlr", &[0u8; 24]), minf].concat());
+        let trak = mp4_box(b"trak", &[mp4_box(b"tkhd", &[0u8; 84]), mdia].concat());
+        let moov = mp4_box(b"moov", &[mp4_box(b"mvhd", mvhd), trak].concat());
+        [mp4_box(b"ftyp", b"iso5\0\0\0\0iso6mp41"), moov, mp4_box(b"sidx", &[0u8; 32])].concat()
+    }
+
+    /// 视频 sample entry：宽高位于 entry 头之后第 24 字节，后接解码配置
+    pub(crate) fn visual_entry(width: u16, height: u16, config: &[u8]) -> Vec<u8> {
+        let mut entry = vec![0u8; 24];
+        entry.extend(width.to_be_bytes());
+        entry.extend(height.to_be_bytes());
+        entry.extend([0u8; 50]);
+        entry.extend(mp4_box(b"avcC", config));
+        entry
+    }
+
+    /// AAC sample entry：声道数与采样率位于 entry 头之后第 16、24 字节，esds 中带码率与 AudioSpecificConfig
+    fn audio_entry(channels: u16, sample_rate: u16, bitrate: u32, config: &[u8]) -> Vec<u8> {
+        let mut entry = vec![0u8; 16];
+        entry.extend(channels.to_be_bytes());
+        entry.extend([0u8; 6]);
+        entry.extend(sample_rate.to_be_bytes());
+        entry.extend([0u8; 2]);
+        let mut decoder = vec![0x40, 0x15, 0, 0, 0];
+        decoder.extend(bitrate.to_be_bytes());
+        decoder.extend(bitrate.to_be_bytes());
+        decoder.extend([0x05, config.len() as u8]);
+        decoder.extend(config);
+        let mut es = vec![0, 1, 0, 0x04, decoder.len() as u8];
+        es.extend(decoder);
+        let mut esds = vec![0, 0, 0, 0, 0x03, 0x80, 0x80, 0x80, es.len() as u8];
+        esds.extend(es);
+        entry.extend(mp4_box(b"esds", &esds));
+        entry
+    }
+
+    #[test]
+    fn test_probe_codec_hevc_with_padding() {
+        let tmp = std::env::temp_dir().join("bili2mp4_codec_test");
+        std::fs::create_dir_all(&tmp).ok();
+        let p = tmp.join("video.m4s");
+        let mut data = vec![0x30u8; 9];
+        data.extend(init_segment(b"hev1"));
+        std::fs::write(&p, data).unwrap();
+        assert_eq!(probe_codec(&p).unwrap(), Some(Codec::Hevc));
+
+        std::fs::write(&p, init_segment(b"fLaC")).unwrap();
+        assert_eq!(probe_codec(&p).unwrap(), Some(Codec::Flac));
+
+        std::fs::write(&p, mp4_box(b"ftyp", b"iso5")).unwrap();
+        assert_eq!(probe_codec(&p).unwrap(), None);
+        std::fs::remove_dir_all(&tmp).ok();
+    }
+
+    #[test]
+    fn test_sample_entry_difference_and_duration() {
+        let tmp = std::env::temp_dir().join("bili2mp4_codec_entry_test");
+        std::fs::create_dir_all(&tmp).unwrap();
+        let probe = |name: &str, data: Vec<u8>| {
+            let p = tmp.join(name);
+            std::fs::write(&p, data).unwrap();
+            probe_sample_entry(&p).unwrap().unwrap()
+        };
+        let mut mvhd = vec![0u8; 100];
+        mvhd[12..16].copy_from_slice(&1000u32.to_be_bytes());
+        mvhd[16..20].copy_from_slice(&5500u32.to_be_bytes());
+        let a = probe("a.mp4", init_segment_with(b"avc1", &visual_entry(1920, 1080, b"sps1"), &mvhd));
+        let b = probe("b.mp4", init_segment_with(b"avc1", &visual_entry(1920, 1080, b"sps1"), &[0u8; 100]));
+        let c = probe("c.mp4", init_segment_with(b"avc1", &visual_entry(1280, 720, b"sps1"), &[0u8; 100]));
+        let d = probe("d.mp4", init_segment_with(b"avc1", &visual_entry(1920, 1080, b"sps2"), &[0u8; 100]));
+        let e = probe("e.mp4", init_segment_with(b"hev1", &visual_entry(1920, 1080, b"sps1"), &[0u8; 100]));
+
+        assert_eq!(a.codec, Codec::Avc);
+        assert_eq!(a.difference(&b), None);
+        assert_eq!(a.difference(&c).as_deref(), Some("1920x1080 与 1280x720"));
+        assert!(a.difference(&d).unwrap().contains("解码参数"));
+        assert_eq!(a.difference(&e).as_deref(), Some("编码 H.264 与 HEVC"));
+        // btrt 中的码率不影响拼接
+        let btrt = [visual_entry(1920, 1080, b"sps1"), mp4_box(b"btrt", &[1u8; 12])].concat();
+        assert_eq!(a.difference(&probe("f.mp4", init_segment_with(b"avc1", &btrt, &[0u8; 100]))), None);
+
+        let aac = |name: &str, bitrate: u32, config: &[u8]| {
+            probe(name, init_segment_with(b"mp4a", &audio_entry(2, 48000, bitrate, config), &[0u8; 100]))
+        };
+        let g = aac("g.mp4", 128_000, &[0x11, 0x90]);
+        assert_eq!(g.difference(&aac("h.mp4", 320_000, &[0x11, 0x90])), None);
+        assert!(g.difference(&aac("i.mp4", 128_000, &[0x12, 0x10])).unwrap().contains("AAC 解码参数"));
+        assert_eq!(probe_duration_ms(&tmp.join("a.mp4")).unwrap(), Some(5500));
+        assert_eq!(probe_duration_ms(&tmp.join("b.mp4")).unwrap(), None);
+        std::fs::remove_dir_all(&tmp).ok();
+    }
+
+    #[test]
+    fn test_probe_keyframes() {
+        let tmp = std::env::temp_dir().join("bili2mp4_codec_keyframe_test");
+        std::fs::create_dir_all(&tmp).unwrap();
+        let p = tmp.join("video.m4s");
+        let mut init = init_segment(b"avc1");
+        let mdhd = init.windows(4).position(|w| w == b"mdhd").unwrap() + 4;
+        init[mdhd + 12..mdhd + 16].copy_from_slice(&1000u32.to_be_bytes());
+
+        // sidx：时间刻度 90000，三个分段各 2 秒，第二段不以 SAP 开始
+        let mut sidx = vec![0u8; 4];
+        sidx.extend(1u32.to_be_bytes());
+        sidx.extend(90_000u32.to_be_bytes());
+        sidx.extend(9_000u32.to_be_bytes());
+        sidx.extend([0u8; 4]);
+        sidx.extend([0, 0, 0, 3]);
+        for sap in [0x90u8, 0x00, 0x90] {
+            sidx.extend(100u32.to_be_bytes());
+            sidx.extend(180_000u32.to_be_bytes());
+            sidx.extend([sap, 0, 0, 0]);
+        }
+        let mut data = vec![0x30u8; 9];
+        data.extend([init.clone(), mp4_box(b"sidx", &sidx)].concat());
+        std::fs::write(&p, data).unwrap();
+        assert_eq!(probe_keyframes(&p).unwrap(), Some(vec![100, 4100]));
+
+        // 无 sidx：取各 moof 的 tfdt，首个样本为非同步样本的 moof 不计
+        let moof = |time: u32, first_flags: u32| {
+            let tfhd = mp4_box(b"tfhd", &[0, 0x02, 0, 0, 0, 0, 0, 1]);
+            let tfdt = mp4_box(b"tfdt", &[[0u8; 4], time.to_be_bytes()].concat());
+            let trun = mp4_box(b"trun", &[[0, 0, 0, 0x05], [0, 0, 0, 1], [0; 4], first_flags.to_be_bytes()].concat());
+            mp4_box(b"moof", &mp4_box(b"traf", &[tfhd, tfdt, trun].concat()))
+        };
+        let init = init[..init.windows(4).position(|w| w == b"sidx").unwrap() - 4].to_vec();
+        let data = [init.clone(), moof(0, 0x0200_0000), moof(2000, 0x0101_0000), moof(4000, 0x0200_0000)].concat();
+        std::fs::write(&p, data).unwrap();
+        assert_eq!(probe_keyframes(&p).unwrap(), Some(vec![0, 4000]));
+
+        std::fs::write(&p, init).unwrap();
+        assert_eq!(probe_keyframes(&p).unwrap(), None);
+
+        // 普通 MP4：视频轨道时间刻度 1000，6 个样本各 500，同步样本为第 1、5 个
+        let table = |entries: &[u32]| {
+            let mut body = vec![0u8; 4];
+            body.extend(entries.iter().flat_map(|v| v.to_be_bytes()));
+            body
+        };
+        let mut mdhd = [0u8; 24];
+        mdhd[12..16].copy_from_slice(&1000u32.to_be_bytes());
+        let mut hdlr = [0u8; 24];
+        hdlr[8..12].copy_from_slice(b"vide");
+        let stbl = [mp4_box(b"stts", &table(&[1, 6, 500])), mp4_box(b"stss", &table(&[2, 1, 5]))].concat();
+        let minf = mp4_box(b"minf", &mp4_box(b"stbl", &stbl));
+        let mdia = mp4_box(b"mdia", &[mp4_box(b"mdhd", &mdhd), mp4_box(b"hdlr", &hdlr), minf].concat());
+        let moov = mp4_box(b"moov", &mp4_box(b"trak", &mdia));
+        std::fs::write(&p, [mp4_box(b"ftyp", b"isom"), moov, mp4_box(b"mdat", &[0u8; 16])].concat()).unwrap();
+        assert_eq!(probe_keyframes(&p).unwrap(), Some(vec![0, 2000]));
+        std::fs::remove_dir_all(&tmp).ok();
+    }
+}
@@ -0,0 +1,383 @@
+//! faststart：把位于 mdat 之后的 moov 移到 mdat 之前并改写 stco / co64 中的块偏移，
+//! 网络播放时无需先下载到文件末尾
+
+use super::mp4box::ConvertError;
+use super::pipeline::Container;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+/// moov 的读取上限（长视频的采样表可达数十 MB）
+const MAX_MOOV_SIZE: u64 = 256 * 1024 * 1024;
+
+/// moov 之后的其他 box（如 free、udta）的读取上限
+const MAX_TAIL_SIZE: u64 = 16 * 1024 * 1024;
+
+/// 写临时文件时保留的剩余空间
+const TEMP_MARGIN: u64 = 64 * 1024 * 1024;
+
+/// 向下递归改写的容器 box，其余 box 原样复制
+const CONTAINERS: [&[u8; 4]; 4] = [b"trak", b"mdia", b"minf", b"stbl"];
+
+/// 顶层 box 的位置
+#[derive(Debug, Clone, Copy)]
+struct TopBox {
+    kind: [u8; 4],
+    offset: u64,
+    size: u64,
+}
+
+/// 对 MP4 / MOV 输出做 faststart；分片 MP4 与 MKV 不做改动
+pub(super) fn relocate_moov(path: &Path, container: Container) -> Result<(), ConvertError> {
+    if !matches!(container, Container::Mp4 | Container::Mov) {
+        return Ok(());
+    }
+    faststart(path)
+        .map(|_| ())
+        .map_err(|e| ConvertError::Io(format!("无法把 moov 移到文件开头: {}", e)))
+}
+
+/// 把 moov 移到首个 mdat 之前，返回是否改动了文件
+///
+/// 写到同目录的临时文件后替换原文件，中断时原文件不受影响；剩余空间不足时报错。
+/// 不以 ftyp 开头（如 MKV）、分片 MP4 或 moov 已在前时不做改动
+pub fn faststart(path: &Path) -> std::io::Result<bool> {
+    let mut f = File::open(path)?;
+    let len = f.metadata()?.len();
+    let mut head = [0u8; 8];
+    if len < 8 || f.read_exact(&mut head).is_err() || &head[4..] != b"ftyp" {
+        return Ok(false);
+    }
+    let boxes = top_level_boxes(&mut f, len)?;
+    if boxes.iter().any(|b| &b.kind == b"moof") {
+        return Ok(false);
+    }
+    let (Some(mdat), Some(moov)) = (
+        boxes.iter().find(|b| &b.kind == b"mdat"),
+        boxes.iter().find(|b| &b.kind == b"moov"),
+    ) else {
+        return Ok(false);
+    };
+    if moov.offset < mdat.offset {
+        return Ok(false);
+    }
+    if moov.size > MAX_MOOV_SIZE {
+        return Err(invalid("moov 过大"));
+    }
+    let (insert, moov_end) = (mdat.offset, moov.offset + moov.size);
+    if len - moov_end > MAX_TAIL_SIZE {
+        return Err(invalid("moov 之后的数据过大"));
+    }
+    let available = path.parent().and_then(|dir| fs2::available_space(dir).ok());
+    if available.is_some_and(|free| free < len.saturating_add(TEMP_MARGIN)) {
+        return Err(std::io::Error::other("剩余空间不足，无法写出临时文件"));
+    }
+
+    let old_moov = read_at(&mut f, moov.offset, moov.size)?;
+    let tail = read_at(&mut f, moov_end, len - moov_end)?;
+    let header_len = if u32::from_be_bytes(old_moov[..4].try_into().unwrap_or_default()) == 1 { 16 } else { 8 };
+    let body = &old_moov[header_len..];
+
+    // moov 插入后其原位置之前的数据整体后移 `shift`，其后的数据随 moov 长度的变化移动；
+    // co64 升级会使 moov 变大，重新计算直到长度稳定
+    let mut shift = moov.size;
+    let new_moov = loop {
+        let moved = |offset: u64| {
+            if (insert..moov.offset).contains(&offset) {
+                offset + shift
+            } else if offset >= moov_end {
+                offset - moov.size + shift
+            } else {
+                offset
+            }
+        };
+        let rebuilt = make_box(b"moov", &rewrite_container(body, &moved).ok_or_else(|| invalid("moov 结构异常"))?);
+        if rebuilt.len() as u64 == shift {
+            break rebuilt;
+        }
+        shift = rebuilt.len() as u64;
+    };
+    drop(f);
+
+    write_temp(path, insert, moov.offset, &new_moov, &tail)?;
+    Ok(true)
+}
+
+fn invalid(msg: &str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, msg.to_string())
+}
+
+/// 列出全部顶层 box；遇到越界或异常的 box 头时报错
+fn top_level_boxes(f: &mut File, len: u64) -> std::io::Result<Vec<TopBox>> {
+    let mut boxes = Vec::new();
+    let mut offset = 0;
+    while offset + 8 <= len {
+        f.seek(SeekFrom::Start(offset))?;
+        let mut header = [0u8; 16];
+        f.read_exact(&mut header[..8])?;
+        let kind: [u8; 4] = header[4..8].try_into().unwrap_or_default();
+        let size = match u32::from_be_bytes(header[..4].try_into().unwrap_or_default()) {
+            0 => len - offset,
+            1 => {
+                f.read_exact(&mut header[8..])?;
+                u64::from_be_bytes(header[8..].try_into().unwrap_or_default())
+            }
+            n => n as u64,
+        };
+        if size < 8 || offset.checked_add(size).is_none_or(|end| end > len) {
+            return Err(invalid("顶层 box 长度异常"));
+        }
+        boxes.push(TopBox { kind, offset, size });
+        offset += size;
+    }
+    Ok(boxes)
+}
+
+fn read_at(f: &mut File, offset: u64, len: u64) -> std::io::Result<Vec<u8>> {
+    f.seek(SeekFrom::Start(offset))?;
+    let mut buf = vec![0u8; len as usize];
+    f.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+/// 组装 box，长度超过 32 位时使用 64 位长度
+fn make_box(kind: &[u8; 4], body: &[u8]) -> Vec<u8> {
+    let size = body.len() as u64 + 8;
+    let mut out = Vec::with_capacity(body.len() + 16);
+    if size > u32::MAX as u64 {
+        out.extend_from_slice(&1u32.to_be_bytes());
+        out.extend_from_slice(kind);
+        out.extend_from_slice(&(size + 8).to_be_bytes());
+    } else {
+        out.extend_from_slice(&(size as u32).to_be_bytes());
+        out.extend_from_slice(kind);
+    }
+    out.extend_from_slice(body);
+    out
+}
+
+/// 重建容器 box 的内容：进入 trak/mdia/minf/stbl，按 `moved` 改写 stco / co64，
+/// 偏移超出 32 位的 stco 升级为 co64；子 box 无法完整解析时返回 None
+fn rewrite_container(body: &[u8], moved: &impl Fn(u64) -> u64) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(body.len());
+    let mut pos = 0;
+    while pos < body.len() {
+        let header = body.get(pos..pos + 8)?;
+        let kind: [u8; 4] = header[4..8].try_into().ok()?;
+        let (header_len, size) = match u32::from_be_bytes(header[..4].try_into().ok()?) {
+            0 => (8, body.len() - pos),
+            1 => (16, usize::try_from(u64::from_be_bytes(body.get(pos + 8..pos + 16)?.try_into().ok()?)).ok()?),
+            n => (8, n as usize),
+        };
+        if size < header_len || pos.checked_add(size)? > body.len() {
+            return None;
+        }
+        let child = &body[pos + header_len..pos + size];
+        match &kind {
+            k if CONTAINERS.contains(&k) => out.extend(make_box(k, &rewrite_container(child, moved)?)),
+            b"stco" | b"co64" => out.extend(rewrite_chunk_offsets(&kind, child, moved)?),
+            _ => out.extend_from_slice(&body[pos..pos + size]),
+        }
+        pos += size;
+    }
+    Some(out)
+}
+
+/// 改写块偏移表，返回完整的 stco 或 co64 box
+fn rewrite_chunk_offsets(kind: &[u8; 4], body: &[u8], moved: &impl Fn(u64) -> u64) -> Option<Vec<u8>> {
+    let count = u32::from_be_bytes(body.get(4..8)?.try_into().ok()?) as usize;
+    let width = if kind == b"co64" { 8 } else { 4 };
+    let table = body.get(8..8 + count.checked_mul(width)?)?;
+    let offsets: Vec<u64> = table
+        .chunks_exact(width)
+        .map(|c| match width {
+            8 => u64::from_be_bytes(c.try_into().unwrap_or_default()),
+            _ => u32::from_be_bytes(c.try_into().unwrap_or_default()) as u64,
+        })
+        .map(moved)
+        .collect();
+    let wide = width == 8 || offsets.iter().any(|&o| o > u32::MAX as u64);
+    let mut out = body[..8].to_vec();
+    for offset in offsets {
+        if wide {
+            out.extend_from_slice(&offset.to_be_bytes());
+        } else {
+            out.extend_from_slice(&(offset as u32).to_be_bytes());
+        }
+    }
+    Some(make_box(if wide { b"co64" } else { b"stco" }, &out))
+}
+
+/// 按新顺序写到同目录的临时文件，完成后替换原文件
+fn write_temp(path: &Path, insert: u64, moov_offset: u64, moov: &[u8], tail: &[u8]) -> std::io::Result<()> {
+    let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    let temp = path.with_file_name(format!(".{}.faststart", name));
+    let result = (|| {
+        let mut src = File::open(path)?;
+        let mut out = File::create(&temp)?;
+        std::io::copy(&mut (&mut src).take(insert), &mut out)?;
+        out.write_all(moov)?;
+        std::io::copy(&mut (&mut src).take(moov_offset - insert), &mut out)?;
+        out.write_all(tail)?;
+        out.sync_all()?;
+        std::fs::rename(&temp, path)
+    })();
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temp);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::convert::codec::tests::mp4_box;
+
+    /// 两条轨道：视频 stco 指向 mdat 中的两个块，音频 co64 指向一个块
+    fn sample_file(mdat_payload: &[u8]) -> Vec<u8> {
+        let ftyp = mp4_box(b"ftyp", b"isom\0\0\0\0isom");
+        let mdat_start = (ftyp.len() + 8) as u64;
+        let stco = |offsets: &[u32]| {
+            let mut body = vec![0u8; 4];
+            body.extend_from_slice(&(offsets.len() as u32).to_be_bytes());
+            offsets.iter().for_each(|o| body.extend_from_slice(&o.to_be_bytes()));
+            mp4_box(b"stco", &body)
+        };
+        let co64 = |offsets: &[u64]| {
+            let mut body = vec![0u8; 4];
+            body.extend_from_slice(&(offsets.len() as u32).to_be_bytes());
+            offsets.iter().for_each(|o| body.extend_from_slice(&o.to_be_bytes()));
+            mp4_box(b"co64", &body)
+        };
+        let trak = |table: Vec<u8>| {
+            let stbl = mp4_box(b"stbl", &[mp4_box(b"stsd", &[0u8; 8]), table].concat());
+            let minf = mp4_box(b"minf", &stbl);
+            mp4_box(b"trak", &[mp4_box(b"tkhd", &[0u8; 12]), mp4_box(b"mdia", &minf)].concat())
+        };
+        let video = trak(stco(&[mdat_start as u32, mdat_start as u32 + 4]));
+        let audio = trak(co64(&[mdat_start + 8]));
+        let moov = mp4_box(b"moov", &[mp4_box(b"mvhd", &[0u8; 20]), video, audio].concat());
+        [ftyp, mp4_box(b"mdat", mdat_payload), moov, mp4_box(b"free", b"tail")].concat()
+    }
+
+    /// 读出全部块偏移处的 4 字节
+    fn chunk_data(data: &[u8]) -> Vec<[u8; 4]> {
+        let mut out = Vec::new();
+        let mut find = |kind: &[u8; 4], width: usize| {
+            let mut from = 0;
+            while let Some(i) = data[from..].windows(4).position(|w| w == kind) {
+                let at = from + i + 12;
+                let count = u32::from_be_bytes(data[at - 4..at].try_into().unwrap()) as usize;
+                for c in data[at..at + count * width].chunks_exact(width) {
+                    let offset = if width == 8 {
+                        u64::from_be_bytes(c.try_into().unwrap()) as usize
+                    } else {
+                        u32::from_be_bytes(c.try_into().unwrap()) as usize
+                    };
+                    out.push(data[offset..offset + 4].try_into().unwrap());
+                }
+                from = at;
+            }
+        };
+        find(b"stco", 4);
+        find(b"co64", 8);
+        out
+    }
+
+    #[test]
+    fn test_faststart() {
+        let tmp = std::env::temp_dir().join("bili2mp4_faststart_test");
+        std::fs::create_dir_all(&tmp).unwrap();
+        let original = sample_file(b"AAAABBBBCCCC");
+        let expected = chunk_data(&original);
+        assert_eq!(expected, [*b"AAAA", *b"BBBB", *b"CCCC"]);
+
+        let path = tmp.join("out.mp4");
+        std::fs::write(&path, &original).unwrap();
+        assert!(faststart(&path).unwrap());
+        let data = std::fs::read(&path).unwrap();
+        assert_eq!(data.len(), original.len());
+        let moov = data.windows(4).position(|w| w == b"moov").unwrap();
+        let mdat = data.windows(4).position(|w| w == b"mdat").unwrap();
+        assert!(moov < mdat);
+        assert_eq!(chunk_data(&data), expected);
+        assert!(data.ends_with(b"freetail"));
+        // 已处理过的文件不再改动
+        assert!(!faststart(&path).unwrap());
+
+        // 非 MP4 / MOV 容器直接跳过
+        std::fs::write(&path, &original).unwrap();
+        relocate_moov(&path, Container::Fmp4).unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), original);
+        std::fs::remove_dir_all(&tmp).ok();
+    }
+
+    #[test]
+    fn test_skips_matroska() {
+        let tmp = std::env::temp_dir().join("bili2mp4_faststart_mkv_test");
+        std::fs::create_dir_all(&tmp).unwrap();
+        // EBML 头（DocType "matroska"）后接长度未知的 Segment
+        let mut mkv = vec![0x1A, 0x45, 0xDF, 0xA3, 0xA3];
+        mkv.extend([0x42, 0x86, 0x81, 0x01, 0x42, 0xF7, 0x81, 0x01, 0x42, 0xF2, 0x81, 0x04, 0x42, 0xF3, 0x81, 0x08]);
+        mkv.extend([0x42, 0x82, 0x88]);
+        mkv.extend(b"matroska");
+        mkv.extend([0x42, 0x87, 0x81, 0x04, 0x42, 0x85, 0x81, 0x02]);
+        mkv.extend([0x18, 0x53, 0x80, 0x67, 0x01, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]);
+        mkv.extend([0u8; 64]);
+        let path = tmp.join("x.mkv");
+        std::fs::write(&path, &mkv).unwrap();
+        assert!(!faststart(&path).unwrap());
+        relocate_moov(&path, Container::Mp4).unwrap();
+        relocate_moov(&path, Container::Mkv).unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), mkv);
+        std::fs::remove_dir_all(&tmp).ok();
+    }
+
+    #[test]
+    fn test_offsets_after_moov_follow_size_change() {
+        let tmp = std::env::temp_dir().join("bili2mp4_faststart_tail_test");
+        std::fs::create_dir_all(&tmp).unwrap();
+        // moov 使用 64 位长度头，重建后短 8 字节，其后 mdat 中的块偏移随之前移
+        let ftyp = mp4_box(b"ftyp", b"isom\0\0\0\0isom");
+        let head = mp4_box(b"mdat", b"AAAA");
+        let moov_size = 16 + 56;
+        let tail_chunk = (ftyp.len() + head.len() + moov_size + 8) as u32;
+        let mut stco = vec![0u8; 4];
+        stco.extend(2u32.to_be_bytes());
+        stco.extend(((ftyp.len() + 8) as u32).to_be_bytes());
+        stco.extend(tail_chunk.to_be_bytes());
+        let trak = mp4_box(b"trak", &mp4_box(b"mdia", &mp4_box(b"minf", &mp4_box(b"stbl", &mp4_box(b"stco", &stco)))));
+        let mut moov = 1u32.to_be_bytes().to_vec();
+        moov.extend(b"moov");
+        moov.extend(((trak.len() + 16) as u64).to_be_bytes());
+        moov.extend(&trak);
+        assert_eq!(moov.len(), moov_size);
+        let original = [ftyp, head, moov, mp4_box(b"mdat", b"DDDD")].concat();
+        assert_eq!(chunk_data(&original), [*b"AAAA", *b"DDDD"]);
+
+        let path = tmp.join("out.mp4");
+        std::fs::write(&path, &original).unwrap();
+        assert!(faststart(&path).unwrap());
+        let data = std::fs::read(&path).unwrap();
+        assert_eq!(data.len(), original.len() - 8);
+        assert_eq!(chunk_data(&data), [*b"AAAA", *b"DDDD"]);
+        std::fs::remove_dir_all(&tmp).ok();
+    }
+
+    #[test]
+    fn test_stco_upgraded_to_co64() {
+        let mut body = vec![0u8; 4];
+        body.extend_from_slice(&2u32.to_be_bytes());
+        body.extend_from_slice(&100u32.to_be_bytes());
+        body.extend_from_slice(&(u32::MAX - 10).to_be_bytes());
+        let stbl = mp4_box(b"stbl", &mp4_box(b"stco", &body));
+        let rebuilt = rewrite_container(&stbl, &|o| o + 100).unwrap();
+        assert_eq!(&rebuilt[12..16], b"co64");
+        assert_eq!(rebuilt.len(), stbl.len() + 8);
+        assert_eq!(u64::from_be_bytes(rebuilt[24..32].try_into().unwrap()), 200);
+        assert_eq!(u64::from_be_bytes(rebuilt[32..40].try_into().unwrap()), u32::MAX as u64 + 90);
+
+        // 截断的子 box 无法解析时放弃
+        assert!(rewrite_container(&stbl[..stbl.len() - 2], &|o| o).is_none());
+    }
+}
@@ -0,0 +1,129 @@
+//! Matroska (MKV) 结构读取：只解析校验输出所需的 EBML 头与 Tracks
+
+use std::fs::File;
+use std::io::{BufReader, Read, Seek};
+use std::path::Path;
+
+const EBML_HEADER: u32 = 0x1A45_DFA3;
+const SEGMENT: u32 = 0x1853_8067;
+const TRACKS: u32 = 0x1654_AE6B;
+const TRACK_ENTRY: u32 = 0xAE;
+
+/// Tracks 元素的读取上限
+const MAX_TRACKS_SIZE: u64 = 1024 * 1024;
+
+/// 文件是否以 EBML 头开始
+pub(super) fn is_matroska(path: &Path) -> bool {
+    let mut magic = [0u8; 4];
+    File::open(path).and_then(|mut f| f.read_exact(&mut magic)).is_ok() && u32::from_be_bytes(magic) == EBML_HEADER
+}
+
+/// 统计 Tracks 中的 TrackEntry 数；找不到 Tracks（如未写完）时返回 `Ok(None)`
+pub(super) fn count_tracks(path: &Path) -> std::io::Result<Option<usize>> {
+    let mut f = BufReader::new(File::open(path)?);
+    let len = f.get_ref().metadata()?.len();
+    let (id, size) = read_element(&mut f)?;
+    if id != EBML_HEADER {
+        return Ok(None);
+    }
+    f.seek_relative(size.unwrap_or(0) as i64)?;
+
+    let (id, size) = read_element(&mut f)?;
+    if id != SEGMENT {
+        return Ok(None);
+    }
+    let start = f.stream_position()?;
+    let end = size.map_or(len, |s| (start + s).min(len));
+    while f.stream_position()? < end {
+        let (id, size) = read_element(&mut f)?;
+        // 未知长度的元素（通常为直播式写出的 Cluster）无法跳过
+        let Some(size) = size else { return Ok(None) };
+        if id == TRACKS {
+            if size > MAX_TRACKS_SIZE {
+                return Ok(None);
+            }
+            let mut body = vec![0u8; size as usize];
+            f.read_exact(&mut body)?;
+            return Ok(Some(track_entries(&body)));
+        }
+        f.seek_relative(size as i64)?;
+    }
+    Ok(None)
+}
+
+/// 逐个读取 Tracks 的子元素，统计 TrackEntry
+fn track_entries(mut body: &[u8]) -> usize {
+    let mut count = 0;
+    while let Ok((id, Some(size))) = read_element(&mut body) {
+        if id == TRACK_ENTRY {
+            count += 1;
+        }
+        let Some(rest) = body.get(size as usize..) else { break };
+        body = rest;
+    }
+    count
+}
+
+/// 读取元素 ID（保留长度标记位）与数据长度（全 1 表示未知长度，返回 `None`）
+fn read_element(r: &mut impl Read) -> std::io::Result<(u32, Option<u64>)> {
+    let (id, _) = read_vint(r, 4)?;
+    let (size, width) = read_vint(r, 8)?;
+    let marker = 1u64 << (7 * width);
+    let value = size & (marker - 1);
+    Ok((id as u32, (value != marker - 1).then_some(value)))
+}
+
+/// 读取变长整数，返回原始值（含标记位）与字节数
+fn read_vint(r: &mut impl Read, max_len: u32) -> std::io::Result<(u64, u32)> {
+    let mut first = [0u8; 1];
+    r.read_exact(&mut first)?;
+    let width = first[0].leading_zeros() + 1;
+    if width > max_len {
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "无效的 EBML 变长整数"));
+    }
+    let mut value = first[0] as u64;
+    for _ in 1..width {
+        r.read_exact(&mut first)?;
+        value = value << 8 | first[0] as u64;
+    }
+    Ok((value, width))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 构造元素：ID 按原样写出，长度统一用 8 字节编码
+    fn element(id: u32, body: &[u8]) -> Vec<u8> {
+        let id_bytes = id.to_be_bytes();
+        let skip = id_bytes.iter().take_while(|&&b| b == 0).count();
+        let mut out = id_bytes[skip..].to_vec();
+        out.push(0x01);
+        out.extend_from_slice(&(body.len() as u64).to_be_bytes()[1..]);
+        out.extend_from_slice(body);
+        out
+    }
+
+    #[test]
+    fn test_count_tracks() {
+        let tmp = std::env::temp_dir().join("bili2mp4_matroska_test");
+        std::fs::create_dir_all(&tmp).unwrap();
+        let path = tmp.join("out.mkv");
+        let header = element(EBML_HEADER, &element(0x4282, b"matroska"));
+        let entry = element(TRACK_ENTRY, &element(0xD7, &[1]));
+        let tracks = element(TRACKS, &[entry.clone(), entry].concat());
+        let info = element(0x1549_A966, &[0u8; 20]);
+        let segment = element(SEGMENT, &[info.clone(), tracks, element(0x1F43_B675, &[0u8; 100])].concat());
+        std::fs::write(&path, [header.clone(), segment].concat()).unwrap();
+        assert!(is_matroska(&path));
+        assert_eq!(count_tracks(&path).unwrap(), Some(2));
+
+        // 未写完：Segment 中没有 Tracks
+        std::fs::write(&path, [header, element(SEGMENT, &info)].concat()).unwrap();
+        assert_eq!(count_tracks(&path).unwrap(), None);
+
+        std::fs::write(&path, b"\0\0\0\x20ftypisom").unwrap();
+        assert!(!is_matroska(&path));
+        std::fs::remove_dir_all(&tmp).ok();
+    }
+}
@@ -0,0 +1,254 @@
+//! 多 P 合并：各分 P 先分别封装为临时 MP4，再按分 P 顺序无损拼接为一个文件，章节名取分 P 标题
+//!
+//! 拼接不重新编码，各分 P 的音视频编码参数必须一致，否则拒绝合并
+
+use super::args::{ffmpeg_concat_args, mp4box_concat_args};
+use super::codec::{probe_duration_ms, probe_sample_entry, SampleEntry};
+use super::faststart::relocate_moov;
+use super::mp4box::{check_sources, ConvertError, ConvertProgress, WorkDir};
+use super::pipeline::{
+    check_container, mux_with_fallback, plan_output, remux_with_fallback, Attempt, Backend, Container, ConvertTools,
+};
+use crate::cache::{read_details, VideoInfo};
+use crate::config::Profile;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+
+/// 一个章节：分 P 标题及其在合并文件中的位置
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chapter {
+    pub title: String,
+    pub start_ms: u64,
+    pub duration_ms: u64,
+}
+
+/// 检查能否合并并按分 P 排序：至少两个分 P、同一 BV 号、分 P 不重复、音视频编码参数一致
+pub fn check_mergeable(parts: &[VideoInfo]) -> Result<Vec<&VideoInfo>, ConvertError> {
+    let incompatible = |msg: String| Err(ConvertError::Incompatible(msg));
+    if parts.len() < 2 {
+        return incompatible("至少需要两个分 P".to_string());
+    }
+    let bvid = parts[0].bvid.as_deref();
+    if bvid.is_none() || parts.iter().any(|p| p.bvid.as_deref() != bvid) {
+        return incompatible("只能合并同一 BV 号的分 P".to_string());
+    }
+    let mut sorted: Vec<&VideoInfo> = parts.iter().collect();
+    sorted.sort_by_key(|p| p.page);
+    if let Some(dup) = sorted.windows(2).find(|w| w[0].page == w[1].page) {
+        return incompatible(format!("P{} 有多份缓存，请只保留一份", dup[0].page));
+    }
+
+    let first = sorted[0];
+    let (video, audio) = sample_entries(first)?;
+    for part in &sorted[1..] {
+        let (v, a) = sample_entries(part)?;
+        for (label, base, other) in [("视频", &video, &v), ("音频", &audio, &a)] {
+            if let Some(diff) = base.difference(other) {
+                return incompatible(format!("P{} 与 P{} 的{}不一致：{}", first.page, part.page, label, diff));
+            }
+        }
+    }
+    Ok(sorted)
+}
+
+fn sample_entries(part: &VideoInfo) -> Result<(SampleEntry, SampleEntry), ConvertError> {
+    let probe = |path: &Path| {
+        probe_sample_entry(path).ok().flatten().ok_or_else(|| {
+            ConvertError::Incompatible(format!("无法读取 P{} 的编码参数: {}", part.page, path.display()))
+        })
+    };
+    Ok((probe(&part.video_path)?, probe(&part.audio_path)?))
+}
+
+/// 合并后的视频信息：标题取整个视频的标题，用于输出命名、附属文件与媒体库
+pub fn merged_video(parts: &[&VideoInfo]) -> VideoInfo {
+    let first = parts[0];
+    VideoInfo {
+        title: read_details(first).video_title.unwrap_or_else(|| first.title.clone()),
+        page: 1,
+        total_pages: 1,
+        size_bytes: parts.iter().map(|p| p.size_bytes).sum(),
+        duration_ms: parts.iter().map(|p| p.duration_ms).sum(),
+        ..first.clone()
+    }
+}
+
+/// 合并多 P：逐个封装分 P 后按顺序拼接并写入章节，返回输出路径与拼接所用后端
+///
+/// 输出路径按方案命名（视频信息见 [`merged_video`]），进度按分 P 计数，最后一步为拼接
+pub fn merge_parts(
+    parts: &[VideoInfo],
+    out_dir: &Path,
+    tools: &ConvertTools,
+    profile: &Profile,
+    on_progress: impl Fn(ConvertProgress),
+    on_attempt: impl Fn(Attempt),
+    cancel: &AtomicBool,
+) -> Result<(PathBuf, Backend), ConvertError> {
+    let parts = check_mergeable(parts)?;
+    for part in &parts {
+        check_sources(part, cancel)?;
+    }
+    let issues = tools.extra_args.issues();
+    if !issues.is_empty() {
+        return Err(ConvertError::Config(issues.join("；")));
+    }
+    check_container(parts[0], profile.container)?;
+    let merged = merged_video(&parts);
+    let output_path = plan_output(&merged, out_dir, profile)?;
+    if let Some(parent) = output_path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| ConvertError::Io(format!("无法创建目录: {}", e)))?;
+    }
+
+    let work = WorkDir::create("merge")?;
+    let total = parts.len() + 1;
+    let progress = |file: &str, index: usize| {
+        on_progress(ConvertProgress {
+            current_file: file.to_string(),
+            current_index: index,
+            total,
+            percent: (index.saturating_sub(1) * 100 / total) as u32,
+        })
+    };
+    let mut files = Vec::new();
+    let mut chapters = Vec::new();
+    let mut start_ms = 0;
+    for (i, part) in parts.iter().enumerate() {
+        progress(&part.title, i + 1);
+        let file = work.path().join(format!("p{:03}.mp4", part.page));
+        mux_with_fallback(part, &file, Container::Mp4, tools, profile, |_| {}, &on_attempt, cancel)?;
+        let duration_ms = probe_duration_ms(&file)
+            .ok()
+            .flatten()
+            .or(part.duration_ms)
+            .ok_or_else(|| ConvertError::Io(format!("无法读取 P{} 的时长", part.page)))?;
+        chapters.push(Chapter { title: part.title.clone(), start_ms, duration_ms });
+        start_ms += duration_ms;
+        files.push(file);
+    }
+
+    progress(&merged.title, total);
+    let write = |name: &str, content: String| {
+        let path = work.path().join(name);
+        std::fs::write(&path, content).map_err(|e| ConvertError::Io(e.to_string()))?;
+        Ok::<_, ConvertError>(path)
+    };
+    let concat_args = |backend| match backend {
+        Backend::Ffmpeg => Ok(ffmpeg_concat_args(
+            &write("concat.txt", concat_list(&files))?,
+            &write("chapters.ffmeta", ffmetadata(&chapters))?,
+            &output_path,
+            profile.container,
+        )),
+        _ => Ok(mp4box_concat_args(&files, &write("chapters.txt", ogm_chapters(&chapters))?, &output_path)),
+    };
+    let backend = remux_with_fallback(tools, profile, &output_path, concat_args, &on_attempt, cancel)?;
+    relocate_moov(&output_path, profile.container)?;
+    on_progress(ConvertProgress { current_file: merged.title, current_index: total, total, percent: 100 });
+    Ok((output_path, backend))
+}
+
+/// 章节名中的换行替换为空格
+fn chapter_title(title: &str) -> String {
+    title.replace(['\r', '\n'], " ")
+}
+
+/// MP4Box `-chap` 读取的 OGM 章节文件，写入 Nero 章节
+fn ogm_chapters(chapters: &[Chapter]) -> String {
+    let mut out = String::new();
+    for (i, c) in chapters.iter().enumerate() {
+        let ms = c.start_ms;
+        out.push_str(&format!(
+            "CHAPTER{0:02}={1:02}:{2:02}:{3:02}.{4:03}\nCHAPTER{0:02}NAME={5}\n",
+            i + 1,
+            ms / 3_600_000,
+            ms / 60_000 % 60,
+            ms / 1000 % 60,
+            ms % 1000,
+            chapter_title(&c.title)
+        ));
+    }
+    out
+}
+
+/// ffmpeg 元数据文件，`=`、`;`、`#`、`\` 需转义
+fn ffmetadata(chapters: &[Chapter]) -> String {
+    let mut out = String::from(";FFMETADATA1\n");
+    for c in chapters {
+        let mut title = String::new();
+        for ch in chapter_title(&c.title).chars() {
+            if matches!(ch, '=' | ';' | '#' | '\\') {
+                title.push('\\');
+            }
+            title.push(ch);
+        }
+        out.push_str(&format!(
+            "[CHAPTER]\nTIMEBASE=1/1000\nSTART={}\nEND={}\ntitle={}\n",
+            c.start_ms,
+            c.start_ms + c.duration_ms,
+            title
+        ));
+    }
+    out
+}
+
+/// ffmpeg concat 列表，路径中的单引号需转义
+fn concat_list(files: &[PathBuf]) -> String {
+    files
+        .iter()
+        .map(|f| format!("file '{}'\n", f.to_string_lossy().replace('\'', r"'\''")))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::convert::codec::tests::{init_segment, init_segment_with, visual_entry};
+
+    #[test]
+    fn test_check_mergeable_and_chapters() {
+        let tmp = std::env::temp_dir().join("bili2mp4_merge_test");
+        std::fs::remove_dir_all(&tmp).ok();
+        std::fs::create_dir_all(&tmp).unwrap();
+        let part = |page: u32, width: u16| {
+            let dir = tmp.join(format!("c_{}", page));
+            std::fs::create_dir_all(&dir).unwrap();
+            let video = init_segment_with(b"avc1", &visual_entry(width, 1080, b"sps"), &[0u8; 100]);
+            std::fs::write(dir.join("video.m4s"), video).unwrap();
+            std::fs::write(dir.join("audio.m4s"), init_segment(b"mp4a")).unwrap();
+            VideoInfo {
+                page,
+                total_pages: 3,
+                size_bytes: 100,
+                duration_ms: Some(1000),
+                bvid: Some("BV1xx".into()),
+                ..VideoInfo::fixture(&dir, &format!("第 {} 讲", page))
+            }
+        };
+
+        let parts = [part(2, 1920), part(1, 1920)];
+        let sorted = check_mergeable(&parts).unwrap();
+        assert_eq!(sorted.iter().map(|p| p.page).collect::<Vec<_>>(), [1, 2]);
+        let merged = merged_video(&sorted);
+        assert_eq!((merged.page, merged.total_pages, merged.size_bytes), (1, 1, 200));
+        assert_eq!(merged.duration_ms, Some(2000));
+
+        let err = check_mergeable(&[part(1, 1920), part(3, 1280)]).unwrap_err().to_string();
+        assert!(err.contains("P1 与 P3 的视频不一致：1920x1080 与 1280x1080"), "{}", err);
+        assert!(check_mergeable(&[part(1, 1920), part(1, 1920)]).is_err());
+        let other = VideoInfo { bvid: Some("BV2".into()), ..part(2, 1920) };
+        assert!(check_mergeable(&[part(1, 1920), other]).is_err());
+
+        let chapters = [
+            Chapter { title: "开篇".into(), start_ms: 0, duration_ms: 61_500 },
+            Chapter { title: "a=b;\n下".into(), start_ms: 61_500, duration_ms: 3_600_000 },
+        ];
+        assert_eq!(
+            ogm_chapters(&chapters),
+            "CHAPTER01=00:00:00.000\nCHAPTER01NAME=开篇\nCHAPTER02=00:01:01.500\nCHAPTER02NAME=a=b; 下\n"
+        );
+        assert!(ffmetadata(&chapters).ends_with("START=61500\nEND=3661500\ntitle=a\\=b\\; 下\n"));
+        assert_eq!(concat_list(&[PathBuf::from("/t/it's.mp4")]), "file '/t/it'\\''s.mp4'\n");
+        std::fs::remove_dir_all(&tmp).ok();
+    }
+}
@@ -1,3 +1,26 @@
+mod args;
+pub(crate) mod codec;
+mod faststart;
+mod matroska;
+mod merge;
 mod mp4box;
+mod pipeline;
+mod preflight;
+mod split;
+mod transcode;
+mod trim;
+mod verify;
 
-pub use mp4box::{convert_one, convert_one_raw, convert_one_ffmpeg, ConvertError, ConvertProgress};
+pub use args::{command_line, CommandPreview, ExtraArgs};
+pub use codec::{count_tracks, probe_codec, probe_duration_ms, probe_keyframes, Codec};
+pub use faststart::faststart;
+pub use merge::{check_mergeable, merge_parts, merged_video, Chapter};
+pub use mp4box::{convert_one, convert_one_raw, convert_one_ffmpeg, has_padding, ConvertError, ConvertProgress};
+pub use pipeline::{convert_with_fallback, plan_output, target_path, Attempt, Backend, Container, ConvertTools, DEFAULT_CHAIN};
+pub use preflight::{format_bytes, preflight, Preflight, SpaceStatus, VolumeUsage};
+pub use split::{split_output, SplitLimit, FAT32_MAX_BYTES};
+pub use transcode::{
+    convert_transcode, plan_transcode, TranscodeMode, TranscodePolicy, TranscodePreset, TranscodeSettings,
+};
+pub use trim::{convert_trimmed, format_time, parse_time, trimmed_output, TrimRange};
+pub use verify::verify_output;
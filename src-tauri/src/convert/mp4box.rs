@@ -1,8 +1,12 @@
 //! MP4Box 转换核心
 //! 支持新版 B 站 m4s 的 9 字节头部填充去除
 
+use super::args::{ffmpeg_args, mp4box_args};
+use super::faststart::relocate_moov;
+use super::pipeline::Container;
 use crate::cache::VideoInfo;
 use crate::filemgr::{resolve_output_path, ConflictStrategy};
+use crate::toolchain::Tool;
 use std::fs::File;
 use std::io::Read;
 use std::path::{Path, PathBuf};
@@ -22,21 +26,100 @@ pub struct ConvertProgress {
 pub enum ConvertError {
     #[error("文件不存在: {0}")]
     FileNotFound(String),
-    #[error("MP4Box 未找到，请安装: brew install gpac")]
-    Mp4BoxNotFound,
+    #[error("{} 未找到，{}", .tool.label(), .tool.install_hint())]
+    ToolNotFound { tool: Tool },
     #[error("MP4Box 执行失败: {0}")]
     Mp4BoxFailed(String),
+    #[error("ffmpeg 执行失败: {0}")]
+    FfmpegFailed(String),
+    /// 创建目录、去除填充、读取或改写输出等文件操作失败，与所用后端无关
+    #[error("文件读写失败: {0}")]
+    Io(String),
     #[error("用户取消")]
     Cancelled,
     #[error("冲突策略跳过")]
     Skipped(#[from] crate::filemgr::ConflictError),
+    #[error("没有可用的转换后端: {0}")]
+    NoBackend(String),
+    #[error("配置错误: {0}")]
+    Config(String),
+    #[error("无法无损合并: {0}")]
+    Incompatible(String),
+    #[error("无法无损封装: {0}")]
+    UnsupportedCodec(String),
+}
+
+impl ConvertError {
+    /// 外部工具非零退出
+    pub(super) fn tool_failed(tool: Tool, detail: String) -> Self {
+        match tool {
+            Tool::Mp4Box => ConvertError::Mp4BoxFailed(detail),
+            Tool::Ffmpeg => ConvertError::FfmpegFailed(detail),
+        }
+    }
 }
 
 const M4S_HEADER_PADDING: [u8; 9] = [0x30; 9];
 
-struct TempCleanup(Option<PathBuf>, Option<PathBuf>);
+/// 去除填充时写入的临时目录
+pub(super) fn temp_dir() -> PathBuf {
+    std::env::temp_dir().join("bili2mp4")
+}
+
+/// 合并、截取等多步处理的临时目录，结束后删除
+pub(super) struct WorkDir(PathBuf);
+
+impl WorkDir {
+    pub(super) fn create(prefix: &str) -> Result<Self, ConvertError> {
+        let suffix = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or(0);
+        let dir = temp_dir().join(format!("{}_{}", prefix, suffix));
+        std::fs::create_dir_all(&dir).map_err(|e| ConvertError::Io(format!("无法创建临时目录: {}", e)))?;
+        Ok(Self(dir))
+    }
+
+    pub(super) fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for WorkDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// 执行外部命令写出 `output`；失败或取消时删除输出
+pub(super) fn run_tool(
+    tool: Tool,
+    program: &str,
+    args: &[String],
+    output: &Path,
+    cancel: &AtomicBool,
+) -> Result<(), ConvertError> {
+    let result = Command::new(program).args(args).output().map_err(|_| ConvertError::ToolNotFound { tool })?;
+    if cancel.load(Ordering::Relaxed) {
+        let _ = std::fs::remove_file(output);
+        return Err(ConvertError::Cancelled);
+    }
+    if !result.status.success() {
+        let _ = std::fs::remove_file(output);
+        return Err(ConvertError::tool_failed(tool, truncate_stderr(&result.stderr)));
+    }
+    Ok(())
+}
+
+/// 文件是否以 9 字节 0x30 填充开头（转换时需复制一份到临时目录）
+pub fn has_padding(path: &Path) -> bool {
+    let mut header = [0u8; 9];
+    File::open(path).and_then(|mut f| f.read_exact(&mut header)).is_ok() && header == M4S_HEADER_PADDING
+}
+
+pub(super) struct TempCleanup(Option<PathBuf>, Option<PathBuf>);
 impl TempCleanup {
-    fn new(v: Option<PathBuf>, a: Option<PathBuf>) -> Self { Self(v, a) }
+    pub(super) fn new(v: Option<PathBuf>, a: Option<PathBuf>) -> Self { Self(v, a) }
 }
 impl Drop for TempCleanup {
     fn drop(&mut self) {
@@ -46,14 +129,14 @@ impl Drop for TempCleanup {
 }
 
 /// 若 m4s 含 9 字节 0x30 填充，去除后写入临时文件并返回路径；否则返回原路径
-fn ensure_clean_m4s(path: &Path) -> Result<(PathBuf, bool), std::io::Error> {
+pub(super) fn ensure_clean_m4s(path: &Path) -> Result<(PathBuf, bool), std::io::Error> {
     let mut f = File::open(path)?;
     let mut header = [0u8; 9];
     f.read_exact(&mut header)?;
     if header != M4S_HEADER_PADDING {
         return Ok((path.to_path_buf(), false));
     }
-    let temp_dir = std::env::temp_dir().join("bili2mp4");
+    let temp_dir = temp_dir();
     std::fs::create_dir_all(&temp_dir)?;
     let suffix = std::time::SystemTime::now()
         .duration_since(std::time::UNIX_EPOCH)
@@ -78,78 +161,10 @@ pub fn convert_one(
     on_progress: impl Fn(ConvertProgress),
     cancel: &AtomicBool,
 ) -> Result<std::path::PathBuf, ConvertError> {
-    if cancel.load(Ordering::Relaxed) {
-        return Err(ConvertError::Cancelled);
-    }
-
-    if !video.video_path.exists() {
-        return Err(ConvertError::FileNotFound(
-            video.video_path.display().to_string(),
-        ));
-    }
-    if !video.audio_path.exists() {
-        return Err(ConvertError::FileNotFound(
-            video.audio_path.display().to_string(),
-        ));
-    }
-
-    let output_path = resolve_output_path(out_dir, &video.title, strategy)?;
-
-    on_progress(ConvertProgress {
-        current_file: video.title.clone(),
-        current_index: 1,
-        total: 1,
-        percent: 0,
-    });
-
-    let (video_clean, video_temp) = ensure_clean_m4s(&video.video_path)
-        .map_err(|e| ConvertError::Mp4BoxFailed(e.to_string()))?;
-    let (audio_clean, audio_temp) = ensure_clean_m4s(&video.audio_path)
-        .map_err(|e| ConvertError::Mp4BoxFailed(e.to_string()))?;
-    let _cleanup = TempCleanup::new(video_temp.then_some(video_clean.clone()), audio_temp.then_some(audio_clean.clone()));
-
-    let video_str = video_clean.to_string_lossy();
-    let audio_str = audio_clean.to_string_lossy();
-    let out_str = output_path.to_string_lossy();
-
-    let args = [
-        "-add",
-        &format!("{}#video", video_str),
-        "-add",
-        &format!("{}#audio", audio_str),
-        "-new",
-        &out_str,
-        "-itags",
-        "tool=Bili2MP4",
-    ];
-
-    let output = Command::new(mp4box_path)
-        .args(&args)
-        .output()
-        .map_err(|_| ConvertError::Mp4BoxNotFound)?;
-
-    if cancel.load(Ordering::Relaxed) {
-        let _ = std::fs::remove_file(&output_path);
-        return Err(ConvertError::Cancelled);
-    }
-
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        let mut err_msg = stderr.to_string();
-        if err_msg.len() > 200 {
-            err_msg.truncate(200);
-            err_msg.push_str("...");
-        }
-        return Err(ConvertError::Mp4BoxFailed(err_msg));
-    }
-
-    on_progress(ConvertProgress {
-        current_file: video.title.clone(),
-        current_index: 1,
-        total: 1,
-        percent: 100,
-    });
-
+    check_sources(video, cancel)?;
+    let output_path = resolve_output_path(out_dir, &video.title, Container::Mp4.extension(), strategy)?;
+    mux_mp4box(video, &output_path, Container::Mp4, mp4box_path, false, &[], on_progress, cancel)?;
+    relocate_moov(&output_path, Container::Mp4)?;
     Ok(output_path)
 }
 
@@ -161,46 +176,10 @@ pub fn convert_one_ffmpeg(
     strategy: ConflictStrategy,
     cancel: &AtomicBool,
 ) -> Result<std::path::PathBuf, ConvertError> {
-    if cancel.load(Ordering::Relaxed) {
-        return Err(ConvertError::Cancelled);
-    }
-    if !video.video_path.exists() || !video.audio_path.exists() {
-        return Err(ConvertError::FileNotFound("video or audio".to_string()));
-    }
-
-    let output_path = resolve_output_path(out_dir, &video.title, strategy)?;
-
-    let (video_clean, video_temp) = ensure_clean_m4s(&video.video_path)
-        .map_err(|e| ConvertError::Mp4BoxFailed(e.to_string()))?;
-    let (audio_clean, audio_temp) = ensure_clean_m4s(&video.audio_path)
-        .map_err(|e| ConvertError::Mp4BoxFailed(e.to_string()))?;
-    let _cleanup = TempCleanup::new(video_temp.then_some(video_clean.clone()), audio_temp.then_some(audio_clean.clone()));
-
-    let video_str = video_clean.to_string_lossy();
-    let audio_str = audio_clean.to_string_lossy();
-    let out_str = output_path.to_string_lossy();
-
-    // ffmpeg -y -i video.m4s -i audio.m4s -c copy -movflags +faststart output.mp4
-    let output = Command::new(ffmpeg_path)
-        .args(["-y", "-i", &video_str, "-i", &audio_str, "-c", "copy", "-movflags", "+faststart", &out_str])
-        .output()
-        .map_err(|_| ConvertError::Mp4BoxFailed("ffmpeg 未找到，请安装: brew install ffmpeg".to_string()))?;
-
-    if cancel.load(Ordering::Relaxed) {
-        let _ = std::fs::remove_file(&output_path);
-        return Err(ConvertError::Cancelled);
-    }
-
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        let mut err_msg = stderr.to_string();
-        if err_msg.len() > 200 {
-            err_msg.truncate(200);
-            err_msg.push_str("...");
-        }
-        return Err(ConvertError::Mp4BoxFailed(format!("ffmpeg: {}", err_msg)));
-    }
-
+    check_sources(video, cancel)?;
+    let output_path = resolve_output_path(out_dir, &video.title, Container::Mp4.extension(), strategy)?;
+    mux_ffmpeg(video, &output_path, Container::Mp4, ffmpeg_path, &[], cancel)?;
+    relocate_moov(&output_path, Container::Mp4)?;
     Ok(output_path)
 }
 
@@ -213,16 +192,38 @@ pub fn convert_one_raw(
     on_progress: impl Fn(ConvertProgress),
     cancel: &AtomicBool,
 ) -> Result<std::path::PathBuf, ConvertError> {
+    check_sources(video, cancel)?;
+    let output_path = resolve_output_path(out_dir, &video.title, Container::Mp4.extension(), strategy)?;
+    mux_mp4box(video, &output_path, Container::Mp4, mp4box_path, true, &[], on_progress, cancel)?;
+    relocate_moov(&output_path, Container::Mp4)?;
+    Ok(output_path)
+}
+
+/// 检查取消标记与源文件
+pub(super) fn check_sources(video: &VideoInfo, cancel: &AtomicBool) -> Result<(), ConvertError> {
     if cancel.load(Ordering::Relaxed) {
         return Err(ConvertError::Cancelled);
     }
-
-    if !video.video_path.exists() || !video.audio_path.exists() {
-        return Err(ConvertError::FileNotFound("video or audio".to_string()));
+    for path in [&video.video_path, &video.audio_path] {
+        if !path.exists() {
+            return Err(ConvertError::FileNotFound(path.display().to_string()));
+        }
     }
+    Ok(())
+}
 
-    let output_path = resolve_output_path(out_dir, &video.title, strategy)?;
-
+/// 用 MP4Box 将音视频封装到已确定的输出路径；`raw` 为 true 时使用 :raw 导入，`extra` 为已校验的附加参数
+#[allow(clippy::too_many_arguments)]
+pub(super) fn mux_mp4box(
+    video: &VideoInfo,
+    output_path: &Path,
+    container: Container,
+    mp4box_path: &str,
+    raw: bool,
+    extra: &[String],
+    on_progress: impl Fn(ConvertProgress),
+    cancel: &AtomicBool,
+) -> Result<(), ConvertError> {
     on_progress(ConvertProgress {
         current_file: video.title.clone(),
         current_index: 1,
@@ -231,39 +232,23 @@ pub fn convert_one_raw(
     });
 
     let (video_clean, video_temp) = ensure_clean_m4s(&video.video_path)
-        .map_err(|e| ConvertError::Mp4BoxFailed(e.to_string()))?;
+        .map_err(|e| ConvertError::Io(e.to_string()))?;
     let (audio_clean, audio_temp) = ensure_clean_m4s(&video.audio_path)
-        .map_err(|e| ConvertError::Mp4BoxFailed(e.to_string()))?;
+        .map_err(|e| ConvertError::Io(e.to_string()))?;
     let _cleanup = TempCleanup::new(video_temp.then_some(video_clean.clone()), audio_temp.then_some(audio_clean.clone()));
 
-    let video_str = video_clean.to_string_lossy();
-    let audio_str = audio_clean.to_string_lossy();
-    let out_str = output_path.to_string_lossy();
-
-    let args = [
-        "-add",
-        &format!("{}#video:raw", video_str),
-        "-add",
-        &format!("{}#audio:raw", audio_str),
-        "-new",
-        &out_str,
-        "-itags",
-        "tool=Bili2MP4",
-    ];
-
     let output = Command::new(mp4box_path)
-        .args(&args)
+        .args(mp4box_args(&video_clean, &audio_clean, output_path, container, raw, extra))
         .output()
-        .map_err(|_| ConvertError::Mp4BoxNotFound)?;
+        .map_err(|_| ConvertError::ToolNotFound { tool: Tool::Mp4Box })?;
 
     if cancel.load(Ordering::Relaxed) {
-        let _ = std::fs::remove_file(&output_path);
+        let _ = std::fs::remove_file(output_path);
         return Err(ConvertError::Cancelled);
     }
 
     if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(ConvertError::Mp4BoxFailed(stderr.to_string()));
+        return Err(ConvertError::Mp4BoxFailed(truncate_stderr(&output.stderr)));
     }
 
     on_progress(ConvertProgress {
@@ -272,8 +257,51 @@ pub fn convert_one_raw(
         total: 1,
         percent: 100,
     });
+    Ok(())
+}
 
-    Ok(output_path)
+/// 用 ffmpeg -c copy 将音视频封装到已确定的输出路径
+pub(super) fn mux_ffmpeg(
+    video: &VideoInfo,
+    output_path: &Path,
+    container: Container,
+    ffmpeg_path: &str,
+    extra: &[String],
+    cancel: &AtomicBool,
+) -> Result<(), ConvertError> {
+    let (video_clean, video_temp) = ensure_clean_m4s(&video.video_path)
+        .map_err(|e| ConvertError::Io(e.to_string()))?;
+    let (audio_clean, audio_temp) = ensure_clean_m4s(&video.audio_path)
+        .map_err(|e| ConvertError::Io(e.to_string()))?;
+    let _cleanup = TempCleanup::new(video_temp.then_some(video_clean.clone()), audio_temp.then_some(audio_clean.clone()));
+
+    let output = Command::new(ffmpeg_path)
+        .args(ffmpeg_args(&video_clean, &audio_clean, output_path, container, extra))
+        .output()
+        .map_err(|_| ConvertError::ToolNotFound { tool: Tool::Ffmpeg })?;
+
+    if cancel.load(Ordering::Relaxed) {
+        let _ = std::fs::remove_file(output_path);
+        return Err(ConvertError::Cancelled);
+    }
+
+    if !output.status.success() {
+        return Err(ConvertError::FfmpegFailed(truncate_stderr(&output.stderr)));
+    }
+    Ok(())
+}
+
+fn truncate_stderr(stderr: &[u8]) -> String {
+    let mut err_msg = String::from_utf8_lossy(stderr).to_string();
+    if err_msg.len() > 200 {
+        let mut end = 200;
+        while !err_msg.is_char_boundary(end) {
+            end -= 1;
+        }
+        err_msg.truncate(end);
+        err_msg.push_str("...");
+    }
+    err_msg
 }
 
 #[cfg(test)]
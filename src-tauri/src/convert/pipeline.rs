@@ -0,0 +1,345 @@
+//! 转换回退链：默认 MP4Box → MP4Box :raw → ffmpeg，可由转换方案调整
+
+use super::args::{backend_args, command_line, CommandPreview, ExtraArgs};
+use super::codec::{probe_codec, Codec};
+use super::faststart::relocate_moov;
+use super::mp4box::{check_sources, mux_ffmpeg, mux_mp4box, run_tool, ConvertError, ConvertProgress};
+use crate::cache::{read_details, VideoInfo};
+use crate::config::{resolve_ffmpeg_path, resolve_mp4box_path, AppConfig, Profile};
+use crate::filemgr::{library_path, render_name, resolve_path, OutputLayout};
+use crate::toolchain::{probe, Tool};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+
+/// 转换所需外部工具路径及各后端附加参数
+#[derive(Debug, Clone)]
+pub struct ConvertTools {
+    pub mp4box: String,
+    pub ffmpeg: String,
+    pub extra_args: ExtraArgs,
+}
+
+impl ConvertTools {
+    /// 按配置解析工具路径
+    pub fn from_config(config: &AppConfig) -> Self {
+        Self {
+            mp4box: resolve_mp4box_path(config),
+            ffmpeg: resolve_ffmpeg_path(config),
+            extra_args: config.extra_args.clone(),
+        }
+    }
+
+    pub fn program(&self, backend: Backend) -> &str {
+        match backend {
+            Backend::Mp4Box | Backend::Mp4BoxRaw => &self.mp4box,
+            Backend::Ffmpeg => &self.ffmpeg,
+        }
+    }
+
+    /// 预览某后端将执行的命令行
+    pub fn preview(&self, backend: Backend, video: &Path, audio: &Path, output: &Path, container: Container) -> String {
+        let args = backend_args(backend, video, audio, output, container, &self.extra_args);
+        command_line(self.program(backend), &args)
+    }
+
+    /// 以占位文件名预览回退链中能写出该容器的后端的命令行
+    pub fn preview_all(&self, container: Container) -> Vec<CommandPreview> {
+        let (video, audio) = (Path::new("video.m4s"), Path::new("audio.m4s"));
+        let output = PathBuf::from(format!("output.{}", container.extension()));
+        DEFAULT_CHAIN
+            .iter()
+            .filter(|&&backend| container.written_by(backend))
+            .map(|&backend| CommandPreview {
+                backend,
+                command: self.preview(backend, video, audio, &output, container),
+            })
+            .collect()
+    }
+}
+
+/// 转换后端
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Backend {
+    /// MP4Box #video/#audio
+    #[serde(rename = "mp4box")]
+    Mp4Box,
+    /// MP4Box :raw 模式
+    #[serde(rename = "mp4box_raw")]
+    Mp4BoxRaw,
+    /// ffmpeg -c copy
+    Ffmpeg,
+}
+
+impl Backend {
+    pub fn label(self) -> &'static str {
+        match self {
+            Backend::Mp4Box => "MP4Box",
+            Backend::Mp4BoxRaw => "raw",
+            Backend::Ffmpeg => "ffmpeg",
+        }
+    }
+
+    /// 执行该后端的外部工具
+    pub fn tool(self) -> Tool {
+        match self {
+            Backend::Mp4Box | Backend::Mp4BoxRaw => Tool::Mp4Box,
+            Backend::Ffmpeg => Tool::Ffmpeg,
+        }
+    }
+}
+
+/// 输出容器
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Container {
+    #[default]
+    Mp4,
+    /// 分片 MP4（moov 在前、按关键帧分片），写入中断时已写部分仍可播放
+    Fmp4,
+    /// Matroska，可容纳 FLAC、Opus 等任意编码，只能由 ffmpeg 写出
+    Mkv,
+    /// QuickTime，便于导入剪辑软件，只能由 ffmpeg 写出
+    Mov,
+}
+
+impl Container {
+    pub fn extension(self) -> &'static str {
+        match self {
+            Container::Mp4 | Container::Fmp4 => "mp4",
+            Container::Mkv => "mkv",
+            Container::Mov => "mov",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Container::Mp4 => "MP4",
+            Container::Fmp4 => "分片 MP4",
+            Container::Mkv => "MKV",
+            Container::Mov => "MOV",
+        }
+    }
+
+    /// 能否无损放入该编码：MP4 可容纳 m4s 中的任何编码；MKV 需要已知编码以写出 CodecID；
+    /// MOV 只用于常见的 H.264 / HEVC 与 AAC / AC-3 / E-AC-3
+    pub fn supports(self, codec: &Codec) -> bool {
+        match self {
+            Container::Mp4 | Container::Fmp4 => true,
+            Container::Mkv => !matches!(codec, Codec::Other(_)),
+            Container::Mov => matches!(codec, Codec::Avc | Codec::Hevc | Codec::Aac | Codec::Ac3 | Codec::Eac3),
+        }
+    }
+
+    /// 后端能否写出该容器；MP4Box 只写 MP4 与分片 MP4
+    pub fn written_by(self, backend: Backend) -> bool {
+        backend == Backend::Ffmpeg || matches!(self, Container::Mp4 | Container::Fmp4)
+    }
+
+    /// 拼接、截取等再处理能否由该后端完成；MP4Box 的 `-cat` / `-splitx` 只输出普通 MP4
+    pub fn remuxed_by(self, backend: Backend) -> bool {
+        backend == Backend::Ffmpeg || self == Container::Mp4
+    }
+
+    /// 能否无损分段：分段需读取输出 moov 中的时长与关键帧表，分片 MP4 与 MKV 不支持
+    pub fn splittable(self) -> bool {
+        matches!(self, Container::Mp4 | Container::Mov)
+    }
+
+    /// 该容器无法容纳这些编码的原因
+    pub fn codec_issue(self, codecs: &[Codec]) -> Option<String> {
+        let unsupported: Vec<&str> = codecs.iter().filter(|c| !self.supports(c)).map(|c| c.label()).collect();
+        (!unsupported.is_empty()).then(|| format!("{} 不支持 {} 编码", self.label(), unsupported.join("/")))
+    }
+
+    /// 某后端无法把这些编码写入该容器的原因
+    pub fn unsupported_reason(self, backend: Backend, codecs: &[Codec]) -> Option<String> {
+        if !self.written_by(backend) {
+            return Some(format!("{} 无法写出 {}", backend.label(), self.label()));
+        }
+        self.codec_issue(codecs)
+    }
+}
+
+/// 回退链中每个后端的尝试过程
+#[derive(Debug)]
+pub enum Attempt<'a> {
+    Started(Backend),
+    Failed(Backend, &'a ConvertError),
+    /// 工具无法运行或不支持源编码，未尝试
+    Skipped(Backend, &'a str),
+}
+
+/// 默认回退链
+pub const DEFAULT_CHAIN: [Backend; 3] = [Backend::Mp4Box, Backend::Mp4BoxRaw, Backend::Ffmpeg];
+
+/// 按转换方案的文件名模板（或媒体库结构）与容器生成输出路径（未应用冲突策略）
+pub fn target_path(video: &VideoInfo, out_dir: &Path, profile: &Profile) -> Result<PathBuf, ConvertError> {
+    let rel = match profile.layout {
+        OutputLayout::Template => render_name(&profile.naming_template, video).map_err(ConvertError::Config)?,
+        OutputLayout::MediaServer => library_path(video, &read_details(video)),
+    };
+    let name = rel.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    Ok(out_dir.join(&rel).with_file_name(format!("{}.{}", name, profile.container.extension())))
+}
+
+/// 生成输出路径并应用冲突策略
+pub fn plan_output(video: &VideoInfo, out_dir: &Path, profile: &Profile) -> Result<PathBuf, ConvertError> {
+    Ok(resolve_path(target_path(video, out_dir, profile)?, profile.conflict_strategy)?)
+}
+
+/// 按转换方案的后端顺序依次尝试，返回首个成功的输出路径及所用后端
+///
+/// 开始前探测工具版本与源编码，跳过确定无法处理或无法写出方案容器的后端；
+/// 跳过与取消不会触发回退。所有后端写入同一个输出路径，成功后把 moov 移到文件开头
+pub fn convert_with_fallback(
+    video: &VideoInfo,
+    out_dir: &Path,
+    tools: &ConvertTools,
+    profile: &Profile,
+    on_progress: impl Fn(ConvertProgress),
+    on_attempt: impl Fn(Attempt),
+    cancel: &AtomicBool,
+) -> Result<(PathBuf, Backend), ConvertError> {
+    check_sources(video, cancel)?;
+    // 配置文件可能被手工修改，执行前再次校验附加参数
+    let issues = tools.extra_args.issues();
+    if !issues.is_empty() {
+        return Err(ConvertError::Config(issues.join("；")));
+    }
+    let output_path = plan_output(video, out_dir, profile)?;
+    if let Some(parent) = output_path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| ConvertError::Io(format!("无法创建目录: {}", e)))?;
+    }
+    let backend =
+        mux_with_fallback(video, &output_path, profile.container, tools, profile, on_progress, on_attempt, cancel)?;
+    relocate_moov(&output_path, profile.container)?;
+    Ok((output_path, backend))
+}
+
+/// 探测源视频与音频的编码，无法识别的略过
+pub(super) fn probe_codecs(video: &VideoInfo) -> Vec<Codec> {
+    [&video.video_path, &video.audio_path]
+        .into_iter()
+        .filter_map(|p| probe_codec(p).ok().flatten())
+        .collect()
+}
+
+/// 源编码无法放入方案容器时报错；用于不经 [`mux_with_fallback`] 写出方案容器的合并与截取
+pub(super) fn check_container(video: &VideoInfo, container: Container) -> Result<(), ConvertError> {
+    match container.codec_issue(&probe_codecs(video)) {
+        Some(reason) => Err(ConvertError::UnsupportedCodec(reason)),
+        None => Ok(()),
+    }
+}
+
+/// 按方案的后端顺序把音视频封装为 `container` 并写到已确定的输出路径，返回成功的后端；
+/// 合并与截取的中间文件总是 MP4
+#[allow(clippy::too_many_arguments)]
+pub(super) fn mux_with_fallback(
+    video: &VideoInfo,
+    output_path: &Path,
+    container: Container,
+    tools: &ConvertTools,
+    profile: &Profile,
+    on_progress: impl Fn(ConvertProgress),
+    on_attempt: impl Fn(Attempt),
+    cancel: &AtomicBool,
+) -> Result<Backend, ConvertError> {
+    let codecs = probe_codecs(video);
+    // 容器无法容纳源编码时换哪个后端都不行，不属于后端不可用
+    if let Some(reason) = container.codec_issue(&codecs) {
+        return Err(ConvertError::UnsupportedCodec(reason));
+    }
+    let mp4box = probe(Tool::Mp4Box, &tools.mp4box);
+    let ffmpeg = probe(Tool::Ffmpeg, &tools.ffmpeg);
+
+    let mut last_err = None;
+    let mut skipped = Vec::new();
+    for &backend in &profile.backends {
+        let info = if backend == Backend::Ffmpeg { &ffmpeg } else { &mp4box };
+        let reason = container
+            .unsupported_reason(backend, &codecs)
+            .or_else(|| info.unavailable_reason(&codecs));
+        if let Some(reason) = reason {
+            on_attempt(Attempt::Skipped(backend, &reason));
+            skipped.push(reason);
+            continue;
+        }
+
+        on_attempt(Attempt::Started(backend));
+        let result = match backend {
+            Backend::Mp4Box | Backend::Mp4BoxRaw => mux_mp4box(
+                video,
+                output_path,
+                container,
+                &tools.mp4box,
+                backend == Backend::Mp4BoxRaw,
+                tools.extra_args.for_backend(backend),
+                &on_progress,
+                cancel,
+            ),
+            Backend::Ffmpeg => {
+                mux_ffmpeg(video, output_path, container, &tools.ffmpeg, &tools.extra_args.ffmpeg, cancel)
+            }
+        };
+        match result {
+            Ok(()) => return Ok(backend),
+            Err(e @ (ConvertError::Cancelled | ConvertError::Skipped(_))) => return Err(e),
+            Err(e) => {
+                on_attempt(Attempt::Failed(backend, &e));
+                last_err = Some(e);
+            }
+        }
+    }
+    skipped.dedup();
+    Err(last_err.unwrap_or_else(|| ConvertError::NoBackend(skipped.join("；"))))
+}
+
+/// 对已封装的 MP4 再处理（拼接、截取），按方案的后端顺序尝试，返回成功的后端
+///
+/// `args` 给出某后端的完整参数，可在其中写入章节等辅助文件；
+/// MP4Box 与 :raw 的再处理方式相同，只尝试一次；无法输出方案容器的后端跳过
+pub(super) fn remux_with_fallback(
+    tools: &ConvertTools,
+    profile: &Profile,
+    output: &Path,
+    args: impl Fn(Backend) -> Result<Vec<String>, ConvertError>,
+    on_attempt: impl Fn(Attempt),
+    cancel: &AtomicBool,
+) -> Result<Backend, ConvertError> {
+    let mut tried_mp4box = false;
+    let mut last_err = None;
+    let mut skipped = Vec::new();
+    for &backend in &profile.backends {
+        if !profile.container.remuxed_by(backend) {
+            let reason = format!("{} 无法输出 {}", backend.label(), profile.container.label());
+            on_attempt(Attempt::Skipped(backend, &reason));
+            skipped.push(reason);
+            continue;
+        }
+        if backend != Backend::Ffmpeg {
+            if tried_mp4box {
+                continue;
+            }
+            tried_mp4box = true;
+        }
+        on_attempt(Attempt::Started(backend));
+        let result =
+            args(backend).and_then(|args| run_tool(backend.tool(), tools.program(backend), &args, output, cancel));
+        match result {
+            Ok(()) => return Ok(backend),
+            Err(e @ ConvertError::Cancelled) => return Err(e),
+            Err(e) => {
+                on_attempt(Attempt::Failed(backend, &e));
+                last_err = Some(e);
+            }
+        }
+    }
+    skipped.dedup();
+    Err(last_err.unwrap_or_else(|| {
+        let reason = if skipped.is_empty() { "方案中没有可用的后端".to_string() } else { skipped.join("；") };
+        ConvertError::NoBackend(reason)
+    }))
+}
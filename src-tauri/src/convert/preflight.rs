@@ -0,0 +1,268 @@
+//! 转换前磁盘空间预检：按冲突策略估算输出与临时文件占用，检查输出卷与临时目录卷的剩余空间
+
+use super::mp4box::{has_padding, temp_dir};
+use super::pipeline::target_path;
+use crate::cache::VideoInfo;
+use crate::config::Profile;
+use crate::filemgr::ConflictStrategy;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+/// 剩余空间余量下限：512 MiB
+const MIN_MARGIN: u64 = 512 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SpaceStatus {
+    /// 空间充足，或无法获取剩余空间
+    Ok,
+    /// 够用但余量不足，建议确认后继续
+    Low,
+    /// 不够用，应拒绝开始
+    Insufficient,
+}
+
+/// 单个卷的需求与剩余空间
+#[derive(Debug, Clone, Serialize)]
+pub struct VolumeUsage {
+    /// 用于查询的目录
+    pub path: String,
+    pub required_bytes: u64,
+    /// 无法查询时为空
+    pub available_bytes: Option<u64>,
+    pub status: SpaceStatus,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Preflight {
+    /// 所选视频的源文件总大小
+    pub selected_bytes: u64,
+    /// 按冲突策略估算的新增输出大小
+    pub output_bytes: u64,
+    /// 临时文件峰值（任务串行执行，取单个任务的最大值）
+    pub temp_bytes: u64,
+    /// 分段时原输出与各段同时存在，输出卷需额外容纳的峰值
+    pub split_bytes: u64,
+    /// 因输出已存在将被跳过的数量
+    pub skipped: usize,
+    /// 输出目录与临时目录在同一个卷上，需求合并计算
+    pub same_volume: bool,
+    pub output: VolumeUsage,
+    pub temp: VolumeUsage,
+    pub status: SpaceStatus,
+    /// 面向用户的中文说明
+    pub message: String,
+}
+
+impl Preflight {
+    fn describe(&self) -> String {
+        let volume = |v: &VolumeUsage| match v.available_bytes {
+            Some(free) => format!("需要 {}，剩余 {}", format_bytes(v.required_bytes), format_bytes(free)),
+            None => format!("需要 {}，剩余空间未知", format_bytes(v.required_bytes)),
+        };
+        let mut msg = format!(
+            "源文件 {}，预计输出 {}，临时文件 {}",
+            format_bytes(self.selected_bytes),
+            format_bytes(self.output_bytes),
+            format_bytes(self.temp_bytes)
+        );
+        if self.split_bytes > 0 {
+            msg.push_str(&format!("，分段另需 {}", format_bytes(self.split_bytes)));
+        }
+        if self.skipped > 0 {
+            msg.push_str(&format!("（{} 个已存在将跳过）", self.skipped));
+        }
+        if self.same_volume {
+            msg.push_str(&format!("；输出与临时目录同卷: {}", volume(&self.output)));
+        } else {
+            msg.push_str(&format!("；输出卷: {}；临时目录卷: {}", volume(&self.output), volume(&self.temp)));
+        }
+        match self.status {
+            SpaceStatus::Ok => msg,
+            SpaceStatus::Low => format!("磁盘空间余量不足，{}", msg),
+            SpaceStatus::Insufficient => format!("磁盘空间不足，{}", msg),
+        }
+    }
+}
+
+/// 估算一批视频转换到 `out_dir` 的空间需求并检查剩余空间
+pub fn preflight(items: &[VideoInfo], out_dir: &Path, profile: &Profile) -> Preflight {
+    let mut selected_bytes = 0;
+    let mut output_bytes = 0u64;
+    let mut temp_bytes = 0;
+    let mut split_bytes = 0;
+    let mut skipped = 0;
+    for video in items {
+        selected_bytes += video.size_bytes;
+        // 无损封装的输出与源文件相当，预留 1% 容器开销
+        let estimate = video.size_bytes + video.size_bytes / 100;
+        let existing = target_path(video, out_dir, profile)
+            .ok()
+            .and_then(|p| std::fs::metadata(p).ok())
+            .map(|m| m.len());
+        let added = match (existing, profile.conflict_strategy) {
+            (Some(_), ConflictStrategy::Skip) => {
+                skipped += 1;
+                continue;
+            }
+            (Some(old), ConflictStrategy::Overwrite) => estimate.saturating_sub(old),
+            _ => estimate,
+        };
+        output_bytes += added;
+        // 时长未知时按可能分段计算
+        if profile.split.is_set() && profile.split.exceeded_by(estimate, video.duration_ms.unwrap_or(u64::MAX)) {
+            split_bytes = split_bytes.max(estimate);
+        }
+        let padded: u64 = [&video.video_path, &video.audio_path]
+            .into_iter()
+            .filter(|p| has_padding(p))
+            .filter_map(|p| std::fs::metadata(p).ok())
+            .map(|m| m.len())
+            .sum();
+        temp_bytes = temp_bytes.max(padded);
+    }
+
+    let temp_root = temp_dir();
+    let out_probe = existing_ancestor(out_dir);
+    let temp_probe = existing_ancestor(&temp_root);
+    let same_volume = match (&out_probe, &temp_probe) {
+        (Some(a), Some(b)) => volume_id(a).is_some() && volume_id(a) == volume_id(b),
+        _ => false,
+    };
+    let (out_required, temp_required) = if same_volume {
+        let total = output_bytes + split_bytes + temp_bytes;
+        (total, total)
+    } else {
+        (output_bytes + split_bytes, temp_bytes)
+    };
+    let output = usage(out_dir, out_probe.as_deref(), out_required);
+    let temp = usage(&temp_root, temp_probe.as_deref(), temp_required);
+    let status = output.status.max(temp.status);
+    let mut check = Preflight {
+        selected_bytes,
+        output_bytes,
+        temp_bytes,
+        split_bytes,
+        skipped,
+        same_volume,
+        output,
+        temp,
+        status,
+        message: String::new(),
+    };
+    check.message = check.describe();
+    check
+}
+
+fn usage(path: &Path, probe: Option<&Path>, required: u64) -> VolumeUsage {
+    let available_bytes = probe.and_then(|p| fs2::available_space(p).ok());
+    VolumeUsage {
+        path: path.display().to_string(),
+        required_bytes: required,
+        available_bytes,
+        status: evaluate(required, available_bytes),
+    }
+}
+
+/// 余量取需求的 5% 与 512 MiB 中的较大者
+fn evaluate(required: u64, available: Option<u64>) -> SpaceStatus {
+    let Some(available) = available else {
+        return SpaceStatus::Ok;
+    };
+    if required == 0 {
+        SpaceStatus::Ok
+    } else if available < required {
+        SpaceStatus::Insufficient
+    } else if available - required < MIN_MARGIN.max(required / 20) {
+        SpaceStatus::Low
+    } else {
+        SpaceStatus::Ok
+    }
+}
+
+/// 输出目录可能尚未创建，向上取首个存在的目录
+fn existing_ancestor(path: &Path) -> Option<PathBuf> {
+    path.ancestors().find(|p| p.exists()).map(Path::to_path_buf)
+}
+
+#[cfg(unix)]
+fn volume_id(path: &Path) -> Option<String> {
+    use std::os::unix::fs::MetadataExt;
+    std::fs::metadata(path).ok().map(|m| m.dev().to_string())
+}
+
+/// 非 Unix 平台按盘符（路径前缀）判断
+#[cfg(not(unix))]
+fn volume_id(path: &Path) -> Option<String> {
+    match path.components().next()? {
+        std::path::Component::Prefix(p) => Some(p.as_os_str().to_string_lossy().to_ascii_uppercase()),
+        _ => None,
+    }
+}
+
+/// 字节数转为 KB / MB / GB 文本
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.2} {}", value, UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::convert::SplitLimit;
+
+    const GIB: u64 = 1024 * 1024 * 1024;
+
+    #[test]
+    fn test_evaluate_margin() {
+        assert_eq!(evaluate(GIB, None), SpaceStatus::Ok);
+        assert_eq!(evaluate(GIB, Some(10 * GIB)), SpaceStatus::Ok);
+        assert_eq!(evaluate(GIB, Some(GIB + 100 * 1024 * 1024)), SpaceStatus::Low);
+        assert_eq!(evaluate(2 * GIB, Some(GIB)), SpaceStatus::Insufficient);
+        assert_eq!(format_bytes(1536 * 1024 * 1024), "1.50 GB");
+    }
+
+    #[test]
+    fn test_preflight_counts_padding_and_skips() {
+        let tmp = std::env::temp_dir().join("bili2mp4_preflight_test");
+        std::fs::remove_dir_all(&tmp).ok();
+        let cache = tmp.join("cache");
+        let out = tmp.join("out");
+        std::fs::create_dir_all(&cache).unwrap();
+        std::fs::create_dir_all(&out).unwrap();
+        let mut padded = vec![0x30u8; 9];
+        padded.extend([0u8; 991]);
+        std::fs::write(cache.join("video.m4s"), &padded).unwrap();
+        std::fs::write(cache.join("audio.m4s"), [0u8; 500]).unwrap();
+        let video = |title: &str| VideoInfo { size_bytes: 1500, ..VideoInfo::fixture(&cache, title) };
+        std::fs::write(out.join("已存在.mp4"), b"old").unwrap();
+
+        let skip = Profile { conflict_strategy: ConflictStrategy::Skip, ..Default::default() };
+        let p = preflight(&[video("新"), video("已存在")], &out, &skip);
+        assert_eq!(p.selected_bytes, 3000);
+        assert_eq!(p.output_bytes, 1515);
+        assert_eq!(p.temp_bytes, 1000);
+        assert_eq!(p.skipped, 1);
+        assert_eq!(p.split_bytes, 0);
+
+        let rename = Profile::default();
+        assert_eq!(preflight(&[video("新"), video("已存在")], &out, &rename).output_bytes, 3030);
+
+        // 超过分段上限的输出在分段期间与各段同时存在
+        let split = Profile { split: SplitLimit { max_bytes: Some(1000), max_seconds: None }, ..Default::default() };
+        let p = preflight(&[video("新")], &out, &split);
+        assert_eq!(p.split_bytes, 1515);
+        assert!(p.message.contains("分段另需"));
+        std::fs::remove_dir_all(&tmp).ok();
+    }
+}
@@ -0,0 +1,230 @@
+//! 无损分段：输出超过大小或时长上限时，在关键帧处切成 `…-part1.mp4` 等若干段，不重新编码
+
+use super::args::{ffmpeg_cut_args, mp4box_cut_args};
+use super::codec::{probe_duration_ms, probe_keyframes};
+use super::faststart::relocate_moov;
+use super::mp4box::ConvertError;
+use super::pipeline::{remux_with_fallback, Attempt, Backend, ConvertTools};
+use super::preflight::format_bytes;
+use super::trim::{format_time, TrimRange};
+use crate::config::Profile;
+use crate::filemgr::{part_path, resolve_path};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+
+/// FAT32 单个文件的大小上限
+pub const FAT32_MAX_BYTES: u64 = 4 * 1024 * 1024 * 1024 - 1;
+
+/// 按大小估算分段时预留的余量，码率不均匀时仍可能超出，超出后收紧重切
+const SIZE_MARGINS: [f64; 3] = [0.95, 0.85, 0.7];
+
+/// 分段上限；两项都为空时不分段
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SplitLimit {
+    /// 每段最大字节数
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_bytes: Option<u64>,
+    /// 每段最大时长（秒），分段点须落在关键帧上，实际可能略长
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_seconds: Option<u64>,
+}
+
+impl SplitLimit {
+    pub fn is_set(&self) -> bool {
+        self.max_bytes.is_some() || self.max_seconds.is_some()
+    }
+
+    pub fn issues(&self) -> Vec<String> {
+        let mut issues = Vec::new();
+        if self.max_bytes.is_some_and(|b| b < 1024 * 1024) {
+            issues.push("分段大小不能小于 1 MB".to_string());
+        }
+        if self.max_seconds == Some(0) {
+            issues.push("分段时长必须大于 0".to_string());
+        }
+        issues
+    }
+
+    /// 显示用，如 `4.00 GB / 00:30:00.000`
+    pub fn label(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(bytes) = self.max_bytes {
+            parts.push(if bytes == FAT32_MAX_BYTES { "FAT32 (4 GB)".to_string() } else { format_bytes(bytes) });
+        }
+        if let Some(secs) = self.max_seconds {
+            parts.push(format_time(secs * 1000));
+        }
+        parts.join(" / ")
+    }
+
+    /// 该大小与时长的文件是否需要分段
+    pub fn exceeded_by(&self, bytes: u64, duration_ms: u64) -> bool {
+        self.max_bytes.is_some_and(|max| bytes > max) || self.max_seconds.is_some_and(|max| duration_ms > max * 1000)
+    }
+}
+
+/// 规划分段范围：每段时长取时长上限与按平均码率折算的大小上限（乘以余量）中较小者，
+/// 在此之前的最后一个关键帧处切开；其间没有关键帧时延到下一个关键帧。
+/// 没有关键帧信息时直接按时间切，由后端定位到关键帧
+fn plan_splits(keyframes: &[u64], duration_ms: u64, total_bytes: u64, limit: &SplitLimit, margin: f64) -> Vec<TrimRange> {
+    let by_size = limit
+        .max_bytes
+        .map(|max| (max as f64 * margin / total_bytes.max(1) as f64 * duration_ms as f64) as u64);
+    let by_time = limit.max_seconds.map(|s| s * 1000);
+    let span = by_size.into_iter().chain(by_time).min().unwrap_or(duration_ms).max(1);
+
+    let mut ranges = Vec::new();
+    let mut start = 0;
+    loop {
+        let target = start + span;
+        let end = if target >= duration_ms {
+            None
+        } else if keyframes.is_empty() {
+            Some(target)
+        } else {
+            keyframes
+                .iter()
+                .rev()
+                .find(|&&k| k > start && k <= target)
+                .or_else(|| keyframes.iter().find(|&&k| k > target))
+                .copied()
+                .filter(|&k| k < duration_ms)
+        };
+        ranges.push(TrimRange { start_ms: start, end_ms: end });
+        match end {
+            Some(end) => start = end,
+            None => break ranges,
+        }
+    }
+}
+
+/// 输出超过上限时无损分段：各段名为 `…-part1` 等并应用方案的冲突策略，按方案的后端顺序截取，
+/// 成功后删除原输出；未超过时原样返回。按大小分段时某段仍超出上限则收紧余量重切
+pub fn split_output(
+    output: &Path,
+    limit: &SplitLimit,
+    tools: &ConvertTools,
+    profile: &Profile,
+    on_attempt: impl Fn(Attempt),
+    cancel: &AtomicBool,
+) -> Result<Vec<PathBuf>, ConvertError> {
+    let io_err = |e: std::io::Error| ConvertError::Io(format!("无法读取输出: {}", e));
+    let total_bytes = std::fs::metadata(output).map_err(io_err)?.len();
+    let duration_ms = probe_duration_ms(output)
+        .map_err(io_err)?
+        .ok_or_else(|| ConvertError::Config("无法读取输出时长，不能分段".to_string()))?;
+    if !limit.exceeded_by(total_bytes, duration_ms) {
+        return Ok(vec![output.to_path_buf()]);
+    }
+    if !profile.container.splittable() {
+        return Err(ConvertError::Config(format!("{} 输出不支持分段", profile.container.label())));
+    }
+    let keyframes = probe_keyframes(output).ok().flatten().unwrap_or_default();
+
+    let cut = |margin| -> Result<(Vec<PathBuf>, Option<PathBuf>), ConvertError> {
+        let ranges = plan_splits(&keyframes, duration_ms, total_bytes, limit, margin);
+        let parts = cut_parts(output, &ranges, tools, profile, &on_attempt, cancel)?;
+        let oversized = limit.max_bytes.and_then(|max| {
+            parts.iter().find(|p| std::fs::metadata(p).map(|m| m.len() > max).unwrap_or(false)).cloned()
+        });
+        Ok((parts, oversized))
+    };
+    let finish = |parts| {
+        std::fs::remove_file(output).map_err(|e| ConvertError::Io(format!("无法删除原输出: {}", e)))?;
+        Ok(parts)
+    };
+
+    let [retries @ .., last] = SIZE_MARGINS;
+    for margin in retries {
+        match cut(margin)? {
+            (parts, None) => return finish(parts),
+            (parts, Some(_)) => remove_parts(&parts),
+        }
+    }
+    let (parts, oversized) = cut(last)?;
+    if let Some(part) = oversized {
+        remove_parts(&parts);
+        return Err(ConvertError::Config(format!("分段 {} 仍超出 {}，关键帧间隔过大", part.display(), limit.label())));
+    }
+    finish(parts)
+}
+
+/// 逐段截取；任一段失败时删除已写出的段
+fn cut_parts(
+    output: &Path,
+    ranges: &[TrimRange],
+    tools: &ConvertTools,
+    profile: &Profile,
+    on_attempt: &impl Fn(Attempt),
+    cancel: &AtomicBool,
+) -> Result<Vec<PathBuf>, ConvertError> {
+    let mut parts = Vec::new();
+    for (i, range) in ranges.iter().enumerate() {
+        let result = resolve_path(part_path(output, i + 1), profile.conflict_strategy)
+            .map_err(ConvertError::from)
+            .and_then(|part| {
+                let cut_args = |backend| {
+                    Ok(match backend {
+                        Backend::Ffmpeg => {
+                            ffmpeg_cut_args(output, range.start_ms, range.end_ms, &part, profile.container)
+                        }
+                        _ => mp4box_cut_args(output, range.start_ms, range.end_ms, &part),
+                    })
+                };
+                parts.push(part.clone());
+                remux_with_fallback(tools, profile, &part, cut_args, on_attempt, cancel)?;
+                relocate_moov(&part, profile.container)
+            });
+        if let Err(e) = result {
+            remove_parts(&parts);
+            return Err(e);
+        }
+    }
+    Ok(parts)
+}
+
+fn remove_parts(parts: &[PathBuf]) {
+    for part in parts {
+        std::fs::remove_file(part).ok();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plan_splits() {
+        let keyframes: Vec<u64> = (0..60).map(|i| i * 10_000).collect();
+        // 10 分钟、1000 MB，每段 400 MB：按 95% 余量约 228 秒一段，落在 220 秒的关键帧上
+        let limit = SplitLimit { max_bytes: Some(400 * 1024 * 1024), max_seconds: None };
+        let ranges = plan_splits(&keyframes, 600_000, 1000 * 1024 * 1024, &limit, 0.95);
+        let ends: Vec<Option<u64>> = ranges.iter().map(|r| r.end_ms).collect();
+        assert_eq!(ends, vec![Some(220_000), Some(440_000), None]);
+        assert_eq!(ranges[1].start_ms, 220_000);
+
+        // 时长上限更小时以时长为准
+        let limit = SplitLimit { max_seconds: Some(200), ..limit };
+        let ranges = plan_splits(&keyframes, 600_000, 1000 * 1024 * 1024, &limit, 0.95);
+        assert_eq!(ranges.len(), 3);
+        assert_eq!(ranges[2], TrimRange { start_ms: 400_000, end_ms: None });
+
+        // 上限内没有关键帧时延到下一个关键帧；没有关键帧信息时按时间切
+        let sparse = [0, 250_000, 500_000];
+        let limit = SplitLimit { max_bytes: None, max_seconds: Some(120) };
+        let ends: Vec<Option<u64>> =
+            plan_splits(&sparse, 600_000, 0, &limit, 1.0).iter().map(|r| r.end_ms).collect();
+        assert_eq!(ends, vec![Some(250_000), Some(500_000), None]);
+        assert_eq!(plan_splits(&[], 300_000, 0, &limit, 1.0).len(), 3);
+
+        assert!(limit.exceeded_by(0, 120_001));
+        assert!(!limit.exceeded_by(u64::MAX, 120_000));
+        let fat32 = SplitLimit { max_bytes: Some(FAT32_MAX_BYTES), max_seconds: Some(1800) };
+        assert_eq!(fat32.label(), "FAT32 (4 GB) / 00:30:00.000");
+        assert_eq!(SplitLimit { max_bytes: Some(10), max_seconds: Some(0) }.issues().len(), 2);
+        let json: SplitLimit = serde_json::from_str(r#"{"max_seconds": 600}"#).unwrap();
+        assert_eq!(json, SplitLimit { max_bytes: None, max_seconds: Some(600) });
+    }
+}
@@ -0,0 +1,377 @@
+//! 转码：经 ffmpeg 重新编码为兼容性更好或体积更小的格式，按 `-progress` 输出报告实际进度
+//!
+//! 默认仍为无损封装；单项指定、方案设置为总是转码，或自动模式下源编码不在允许列表中时才转码
+
+use super::args::ffmpeg_transcode_args;
+use super::codec::{probe_codec, probe_duration_ms, Codec};
+use super::faststart::relocate_moov;
+use super::mp4box::{check_sources, ensure_clean_m4s, ConvertError, ConvertProgress, TempCleanup};
+use super::pipeline::{plan_output, ConvertTools};
+use super::trim::{snap_to_keyframes, trimmed_output, TrimRange};
+use crate::cache::VideoInfo;
+use crate::config::Profile;
+use crate::toolchain::Tool;
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// 转码预设
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TranscodePreset {
+    /// H.264 High + AAC，几乎所有设备与剪辑软件都能解码
+    #[default]
+    H264Compat,
+    /// HEVC + AAC，体积更小
+    SmallHevc,
+    /// 视频不变，只把音频转为 AAC
+    AudioAac,
+}
+
+impl TranscodePreset {
+    pub fn label(self) -> &'static str {
+        match self {
+            TranscodePreset::H264Compat => "H.264 兼容",
+            TranscodePreset::SmallHevc => "小体积 HEVC",
+            TranscodePreset::AudioAac => "音频转 AAC",
+        }
+    }
+
+    /// 重新编码视频时的默认 CRF；只转音频时为空
+    pub fn default_crf(self) -> Option<u8> {
+        match self {
+            TranscodePreset::H264Compat => Some(20),
+            TranscodePreset::SmallHevc => Some(28),
+            TranscodePreset::AudioAac => None,
+        }
+    }
+
+    pub fn encodes_video(self) -> bool {
+        self != TranscodePreset::AudioAac
+    }
+}
+
+impl FromStr for TranscodePreset {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "h264" | "h264_compat" => Ok(Self::H264Compat),
+            "hevc" | "small_hevc" => Ok(Self::SmallHevc),
+            "aac" | "audio_aac" => Ok(Self::AudioAac),
+            _ => Err(format!("未知的转码预设: {}（可用: h264、hevc、aac）", s)),
+        }
+    }
+}
+
+/// 一次转码的参数
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TranscodeSettings {
+    pub preset: TranscodePreset,
+    /// 视频质量（0–51，越小越清晰），为空时使用预设的默认值；指定视频码率时不使用
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub crf: Option<u8>,
+    /// 视频码率（kbps），指定后按码率编码
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub video_kbps: Option<u32>,
+    /// 音频码率（kbps），默认 192
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub audio_kbps: Option<u32>,
+}
+
+impl TranscodeSettings {
+    pub const DEFAULT_AUDIO_KBPS: u32 = 192;
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.crf.is_some_and(|crf| crf > 51) {
+            return Err(format!("CRF 应在 0–51 之间: {}", self.crf.unwrap_or_default()));
+        }
+        if self.video_kbps == Some(0) || self.audio_kbps == Some(0) {
+            return Err("码率必须大于 0".to_string());
+        }
+        Ok(())
+    }
+
+    /// 显示用，如「H.264 兼容，CRF 20，音频 192 kbps」
+    pub fn label(&self) -> String {
+        let mut parts = vec![self.preset.label().to_string()];
+        if self.preset.encodes_video() {
+            match (self.video_kbps, self.crf.or(self.preset.default_crf())) {
+                (Some(kbps), _) => parts.push(format!("视频 {} kbps", kbps)),
+                (None, Some(crf)) => parts.push(format!("CRF {}", crf)),
+                (None, None) => {}
+            }
+        }
+        parts.push(format!("音频 {} kbps", self.audio_kbps.unwrap_or(Self::DEFAULT_AUDIO_KBPS)));
+        parts.join("，")
+    }
+}
+
+/// 方案中何时转码
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TranscodeMode {
+    /// 只做无损封装（单项指定时仍转码）
+    #[default]
+    Never,
+    /// 总是转码
+    Always,
+    /// 源视频或音频编码不在允许列表中时转码
+    Auto,
+}
+
+/// 转换方案中的转码设置
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TranscodePolicy {
+    pub mode: TranscodeMode,
+    #[serde(flatten)]
+    pub settings: TranscodeSettings,
+    /// 自动模式下播放设备可直接解码的编码
+    pub allowed_codecs: Vec<Codec>,
+}
+
+impl Default for TranscodePolicy {
+    fn default() -> Self {
+        Self {
+            mode: TranscodeMode::Never,
+            settings: TranscodeSettings::default(),
+            allowed_codecs: vec![Codec::Avc, Codec::Aac],
+        }
+    }
+}
+
+impl TranscodePolicy {
+    pub fn issues(&self) -> Vec<String> {
+        let mut issues: Vec<String> = self.settings.validate().err().into_iter().collect();
+        if self.mode == TranscodeMode::Auto && self.allowed_codecs.is_empty() {
+            issues.push("自动转码需要至少一个允许的编码".to_string());
+        }
+        issues
+    }
+}
+
+/// 决定是否转码：单项指定优先，其次为方案的总是转码或自动判断；返回参数与原因
+pub fn plan_transcode(
+    video: &VideoInfo,
+    policy: &TranscodePolicy,
+    item: Option<&TranscodeSettings>,
+) -> Option<(TranscodeSettings, String)> {
+    if let Some(settings) = item {
+        return Some((*settings, "单项指定".to_string()));
+    }
+    match policy.mode {
+        TranscodeMode::Never => None,
+        TranscodeMode::Always => Some((policy.settings, "方案设置为总是转码".to_string())),
+        TranscodeMode::Auto => {
+            let codec = [&video.video_path, &video.audio_path]
+                .into_iter()
+                .filter_map(|p| probe_codec(p).ok().flatten())
+                .find(|codec| !policy.allowed_codecs.contains(codec))?;
+            let reason = format!("源编码 {} 不在允许列表中", codec.label());
+            if !codec.is_video() || policy.settings.preset.encodes_video() {
+                return Some((policy.settings, reason));
+            }
+            // 只转音频无法处理视频编码，改为重新编码为允许的视频编码
+            let allowed = &policy.allowed_codecs;
+            let preset = if allowed.contains(&Codec::Hevc) && !allowed.contains(&Codec::Avc) {
+                TranscodePreset::SmallHevc
+            } else {
+                TranscodePreset::H264Compat
+            };
+            let settings = TranscodeSettings { preset, ..policy.settings };
+            Some((settings, format!("{}，改用 {}", reason, preset.label())))
+        }
+    }
+}
+
+/// 转码到按方案生成的输出路径，返回输出路径及实际截取范围（未截取或无法对齐到关键帧时为空）
+///
+/// 截取时重新编码视频可精确到帧，按请求范围截取；只转音频时视频不重新编码，起止按关键帧对齐，
+/// 无法读取关键帧时按请求范围交由 ffmpeg 定位
+#[allow(clippy::too_many_arguments)]
+pub fn convert_transcode(
+    video: &VideoInfo,
+    settings: &TranscodeSettings,
+    trim: Option<&TrimRange>,
+    out_dir: &Path,
+    tools: &ConvertTools,
+    profile: &Profile,
+    on_progress: impl Fn(ConvertProgress),
+    cancel: &AtomicBool,
+) -> Result<(PathBuf, Option<TrimRange>), ConvertError> {
+    settings.validate().map_err(ConvertError::Config)?;
+    check_sources(video, cancel)?;
+    let issues = tools.extra_args.issues();
+    if !issues.is_empty() {
+        return Err(ConvertError::Config(issues.join("；")));
+    }
+    // 重新编码的视频与 AAC 音频任何容器都能容纳，只转音频时检查原视频编码
+    if !settings.preset.encodes_video() {
+        let codecs: Vec<Codec> = probe_codec(&video.video_path).ok().flatten().into_iter().collect();
+        if let Some(reason) = profile.container.codec_issue(&codecs) {
+            return Err(ConvertError::UnsupportedCodec(reason));
+        }
+    }
+    if let Some(range) = trim {
+        range.validate()?;
+    }
+    let actual = trim.and_then(|range| {
+        if settings.preset.encodes_video() {
+            Some(*range)
+        } else {
+            snap_to_keyframes(video, range)
+        }
+    });
+    let cut = actual.or(trim.copied());
+    let output_path = match &cut {
+        Some(range) => trimmed_output(video, range, out_dir, profile)?,
+        None => plan_output(video, out_dir, profile)?,
+    };
+    if let Some(parent) = output_path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| ConvertError::Io(format!("无法创建目录: {}", e)))?;
+    }
+
+    // 截取时进度按截取长度计算
+    let total_ms = video
+        .duration_ms
+        .or_else(|| probe_duration_ms(&video.video_path).ok().flatten())
+        .map(|d| match cut {
+            Some(range) => range.end_ms.unwrap_or(d).min(d).saturating_sub(range.start_ms),
+            None => d,
+        });
+    let (video_clean, video_temp) =
+        ensure_clean_m4s(&video.video_path).map_err(|e| ConvertError::Io(e.to_string()))?;
+    let (audio_clean, audio_temp) =
+        ensure_clean_m4s(&video.audio_path).map_err(|e| ConvertError::Io(e.to_string()))?;
+    let _cleanup = TempCleanup::new(video_temp.then_some(video_clean.clone()), audio_temp.then_some(audio_clean.clone()));
+
+    // 音频已是 AAC 时直接复制，不再有损重编码
+    let copy_audio = probe_codec(&video.audio_path).ok().flatten() == Some(Codec::Aac);
+    let args = ffmpeg_transcode_args(
+        &video_clean,
+        &audio_clean,
+        &output_path,
+        profile.container,
+        settings,
+        cut.as_ref(),
+        copy_audio,
+        &tools.extra_args.ffmpeg,
+    );
+    let progress = |percent: u32| {
+        on_progress(ConvertProgress { current_file: video.title.clone(), current_index: 1, total: 1, percent })
+    };
+    progress(0);
+    run_with_progress(&tools.ffmpeg, &args, total_ms, progress, cancel).inspect_err(|_| {
+        let _ = std::fs::remove_file(&output_path);
+    })?;
+    relocate_moov(&output_path, profile.container)?;
+    progress(100);
+    Ok((output_path, actual))
+}
+
+/// 运行 ffmpeg 并解析 `-progress pipe:1` 输出；取消时结束进程
+fn run_with_progress(
+    ffmpeg: &str,
+    args: &[String],
+    total_ms: Option<u64>,
+    progress: impl Fn(u32),
+    cancel: &AtomicBool,
+) -> Result<(), ConvertError> {
+    let mut child = Command::new(ffmpeg)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|_| ConvertError::ToolNotFound { tool: Tool::Ffmpeg })?;
+    // stderr 单独读取，避免管道写满阻塞 ffmpeg
+    let stderr = child.stderr.take().map(|mut pipe| {
+        std::thread::spawn(move || {
+            let mut buf = Vec::new();
+            let _ = pipe.read_to_end(&mut buf);
+            buf
+        })
+    });
+    if let Some(stdout) = child.stdout.take() {
+        for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+            if cancel.load(Ordering::Relaxed) {
+                let _ = child.kill();
+                break;
+            }
+            if let (Some(total), Some(ms)) = (total_ms.filter(|&t| t > 0), out_time_ms(&line)) {
+                progress((ms * 100 / total).min(99) as u32);
+            }
+        }
+    }
+    let status = child.wait().map_err(|e| ConvertError::FfmpegFailed(format!("转码: {}", e)))?;
+    let stderr = stderr.and_then(|h| h.join().ok()).unwrap_or_default();
+    if cancel.load(Ordering::Relaxed) {
+        return Err(ConvertError::Cancelled);
+    }
+    if !status.success() {
+        return Err(ConvertError::FfmpegFailed(format!("转码: {}", last_lines(&stderr))));
+    }
+    Ok(())
+}
+
+/// `out_time_us=` / `out_time_ms=`（两者单位均为微秒）换算为毫秒
+fn out_time_ms(line: &str) -> Option<u64> {
+    let value = line.strip_prefix("out_time_us=").or_else(|| line.strip_prefix("out_time_ms="))?;
+    value.trim().parse::<u64>().ok().map(|us| us / 1000)
+}
+
+/// 转码失败时 stderr 很长，只保留最后几行
+fn last_lines(stderr: &[u8]) -> String {
+    let text = String::from_utf8_lossy(stderr);
+    let lines: Vec<&str> = text.lines().filter(|l| !l.trim().is_empty()).collect();
+    lines[lines.len().saturating_sub(3)..].join(" | ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::convert::codec::tests::init_segment;
+
+    #[test]
+    fn test_plan_transcode_and_settings() {
+        let tmp = std::env::temp_dir().join("bili2mp4_transcode_test");
+        std::fs::create_dir_all(&tmp).unwrap();
+        std::fs::write(tmp.join("video.m4s"), init_segment(b"hev1")).unwrap();
+        std::fs::write(tmp.join("audio.m4s"), init_segment(b"mp4a")).unwrap();
+        let video = VideoInfo { duration_ms: Some(60_000), ..VideoInfo::fixture(&tmp, "t") };
+
+        let mut policy = TranscodePolicy::default();
+        assert_eq!(plan_transcode(&video, &policy, None), None);
+        let item = TranscodeSettings { preset: TranscodePreset::AudioAac, ..Default::default() };
+        assert_eq!(plan_transcode(&video, &policy, Some(&item)).unwrap().0, item);
+        policy.mode = TranscodeMode::Auto;
+        let (settings, reason) = plan_transcode(&video, &policy, None).unwrap();
+        assert_eq!(settings.preset, TranscodePreset::H264Compat);
+        assert_eq!(reason, "源编码 HEVC 不在允许列表中");
+        // 只转音频的预设无法处理不允许的视频编码，改为重新编码视频
+        policy.settings.preset = TranscodePreset::AudioAac;
+        let (settings, reason) = plan_transcode(&video, &policy, None).unwrap();
+        assert_eq!(settings.preset, TranscodePreset::H264Compat);
+        assert_eq!(reason, "源编码 HEVC 不在允许列表中，改用 H.264 兼容");
+        policy.allowed_codecs.push(Codec::Hevc);
+        assert_eq!(plan_transcode(&video, &policy, None), None);
+
+        let json: TranscodePolicy =
+            serde_json::from_str(r#"{"mode":"auto","preset":"small_hevc","crf":30,"allowed_codecs":["avc","aac"]}"#).unwrap();
+        assert_eq!(json.settings.label(), "小体积 HEVC，CRF 30，音频 192 kbps");
+        let bitrate = TranscodeSettings { video_kbps: Some(3000), audio_kbps: Some(128), ..Default::default() };
+        assert_eq!(bitrate.label(), "H.264 兼容，视频 3000 kbps，音频 128 kbps");
+        assert!(TranscodeSettings { crf: Some(60), ..Default::default() }.validate().is_err());
+        assert_eq!("hevc".parse::<TranscodePreset>(), Ok(TranscodePreset::SmallHevc));
+
+        assert_eq!(out_time_ms("out_time_us=1500000"), Some(1500));
+        assert_eq!(out_time_ms("out_time_ms=2000000"), Some(2000));
+        assert_eq!(out_time_ms("frame=10"), None);
+        std::fs::remove_dir_all(&tmp).ok();
+    }
+}
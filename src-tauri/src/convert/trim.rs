@@ -0,0 +1,220 @@
+//! 无损截取：起止时间按关键帧对齐，由后端直接从源文件截取，不重新编码
+
+use super::args::{ffmpeg_trim_args, mp4box_args, mp4box_cut_args};
+use super::codec::probe_keyframes;
+use super::faststart::relocate_moov;
+use super::mp4box::{check_sources, ensure_clean_m4s, run_tool, ConvertError, ConvertProgress, TempCleanup, WorkDir};
+use super::pipeline::{check_container, remux_with_fallback, target_path, Attempt, Backend, ConvertTools};
+use crate::cache::VideoInfo;
+use crate::config::Profile;
+use crate::filemgr::resolve_path;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+
+/// 截取范围（毫秒）；`end_ms` 为空表示截到末尾
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrimRange {
+    #[serde(default)]
+    pub start_ms: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end_ms: Option<u64>,
+}
+
+impl TrimRange {
+    /// 由 `[[时:]分:]秒[.毫秒]` 形式的起止时间构造；两端都未指定时返回 `Ok(None)`
+    pub fn parse(start: Option<&str>, end: Option<&str>) -> Result<Option<Self>, ConvertError> {
+        let time = |s: Option<&str>| {
+            s.map(|s| parse_time(s).ok_or_else(|| ConvertError::Config(format!("无法识别的时间: {}", s))))
+                .transpose()
+        };
+        let (start_ms, end_ms) = (time(start)?, time(end)?);
+        if start_ms.is_none() && end_ms.is_none() {
+            return Ok(None);
+        }
+        let range = Self { start_ms: start_ms.unwrap_or(0), end_ms };
+        range.validate()?;
+        Ok(Some(range))
+    }
+
+    /// 结束时间须晚于开始时间
+    pub fn validate(&self) -> Result<(), ConvertError> {
+        match self.end_ms {
+            Some(end) if end <= self.start_ms => {
+                Err(ConvertError::Config(format!("截取范围无效: {}", self.label())))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// 显示用，如 `00:10:05.000–00:13:07.500`
+    pub fn label(&self) -> String {
+        let end = self.end_ms.map(format_time).unwrap_or_else(|| "结尾".to_string());
+        format!("{}–{}", format_time(self.start_ms), end)
+    }
+
+    /// 附加在输出文件名后，如 ` [00.10.05-00.13.07]`
+    fn file_suffix(&self) -> String {
+        let hms = |ms: u64| format!("{:02}.{:02}.{:02}", ms / 3_600_000, ms / 60_000 % 60, ms / 1000 % 60);
+        let end = self.end_ms.map(hms).unwrap_or_else(|| "结尾".to_string());
+        format!(" [{}-{}]", hms(self.start_ms), end)
+    }
+
+    /// 按关键帧对齐：开始取不晚于请求的最后一个关键帧，结束取不早于请求的第一个关键帧
+    /// （其后没有关键帧时截到末尾），请求的内容都在结果之内
+    pub fn snap(&self, keyframes: &[u64]) -> Self {
+        let start_ms = keyframes.iter().rev().find(|&&k| k <= self.start_ms).copied().unwrap_or(0);
+        let end_ms = self.end_ms.and_then(|end| keyframes.iter().find(|&&k| k >= end).copied());
+        Self { start_ms, end_ms }
+    }
+}
+
+/// 按源视频的关键帧对齐截取范围；无法读取关键帧时返回 `None`，由后端自行定位
+pub fn snap_to_keyframes(video: &VideoInfo, range: &TrimRange) -> Option<TrimRange> {
+    match probe_keyframes(&video.video_path) {
+        Ok(Some(keyframes)) => Some(range.snap(&keyframes)),
+        _ => None,
+    }
+}
+
+/// 解析 `[[时:]分:]秒[.毫秒]`，如 `90`、`1:30`、`1:02:03.5`
+pub fn parse_time(s: &str) -> Option<u64> {
+    let parts: Vec<&str> = s.trim().split(':').collect();
+    if parts.len() > 3 {
+        return None;
+    }
+    let (last, rest) = parts.split_last()?;
+    let (secs, frac) = last.split_once('.').unwrap_or((last, ""));
+    if frac.len() > 3 || !frac.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let mut total: u64 = 0;
+    for part in rest.iter().chain([&secs]) {
+        if part.is_empty() || !part.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        total = total.checked_mul(60)?.checked_add(part.parse().ok()?)?;
+    }
+    let ms: u64 = format!("{:0<3}", frac).parse().ok()?;
+    total.checked_mul(1000)?.checked_add(ms)
+}
+
+/// 毫秒格式化为 `时:分:秒.毫秒`
+pub fn format_time(ms: u64) -> String {
+    format!("{:02}:{:02}:{:02}.{:03}", ms / 3_600_000, ms / 60_000 % 60, ms / 1000 % 60, ms % 1000)
+}
+
+/// 截取的输出路径：按方案生成的文件名后加上实际范围，再应用冲突策略
+pub fn trimmed_output(video: &VideoInfo, range: &TrimRange, out_dir: &Path, profile: &Profile) -> Result<PathBuf, ConvertError> {
+    let target = target_path(video, out_dir, profile)?;
+    let stem = target.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+    let ext = profile.container.extension();
+    let target = target.with_file_name(format!("{}{}.{}", stem, range.file_suffix(), ext));
+    Ok(resolve_path(target, profile.conflict_strategy)?)
+}
+
+/// 截取转换：起止时间按视频的关键帧对齐后，由后端直接从源文件截取到输出路径；
+/// 返回输出路径、所用后端与实际范围
+///
+/// ffmpeg 以 `-ss`/`-to` 作为两路输入的选项定位；MP4Box 先分别截取音视频，再封装截取的部分。
+/// 无法读取关键帧时按请求范围交由后端定位（同样落在关键帧上），实际范围未知，返回 `None`
+#[allow(clippy::too_many_arguments)]
+pub fn convert_trimmed(
+    video: &VideoInfo,
+    range: &TrimRange,
+    out_dir: &Path,
+    tools: &ConvertTools,
+    profile: &Profile,
+    on_progress: impl Fn(ConvertProgress),
+    on_attempt: impl Fn(Attempt),
+    cancel: &AtomicBool,
+) -> Result<(PathBuf, Backend, Option<TrimRange>), ConvertError> {
+    range.validate()?;
+    check_sources(video, cancel)?;
+    let issues = tools.extra_args.issues();
+    if !issues.is_empty() {
+        return Err(ConvertError::Config(issues.join("；")));
+    }
+    if let Some(duration) = video.duration_ms.filter(|&d| range.start_ms >= d) {
+        return Err(ConvertError::Config(format!(
+            "开始时间 {} 超出视频时长 {}",
+            format_time(range.start_ms),
+            format_time(duration)
+        )));
+    }
+    check_container(video, profile.container)?;
+    let actual = snap_to_keyframes(video, range);
+    let cut = actual.unwrap_or(*range);
+
+    let output_path = trimmed_output(video, &cut, out_dir, profile)?;
+    if let Some(parent) = output_path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| ConvertError::Io(format!("无法创建目录: {}", e)))?;
+    }
+    let progress = |percent| {
+        on_progress(ConvertProgress { current_file: video.title.clone(), current_index: 1, total: 1, percent })
+    };
+    progress(0);
+    let (video_clean, video_temp) =
+        ensure_clean_m4s(&video.video_path).map_err(|e| ConvertError::Io(e.to_string()))?;
+    let (audio_clean, audio_temp) =
+        ensure_clean_m4s(&video.audio_path).map_err(|e| ConvertError::Io(e.to_string()))?;
+    let _cleanup = TempCleanup::new(video_temp.then_some(video_clean.clone()), audio_temp.then_some(audio_clean.clone()));
+
+    // MP4Box 的截取片段只含所选范围
+    let work = WorkDir::create("trim")?;
+    let cut_args = |backend| match backend {
+        Backend::Ffmpeg => {
+            Ok(ffmpeg_trim_args(&video_clean, &audio_clean, &cut, &output_path, profile.container, &tools.extra_args.ffmpeg))
+        }
+        _ => {
+            let mut pieces = Vec::new();
+            for (source, name) in [(&video_clean, "video.mp4"), (&audio_clean, "audio.mp4")] {
+                let piece = work.path().join(name);
+                let args = mp4box_cut_args(source, cut.start_ms, cut.end_ms, &piece);
+                run_tool(backend.tool(), tools.program(backend), &args, &piece, cancel)?;
+                pieces.push(piece);
+            }
+            let extra = tools.extra_args.for_backend(backend);
+            Ok(mp4box_args(&pieces[0], &pieces[1], &output_path, profile.container, backend == Backend::Mp4BoxRaw, extra))
+        }
+    };
+    let backend = remux_with_fallback(tools, profile, &output_path, cut_args, &on_attempt, cancel)?;
+    relocate_moov(&output_path, profile.container)?;
+    progress(100);
+    Ok((output_path, backend, actual))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trim_range() {
+        assert_eq!(parse_time("90"), Some(90_000));
+        assert_eq!(parse_time("1:30"), Some(90_000));
+        assert_eq!(parse_time("1:02:03.5"), Some(3_723_500));
+        assert_eq!(parse_time("0:00.045"), Some(45));
+        for bad in ["", "1::2", "a", "1.2345", "1:2:3:4", "-5"] {
+            assert_eq!(parse_time(bad), None, "{}", bad);
+        }
+
+        assert_eq!(TrimRange::parse(None, None).unwrap(), None);
+        let range = TrimRange::parse(Some("10:05"), Some("13:00")).unwrap().unwrap();
+        assert_eq!(range, TrimRange { start_ms: 605_000, end_ms: Some(780_000) });
+        assert!(TrimRange::parse(Some("2:00"), Some("1:00")).is_err());
+        assert!(TrimRange::parse(Some("x"), None).is_err());
+
+        let keyframes = [0, 600_000, 604_000, 610_000, 782_000];
+        let snapped = range.snap(&keyframes);
+        assert_eq!(snapped, TrimRange { start_ms: 604_000, end_ms: Some(782_000) });
+        assert_eq!(snapped.label(), "00:10:04.000–00:13:02.000");
+        assert_eq!(snapped.file_suffix(), " [00.10.04-00.13.02]");
+        // 结束之后没有关键帧时截到末尾
+        let tail = TrimRange { start_ms: 700_000, end_ms: Some(790_000) }.snap(&keyframes);
+        assert_eq!(tail, TrimRange { start_ms: 610_000, end_ms: None });
+        assert_eq!(tail.file_suffix(), " [00.10.10-结尾]");
+
+        let json: TrimRange = serde_json::from_str(r#"{"end_ms":5000}"#).unwrap();
+        assert_eq!(json, TrimRange { start_ms: 0, end_ms: Some(5000) });
+    }
+}
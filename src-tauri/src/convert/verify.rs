@@ -0,0 +1,66 @@
+//! 转换结果校验：输出可解析、包含音视频两条轨道、大小与源文件相符
+
+use super::codec::count_tracks;
+use super::mp4box::has_padding;
+use crate::cache::VideoInfo;
+use std::path::Path;
+
+/// 源文件开头的 0x30 填充长度，不计入媒体数据
+const PADDING_LEN: u64 = 9;
+
+/// 输出至少应为源媒体数据的 90%（无损封装只增减容器开销）
+const MIN_SIZE_RATIO: f64 = 0.9;
+
+/// 校验输出文件；失败时返回原因
+pub fn verify_output(video: &VideoInfo, output: &Path) -> Result<(), String> {
+    let out_len = std::fs::metadata(output)
+        .map_err(|e| format!("无法读取输出文件 {}: {}", output.display(), e))?
+        .len();
+    let tracks = count_tracks(output)
+        .map_err(|e| format!("无法解析输出文件: {}", e))?
+        .ok_or_else(|| "输出文件缺少轨道信息，可能未写完".to_string())?;
+    if tracks < 2 {
+        return Err(format!("输出文件只有 {} 条轨道，缺少音频或视频", tracks));
+    }
+
+    let source_len: u64 = [&video.video_path, &video.audio_path]
+        .into_iter()
+        .map(|p| {
+            std::fs::metadata(p)
+                .map(|m| m.len().saturating_sub(if has_padding(p) { PADDING_LEN } else { 0 }))
+                .map_err(|e| format!("无法读取源文件 {}: {}", p.display(), e))
+        })
+        .sum::<Result<u64, String>>()?;
+    if (out_len as f64) < source_len as f64 * MIN_SIZE_RATIO {
+        return Err(format!("输出文件 {} 字节，明显小于源文件 {} 字节", out_len, source_len));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::convert::codec::tests::{init_segment, mp4_box};
+
+    #[test]
+    fn test_verify_output() {
+        let tmp = std::env::temp_dir().join("bili2mp4_verify_test");
+        std::fs::create_dir_all(&tmp).unwrap();
+        let video = VideoInfo::fixture(&tmp, "t");
+        std::fs::write(&video.video_path, [0u8; 600]).unwrap();
+        std::fs::write(&video.audio_path, [0u8; 400]).unwrap();
+
+        let trak = mp4_box(b"trak", &[0u8; 8]);
+        let moov = mp4_box(b"moov", &[trak.clone(), trak].concat());
+        let out = tmp.join("out.mp4");
+        std::fs::write(&out, [mp4_box(b"ftyp", b"isom"), mp4_box(b"mdat", &[0u8; 1000]), moov].concat()).unwrap();
+        assert_eq!(verify_output(&video, &out), Ok(()));
+
+        std::fs::write(&out, [mp4_box(b"mdat", &[0u8; 500]), mp4_box(b"moov", &[])].concat()).unwrap();
+        assert!(verify_output(&video, &out).unwrap_err().contains("0 条轨道"));
+
+        std::fs::write(&out, init_segment(b"avc1")).unwrap();
+        assert!(verify_output(&video, &out).is_err());
+        std::fs::remove_dir_all(&tmp).ok();
+    }
+}
@@ -0,0 +1,5 @@
+mod protocol;
+
+pub use protocol::{
+    ConvertEvent, ErrorCode, EventEnvelope, Level, LogLine, CONVERT_EVENT, CONVERT_LOG, SCHEMA_VERSION,
+};
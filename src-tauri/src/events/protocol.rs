@@ -0,0 +1,358 @@
+//! 转换事件协议
+//!
+//! 后端通过 `convert-event` 事件推送 [`EventEnvelope`]，JSON 形如：
+//!
+//! ```json
+//! { "version": 1, "type": "backend_failed", "item_id": 3, "backend": "mp4box",
+//!   "code": "backend_failed", "error": "MP4Box 执行失败: ..." }
+//! ```
+//!
+//! - `type` 为 [`ConvertEvent`] 变体名的 snake_case 形式
+//! - 新增变体或可选字段不升级版本；删除、重命名字段或改变语义时 [`SCHEMA_VERSION`] 加一
+//! - 兼容旧前端：每个事件同时以 `convert-log` 推送 `{ level, message }`，内容由 [`ConvertEvent::message`] 生成
+
+use crate::convert::{Backend, ConvertError, TranscodeSettings, TrimRange};
+use crate::queue::JobId;
+use crate::toolchain::Tool;
+use serde::{Deserialize, Serialize};
+
+/// 当前事件协议版本
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// 结构化事件名
+pub const CONVERT_EVENT: &str = "convert-event";
+/// 文本日志事件名（由结构化事件派生）
+pub const CONVERT_LOG: &str = "convert-log";
+
+/// 机器可读的错误码
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// 源 m4s 文件缺失
+    FileNotFound,
+    /// 外部工具无法启动
+    ToolNotFound,
+    /// 后端执行失败（非零退出）
+    BackendFailed,
+    /// 文件读写失败（创建目录、去除填充、读取或改写输出），与所用后端无关
+    Io,
+    /// 冲突策略为跳过且输出已存在
+    OutputExists,
+    /// 工具无法运行或版本不支持源编码
+    BackendUnavailable,
+    /// 用户取消
+    Cancelled,
+    /// 完成后操作或钩子命令失败（不影响转换结果）
+    PostActionFailed,
+    /// 配置文件无法读取或解析，或指定的转换方案无效
+    ConfigInvalid,
+    /// 磁盘剩余空间不足或余量偏低
+    LowDiskSpace,
+    /// 多 P 合并时各分 P 的编码参数不一致，或方案容器无法容纳源编码
+    Incompatible,
+}
+
+impl From<&ConvertError> for ErrorCode {
+    fn from(e: &ConvertError) -> Self {
+        match e {
+            ConvertError::FileNotFound(_) => ErrorCode::FileNotFound,
+            ConvertError::ToolNotFound { .. } => ErrorCode::ToolNotFound,
+            ConvertError::Mp4BoxFailed(_) | ConvertError::FfmpegFailed(_) => ErrorCode::BackendFailed,
+            ConvertError::Io(_) => ErrorCode::Io,
+            ConvertError::Cancelled => ErrorCode::Cancelled,
+            ConvertError::Skipped(_) => ErrorCode::OutputExists,
+            ConvertError::NoBackend(_) => ErrorCode::BackendUnavailable,
+            ConvertError::Config(_) => ErrorCode::ConfigInvalid,
+            ConvertError::Incompatible(_) | ConvertError::UnsupportedCodec(_) => ErrorCode::Incompatible,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Level {
+    Info,
+    Warn,
+    Error,
+}
+
+impl Level {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Level::Info => "info",
+            Level::Warn => "warn",
+            Level::Error => "error",
+        }
+    }
+}
+
+/// 转换过程事件
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ConvertEvent {
+    /// 外部工具路径解析结果；`found` 为 false 时 `path` 为兜底的裸命令名
+    ToolResolved { tool: String, path: String, found: bool },
+    /// 一批任务开始
+    BatchStarted { out_dir: String, total: usize },
+    /// 单个任务开始；`index` 从 1 开始
+    ItemStarted { item_id: JobId, title: String, index: usize, total: usize },
+    /// 尝试某个后端
+    BackendAttempt { item_id: JobId, backend: Backend },
+    /// 某个后端失败，随后可能回退到下一个后端
+    BackendFailed { item_id: JobId, backend: Backend, code: ErrorCode, error: String },
+    /// 体检判定后端不可用，未尝试即跳过
+    BackendSkipped { item_id: JobId, backend: Backend, code: ErrorCode, reason: String },
+    /// 改为经 ffmpeg 转码（单项指定、方案设置或源编码不在允许列表中）
+    ItemTranscoding { item_id: JobId, settings: TranscodeSettings, reason: String },
+    /// 截取范围已按关键帧对齐，随后为 `item_succeeded`；读不到关键帧时 `actual` 为空，起止由后端定位
+    ItemTrimmed { item_id: JobId, requested: TrimRange, actual: Option<TrimRange> },
+    /// 输出超过分段上限，已在关键帧处无损切成多段（原输出已删除），随后为 `item_succeeded`
+    ItemSplit { item_id: JobId, parts: Vec<String> },
+    /// 任务成功；分段时 `output_path` 为第一段
+    ItemSucceeded { item_id: JobId, backend: Backend, output_path: String },
+    /// 输出已存在，按冲突策略跳过
+    ItemSkipped { item_id: JobId, output_path: String, code: ErrorCode },
+    /// 所有后端均失败，或遇到不可回退的错误
+    ItemFailed { item_id: JobId, backend: Option<Backend>, code: ErrorCode, error: String },
+    /// 任务被取消
+    ItemCancelled { item_id: JobId, code: ErrorCode },
+    /// 一批任务结束；`paused` 为仍暂停、未执行的个数，`split` 为成功项中被分段的个数。
+    /// 有暂停的任务时，这些任务恢复并结束（或被移除）后再推送一次整批的结果
+    BatchFinished {
+        succeeded: usize,
+        skipped: usize,
+        failed: usize,
+        cancelled: usize,
+        #[serde(default)]
+        paused: usize,
+        #[serde(default)]
+        split: usize,
+    },
+    /// 配置文件无效，本批按默认设置运行（不会回写配置文件）
+    ConfigInvalid { code: ErrorCode, error: String },
+    /// 配置可用但有需提示的问题，如迁移时丢弃的取值、不存在的工具路径
+    ConfigWarning { message: String },
+    /// 磁盘空间预检未通过（余量偏低，或用户确认后强制开始）
+    SpaceWarning { code: ErrorCode, message: String },
+    /// 源缓存目录已移到回收站（`destination` 为空）或隔离目录；手动清理时 `item_id` 为空
+    SourceCleaned { item_id: Option<JobId>, cache_dir: String, destination: Option<String>, bytes: u64 },
+    /// 完成后操作（`action` 为 open_folder 等）或钩子命令（`action` 为 hook）失败；
+    /// 批量操作时 `item_id` 为空
+    PostActionFailed { item_id: Option<JobId>, action: String, code: ErrorCode, error: String },
+}
+
+impl ConvertEvent {
+    pub fn level(&self) -> Level {
+        match self {
+            ConvertEvent::ToolResolved { found: false, .. }
+            | ConvertEvent::BackendFailed { .. }
+            | ConvertEvent::BackendSkipped { .. }
+            | ConvertEvent::ItemCancelled { .. }
+            | ConvertEvent::ConfigInvalid { .. }
+            | ConvertEvent::ConfigWarning { .. }
+            | ConvertEvent::SpaceWarning { .. }
+            | ConvertEvent::PostActionFailed { .. } => Level::Warn,
+            ConvertEvent::ItemFailed { .. } => Level::Error,
+            _ => Level::Info,
+        }
+    }
+
+    /// 面向用户的中文日志文本
+    pub fn message(&self) -> String {
+        match self {
+            ConvertEvent::ToolResolved { tool, path, found } => {
+                let note = if *found { String::new() } else { missing_tool_note(tool) };
+                format!("{} 路径: {}{}", tool, path, note)
+            }
+            ConvertEvent::BatchStarted { out_dir, total } => {
+                format!("--- 开始转换，共 {} 个，输出目录: {} ---", total, out_dir)
+            }
+            ConvertEvent::ItemStarted { title, index, total, .. } => {
+                format!("[{}/{}] 正在转换: {}", index, total, title)
+            }
+            ConvertEvent::BackendAttempt { backend, .. } => match backend {
+                Backend::Mp4Box => "  尝试 MP4Box...".to_string(),
+                Backend::Mp4BoxRaw => "  尝试 :raw 模式...".to_string(),
+                Backend::Ffmpeg => "  尝试 ffmpeg 兜底...".to_string(),
+            },
+            ConvertEvent::BackendFailed { backend, error, .. } => {
+                format!("  {} 失败: {}", backend.label(), error)
+            }
+            ConvertEvent::BackendSkipped { backend, reason, .. } => {
+                format!("  跳过 {}: {}", backend.label(), reason)
+            }
+            ConvertEvent::ItemTranscoding { settings, reason, .. } => {
+                format!("  转码（{}）: {}", settings.label(), reason)
+            }
+            ConvertEvent::ItemTrimmed { requested, actual: Some(actual), .. } if requested == actual => {
+                format!("  截取 {}", actual.label())
+            }
+            ConvertEvent::ItemTrimmed { requested, actual: Some(actual), .. } => {
+                format!("  截取 {}（请求 {}，已对齐到关键帧）", actual.label(), requested.label())
+            }
+            ConvertEvent::ItemTrimmed { requested, actual: None, .. } => {
+                format!("  截取 {}（未读到关键帧，起止由后端定位）", requested.label())
+            }
+            ConvertEvent::ItemSplit { parts, .. } => {
+                let names: Vec<String> = parts
+                    .iter()
+                    .map(|p| std::path::Path::new(p).file_name().map_or(p.clone(), |n| n.to_string_lossy().into_owned()))
+                    .collect();
+                format!("  已分为 {} 段: {}", parts.len(), names.join(", "))
+            }
+            ConvertEvent::ItemSucceeded { backend, output_path, .. } => match backend {
+                Backend::Mp4Box => format!("  ✓ 成功: {}", output_path),
+                _ => format!("  ✓ {} 成功: {}", backend.label(), output_path),
+            },
+            ConvertEvent::ItemSkipped { .. } => "  跳过 (输出文件已存在)".to_string(),
+            ConvertEvent::ItemFailed { error, .. } => format!("  ✗ 失败: {}", error),
+            ConvertEvent::ItemCancelled { .. } => "用户取消".to_string(),
+            ConvertEvent::BatchFinished { succeeded, paused, split, .. } => {
+                let mut message = format!("--- 完成，成功 {} 个", succeeded);
+                if *split > 0 {
+                    message.push_str(&format!("（其中 {} 个已分段）", split));
+                }
+                if *paused > 0 {
+                    message.push_str(&format!("，{} 个已暂停未执行", paused));
+                }
+                message + " ---"
+            }
+            ConvertEvent::ConfigInvalid { error, .. } => {
+                format!("{}，本次按默认设置运行", error)
+            }
+            ConvertEvent::ConfigWarning { message } => format!("配置: {}", message),
+            ConvertEvent::SpaceWarning { message, .. } => message.clone(),
+            ConvertEvent::SourceCleaned { cache_dir, destination, .. } => match destination {
+                Some(dest) => format!("  源缓存已移到隔离目录: {} → {}", cache_dir, dest),
+                None => format!("  源缓存已移到回收站: {}", cache_dir),
+            },
+            ConvertEvent::PostActionFailed { action, error, .. } if action == "hook" => {
+                format!("  钩子命令失败: {}", error)
+            }
+            ConvertEvent::PostActionFailed { action, error, .. } => {
+                format!("完成后操作 {} 失败: {}", action, error)
+            }
+        }
+    }
+
+    /// 由单个任务的最终结果生成事件
+    pub fn from_result(
+        item_id: JobId,
+        result: Result<(&std::path::Path, Backend), &ConvertError>,
+        last_backend: Option<Backend>,
+    ) -> Self {
+        match result {
+            Ok((path, backend)) => ConvertEvent::ItemSucceeded {
+                item_id,
+                backend,
+                output_path: path.display().to_string(),
+            },
+            Err(e @ ConvertError::Skipped(crate::filemgr::ConflictError::Skip(path))) => ConvertEvent::ItemSkipped {
+                item_id,
+                output_path: path.display().to_string(),
+                code: e.into(),
+            },
+            Err(e @ ConvertError::Cancelled) => ConvertEvent::ItemCancelled { item_id, code: e.into() },
+            Err(e) => ConvertEvent::ItemFailed {
+                item_id,
+                backend: last_backend,
+                code: e.into(),
+                error: e.to_string(),
+            },
+        }
+    }
+}
+
+fn missing_tool_note(tool: &str) -> String {
+    match [Tool::Mp4Box, Tool::Ffmpeg].into_iter().find(|t| t.label() == tool) {
+        Some(t) => format!(" (未找到，{})", t.install_hint()),
+        None => " (未找到)".to_string(),
+    }
+}
+
+/// 带版本号的事件外层
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EventEnvelope {
+    pub version: u32,
+    #[serde(flatten)]
+    pub event: ConvertEvent,
+}
+
+impl From<ConvertEvent> for EventEnvelope {
+    fn from(event: ConvertEvent) -> Self {
+        Self { version: SCHEMA_VERSION, event }
+    }
+}
+
+/// 兼容旧前端的文本日志
+#[derive(Debug, Clone, Serialize)]
+pub struct LogLine {
+    pub level: &'static str,
+    pub message: String,
+}
+
+impl From<&ConvertEvent> for LogLine {
+    fn from(event: &ConvertEvent) -> Self {
+        Self { level: event.level().as_str(), message: event.message() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_envelope_json_shape() {
+        let env = EventEnvelope::from(ConvertEvent::BackendFailed {
+            item_id: 3,
+            backend: Backend::Mp4BoxRaw,
+            code: ErrorCode::BackendFailed,
+            error: "boom".to_string(),
+        });
+        let v = serde_json::to_value(&env).unwrap();
+        assert_eq!(v["version"], SCHEMA_VERSION);
+        assert_eq!(v["type"], "backend_failed");
+        assert_eq!(v["item_id"], 3);
+        assert_eq!(v["backend"], "mp4box_raw");
+        assert_eq!(v["code"], "backend_failed");
+        let back: EventEnvelope = serde_json::from_value(v).unwrap();
+        assert_eq!(back, env);
+    }
+
+    #[test]
+    fn test_from_result_and_message() {
+        let skipped = ConvertError::Skipped(crate::filemgr::ConflictError::Skip("/out/a.mp4".into()));
+        let e = ConvertEvent::from_result(7, Err(&skipped), None);
+        assert_eq!(
+            e,
+            ConvertEvent::ItemSkipped { item_id: 7, output_path: "/out/a.mp4".to_string(), code: ErrorCode::OutputExists }
+        );
+        assert_eq!(e.level(), Level::Info);
+
+        let failed = ConvertError::FfmpegFailed("x".to_string());
+        let e = ConvertEvent::from_result(7, Err(&failed), Some(Backend::Ffmpeg));
+        assert_eq!(e.level(), Level::Error);
+        assert_eq!(e.message(), "  ✗ 失败: ffmpeg 执行失败: x");
+        assert!(matches!(e, ConvertEvent::ItemFailed { code: ErrorCode::BackendFailed, .. }));
+
+        // 文件操作失败不归到任何后端
+        let io = ConvertError::Io("无法创建目录: x".to_string());
+        let e = ConvertEvent::from_result(7, Err(&io), Some(Backend::Ffmpeg));
+        assert_eq!(e.message(), "  ✗ 失败: 文件读写失败: 无法创建目录: x");
+        assert!(matches!(e, ConvertEvent::ItemFailed { code: ErrorCode::Io, .. }));
+        assert_eq!(serde_json::to_value(ErrorCode::Io).unwrap(), "io");
+
+        let missing = ConvertError::ToolNotFound { tool: Tool::Ffmpeg };
+        let e = ConvertEvent::from_result(7, Err(&missing), Some(Backend::Ffmpeg));
+        assert!(matches!(e, ConvertEvent::ItemFailed { code: ErrorCode::ToolNotFound, .. }));
+        assert_eq!(e.message(), format!("  ✗ 失败: ffmpeg 未找到，{}", Tool::Ffmpeg.install_hint()));
+
+        let parts = vec!["/out/a-part1.mp4".to_string(), "/out/a-part2.mp4".to_string()];
+        let e = ConvertEvent::ItemSplit { item_id: 7, parts };
+        assert_eq!(e.message(), "  已分为 2 段: a-part1.mp4, a-part2.mp4");
+        let e = ConvertEvent::BatchFinished { succeeded: 3, skipped: 0, failed: 0, cancelled: 0, paused: 0, split: 1 };
+        assert_eq!(e.message(), "--- 完成，成功 3 个（其中 1 个已分段） ---");
+        let e = ConvertEvent::BatchFinished { succeeded: 1, skipped: 0, failed: 0, cancelled: 0, paused: 2, split: 0 };
+        assert_eq!(e.message(), "--- 完成，成功 1 个，2 个已暂停未执行 ---");
+    }
+}
@@ -0,0 +1,253 @@
+//! 转换后清理源缓存：输出校验通过后，把缓存目录移到系统回收站或隔离目录
+//!
+//! 只处理扫描根目录之内的缓存目录，不直接删除文件
+
+use crate::cache::{VideoInfo, META_FILES};
+use crate::config::config_dir;
+use crate::convert::verify_output;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// 清理记录文件名，位于配置文件同目录，每行一条 JSON
+const CLEANUP_LOG: &str = "cleanup.log";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CleanupMode {
+    /// 不清理（默认）
+    #[default]
+    Off,
+    /// 移到系统回收站
+    Trash,
+    /// 移到隔离目录，保留相对扫描根目录的层级
+    Quarantine,
+}
+
+#[derive(Debug, Error)]
+pub enum CleanupError {
+    #[error("未启用源文件清理")]
+    Disabled,
+    #[error("缓存目录不在扫描根目录内，拒绝清理: {0}")]
+    OutsideRoot(PathBuf),
+    #[error("输出文件位于缓存目录内，拒绝清理: {0}")]
+    OutputInside(PathBuf),
+    #[error("缓存目录内还有其他视频的缓存，拒绝清理: {0}")]
+    NestedCache(PathBuf),
+    #[error("输出校验未通过，保留源文件: {0}")]
+    NotVerified(String),
+    #[error("未配置隔离目录 quarantine_dir")]
+    NoQuarantine,
+    #[error("隔离目录不能位于扫描根目录内: {0}")]
+    QuarantineInsideRoot(PathBuf),
+    #[error("移到回收站失败: {0}")]
+    Trash(String),
+    #[error("清理失败: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// 一次清理（或试运行）的结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CleanupRecord {
+    pub cache_dir: String,
+    /// 通过校验的输出文件
+    pub output_path: String,
+    pub mode: CleanupMode,
+    /// 隔离目录中的新位置；移到回收站时为空
+    pub destination: Option<String>,
+    /// 释放（或将释放）的字节数
+    pub bytes: u64,
+    pub dry_run: bool,
+    pub time: String,
+}
+
+/// 校验 `output` 后清理 `video` 的缓存目录；`dry_run` 时只做检查并返回将执行的操作
+pub fn cleanup_source(
+    video: &VideoInfo,
+    output: &Path,
+    root: &Path,
+    mode: CleanupMode,
+    quarantine_dir: Option<&Path>,
+    dry_run: bool,
+) -> Result<CleanupRecord, CleanupError> {
+    if mode == CleanupMode::Off {
+        return Err(CleanupError::Disabled);
+    }
+    let root = root.canonicalize()?;
+    let cache_dir = video.cache_dir.canonicalize()?;
+    let relative = match cache_dir.strip_prefix(&root) {
+        Ok(rel) if !rel.as_os_str().is_empty() => rel.to_path_buf(),
+        _ => return Err(CleanupError::OutsideRoot(video.cache_dir.clone())),
+    };
+    let output = output.canonicalize().map_err(|e| CleanupError::NotVerified(e.to_string()))?;
+    if output.starts_with(&cache_dir) {
+        return Err(CleanupError::OutputInside(output));
+    }
+    if let Some(nested) = nested_cache(&cache_dir) {
+        return Err(CleanupError::NestedCache(nested));
+    }
+    verify_output(video, &output).map_err(CleanupError::NotVerified)?;
+
+    let destination = match mode {
+        CleanupMode::Quarantine => {
+            let dir = quarantine_dir.ok_or(CleanupError::NoQuarantine)?;
+            let base = nearest_canonical(dir).unwrap_or_else(|| dir.to_path_buf());
+            if base.starts_with(&root) {
+                return Err(CleanupError::QuarantineInsideRoot(dir.to_path_buf()));
+            }
+            Some(free_destination(&base.join(&relative)))
+        }
+        _ => None,
+    };
+    let record = CleanupRecord {
+        cache_dir: cache_dir.display().to_string(),
+        output_path: output.display().to_string(),
+        mode,
+        destination: destination.as_ref().map(|d| d.display().to_string()),
+        bytes: dir_size(&cache_dir),
+        dry_run,
+        time: chrono::Local::now().to_rfc3339(),
+    };
+    if dry_run {
+        return Ok(record);
+    }
+
+    match destination {
+        Some(dest) => move_dir(&cache_dir, &dest)?,
+        None => trash::delete(&cache_dir).map_err(|e| CleanupError::Trash(e.to_string()))?,
+    }
+    Ok(record)
+}
+
+/// 在配置目录的 cleanup.log 追加一条记录
+pub fn append_cleanup_log(record: &CleanupRecord) -> std::io::Result<()> {
+    let Some(dir) = config_dir() else {
+        return Ok(());
+    };
+    std::fs::create_dir_all(&dir)?;
+    let mut f = std::fs::OpenOptions::new().create(true).append(true).open(dir.join(CLEANUP_LOG))?;
+    writeln!(f, "{}", serde_json::to_string(record)?)
+}
+
+/// 子目录中另有元数据文件时返回其所在目录：那是另一个视频的缓存，不能随本目录一起移走
+fn nested_cache(cache_dir: &Path) -> Option<PathBuf> {
+    walkdir::WalkDir::new(cache_dir)
+        .min_depth(2)
+        .into_iter()
+        .filter_map(Result::ok)
+        .find(|e| e.file_type().is_file() && e.file_name().to_str().is_some_and(|n| META_FILES.contains(&n)))
+        .and_then(|e| e.path().parent().map(Path::to_path_buf))
+}
+
+/// 路径尚不存在时，取最近的已存在上级目录规范化后再拼回剩余部分
+fn nearest_canonical(path: &Path) -> Option<PathBuf> {
+    let existing = path.ancestors().find(|p| p.exists())?;
+    let rest = path.strip_prefix(existing).ok()?;
+    Some(existing.canonicalize().ok()?.join(rest))
+}
+
+/// 目标已存在时追加序号，如 `c_123 (1)`
+fn free_destination(dest: &Path) -> PathBuf {
+    if !dest.exists() {
+        return dest.to_path_buf();
+    }
+    let name = dest.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    (1..)
+        .map(|n| dest.with_file_name(format!("{} ({})", name, n)))
+        .find(|p| !p.exists())
+        .unwrap_or_else(|| dest.to_path_buf())
+}
+
+/// 先尝试重命名；跨卷时复制后删除源目录，复制失败则删除已复制的部分
+fn move_dir(from: &Path, to: &Path) -> Result<(), CleanupError> {
+    if let Some(parent) = to.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    if std::fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    if let Err(e) = copy_dir(from, to) {
+        std::fs::remove_dir_all(to).ok();
+        return Err(e.into());
+    }
+    std::fs::remove_dir_all(from)?;
+    Ok(())
+}
+
+fn copy_dir(from: &Path, to: &Path) -> std::io::Result<()> {
+    for entry in walkdir::WalkDir::new(from) {
+        let entry = entry?;
+        let target = to.join(entry.path().strip_prefix(from).unwrap_or(entry.path()));
+        if entry.file_type().is_dir() {
+            std::fs::create_dir_all(&target)?;
+        } else {
+            std::fs::copy(entry.path(), &target)?;
+        }
+    }
+    Ok(())
+}
+
+fn dir_size(dir: &Path) -> u64 {
+    walkdir::WalkDir::new(dir)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter_map(|e| e.metadata().ok())
+        .filter(|m| m.is_file())
+        .map(|m| m.len())
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::convert::codec::tests::mp4_box;
+
+    #[test]
+    fn test_quarantine_within_root_only() {
+        let tmp = std::env::temp_dir().join("bili2mp4_cleanup_test");
+        std::fs::remove_dir_all(&tmp).ok();
+        let root = tmp.join("download");
+        let cache = root.join("BV1xx").join("c_1");
+        let quarantine = tmp.join("quarantine");
+        std::fs::create_dir_all(cache.join("80")).unwrap();
+        let video = VideoInfo {
+            size_bytes: 1000,
+            video_path: cache.join("80/video.m4s"),
+            audio_path: cache.join("80/audio.m4s"),
+            ..VideoInfo::fixture(&cache, "t")
+        };
+        std::fs::write(&video.video_path, [0u8; 600]).unwrap();
+        std::fs::write(&video.audio_path, [0u8; 400]).unwrap();
+        let output = tmp.join("t.mp4");
+        let trak = mp4_box(b"trak", &[]);
+        std::fs::write(&output, [mp4_box(b"mdat", &[0u8; 1000]), mp4_box(b"moov", &[trak.clone(), trak].concat())].concat())
+            .unwrap();
+
+        let run = |root: &Path, dry_run| {
+            cleanup_source(&video, &output, root, CleanupMode::Quarantine, Some(&quarantine), dry_run)
+        };
+        assert!(matches!(run(&root.join("other"), true), Err(CleanupError::Io(_))));
+        std::fs::create_dir_all(root.join("other")).unwrap();
+        assert!(matches!(run(&root.join("other"), true), Err(CleanupError::OutsideRoot(_))));
+        assert!(matches!(run(&cache, true), Err(CleanupError::OutsideRoot(_))));
+
+        // 子目录中嵌套了另一个视频的缓存时拒绝
+        std::fs::create_dir_all(cache.join("80/extra")).unwrap();
+        std::fs::write(cache.join("80/extra/entry.json"), "{}").unwrap();
+        assert!(matches!(run(&root, true), Err(CleanupError::NestedCache(p)) if p.ends_with("80/extra")));
+        std::fs::remove_dir_all(cache.join("80/extra")).unwrap();
+        std::fs::write(cache.join("entry.json"), "{}").unwrap();
+
+        let plan = run(&root, true).unwrap();
+        assert_eq!(plan.bytes, 1002);
+        assert!(cache.exists());
+
+        let done = run(&root, false).unwrap();
+        assert!(!cache.exists());
+        let dest = PathBuf::from(done.destination.unwrap());
+        assert_eq!(dest, quarantine.canonicalize().unwrap().join("BV1xx/c_1"));
+        assert!(dest.join("80/video.m4s").is_file());
+        std::fs::remove_dir_all(&tmp).ok();
+    }
+}
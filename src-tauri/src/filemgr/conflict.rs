@@ -5,6 +5,7 @@ use std::path::{Path, PathBuf};
 use thiserror::Error;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
 pub enum ConflictStrategy {
     /// 覆盖已存在文件
     Overwrite,
@@ -17,23 +18,27 @@ pub enum ConflictStrategy {
 
 #[derive(Debug, Error)]
 pub enum ConflictError {
-    #[error("用户选择跳过")]
-    Skip,
+    #[error("用户选择跳过: {0}")]
+    Skip(PathBuf),
 }
 
 /// 根据策略解析最终输出路径
 pub fn resolve_output_path(
     out_dir: &Path,
     title: &str,
+    extension: &str,
     strategy: ConflictStrategy,
 ) -> Result<PathBuf, ConflictError> {
-    let path = output::output_path(out_dir, title);
+    resolve_path(output::output_path(out_dir, title, extension), strategy)
+}
 
+/// 对已确定的输出路径应用冲突策略；重命名时在同一目录内追加序号
+pub fn resolve_path(path: PathBuf, strategy: ConflictStrategy) -> Result<PathBuf, ConflictError> {
     match strategy {
         ConflictStrategy::Overwrite => Ok(path),
         ConflictStrategy::Skip => {
             if path.exists() {
-                Err(ConflictError::Skip)
+                Err(ConflictError::Skip(path))
             } else {
                 Ok(path)
             }
@@ -47,7 +52,7 @@ pub fn resolve_output_path(
             let mut n = 1;
             loop {
                 let name = format!("{}({}).{}", stem, n, ext);
-                let next = out_dir.join(&name);
+                let next = path.with_file_name(&name);
                 if !next.exists() {
                     break Ok(next);
                 }
@@ -65,7 +70,7 @@ mod tests {
     fn test_resolve_overwrite() {
         let out = std::env::temp_dir().join("bili2mp4_test_overwrite");
         std::fs::create_dir_all(&out).ok();
-        let p = resolve_output_path(&out, "test", ConflictStrategy::Overwrite).unwrap();
+        let p = resolve_output_path(&out, "test", "mp4", ConflictStrategy::Overwrite).unwrap();
         assert!(p.ends_with("test.mp4"));
     }
 
@@ -75,7 +80,7 @@ mod tests {
         std::fs::create_dir_all(&out).ok();
         let existing = out.join("video.mp4");
         std::fs::write(&existing, b"").ok();
-        let p = resolve_output_path(&out, "video", ConflictStrategy::Rename).unwrap();
+        let p = resolve_output_path(&out, "video", "mp4", ConflictStrategy::Rename).unwrap();
         assert!(p.ends_with("video(1).mp4"));
         std::fs::remove_file(existing).ok();
     }
@@ -86,7 +91,7 @@ mod tests {
         std::fs::create_dir_all(&out).ok();
         let existing = out.join("exists.mp4");
         std::fs::write(&existing, b"").ok();
-        let r = resolve_output_path(&out, "exists", ConflictStrategy::Skip);
+        let r = resolve_output_path(&out, "exists", "mp4", ConflictStrategy::Skip);
         assert!(r.is_err());
         std::fs::remove_file(existing).ok();
     }
@@ -0,0 +1,240 @@
+//! 媒体库布局：按 Kodi / Jellyfin 的约定组织输出目录，并写入 NFO 与海报
+//!
+//! 单 P 视频按电影处理，多 P 视频按剧集处理，每个分 P 为第一季的一集
+
+use super::output::sanitize_filename;
+use super::sidecar::find_cover;
+use crate::cache::{read_details, MediaDetails, VideoInfo};
+use crate::util::escape_markup;
+use serde::{Deserialize, Serialize};
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+/// 输出目录结构
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputLayout {
+    /// 按方案的文件名模板（默认）
+    #[default]
+    Template,
+    /// 媒体服务器目录结构，忽略文件名模板
+    MediaServer,
+}
+
+fn is_series(video: &VideoInfo) -> bool {
+    video.total_pages > 1
+}
+
+/// 电影或剧集的标题：优先整个视频的标题
+fn show_title(video: &VideoInfo, details: &MediaDetails) -> String {
+    details.video_title.clone().unwrap_or_else(|| video.title.clone())
+}
+
+/// 媒体库中的相对路径（不含扩展名）
+///
+/// 单 P：`标题 (2024)/标题 (2024)`；多 P：`标题 (2024)/Season 01/标题 S01E02`；没有发布日期时省略年份
+pub fn library_path(video: &VideoInfo, details: &MediaDetails) -> PathBuf {
+    let title = sanitize_filename(&show_title(video, details));
+    let folder = match details.published {
+        Some(date) => format!("{} ({})", title, date.format("%Y")),
+        None => title.clone(),
+    };
+    if is_series(video) {
+        Path::new(&folder)
+            .join("Season 01")
+            .join(format!("{} S01E{:02}", title, video.page))
+    } else {
+        Path::new(&folder).join(&folder)
+    }
+}
+
+/// 在输出视频旁写入 NFO 与海报，返回写入的文件
+///
+/// 电影写 `movie.nfo`、`poster`、`fanart`；剧集每集写同名 `.nfo`，
+/// 剧集目录中的 `tvshow.nfo` 与海报只在不存在时写入，保留媒体服务器或用户的修改
+pub fn write_library_files(video: &VideoInfo, output: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let details = read_details(video);
+    let dir = output.parent().unwrap_or(Path::new("."));
+    let mut written = Vec::new();
+    let art_dir = if is_series(video) {
+        let episode_nfo = output.with_extension("nfo");
+        std::fs::write(&episode_nfo, episode_nfo_xml(video, &details))?;
+        written.push(episode_nfo);
+        let show_dir = dir.parent().unwrap_or(dir);
+        let show_nfo = show_dir.join("tvshow.nfo");
+        if !show_nfo.exists() {
+            std::fs::write(&show_nfo, show_nfo_xml(video, &details))?;
+            written.push(show_nfo);
+        }
+        show_dir
+    } else {
+        let movie_nfo = dir.join("movie.nfo");
+        std::fs::write(&movie_nfo, movie_nfo_xml(video, &details))?;
+        written.push(movie_nfo);
+        dir
+    };
+    if let Some(cover) = find_cover(&video.cache_dir) {
+        let ext = cover.extension().and_then(|e| e.to_str()).unwrap_or("jpg").to_ascii_lowercase();
+        for name in ["poster", "fanart"] {
+            let dest = art_dir.join(format!("{}.{}", name, ext));
+            if is_series(video) && dest.exists() {
+                continue;
+            }
+            std::fs::copy(&cover, &dest)?;
+            written.push(dest);
+        }
+    }
+    Ok(written)
+}
+
+fn movie_nfo_xml(video: &VideoInfo, details: &MediaDetails) -> String {
+    let mut xml = String::new();
+    element(&mut xml, "title", &show_title(video, details));
+    common_fields(&mut xml, details);
+    runtime(&mut xml, video);
+    unique_id(&mut xml, video.bvid.as_deref());
+    document("movie", &xml)
+}
+
+fn show_nfo_xml(video: &VideoInfo, details: &MediaDetails) -> String {
+    let mut xml = String::new();
+    element(&mut xml, "title", &show_title(video, details));
+    common_fields(&mut xml, details);
+    unique_id(&mut xml, video.bvid.as_deref());
+    document("tvshow", &xml)
+}
+
+fn episode_nfo_xml(video: &VideoInfo, details: &MediaDetails) -> String {
+    let mut xml = String::new();
+    element(&mut xml, "title", &video.title);
+    element(&mut xml, "showtitle", &show_title(video, details));
+    element(&mut xml, "season", "1");
+    element(&mut xml, "episode", &video.page.to_string());
+    if let Some(plot) = &details.description {
+        element(&mut xml, "plot", plot);
+    }
+    if let Some(uploader) = &details.uploader {
+        element(&mut xml, "studio", uploader);
+    }
+    if let Some(date) = details.published {
+        element(&mut xml, "aired", &date.format("%Y-%m-%d").to_string());
+    }
+    runtime(&mut xml, video);
+    let id = video.bvid.as_ref().map(|b| format!("{}?p={}", b, video.page));
+    unique_id(&mut xml, id.as_deref());
+    document("episodedetails", &xml)
+}
+
+/// 电影与剧集共有的简介、UP 主、发布日期、分区与标签
+fn common_fields(xml: &mut String, details: &MediaDetails) {
+    if let Some(plot) = &details.description {
+        element(xml, "plot", plot);
+    }
+    if let Some(uploader) = &details.uploader {
+        element(xml, "studio", uploader);
+    }
+    if let Some(date) = details.published {
+        element(xml, "premiered", &date.format("%Y-%m-%d").to_string());
+        element(xml, "year", &date.format("%Y").to_string());
+    }
+    if let Some(genre) = &details.genre {
+        element(xml, "genre", genre);
+    }
+    for tag in &details.tags {
+        element(xml, "tag", tag);
+    }
+}
+
+/// 时长，单位分钟
+fn runtime(xml: &mut String, video: &VideoInfo) {
+    if let Some(ms) = video.duration_ms {
+        element(xml, "runtime", &ms.div_ceil(60_000).to_string());
+    }
+}
+
+fn unique_id(xml: &mut String, id: Option<&str>) {
+    if let Some(id) = id {
+        let _ = writeln!(xml, "  <uniqueid type=\"bilibili\" default=\"true\">{}</uniqueid>", escape_markup(id));
+    }
+}
+
+fn element(xml: &mut String, name: &str, value: &str) {
+    let _ = writeln!(xml, "  <{0}>{1}</{0}>", name, escape_markup(value));
+}
+
+fn document(root: &str, body: &str) -> String {
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n<{0}>\n{1}</{0}>\n",
+        root, body
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    #[test]
+    fn test_library_layout_and_nfo() {
+        let tmp = std::env::temp_dir().join("bili2mp4_library_test");
+        std::fs::remove_dir_all(&tmp).ok();
+        let cache = tmp.join("cache/c_1");
+        std::fs::create_dir_all(&cache).unwrap();
+        std::fs::write(cache.join("cover.jpg"), b"jpg").unwrap();
+        std::fs::write(
+            cache.join("entry.json"),
+            r#"{"title":"合集 <1>","desc":"简介","owner_name":"UP","pubdate":1700000000,"tags":["教程"]}"#,
+        )
+        .unwrap();
+        let video = VideoInfo {
+            page: 2,
+            total_pages: 3,
+            duration_ms: Some(90_000),
+            bvid: Some("BV1xx".into()),
+            uploader: Some("UP".into()),
+            ..VideoInfo::fixture(&cache, "第二集")
+        };
+        let details = read_details(&video);
+        assert_eq!(
+            library_path(&video, &details),
+            Path::new("合集 1 (2023)/Season 01/合集 1 S01E02")
+        );
+        let single = VideoInfo { page: 1, total_pages: 1, ..video.clone() };
+        let undated = MediaDetails { published: None, ..details.clone() };
+        assert_eq!(library_path(&single, &undated), Path::new("合集 1/合集 1"));
+
+        let output = tmp.join("out").join(library_path(&video, &details)).with_extension("mp4");
+        std::fs::create_dir_all(output.parent().unwrap()).unwrap();
+        let written = write_library_files(&video, &output).unwrap();
+        let show_dir = tmp.join("out/合集 1 (2023)");
+        assert_eq!(
+            written,
+            [
+                output.with_extension("nfo"),
+                show_dir.join("tvshow.nfo"),
+                show_dir.join("poster.jpg"),
+                show_dir.join("fanart.jpg"),
+            ]
+        );
+        let episode = std::fs::read_to_string(output.with_extension("nfo")).unwrap();
+        assert!(episode.contains("<episodedetails>"));
+        assert!(episode.contains("<episode>2</episode>"));
+        assert!(episode.contains("<runtime>2</runtime>"));
+        assert!(episode.contains("<plot>简介</plot>"));
+        let show = std::fs::read_to_string(show_dir.join("tvshow.nfo")).unwrap();
+        assert!(show.contains("<title>合集 &lt;1&gt;</title>"));
+        assert!(show.contains("<studio>UP</studio>"));
+        assert!(show.contains("<premiered>2023-11-14</premiered>"));
+        assert!(show.contains("<tag>教程</tag>"));
+        // 剧集级文件已存在时不再覆盖
+        assert_eq!(write_library_files(&video, &output).unwrap(), [output.with_extension("nfo")]);
+
+        let movie_dir = tmp.join("movie");
+        std::fs::create_dir_all(&movie_dir).unwrap();
+        let written = write_library_files(&single, &movie_dir.join("a.mp4")).unwrap();
+        assert_eq!(written[0], movie_dir.join("movie.nfo"));
+        assert!(std::fs::read_to_string(&written[0]).unwrap().starts_with("<?xml"));
+        assert_eq!(details.published, NaiveDate::from_ymd_opt(2023, 11, 14));
+        std::fs::remove_dir_all(&tmp).ok();
+    }
+}
@@ -1,4 +1,11 @@
+mod cleanup;
 mod conflict;
+mod library;
 mod output;
+mod sidecar;
 
-pub use conflict::{ConflictError, ConflictStrategy, resolve_output_path};
+pub use cleanup::{append_cleanup_log, cleanup_source, CleanupError, CleanupMode, CleanupRecord};
+pub use conflict::{ConflictError, ConflictStrategy, resolve_output_path, resolve_path};
+pub use library::{library_path, write_library_files, OutputLayout};
+pub use output::{check_template, part_path, render_name, TEMPLATE_FIELDS};
+pub use sidecar::{copy_sidecars, find_cover, Sidecars};
@@ -1,6 +1,7 @@
 //! 输出路径与文件名安全化
 
-use std::path::PathBuf;
+use crate::cache::VideoInfo;
+use std::path::{Path, PathBuf};
 
 const INVALID_CHARS: [char; 10] = ['/', '\\', ':', '*', '?', '"', '<', '>', '|', '\0'];
 const MAX_FILENAME_LEN: usize = 200;
@@ -20,10 +21,85 @@ pub fn sanitize_filename(name: &str) -> String {
     }
 }
 
-/// 生成输出文件路径：out_dir / {title}.mp4
-pub fn output_path(out_dir: &std::path::Path, title: &str) -> PathBuf {
+/// 生成输出文件路径：out_dir / {title}.{extension}
+pub fn output_path(out_dir: &Path, title: &str, extension: &str) -> PathBuf {
     let name = sanitize_filename(title);
-    out_dir.join(format!("{}.mp4", name))
+    out_dir.join(format!("{}.{}", name, extension))
+}
+
+/// 分段输出的路径：在文件名后加 `-part{n}`，如 `标题.mp4` → `标题-part1.mp4`
+pub fn part_path(path: &Path, part: usize) -> PathBuf {
+    let stem = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+    let name = match path.extension() {
+        Some(ext) => format!("{}-part{}.{}", stem, part, ext.to_string_lossy()),
+        None => format!("{}-part{}", stem, part),
+    };
+    path.with_file_name(name)
+}
+
+/// 文件名模板可用的占位符
+pub const TEMPLATE_FIELDS: [&str; 5] = ["title", "page", "total_pages", "quality", "bvid"];
+
+/// 按模板生成相对输出路径（不含扩展名），`/` 分隔子目录，每一级单独安全化
+///
+/// 例：`{bvid}/{page}-{title}` → `BV1xx/1-标题`；未知占位符或括号不成对时报错
+pub fn render_name(template: &str, video: &VideoInfo) -> Result<PathBuf, String> {
+    let value = |field: &str| -> Option<String> {
+        Some(match field {
+            "title" => video.title.clone(),
+            "page" => video.page.to_string(),
+            "total_pages" => video.total_pages.to_string(),
+            "quality" => video.quality.clone(),
+            "bvid" => video.bvid.clone().unwrap_or_default(),
+            _ => return None,
+        })
+    };
+    let mut path = PathBuf::new();
+    for part in template.split(['/', '\\']) {
+        let mut rendered = String::new();
+        let mut rest = part;
+        while let Some(start) = rest.find('{') {
+            rendered.push_str(&rest[..start]);
+            let end = rest[start..]
+                .find('}')
+                .ok_or_else(|| format!("模板括号不成对: {}", template))?;
+            let field = &rest[start + 1..start + end];
+            rendered.push_str(&value(field).ok_or_else(|| format!("未知占位符 {{{}}}", field))?);
+            rest = &rest[start + end + 1..];
+        }
+        rendered.push_str(rest);
+        let name = sanitize_filename(&rendered);
+        // 不允许通过 . / .. 跳出输出目录
+        if name.chars().all(|c| c == '.') {
+            continue;
+        }
+        path.push(name);
+    }
+    if path.as_os_str().is_empty() {
+        return Err(format!("模板生成的文件名为空: {}", template));
+    }
+    Ok(path)
+}
+
+/// 校验模板语法，不依赖具体视频
+pub fn check_template(template: &str) -> Result<(), String> {
+    let sample = VideoInfo {
+        cache_dir: PathBuf::new(),
+        title: "title".into(),
+        quality: "1080P".into(),
+        page: 1,
+        total_pages: 1,
+        size_bytes: 0,
+        cached_at: None,
+        duration_ms: None,
+        bvid: Some("BV1xx411c7mD".into()),
+        cid: None,
+        uploader: None,
+        video_path: PathBuf::new(),
+        audio_path: PathBuf::new(),
+        download: Default::default(),
+    };
+    render_name(template, &sample).map(|_| ())
 }
 
 #[cfg(test)]
@@ -40,9 +116,32 @@ mod tests {
         assert_eq!(sanitize_filename("   "), "未命名");
     }
 
+    #[test]
+    fn test_render_name() {
+        let mut video = VideoInfo {
+            page: 2,
+            total_pages: 3,
+            bvid: Some("BV1xx".into()),
+            ..VideoInfo::fixture(Path::new(""), "标题: 第一集")
+        };
+        assert_eq!(render_name("{title}", &video).unwrap(), Path::new("标题 第一集"));
+        assert_eq!(
+            render_name("{bvid}/P{page}of{total_pages} {quality}", &video).unwrap(),
+            Path::new("BV1xx/P2of3 1080P")
+        );
+        assert_eq!(render_name("../{title}", &video).unwrap(), Path::new("标题 第一集"));
+        video.bvid = None;
+        assert_eq!(render_name("{bvid}/{title}", &video).unwrap(), Path::new("未命名/标题 第一集"));
+        assert!(render_name("{name}", &video).is_err());
+        assert!(render_name("{title", &video).is_err());
+        assert!(check_template("{title}-{page}").is_ok());
+    }
+
     #[test]
     fn test_output_path() {
         let out = Path::new("/tmp");
-        assert_eq!(output_path(out, "测试").to_str().unwrap(), "/tmp/测试.mp4");
+        assert_eq!(output_path(out, "测试", "mp4").to_str().unwrap(), "/tmp/测试.mp4");
+        assert_eq!(output_path(out, "a/b", "mkv").to_str().unwrap(), "/tmp/ab.mkv");
+        assert_eq!(part_path(Path::new("/tmp/BV1xx/标题.mp4"), 2), Path::new("/tmp/BV1xx/标题-part2.mp4"));
     }
 }
@@ -0,0 +1,108 @@
+//! 附属文件：弹幕 XML、封面图随视频一起复制到输出目录
+
+use crate::cache::VideoInfo;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+const IMAGE_EXTS: [&str; 4] = ["jpg", "jpeg", "png", "webp"];
+
+/// 需要复制的附属文件
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Sidecars {
+    /// 弹幕 XML，输出为 `<文件名>.xml`
+    pub danmaku: bool,
+    /// 封面图，输出为 `<文件名>.<原扩展名>`
+    pub cover: bool,
+}
+
+impl Sidecars {
+    pub fn is_empty(&self) -> bool {
+        !self.danmaku && !self.cover
+    }
+}
+
+/// 在缓存目录中查找弹幕：优先 danmaku.xml（安卓客户端），其次任意 .xml
+fn find_danmaku(cache_dir: &Path) -> Option<PathBuf> {
+    let preferred = cache_dir.join("danmaku.xml");
+    if preferred.is_file() {
+        return Some(preferred);
+    }
+    find_by_ext(cache_dir, &["xml"])
+}
+
+/// 在缓存目录及其上一级中查找封面：优先 cover.*，其次任意图片
+pub fn find_cover(cache_dir: &Path) -> Option<PathBuf> {
+    let dirs = [Some(cache_dir), cache_dir.parent()];
+    for dir in dirs.into_iter().flatten() {
+        for ext in IMAGE_EXTS {
+            let p = dir.join(format!("cover.{}", ext));
+            if p.is_file() {
+                return Some(p);
+            }
+        }
+        if let Some(p) = find_by_ext(dir, &IMAGE_EXTS) {
+            return Some(p);
+        }
+    }
+    None
+}
+
+/// 按文件名排序后取首个匹配扩展名的文件，保证结果稳定
+fn find_by_ext(dir: &Path, exts: &[&str]) -> Option<PathBuf> {
+    let mut files: Vec<PathBuf> = std::fs::read_dir(dir)
+        .ok()?
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| {
+            p.is_file()
+                && p.extension()
+                    .and_then(|e| e.to_str())
+                    .is_some_and(|e| exts.contains(&e.to_ascii_lowercase().as_str()))
+        })
+        .collect();
+    files.sort();
+    files.into_iter().next()
+}
+
+/// 按选项复制附属文件到输出视频旁，返回已复制的文件；缓存中不存在的附属文件直接忽略
+pub fn copy_sidecars(video: &VideoInfo, output: &Path, sidecars: &Sidecars) -> std::io::Result<Vec<PathBuf>> {
+    let mut sources = Vec::new();
+    if sidecars.danmaku {
+        sources.extend(find_danmaku(&video.cache_dir));
+    }
+    if sidecars.cover {
+        sources.extend(find_cover(&video.cache_dir));
+    }
+    let mut copied = Vec::new();
+    for src in sources {
+        let ext = src.extension().and_then(|e| e.to_str()).unwrap_or_default();
+        let dest = output.with_extension(ext.to_ascii_lowercase());
+        std::fs::copy(&src, &dest)?;
+        copied.push(dest);
+    }
+    Ok(copied)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_copy_sidecars() {
+        let tmp = std::env::temp_dir().join("bili2mp4_sidecar_test");
+        std::fs::remove_dir_all(&tmp).ok();
+        let cache = tmp.join("cache/123/c_456");
+        std::fs::create_dir_all(&cache).unwrap();
+        std::fs::write(cache.join("danmaku.xml"), "<i></i>").unwrap();
+        std::fs::write(tmp.join("cache/123/Cover.JPG"), b"jpg").unwrap();
+
+        let video = VideoInfo::fixture(&cache, "t");
+        let output = tmp.join("out/标题.mp4");
+        std::fs::create_dir_all(output.parent().unwrap()).unwrap();
+
+        assert!(copy_sidecars(&video, &output, &Sidecars::default()).unwrap().is_empty());
+        let copied = copy_sidecars(&video, &output, &Sidecars { danmaku: true, cover: true }).unwrap();
+        assert_eq!(copied, vec![tmp.join("out/标题.xml"), tmp.join("out/标题.jpg")]);
+        std::fs::remove_dir_all(&tmp).ok();
+    }
+}
@@ -0,0 +1,192 @@
+//! 转换完成后操作：打开文件夹、播放、系统通知
+
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::process::Command;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum CompleteAction {
+    /// 不做任何操作
+    None,
+    /// 打开输出文件夹
+    #[default]
+    OpenFolder,
+    /// 打开输出文件夹并用默认播放器播放
+    OpenAndPlay,
+    /// 发送系统通知
+    Notify,
+}
+
+impl CompleteAction {
+    /// 与配置文件中的取值一致
+    pub fn as_str(self) -> &'static str {
+        match self {
+            CompleteAction::None => "none",
+            CompleteAction::OpenFolder => "open_folder",
+            CompleteAction::OpenAndPlay => "open_and_play",
+            CompleteAction::Notify => "notify",
+        }
+    }
+}
+
+/// 完成后操作的触发时机
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum CompleteScope {
+    /// 整批结束后执行一次
+    #[default]
+    Batch,
+    /// 每个任务成功后执行
+    Item,
+}
+
+/// 用系统默认程序打开文件或文件夹（macOS: open，Windows: explorer，Linux: xdg-open）
+pub fn open_path(path: &Path) -> std::io::Result<()> {
+    #[cfg(target_os = "macos")]
+    {
+        Command::new("open").arg(path).spawn()?;
+        Ok(())
+    }
+    #[cfg(target_os = "windows")]
+    {
+        Command::new("explorer").arg(path).spawn()?;
+        Ok(())
+    }
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    {
+        Command::new("xdg-open").arg(path).spawn()?;
+        Ok(())
+    }
+}
+
+/// 在文件管理器中显示并选中文件
+///
+/// Linux 通过 D-Bus org.freedesktop.FileManager1.ShowItems 实现，不支持时退回 xdg-open 打开所在目录
+pub fn reveal_path(path: &Path) -> std::io::Result<()> {
+    #[cfg(target_os = "macos")]
+    {
+        Command::new("open").arg("-R").arg(path).spawn()?;
+        Ok(())
+    }
+    #[cfg(target_os = "windows")]
+    {
+        let mut arg = std::ffi::OsString::from("/select,");
+        arg.push(path);
+        Command::new("explorer").arg(arg).spawn()?;
+        Ok(())
+    }
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    {
+        let revealed = Command::new("dbus-send")
+            .args([
+                "--session",
+                "--print-reply",
+                "--dest=org.freedesktop.FileManager1",
+                "--type=method_call",
+                "/org/freedesktop/FileManager1",
+                "org.freedesktop.FileManager1.ShowItems",
+            ])
+            .arg(format!("array:string:{}", file_uri(path)))
+            .arg("string:")
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .status()
+            .map(|s| s.success())
+            .unwrap_or(false);
+        if revealed {
+            return Ok(());
+        }
+        open_path(path.parent().unwrap_or(path))
+    }
+}
+
+/// 本地路径转 file:// URI，非保留字符外的字节按百分号编码
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+fn file_uri(path: &Path) -> String {
+    use std::os::unix::ffi::OsStrExt;
+    let mut uri = String::from("file://");
+    for &b in path.as_os_str().as_bytes() {
+        if b.is_ascii_alphanumeric() || b"/-_.~".contains(&b) {
+            uri.push(b as char);
+        } else {
+            uri.push_str(&format!("%{:02X}", b));
+        }
+    }
+    uri
+}
+
+/// 发送系统通知
+pub fn notify(title: &str, body: &str) -> std::io::Result<()> {
+    #[cfg(target_os = "macos")]
+    {
+        let script = format!(
+            "display notification {} with title {}",
+            applescript_quote(body),
+            applescript_quote(title)
+        );
+        Command::new("osascript").args(["-e", &script]).spawn()?;
+        Ok(())
+    }
+    #[cfg(target_os = "windows")]
+    {
+        // 通过环境变量传参，避免拼接 PowerShell 字符串
+        let script = "Add-Type -AssemblyName System.Windows.Forms; \
+            $n = New-Object System.Windows.Forms.NotifyIcon; \
+            $n.Icon = [System.Drawing.SystemIcons]::Information; $n.Visible = $true; \
+            $n.ShowBalloonTip(5000, $env:BILI2MP4_TITLE, $env:BILI2MP4_BODY, 'Info'); \
+            Start-Sleep -Seconds 6; $n.Dispose()";
+        Command::new("powershell")
+            .args(["-NoProfile", "-WindowStyle", "Hidden", "-Command", script])
+            .env("BILI2MP4_TITLE", title)
+            .env("BILI2MP4_BODY", body)
+            .spawn()?;
+        Ok(())
+    }
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    {
+        Command::new("notify-send").args([title, body]).spawn()?;
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn applescript_quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// 执行完成后操作；`outputs` 为本次成功的输出文件，为空时不执行
+pub fn run_complete_action(action: CompleteAction, out_dir: &Path, outputs: &[String]) -> std::io::Result<()> {
+    let Some(first) = outputs.first() else {
+        return Ok(());
+    };
+    match action {
+        CompleteAction::None => Ok(()),
+        CompleteAction::OpenFolder => open_path(out_dir),
+        CompleteAction::OpenAndPlay => {
+            open_path(out_dir)?;
+            open_path(Path::new(first))
+        }
+        CompleteAction::Notify => {
+            let body = if outputs.len() == 1 {
+                Path::new(first)
+                    .file_name()
+                    .map(|n| n.to_string_lossy().into_owned())
+                    .unwrap_or_else(|| first.clone())
+            } else {
+                format!("成功转换 {} 个文件", outputs.len())
+            };
+            notify("Bili2MP4 转换完成", &body)
+        }
+    }
+}
+
+#[cfg(all(test, not(any(target_os = "macos", target_os = "windows"))))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_uri_escapes() {
+        assert_eq!(file_uri(Path::new("/tmp/a b/标题.mp4")), "file:///tmp/a%20b/%E6%A0%87%E9%A2%98.mp4");
+    }
+}
@@ -0,0 +1,5 @@
+mod actions;
+mod script;
+
+pub use actions::{notify, open_path, reveal_path, run_complete_action, CompleteAction, CompleteScope};
+pub use script::{run_hook, HookError, HookPayload};
@@ -0,0 +1,141 @@
+//! 用户自定义钩子命令
+//!
+//! 每个任务结束后通过系统 shell 执行配置中的命令，任务信息同时以
+//! 环境变量（`BILI2MP4_*`）和 stdin 上的一行 JSON 传入
+
+use crate::queue::{Job, JobStatus};
+use serde::Serialize;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum HookError {
+    #[error("无法启动钩子命令: {0}")]
+    Spawn(#[from] std::io::Error),
+    #[error("钩子命令退出码 {code:?}: {stderr}")]
+    Failed { code: Option<i32>, stderr: String },
+}
+
+/// 传给钩子命令的任务信息
+#[derive(Debug, Clone, Serialize)]
+pub struct HookPayload {
+    pub output_path: Option<String>,
+    /// 分段输出的各段，未分段时为空
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub parts: Vec<String>,
+    pub title: String,
+    pub bvid: Option<String>,
+    pub status: JobStatus,
+    pub error: Option<String>,
+}
+
+impl From<&Job> for HookPayload {
+    fn from(job: &Job) -> Self {
+        Self {
+            output_path: job.output_path.clone(),
+            parts: job.parts.clone(),
+            title: job.video.title.clone(),
+            bvid: job.video.bvid.clone(),
+            status: job.status,
+            error: job.error.clone(),
+        }
+    }
+}
+
+impl HookPayload {
+    fn env(&self) -> Vec<(&'static str, String)> {
+        let status = serde_json::to_value(self.status)
+            .ok()
+            .and_then(|v| v.as_str().map(String::from))
+            .unwrap_or_default();
+        vec![
+            ("BILI2MP4_OUTPUT", self.output_path.clone().unwrap_or_default()),
+            ("BILI2MP4_PARTS", self.parts.join("\n")),
+            ("BILI2MP4_TITLE", self.title.clone()),
+            ("BILI2MP4_BVID", self.bvid.clone().unwrap_or_default()),
+            ("BILI2MP4_STATUS", status),
+            ("BILI2MP4_ERROR", self.error.clone().unwrap_or_default()),
+        ]
+    }
+}
+
+fn shell(command: &str) -> Command {
+    #[cfg(windows)]
+    {
+        let mut c = Command::new("cmd");
+        c.args(["/C", command]);
+        c
+    }
+    #[cfg(not(windows))]
+    {
+        let mut c = Command::new("sh");
+        c.args(["-c", command]);
+        c
+    }
+}
+
+/// 同步执行钩子命令，等待其退出
+pub fn run_hook(command: &str, payload: &HookPayload) -> Result<(), HookError> {
+    let mut child = shell(command)
+        .envs(payload.env())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        let json = serde_json::to_string(payload).unwrap_or_default();
+        // 命令不读取 stdin 时写入会收到 BrokenPipe，忽略即可
+        let _ = writeln!(stdin, "{}", json);
+    }
+
+    let output = child.wait_with_output()?;
+    if output.status.success() {
+        return Ok(());
+    }
+    let mut stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+    if stderr.len() > 200 {
+        let cut = (0..=200).rev().find(|&i| stderr.is_char_boundary(i)).unwrap_or(0);
+        stderr.truncate(cut);
+        stderr.push_str("...");
+    }
+    Err(HookError::Failed {
+        code: output.status.code(),
+        stderr,
+    })
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn payload(status: JobStatus) -> HookPayload {
+        HookPayload {
+            output_path: Some("/out/标题.mp4".to_string()),
+            parts: Vec::new(),
+            title: "标题".to_string(),
+            bvid: Some("BV1xx411c7mD".to_string()),
+            status,
+            error: None,
+        }
+    }
+
+    #[test]
+    fn test_hook_receives_env_and_stdin() {
+        let cmd = r#"test "$BILI2MP4_STATUS" = succeeded && test "$BILI2MP4_BVID" = BV1xx411c7mD && grep -q '"title":"标题"'"#;
+        assert!(run_hook(cmd, &payload(JobStatus::Succeeded)).is_ok());
+    }
+
+    #[test]
+    fn test_hook_failure_reports_code() {
+        let r = run_hook("echo oops >&2; exit 3", &payload(JobStatus::Failed));
+        match r {
+            Err(HookError::Failed { code, stderr }) => {
+                assert_eq!(code, Some(3));
+                assert_eq!(stderr, "oops");
+            }
+            other => panic!("unexpected: {:?}", other),
+        }
+    }
+}
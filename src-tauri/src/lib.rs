@@ -1,193 +1,918 @@
 //! Bili2MP4 Tauri 应用
 
 pub mod cache;
+pub mod catalog;
 pub mod config;
 pub mod convert;
+pub mod events;
 pub mod filemgr;
+pub mod hooks;
+pub mod query;
+pub mod queue;
+pub mod thumbnail;
+pub mod toolchain;
+pub mod util;
 
-use cache::{scan, VideoInfo};
-use config::{load_config, resolve_ffmpeg_path, resolve_mp4box_path, save_config, AppConfig};
-use convert::{convert_one, convert_one_raw, convert_one_ffmpeg, ConvertError, ConvertProgress};
-use std::path::PathBuf;
-use std::sync::atomic::AtomicBool;
+use cache::{
+    find_duplicates, scan_checked, scan_indexed, write_diagnostics, DuplicateGroup, DuplicatePolicy, Rejected,
+    VideoInfo,
+};
+use catalog::{build_catalog, CatalogFormat};
+use config::{load_config, load_layered, resolve_ffmpeg_path, save_config, AppConfig, Profile};
+use convert::{convert_with_fallback, Attempt, Backend, ConvertError, ConvertProgress, ConvertTools, Preflight, SpaceStatus};
+use filemgr::{
+    append_cleanup_log, cleanup_source, copy_sidecars, write_library_files, CleanupMode, CleanupRecord, OutputLayout,
+};
+use events::{ConvertEvent, ErrorCode, EventEnvelope, LogLine, CONVERT_EVENT, CONVERT_LOG};
+use hooks::{run_complete_action, run_hook, CompleteScope, HookPayload};
+use query::{Filter, Page, SortKey};
+use queue::{ConvertItem, Job, JobId, JobQueue, JobStatus, QueueChange};
+use thumbnail::{default_thumbnail_dir, Thumbnail, ThumbnailError, ThumbnailKind, ThumbnailSource};
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
-use tauri::Emitter;
+use tauri::{Emitter, Manager};
 
-static CONVERT_CANCEL: Mutex<Option<Arc<AtomicBool>>> = Mutex::new(None);
+/// 推送结构化事件，并派生一条文本日志
+fn emit_event(app: &tauri::AppHandle, event: ConvertEvent) {
+    let _ = app.emit(CONVERT_LOG, LogLine::from(&event));
+    let _ = app.emit(CONVERT_EVENT, EventEnvelope::from(event));
+}
 
-#[tauri::command]
-fn scan_cache(dir: Option<String>) -> Result<Vec<VideoInfo>, String> {
-    let dir_path = match dir {
-        Some(d) => PathBuf::from(d),
+/// 本次运行中扫描过的根目录（规范化后）；清理源缓存只处理这些目录之内的内容
+#[derive(Default)]
+struct ScannedRoots(Mutex<Vec<PathBuf>>);
+
+impl ScannedRoots {
+    fn add(&self, root: &Path) {
+        let Ok(root) = root.canonicalize() else { return };
+        let mut roots = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        if !roots.contains(&root) {
+            roots.push(root);
+        }
+    }
+
+    /// 包含 `dir` 的扫描根目录，有多个时取最深的一个
+    fn containing(&self, dir: &Path) -> Option<PathBuf> {
+        let dir = dir.canonicalize().ok()?;
+        let roots = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        roots
+            .iter()
+            .filter(|r| dir.starts_with(r))
+            .max_by_key(|r| r.components().count())
+            .cloned()
+    }
+}
+
+/// 要扫描的缓存目录，未指定时取首个存在的默认目录
+fn scan_dir(dir: Option<String>) -> Result<PathBuf, String> {
+    match dir {
+        Some(d) => Ok(PathBuf::from(d)),
         None => {
             let paths = cache::scanner::default_cache_paths();
             paths
                 .into_iter()
                 .find(|p| p.exists())
-                .ok_or_else(|| "未找到默认缓存目录，请手动选择".to_string())?
+                .ok_or_else(|| "未找到默认缓存目录，请手动选择".to_string())
+        }
+    }
+}
+
+/// 最近一次扫描得到的视频及其重复标注，供 [`filter_videos`] 筛选分页；界面不一次取回全部视频
+#[derive(Default)]
+struct ScannedVideos(Mutex<Scanned>);
+
+#[derive(Default)]
+struct Scanned {
+    videos: Vec<VideoInfo>,
+    /// 缓存目录 → 所在的重复组
+    duplicates: HashMap<PathBuf, DuplicateMark>,
+}
+
+/// 列表中一个视频的重复标注
+#[derive(Debug, Clone, serde::Serialize)]
+struct DuplicateMark {
+    /// 分组键，见 [`DuplicateGroup::key`]
+    key: String,
+    /// 在组内按策略的优先顺序，0 为最优
+    rank: usize,
+    /// 组内缓存数
+    count: usize,
+    /// 是否按策略保留；全选时只勾选保留的一份
+    keep: bool,
+}
+
+impl Scanned {
+    fn new(videos: Vec<VideoInfo>, policy: DuplicatePolicy) -> Self {
+        let mut duplicates = HashMap::new();
+        for group in find_duplicates(&videos, policy) {
+            for (rank, &i) in group.members.iter().enumerate() {
+                let mark = DuplicateMark {
+                    key: group.key.clone(),
+                    rank,
+                    count: group.members.len(),
+                    keep: group.keep.contains(&i),
+                };
+                duplicates.insert(videos[i].cache_dir.clone(), mark);
+            }
         }
+        Self { videos, duplicates }
+    }
+}
+
+/// 扫描概要：视频本身由 [`filter_videos`] 分页取回
+#[derive(serde::Serialize)]
+struct ScanSummary {
+    total: usize,
+    total_bytes: u64,
+    /// 出现过的清晰度，供界面筛选
+    qualities: Vec<String>,
+    rejected: Vec<Rejected>,
+}
+
+/// 扫描概要及相对上次扫描的新增、移除与变化数
+#[derive(serde::Serialize)]
+struct ScanChanges {
+    #[serde(flatten)]
+    summary: ScanSummary,
+    added: usize,
+    removed: usize,
+    changed: usize,
+    first_scan: bool,
+}
+
+/// 增量扫描缓存目录，记录为扫描根目录并保存结果与重复标注
+fn scan_root(roots: &ScannedRoots, scanned: &ScannedVideos, dir: Option<String>) -> Result<ScanChanges, String> {
+    let dir_path = scan_dir(dir)?;
+    let config = config_or_default();
+    let report = scan_indexed(&dir_path, config.scan_max_depth).map_err(|e| e.to_string())?;
+    roots.add(&dir_path);
+    let mut qualities: Vec<String> = Vec::new();
+    for v in &report.videos {
+        if !v.quality.is_empty() && !qualities.contains(&v.quality) {
+            qualities.push(v.quality.clone());
+        }
+    }
+    let summary = ScanSummary {
+        total: report.videos.len(),
+        total_bytes: report.videos.iter().map(|v| v.size_bytes).sum(),
+        qualities,
+        rejected: report.rejected,
+    };
+    let changes = ScanChanges {
+        summary,
+        added: report.added.len(),
+        removed: report.removed.len(),
+        changed: report.changed.len(),
+        first_scan: report.first_scan,
     };
-    scan(&dir_path).map_err(|e| e.to_string())
+    *scanned.0.lock().unwrap_or_else(|e| e.into_inner()) = Scanned::new(report.videos, config.duplicate_policy);
+    Ok(changes)
 }
 
 #[tauri::command]
-async fn convert(
-    app: tauri::AppHandle,
-    items: Vec<VideoInfo>,
-    out_dir: String,
-) -> Result<Vec<String>, String> {
-    let config = load_config();
-    let mp4box = resolve_mp4box_path(&config);
-    let ffmpeg = resolve_ffmpeg_path();
-    let strategy = config.conflict_strategy();
-    let out_path = PathBuf::from(&out_dir);
+fn scan_cache(
+    roots: tauri::State<'_, ScannedRoots>,
+    scanned: tauri::State<'_, ScannedVideos>,
+    dir: Option<String>,
+) -> Result<ScanSummary, String> {
+    scan_root(&roots, &scanned, dir).map(|changes| changes.summary)
+}
 
+/// 与 [`scan_cache`] 相同，另返回相对上次扫描的新增、移除与变化数
+#[tauri::command]
+fn scan_changes(
+    roots: tauri::State<'_, ScannedRoots>,
+    scanned: tauri::State<'_, ScannedVideos>,
+    dir: Option<String>,
+) -> Result<ScanChanges, String> {
+    scan_root(&roots, &scanned, dir)
+}
+
+/// 列表中的一个视频
+#[derive(serde::Serialize)]
+struct ListedVideo {
+    #[serde(flatten)]
+    video: VideoInfo,
+    duplicate: Option<DuplicateMark>,
+}
+
+/// 一页视频，字段同 [`Page`]
+#[derive(serde::Serialize)]
+struct VideoPage {
+    total: usize,
+    total_bytes: u64,
+    offset: usize,
+    items: Vec<ListedVideo>,
+}
+
+/// 每页最多返回的视频数
+const MAX_PAGE_SIZE: usize = 500;
+
+/// 在最近一次扫描结果中按表达式、标题关键字与清晰度筛选，排序后分页；
+/// `limit` 为空或过大时按 [`MAX_PAGE_SIZE`] 返回
+#[tauri::command]
+fn filter_videos(
+    scanned: tauri::State<'_, ScannedVideos>,
+    expr: Option<String>,
+    search: Option<String>,
+    quality: Option<String>,
+    sort: Option<String>,
+    offset: Option<usize>,
+    limit: Option<usize>,
+) -> Result<VideoPage, String> {
+    let non_empty = |s: &Option<String>| s.as_deref().map(str::trim).filter(|s| !s.is_empty()).map(String::from);
+    let filters = [
+        non_empty(&expr).map(|e| Filter::parse(&e)),
+        non_empty(&search).map(|s| Filter::field("title", &s, false)),
+        non_empty(&quality).map(|q| Filter::field("quality", &q, true)),
+    ];
+    let filter = filters
+        .into_iter()
+        .flatten()
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .reduce(Filter::and);
+    let sort = SortKey::parse_list(sort.as_deref().unwrap_or_default()).map_err(|e| e.to_string())?;
+    let limit = limit.unwrap_or(MAX_PAGE_SIZE).min(MAX_PAGE_SIZE);
+    let scanned = scanned.0.lock().unwrap_or_else(|e| e.into_inner());
+    let page: Page = query::select(&scanned.videos, filter.as_ref(), &sort, offset.unwrap_or(0), Some(limit));
+    let items = page
+        .items
+        .into_iter()
+        .map(|video| ListedVideo { duplicate: scanned.duplicates.get(&video.cache_dir).cloned(), video })
+        .collect();
+    Ok(VideoPage { total: page.total, total_bytes: page.total_bytes, offset: page.offset, items })
+}
+
+/// 完整重新扫描 `dir`（不使用索引），把无法识别的缓存目录写成 JSON 诊断报告
+#[tauri::command]
+fn export_diagnostics(dir: Option<String>, path: String) -> Result<usize, String> {
+    let dir = scan_dir(dir)?;
+    let (videos, rejected) =
+        scan_checked(&dir, config_or_default().scan_max_depth).map_err(|e| e.to_string())?;
+    write_diagnostics(Path::new(&path), &[&dir], videos.len(), &rejected)
+        .map_err(|e| format!("写入诊断报告失败: {}", e))?;
+    Ok(rejected.len())
+}
+
+/// 扫描 `dir` 并导出缓存清单，格式由 `format` 或文件扩展名决定（csv、jsonl、html）；
+/// 能确定输出目录时按方案命名规则检查每个视频是否已转换。返回视频数
+#[tauri::command]
+fn export_catalog(
+    dir: Option<String>,
+    path: String,
+    format: Option<String>,
+    out_dir: Option<String>,
+    profile: Option<String>,
+) -> Result<usize, String> {
+    let path = PathBuf::from(path);
+    let format = match format {
+        Some(f) => f.parse(),
+        None => CatalogFormat::from_path(&path),
+    }
+    .map_err(|e| e.to_string())?;
+    let target = match resolve_target(out_dir, profile.as_deref()) {
+        Ok(t) => Some(t),
+        // 未指定输出目录时不检查转换状态
+        Err(_) if profile.is_none() => None,
+        Err(e) => return Err(e),
+    };
+    let dir = scan_dir(dir)?;
+    let videos = scan_indexed(&dir, config_or_default().scan_max_depth).map_err(|e| e.to_string())?.videos;
+    let entries = build_catalog(&videos, target.as_ref().map(|(d, p)| (Path::new(d.as_str()), p)));
+    catalog::export_catalog(&path, &entries, format).map_err(|e| e.to_string())?;
+    Ok(entries.len())
+}
+
+/// 缓存视频（`video`）或已转换文件（`output`）的缩略图，`kind` 缺省为 poster；
+/// `inline` 为 true 时附带 base64 data URI，否则界面按返回的路径读取
+#[tauri::command]
+async fn get_thumbnail(
+    video: Option<VideoInfo>,
+    output: Option<String>,
+    kind: Option<ThumbnailKind>,
+    inline: Option<bool>,
+) -> Result<Thumbnail, String> {
+    let dir = default_thumbnail_dir().ok_or_else(|| ThumbnailError::NoCacheDir.to_string())?;
+    let ffmpeg = resolve_ffmpeg_path(&config_or_default());
+    tauri::async_runtime::spawn_blocking(move || {
+        let output = output.map(PathBuf::from);
+        let source = match (&output, &video) {
+            (Some(path), _) => ThumbnailSource::Output(path),
+            (None, Some(video)) => ThumbnailSource::Cache(video),
+            (None, None) => return Err("需要指定缓存视频或输出文件".to_string()),
+        };
+        let thumb = thumbnail::thumbnail(source, kind.unwrap_or(ThumbnailKind::Poster), &ffmpeg, &dir)
+            .map_err(|e| e.to_string())?;
+        if inline.unwrap_or(false) {
+            thumb.inline().map_err(|e| e.to_string())
+        } else {
+            Ok(thumb)
+        }
+    })
+    .await
+    .map_err(|e| format!("缩略图任务异常: {}", e))?
+}
+
+/// 所选视频中的重复缓存分组；未指定策略时使用配置中的 duplicate_policy
+#[tauri::command]
+fn duplicate_groups(items: Vec<VideoInfo>, policy: Option<DuplicatePolicy>) -> Vec<DuplicateGroup> {
+    let policy = policy.unwrap_or_else(|| config_or_default().duplicate_policy);
+    find_duplicates(&items, policy)
+}
+
+fn ensure_out_dir(out_dir: &str) -> Result<(), String> {
+    let out_path = PathBuf::from(out_dir);
     if !out_path.exists() {
         if let Err(e) = std::fs::create_dir_all(&out_path) {
             return Err(format!("无法创建输出目录: {}", e));
         }
     }
+    Ok(())
+}
 
-    let cancel = Arc::new(AtomicBool::new(false));
-    {
-        let mut guard = CONVERT_CANCEL.lock().unwrap();
-        *guard = Some(Arc::clone(&cancel));
+/// 运行时生效的配置：配置文件叠加 BILI2MP4_* 环境变量；界面读写设置只用配置文件层
+fn effective_config() -> Result<AppConfig, config::ConfigError> {
+    load_layered(&[]).map(|l| l.config)
+}
+
+/// 队列内部读取配置；配置无效时按默认值运行（只读，不会回写覆盖），由 [`emit_tool_paths`] 在批次开始时提示
+fn config_or_default() -> AppConfig {
+    effective_config().unwrap_or_default()
+}
+
+/// 确定转换方案与输出目录，优先顺序见 [`AppConfig::output_dir_for`]；不创建目录
+fn resolve_target(out_dir: Option<String>, profile: Option<&str>) -> Result<(String, Profile), String> {
+    // 指定方案时配置必须可读；未指定时与队列一致，损坏的配置按默认值处理
+    let config = match profile {
+        Some(_) => effective_config().map_err(|e| e.to_string())?,
+        None => config_or_default(),
+    };
+    let profile = config.profile(profile).map_err(|e| e.to_string())?;
+    let out_dir = config
+        .output_dir_for(&profile, out_dir.as_deref())
+        .ok_or_else(|| "未指定输出目录".to_string())?;
+    Ok((out_dir, profile))
+}
+
+fn emit_tool_paths(app: &tauri::AppHandle) {
+    let config = match load_layered(&[]) {
+        Ok(layered) => {
+            for message in layered.warnings {
+                emit_event(app, ConvertEvent::ConfigWarning { message });
+            }
+            layered.config
+        }
+        Err(e) => {
+            emit_event(app, ConvertEvent::ConfigInvalid {
+                code: ErrorCode::ConfigInvalid,
+                error: e.to_string(),
+            });
+            AppConfig::default()
+        }
+    };
+    for info in toolchain::doctor(&config) {
+        emit_event(app, ConvertEvent::ToolResolved {
+            tool: info.tool.label().to_string(),
+            found: info.runs && !toolchain::is_bare_name(&info.path),
+            path: info.path,
+        });
     }
+}
 
-    let mp4box_note = if mp4box == "MP4Box" || mp4box == "MP4Box.exe" {
-        #[cfg(target_os = "macos")]
-        { " (未找到，请 brew install gpac)" }
-        #[cfg(target_os = "windows")]
-        { " (未找到，请安装 GPAC 或配置路径)" }
-        #[cfg(not(any(target_os = "macos", target_os = "windows")))]
-        { " (未找到)" }
-    } else {
-        ""
+/// 队列工作线程中执行单个任务
+fn run_job(app: &tauri::AppHandle, job: &Job, (index, total): (usize, usize)) -> Result<Vec<PathBuf>, ConvertError> {
+    let config = config_or_default();
+    let tools = ConvertTools::from_config(&config);
+    let out_path = PathBuf::from(&job.out_dir);
+
+    let progress = |p: ConvertProgress| {
+        let payload = ConvertProgress {
+            current_index: index,
+            total,
+            percent: ((100 * index.saturating_sub(1) + p.percent as usize) / total.max(1)) as u32,
+            ..p
+        };
+        let _ = app.emit("convert-progress", &payload);
     };
-    let _ = app.emit("convert-log", serde_json::json!({
-        "level": if mp4box == "MP4Box" || mp4box == "MP4Box.exe" { "warn" } else { "info" },
-        "message": format!("MP4Box 路径: {}{}", mp4box, mp4box_note)
-    }));
-    let ffmpeg_note = if ffmpeg == "ffmpeg" || ffmpeg == "ffmpeg.exe" {
-        #[cfg(target_os = "macos")]
-        { " (未找到，兜底时需 brew install ffmpeg)" }
-        #[cfg(target_os = "windows")]
-        { " (未找到，兜底时需安装 ffmpeg)" }
-        #[cfg(not(any(target_os = "macos", target_os = "windows")))]
-        { " (未找到)" }
-    } else {
-        ""
+
+    emit_event(app, ConvertEvent::ItemStarted {
+        item_id: job.id,
+        title: job.video.title.clone(),
+        index,
+        total,
+    });
+
+    let last_backend = Cell::new(None);
+    let on_attempt = |attempt: Attempt<'_>| match attempt {
+        Attempt::Started(backend) => {
+            last_backend.set(Some(backend));
+            emit_event(app, ConvertEvent::BackendAttempt { item_id: job.id, backend });
+        }
+        Attempt::Failed(backend, e) => emit_event(app, ConvertEvent::BackendFailed {
+            item_id: job.id,
+            backend,
+            code: e.into(),
+            error: e.to_string(),
+        }),
+        Attempt::Skipped(backend, reason) => emit_event(app, ConvertEvent::BackendSkipped {
+            item_id: job.id,
+            backend,
+            code: ErrorCode::BackendUnavailable,
+            reason: reason.to_string(),
+        }),
     };
-    let _ = app.emit("convert-log", serde_json::json!({
-        "level": if ffmpeg == "ffmpeg" || ffmpeg == "ffmpeg.exe" { "warn" } else { "info" },
-        "message": format!("ffmpeg 路径: {}{}", ffmpeg, ffmpeg_note)
-    }));
-    let _ = app.emit("convert-log", serde_json::json!({ "level": "info", "message": format!("输出目录: {}", out_path.display()) }));
-    let _ = app.emit("convert-log", serde_json::json!({ "level": "info", "message": "--- 开始转换 ---" }));
-
-    let app_clone = app.clone();
-    let result = tauri::async_runtime::spawn_blocking(move || {
-        let mut success_paths = Vec::new();
-        let total = items.len();
-
-        for (i, video) in items.into_iter().enumerate() {
-            if cancel.load(std::sync::atomic::Ordering::Relaxed) {
-                let _ = app_clone.emit("convert-log", serde_json::json!({ "level": "warn", "message": "用户取消" }));
-                break;
+    let profile = config.profile(job.profile.as_deref());
+    let transcode = profile
+        .as_ref()
+        .ok()
+        .and_then(|p| convert::plan_transcode(&job.video, &p.transcode, job.transcode.as_ref()));
+    let transcoded = transcode.is_some() && job.merge.is_empty();
+    let result = match (&profile, transcode, &job.trim) {
+        (Ok(profile), _, _) if !job.merge.is_empty() => {
+            convert::merge_parts(&job.merge, &out_path, &tools, profile, progress, on_attempt, job.cancel_flag())
+        }
+        (Ok(profile), Some((settings, reason)), trim) => {
+            emit_event(app, ConvertEvent::ItemTranscoding { item_id: job.id, settings, reason });
+            last_backend.set(Some(Backend::Ffmpeg));
+            let cancel = job.cancel_flag();
+            convert::convert_transcode(&job.video, &settings, trim.as_ref(), &out_path, &tools, profile, progress, cancel)
+                .map(|(path, actual)| {
+                    if let Some(requested) = trim {
+                        emit_event(app, ConvertEvent::ItemTrimmed { item_id: job.id, requested: *requested, actual });
+                    }
+                    (path, Backend::Ffmpeg)
+                })
+        }
+        (Ok(profile), None, Some(range)) => {
+            convert::convert_trimmed(&job.video, range, &out_path, &tools, profile, progress, on_attempt, job.cancel_flag())
+                .map(|(path, backend, actual)| {
+                    emit_event(app, ConvertEvent::ItemTrimmed { item_id: job.id, requested: *range, actual });
+                    (path, backend)
+                })
+        }
+        (Ok(profile), None, None) => {
+            convert_with_fallback(&job.video, &out_path, &tools, profile, progress, on_attempt, job.cancel_flag())
+        }
+        (Err(e), _, _) => Err(ConvertError::Config(e.to_string())),
+    };
+    // 超过分段上限时切成多段，原输出随之删除；附属文件与媒体库文件按第一段命名
+    let result = result.and_then(|(path, backend)| {
+        let parts = match &profile {
+            Ok(profile) if profile.split.is_set() => {
+                convert::split_output(&path, &profile.split, &tools, profile, on_attempt, job.cancel_flag())?
             }
+            _ => vec![path.clone()],
+        };
+        if parts.len() > 1 {
+            let parts = parts.iter().map(|p| p.display().to_string()).collect();
+            emit_event(app, ConvertEvent::ItemSplit { item_id: job.id, parts });
+        }
+        Ok((parts, backend))
+    });
+    let reported = result.as_ref().map(|(parts, backend)| (parts[0].as_path(), *backend));
+    emit_event(app, ConvertEvent::from_result(job.id, reported, last_backend.get()));
+    if let (Ok(profile), Ok((parts, _))) = (&profile, &result) {
+        let path = &parts[0];
+        if let Err(e) = copy_sidecars(&job.video, path, &profile.sidecars) {
+            emit_post_action_failed(app, Some(job.id), "sidecars", e.to_string());
+        }
+        if profile.layout == OutputLayout::MediaServer {
+            if let Err(e) = write_library_files(&job.video, path) {
+                emit_post_action_failed(app, Some(job.id), "library", e.to_string());
+            }
+        }
+        // 附属文件复制完成后再清理源缓存；截取的输出只含部分内容、转码的输出大小与源文件不符、
+        // 分段的输出不是单个文件、合并的输出对应多个缓存，均不清理
+        if config.cleanup != CleanupMode::Off
+            && job.trim.is_none()
+            && !transcoded
+            && job.merge.is_empty()
+            && parts.len() == 1
+        {
+            match clean_source(app, &config, &job.video, path, false) {
+                Ok(record) => emit_source_cleaned(app, Some(job.id), &record),
+                Err(e) => emit_post_action_failed(app, Some(job.id), "cleanup", e),
+            }
+        }
+    }
+    result.map(|(parts, _)| parts)
+}
 
-            let progress = |p: ConvertProgress| {
-                let payload = ConvertProgress {
-                    current_index: i + 1,
-                    total,
-                    percent: (100 * (i + 1) / total.max(1)) as u32,
-                    ..p
-                };
-                let _ = app_clone.emit("convert-progress", &payload);
-            };
+/// 按配置清理单个视频的源缓存；非试运行时写入清理记录
+fn clean_source(
+    app: &tauri::AppHandle,
+    config: &AppConfig,
+    video: &VideoInfo,
+    output: &Path,
+    dry_run: bool,
+) -> Result<CleanupRecord, String> {
+    let root = app
+        .state::<ScannedRoots>()
+        .containing(&video.cache_dir)
+        .ok_or_else(|| format!("缓存目录不在本次扫描的目录内，拒绝清理: {}", video.cache_dir.display()))?;
+    let quarantine = config.quarantine_dir.as_deref().map(Path::new);
+    let record =
+        cleanup_source(video, output, &root, config.cleanup, quarantine, dry_run).map_err(|e| e.to_string())?;
+    if !dry_run {
+        if let Err(e) = append_cleanup_log(&record) {
+            emit_post_action_failed(app, None, "cleanup_log", e.to_string());
+        }
+    }
+    Ok(record)
+}
 
-            let _ = app_clone.emit("convert-log", serde_json::json!({
-                "level": "info",
-                "message": format!("[{}/{}] 正在转换: {}", i + 1, total, video.title)
-            }));
-
-            match convert_one(&video, &out_path, &mp4box, strategy, &progress, &*cancel) {
-                Ok(path) => {
-                    let s = path.display().to_string();
-                    let _ = app_clone.emit("convert-log", serde_json::json!({ "level": "info", "message": format!("  ✓ 成功: {}", s) }));
-                    success_paths.push(s);
-                }
-                Err(ConvertError::Mp4BoxFailed(e)) => {
-                    let _ = app_clone.emit("convert-log", serde_json::json!({ "level": "warn", "message": format!("  MP4Box 失败: {}", e) }));
-                    let _ = app_clone.emit("convert-log", serde_json::json!({ "level": "info", "message": "  尝试 :raw 模式..." }));
-                    let ok = convert_one_raw(
-                        &video,
-                        &out_path,
-                        &mp4box,
-                        strategy,
-                        &progress,
-                        &*cancel,
-                    );
-                    match &ok {
-                        Ok(p) => {
-                            let s = p.display().to_string();
-                            let _ = app_clone.emit("convert-log", serde_json::json!({ "level": "info", "message": format!("  ✓ raw 成功: {}", s) }));
-                            success_paths.push(s);
-                        }
-                        Err(e) => {
-                            let _ = app_clone.emit("convert-log", serde_json::json!({ "level": "warn", "message": format!("  raw 失败: {}", e) }));
-                            let _ = app_clone.emit("convert-log", serde_json::json!({ "level": "info", "message": "  尝试 ffmpeg 兜底..." }));
-                            if let Ok(p) = convert_one_ffmpeg(&video, &out_path, &ffmpeg, strategy, &*cancel) {
-                                let s = p.display().to_string();
-                                let _ = app_clone.emit("convert-log", serde_json::json!({ "level": "info", "message": format!("  ✓ ffmpeg 成功: {}", s) }));
-                                success_paths.push(s);
-                            } else {
-                                let _ = app_clone.emit("convert-log", serde_json::json!({ "level": "error", "message": format!("  ✗ ffmpeg 也失败") }));
-                            }
-                        }
-                    }
-                }
-                Err(ConvertError::Skipped(_)) => {
-                    let _ = app_clone.emit("convert-log", serde_json::json!({ "level": "info", "message": "  跳过 (输出文件已存在)" }));
-                }
-                Err(ConvertError::Cancelled) => break,
-                Err(e) => {
-                    let _ = app_clone.emit("convert-log", serde_json::json!({ "level": "error", "message": format!("  ✗ 失败: {}", e) }));
-                }
+fn emit_source_cleaned(app: &tauri::AppHandle, item_id: Option<JobId>, record: &CleanupRecord) {
+    emit_event(app, ConvertEvent::SourceCleaned {
+        item_id,
+        cache_dir: record.cache_dir.clone(),
+        destination: record.destination.clone(),
+        bytes: record.bytes,
+    });
+}
+
+/// 队列变化：移除与排序分别推送 job-removed、job-order（ID 列表），供界面同步任务列表
+fn on_queue_change(app: &tauri::AppHandle, change: QueueChange<'_>) {
+    match change {
+        QueueChange::Job(job) => on_job_status(app, job),
+        QueueChange::Removed(ids) => {
+            let _ = app.emit("job-removed", ids);
+        }
+        QueueChange::Reordered(ids) => {
+            let _ = app.emit("job-order", ids);
+        }
+    }
+}
+
+/// 任务状态变化：推送 job-status，并在任务结束时执行钩子与单项完成后操作
+fn on_job_status(app: &tauri::AppHandle, job: &Job) {
+    let _ = app.emit("job-status", job);
+    if !job.status.is_finished() {
+        return;
+    }
+    let config = config_or_default();
+
+    if config.on_complete_scope == CompleteScope::Item {
+        let outputs = job.outputs();
+        if !outputs.is_empty() {
+            if let Err(e) = run_complete_action(config.on_complete, Path::new(&job.out_dir), &outputs) {
+                emit_post_action_failed(app, Some(job.id), config.on_complete.as_str(), e.to_string());
             }
         }
+    }
 
-        let _ = app_clone.emit("convert-log", serde_json::json!({ "level": "info", "message": format!("--- 完成，成功 {} 个 ---", success_paths.len()) }));
-        success_paths
-    })
-    .await
-    .map_err(|e| format!("转换任务异常: {}", e))?;
+    if let Some(command) = config.hook_command() {
+        // 钩子可能较慢（如上传），在独立线程执行，不阻塞队列
+        let command = command.to_string();
+        let payload = HookPayload::from(job);
+        let app = app.clone();
+        let item_id = job.id;
+        std::thread::spawn(move || {
+            if let Err(e) = run_hook(&command, &payload) {
+                emit_post_action_failed(&app, Some(item_id), "hook", e.to_string());
+            }
+        });
+    }
+}
 
-    {
-        let mut guard = CONVERT_CANCEL.lock().unwrap();
-        *guard = None;
+fn emit_post_action_failed(app: &tauri::AppHandle, item_id: Option<JobId>, action: &str, error: String) {
+    emit_event(app, ConvertEvent::PostActionFailed {
+        item_id,
+        action: action.to_string(),
+        code: ErrorCode::PostActionFailed,
+        error,
+    });
+}
+
+/// 未下载完成的缓存转换后得到截断的 MP4，默认拒绝，调用方确认后可传 `allow_incomplete`
+fn check_complete(items: &[VideoInfo], allow_incomplete: Option<bool>) -> Result<(), String> {
+    if allow_incomplete.unwrap_or(false) {
+        return Ok(());
     }
+    let incomplete: Vec<String> = items
+        .iter()
+        .filter_map(|v| v.download.detail().map(|d| format!("{}（{}）", v.title, d)))
+        .collect();
+    if incomplete.is_empty() {
+        return Ok(());
+    }
+    Err(format!("{} 个视频未下载完成: {}", incomplete.len(), incomplete.join("；")))
+}
 
-    Ok(result)
+/// 检查转换请求：截取范围有效、视频已下载完成（或调用方已确认），返回其中的视频
+fn check_items(items: &[ConvertItem], allow_incomplete: Option<bool>) -> Result<Vec<VideoInfo>, String> {
+    for trim in items.iter().filter_map(|i| i.trim.as_ref()) {
+        trim.validate().map_err(|e| e.to_string())?;
+    }
+    for settings in items.iter().filter_map(|i| i.transcode.as_ref()) {
+        settings.validate()?;
+    }
+    let videos: Vec<VideoInfo> = items.iter().map(|i| i.video.clone()).collect();
+    check_complete(&videos, allow_incomplete)?;
+    Ok(videos)
 }
 
+/// 一次性转换：加入队列并等待这批任务全部结束或暂停，返回成功的输出路径；
+/// 带 `trim` 的项按关键帧对齐后无损截取
 #[tauri::command]
-fn cancel_convert() {
-    if let Ok(guard) = CONVERT_CANCEL.lock() {
-        if let Some(ref c) = *guard {
-            c.store(true, std::sync::atomic::Ordering::Relaxed);
+async fn convert(
+    app: tauri::AppHandle,
+    queue: tauri::State<'_, Arc<JobQueue>>,
+    items: Vec<ConvertItem>,
+    out_dir: Option<String>,
+    profile: Option<String>,
+    force: Option<bool>,
+    allow_incomplete: Option<bool>,
+) -> Result<Vec<String>, String> {
+    let videos = check_items(&items, allow_incomplete)?;
+    let out_dir = prepare_batch(&app, &videos, out_dir, profile.as_deref(), force)?;
+    run_batch(&app, Arc::clone(queue.inner()), items, out_dir, profile).await
+}
+
+/// 加入队列并等待这批任务全部结束或暂停，推送批次开始与结束事件并执行整批范围的完成后操作，
+/// 返回已成功的输出路径。有暂停的任务时先行返回，完成后操作推迟到这些任务结束后执行，并补发整批的结束事件
+async fn run_batch(
+    app: &tauri::AppHandle,
+    queue: Arc<JobQueue>,
+    items: Vec<ConvertItem>,
+    out_dir: String,
+    profile: Option<String>,
+) -> Result<Vec<String>, String> {
+    emit_event(app, ConvertEvent::BatchStarted {
+        out_dir: PathBuf::from(&out_dir).display().to_string(),
+        total: items.len(),
+    });
+
+    let ids = queue.enqueue(items, &out_dir, profile.as_deref());
+    let (waiting, batch) = (Arc::clone(&queue), ids.clone());
+    let jobs = tauri::async_runtime::spawn_blocking(move || waiting.wait_for(&batch))
+        .await
+        .map_err(|e| format!("转换任务异常: {}", e))?;
+
+    emit_batch_finished(app, &jobs);
+    let success_paths: Vec<String> = jobs.iter().flat_map(Job::outputs).collect();
+    if jobs.iter().any(|j| j.status == JobStatus::Paused) {
+        let app = app.clone();
+        std::thread::spawn(move || {
+            let jobs = queue.wait_finished(&ids);
+            emit_batch_finished(&app, &jobs);
+            let success_paths: Vec<String> = jobs.iter().flat_map(Job::outputs).collect();
+            run_batch_action(&app, &out_dir, &success_paths);
+        });
+    } else {
+        run_batch_action(app, &out_dir, &success_paths);
+    }
+    Ok(success_paths)
+}
+
+fn emit_batch_finished(app: &tauri::AppHandle, jobs: &[Job]) {
+    let count = |status: JobStatus| jobs.iter().filter(|j| j.status == status).count();
+    emit_event(app, ConvertEvent::BatchFinished {
+        succeeded: count(JobStatus::Succeeded),
+        skipped: count(JobStatus::Skipped),
+        failed: count(JobStatus::Failed),
+        cancelled: count(JobStatus::Cancelled),
+        paused: count(JobStatus::Paused),
+        split: jobs.iter().filter(|j| !j.parts.is_empty()).count(),
+    });
+}
+
+/// 开始一批转换前的检查：空间不足时拒绝开始，除非调用方已向用户确认（force）；
+/// 创建输出目录并报告工具路径与空间警告，返回输出目录
+fn prepare_batch(
+    app: &tauri::AppHandle,
+    videos: &[VideoInfo],
+    out_dir: Option<String>,
+    profile: Option<&str>,
+    force: Option<bool>,
+) -> Result<String, String> {
+    let (out_dir, selected) = resolve_target(out_dir, profile)?;
+    let check = convert::preflight(videos, Path::new(&out_dir), &selected);
+    if check.status == SpaceStatus::Insufficient && !force.unwrap_or(false) {
+        return Err(check.message);
+    }
+    ensure_out_dir(&out_dir)?;
+    emit_tool_paths(app);
+    if check.status != SpaceStatus::Ok {
+        emit_event(app, ConvertEvent::SpaceWarning {
+            code: ErrorCode::LowDiskSpace,
+            message: check.message,
+        });
+    }
+    Ok(out_dir)
+}
+
+/// 完成后操作范围为整批时，对这批的成功输出执行一次
+fn run_batch_action(app: &tauri::AppHandle, out_dir: &str, success_paths: &[String]) {
+    let config = config_or_default();
+    if config.on_complete_scope == CompleteScope::Batch {
+        if let Err(e) = run_complete_action(config.on_complete, Path::new(out_dir), success_paths) {
+            emit_post_action_failed(app, None, config.on_complete.as_str(), e.to_string());
         }
     }
 }
 
+/// 转换前的磁盘空间预检，供界面展示估算与剩余空间
 #[tauri::command]
-fn get_config() -> AppConfig {
-    load_config()
+fn preflight(items: Vec<VideoInfo>, out_dir: Option<String>, profile: Option<String>) -> Result<Preflight, String> {
+    let (out_dir, profile) = resolve_target(out_dir, profile.as_deref())?;
+    Ok(convert::preflight(&items, Path::new(&out_dir), &profile))
+}
+
+/// 单个视频的清理结果
+#[derive(serde::Serialize)]
+struct CleanupOutcome {
+    cache_dir: String,
+    record: Option<CleanupRecord>,
+    error: Option<String>,
 }
 
+/// 清理已转换视频的源缓存：按方案定位输出文件并校验；`dry_run` 时只列出将执行的操作
 #[tauri::command]
-fn set_config(config: AppConfig) -> Result<(), String> {
-    save_config(&config).map_err(|e| e.to_string())
+fn cleanup_sources(
+    app: tauri::AppHandle,
+    items: Vec<VideoInfo>,
+    out_dir: Option<String>,
+    profile: Option<String>,
+    dry_run: bool,
+) -> Result<Vec<CleanupOutcome>, String> {
+    let config = effective_config().map_err(|e| e.to_string())?;
+    if config.cleanup == CleanupMode::Off {
+        return Err("未启用源文件清理，请在配置中设置 cleanup 为 trash 或 quarantine".to_string());
+    }
+    let (out_dir, profile) = resolve_target(out_dir, profile.as_deref())?;
+    Ok(items
+        .iter()
+        .map(|video| {
+            let result = convert::target_path(video, Path::new(&out_dir), &profile)
+                .map_err(|e| e.to_string())
+                .and_then(|output| clean_source(&app, &config, video, &output, dry_run));
+            if let (Ok(record), false) = (&result, dry_run) {
+                emit_source_cleaned(&app, None, record);
+            }
+            let (record, error) = match result {
+                Ok(r) => (Some(r), None),
+                Err(e) => (None, Some(e)),
+            };
+            CleanupOutcome { cache_dir: video.cache_dir.display().to_string(), record, error }
+        })
+        .collect())
+}
+
+/// 取消队列中全部未结束的任务
+#[tauri::command]
+fn cancel_convert(queue: tauri::State<'_, Arc<JobQueue>>) {
+    queue.cancel_all();
+}
+
+/// 把同一视频的多个分 P 无损合并为一个 MP4，每个分 P 一个章节；返回输出路径，超过方案的分段上限时为各段
+///
+/// 合并作为一个任务加入队列，事件与 `convert` 相同；不自动清理源缓存，各分 P 编码参数不一致时拒绝并说明原因
+#[tauri::command]
+async fn merge_parts(
+    app: tauri::AppHandle,
+    queue: tauri::State<'_, Arc<JobQueue>>,
+    items: Vec<VideoInfo>,
+    out_dir: Option<String>,
+    profile: Option<String>,
+    force: Option<bool>,
+    allow_incomplete: Option<bool>,
+) -> Result<Vec<String>, String> {
+    check_complete(&items, allow_incomplete)?;
+    let parts: Vec<VideoInfo> = convert::check_mergeable(&items)
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .cloned()
+        .collect();
+    let out_dir = prepare_batch(&app, &parts, out_dir, profile.as_deref(), force)?;
+    let item = ConvertItem::merged(parts);
+    run_batch(&app, Arc::clone(queue.inner()), vec![item], out_dir, profile).await
+}
+
+/// 加入队列后立即返回任务 ID；与 `convert` 相同先做空间预检，这批任务全部结束后执行整批范围的完成后操作
+#[tauri::command]
+fn enqueue(
+    app: tauri::AppHandle,
+    queue: tauri::State<'_, Arc<JobQueue>>,
+    items: Vec<ConvertItem>,
+    out_dir: Option<String>,
+    profile: Option<String>,
+    force: Option<bool>,
+    allow_incomplete: Option<bool>,
+) -> Result<Vec<JobId>, String> {
+    let videos = check_items(&items, allow_incomplete)?;
+    let out_dir = prepare_batch(&app, &videos, out_dir, profile.as_deref(), force)?;
+    let ids = queue.enqueue(items, &out_dir, profile.as_deref());
+    let (queue, batch) = (Arc::clone(queue.inner()), ids.clone());
+    std::thread::spawn(move || {
+        let jobs = queue.wait_finished(&batch);
+        let success_paths: Vec<String> = jobs.iter().flat_map(Job::outputs).collect();
+        run_batch_action(&app, &out_dir, &success_paths);
+    });
+    Ok(ids)
+}
+
+#[tauri::command]
+fn remove(queue: tauri::State<'_, Arc<JobQueue>>, id: JobId) -> Result<(), String> {
+    queue.remove(id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn pause_item(queue: tauri::State<'_, Arc<JobQueue>>, id: JobId) -> Result<(), String> {
+    queue.pause_item(id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn resume_item(queue: tauri::State<'_, Arc<JobQueue>>, id: JobId) -> Result<(), String> {
+    queue.resume_item(id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn reorder(queue: tauri::State<'_, Arc<JobQueue>>, id: JobId, to_index: usize) -> Result<(), String> {
+    queue.reorder(id, to_index).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn cancel_item(queue: tauri::State<'_, Arc<JobQueue>>, id: JobId) -> Result<(), String> {
+    queue.cancel_item(id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn list_jobs(queue: tauri::State<'_, Arc<JobQueue>>) -> Vec<Job> {
+    queue.list_jobs()
+}
+
+#[tauri::command]
+fn clear_finished(queue: tauri::State<'_, Arc<JobQueue>>) -> usize {
+    queue.clear_finished()
+}
+
+/// 外部工具体检：路径、版本与封装能力
+#[tauri::command]
+fn tool_status() -> Vec<toolchain::ToolInfo> {
+    toolchain::doctor(&config_or_default())
+}
+
+#[tauri::command]
+fn get_config() -> Result<AppConfig, String> {
+    load_config().map_err(|e| e.to_string())
+}
+
+/// 保存配置，返回不妨碍保存但需提示的问题（如工具路径不存在）
+#[tauri::command]
+fn set_config(config: AppConfig) -> Result<Vec<String>, String> {
+    save_config(&config).map_err(|e| e.to_string())?;
+    Ok(config.warnings())
+}
+
+/// 读取配置时的警告：迁移旧版配置时丢弃的取值、不存在的工具路径等
+#[tauri::command]
+fn config_warnings() -> Result<Vec<String>, String> {
+    load_layered(&[]).map(|l| l.warnings).map_err(|e| e.to_string())
+}
+
+/// 校验配置（可为界面中尚未保存的设置）并预览各后端将执行的命令行
+#[tauri::command]
+fn preview_commands(config: Option<AppConfig>) -> Result<Vec<convert::CommandPreview>, String> {
+    let config = match config {
+        Some(c) => c,
+        None => effective_config().map_err(|e| e.to_string())?,
+    };
+    config.validate().map_err(|e| e.to_string())?;
+    let container = config.profile(None).map(|p| p.container).unwrap_or_default();
+    Ok(ConvertTools::from_config(&config).preview_all(container))
+}
+
+/// 全部转换方案，首项为由顶层配置生成的默认方案（若未自定义）
+#[tauri::command]
+fn list_profiles() -> Result<Vec<Profile>, String> {
+    let config = load_config().map_err(|e| e.to_string())?;
+    let mut profiles = config.profiles.clone();
+    if !profiles.iter().any(|p| p.name == config::DEFAULT_PROFILE) {
+        profiles.insert(0, config.profile(None).map_err(|e| e.to_string())?);
+    }
+    Ok(profiles)
+}
+
+#[tauri::command]
+fn export_profile(name: String, path: String) -> Result<(), String> {
+    let profile = load_config()
+        .and_then(|c| c.profile(Some(&name)))
+        .map_err(|e| e.to_string())?;
+    config::export_profile(&profile, Path::new(&path)).map_err(|e| e.to_string())
+}
+
+/// 导入方案文件并保存到配置，同名方案会被替换
+#[tauri::command]
+fn import_profile(path: String) -> Result<Profile, String> {
+    let profile = config::import_profile(Path::new(&path)).map_err(|e| e.to_string())?;
+    let mut config = load_config().map_err(|e| e.to_string())?;
+    config.upsert_profile(profile.clone());
+    save_config(&config).map_err(|e| e.to_string())?;
+    Ok(profile)
 }
 
 #[tauri::command]
@@ -208,34 +933,24 @@ fn default_output_dir() -> Option<String> {
 #[tauri::command]
 fn report_test_result(_success: bool, _message: String) {}
 
-/// 在资源管理器中打开文件夹（macOS: open，Windows: explorer）
+/// 在资源管理器中打开文件夹（macOS: open，Windows: explorer，Linux: xdg-open）
 #[tauri::command]
 fn open_folder(path: String) -> Result<(), String> {
-    let status = {
-        #[cfg(target_os = "macos")]
-        {
-            std::process::Command::new("open").arg(&path).spawn()
-        }
-        #[cfg(target_os = "windows")]
-        {
-            std::process::Command::new("explorer").arg(&path).spawn()
-        }
-        #[cfg(not(any(target_os = "macos", target_os = "windows")))]
-        {
-            let _ = path;
-            return Err("当前平台暂不支持打开文件夹".to_string());
-        }
-    };
-    status.map_err(|e| format!("无法打开文件夹: {}", e))?;
-    Ok(())
+    hooks::open_path(Path::new(&path)).map_err(|e| format!("无法打开文件夹: {}", e))
+}
+
+/// 在文件管理器中显示并选中文件
+#[tauri::command]
+fn reveal_file(path: String) -> Result<(), String> {
+    hooks::reveal_path(Path::new(&path)).map_err(|e| format!("无法显示文件: {}", e))
 }
 
 /// 选择缓存目录对话框的默认路径
 #[tauri::command]
 fn default_cache_dialog_path() -> Option<String> {
-    let home = dirs::home_dir()?;
     #[cfg(target_os = "macos")]
     {
+        let home = dirs::home_dir()?;
         let bilibili = home.join("Movies/bilibili");
         if bilibili.exists() {
             return bilibili.to_str().map(String::from);
@@ -262,6 +977,12 @@ fn default_cache_dialog_path() -> Option<String> {
             }
         }
     }
+    #[cfg(target_os = "linux")]
+    {
+        if let Some(p) = cache::scanner::default_cache_paths().into_iter().next() {
+            return p.to_str().map(String::from);
+        }
+    }
     dirs::document_dir().and_then(|p| p.to_str().map(String::from))
 }
 
@@ -273,17 +994,51 @@ pub fn run(_test_convert: bool) {
         .plugin(tauri_plugin_shell::init())
         .invoke_handler(tauri::generate_handler![
             scan_cache,
+            scan_changes,
+            export_diagnostics,
+            export_catalog,
+            filter_videos,
+            duplicate_groups,
+            get_thumbnail,
             convert,
+            preflight,
+            cleanup_sources,
             cancel_convert,
+            merge_parts,
+            enqueue,
+            remove,
+            pause_item,
+            resume_item,
+            reorder,
+            cancel_item,
+            list_jobs,
+            clear_finished,
+            tool_status,
+            preview_commands,
+            list_profiles,
+            export_profile,
+            import_profile,
             get_config,
             set_config,
+            config_warnings,
             default_cache_paths,
             default_output_dir,
             default_cache_dialog_path,
             open_folder,
+            reveal_file,
             report_test_result,
         ])
         .setup(move |app| {
+            let handle = app.handle().clone();
+            let runner_handle = handle.clone();
+            let queue = JobQueue::start(
+                move |job, position| run_job(&runner_handle, job, position),
+                move |change| on_queue_change(&handle, change),
+            );
+            app.manage(queue);
+            app.manage(ScannedRoots::default());
+            app.manage(ScannedVideos::default());
+
             let test = std::env::args().any(|a| a == "--test-convert")
                 || std::env::var("TAURI_TEST_CONVERT").as_deref() == Ok("1");
             if test {
@@ -0,0 +1,517 @@
+//! 筛选表达式：词法、语法分析与求值
+//!
+//! 语法：`比较 (and|or 比较)*`，支持 `not` 与括号；比较为 `字段 运算符 值`，
+//! 运算符 `=` `!=` `>` `>=` `<` `<=` `~`（包含）`!~`（不包含），含空格或特殊字符的值用双引号
+
+use crate::cache::{quality_rank, DownloadState, VideoInfo};
+use chrono::NaiveDate;
+use std::cmp::Ordering;
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum FilterError {
+    #[error("第 {pos} 个字符处: {msg}")]
+    Syntax { pos: usize, msg: String },
+    #[error("未知字段: {0}（可用: title、uploader、bvid、dir、quality、page、pages、cid、size、cached_at、state）")]
+    UnknownField(String),
+    #[error("字段 {field} 不支持运算符 {op}")]
+    UnsupportedOp { field: &'static str, op: &'static str },
+    #[error("字段 {field} 的值无效: {value}（应为{expected}）")]
+    BadValue { field: &'static str, value: String, expected: &'static str },
+}
+
+/// 可筛选与排序的字段
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Field {
+    Title,
+    Uploader,
+    Bvid,
+    Dir,
+    Quality,
+    Page,
+    Pages,
+    Cid,
+    Size,
+    CachedAt,
+    State,
+}
+
+impl Field {
+    pub(super) fn parse(name: &str) -> Result<Self, FilterError> {
+        Ok(match name.to_ascii_lowercase().as_str() {
+            "title" => Self::Title,
+            "uploader" | "up" | "owner" => Self::Uploader,
+            "bvid" => Self::Bvid,
+            "dir" | "path" => Self::Dir,
+            "quality" => Self::Quality,
+            "page" | "p" => Self::Page,
+            "pages" | "total_pages" => Self::Pages,
+            "cid" => Self::Cid,
+            "size" => Self::Size,
+            "cached_at" | "date" => Self::CachedAt,
+            "state" | "download" => Self::State,
+            _ => return Err(FilterError::UnknownField(name.to_string())),
+        })
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Title => "title",
+            Self::Uploader => "uploader",
+            Self::Bvid => "bvid",
+            Self::Dir => "dir",
+            Self::Quality => "quality",
+            Self::Page => "page",
+            Self::Pages => "pages",
+            Self::Cid => "cid",
+            Self::Size => "size",
+            Self::CachedAt => "cached_at",
+            Self::State => "state",
+        }
+    }
+
+    /// 取视频中该字段的值；缺失时为空
+    pub(super) fn value(self, v: &VideoInfo) -> Option<Value> {
+        let text = |s: &str| Value::Text(s.to_lowercase());
+        Some(match self {
+            Self::Title => text(&v.title),
+            Self::Uploader => text(v.uploader.as_deref()?),
+            Self::Bvid => text(v.bvid.as_deref()?),
+            Self::Dir => text(&v.cache_dir.display().to_string()),
+            Self::Quality => Value::Quality(quality_rank(&v.quality), v.quality.to_lowercase()),
+            Self::Page => Value::Num(v.page as u64),
+            Self::Pages => Value::Num(v.total_pages as u64),
+            Self::Cid => Value::Num(v.cid?),
+            Self::Size => Value::Num(v.size_bytes),
+            Self::CachedAt => Value::Date(v.cached_at.clone()?),
+            Self::State => Value::State(match v.download {
+                DownloadState::Complete => StateValue::Complete,
+                DownloadState::Downloading(_) => StateValue::Downloading,
+                DownloadState::Truncated(_) => StateValue::Truncated,
+            }),
+        })
+    }
+
+    /// 按字段类型解析表达式中的值
+    fn literal(self, raw: &str) -> Result<Value, FilterError> {
+        let bad = |expected| FilterError::BadValue { field: self.name(), value: raw.to_string(), expected };
+        Ok(match self {
+            Self::Title | Self::Uploader | Self::Bvid | Self::Dir => Value::Text(raw.to_lowercase()),
+            Self::Quality => Value::Quality(quality_rank(raw), raw.to_lowercase()),
+            Self::Page | Self::Pages | Self::Cid => Value::Num(raw.parse().map_err(|_| bad("整数"))?),
+            Self::Size => Value::Num(parse_size(raw).ok_or_else(|| bad("大小，如 500MB、2GB"))?),
+            Self::CachedAt => {
+                NaiveDate::parse_from_str(raw, "%Y-%m-%d").map_err(|_| bad("日期，如 2025-01-01"))?;
+                Value::Date(raw.to_string())
+            }
+            Self::State => Value::State(match raw.to_ascii_lowercase().as_str() {
+                "complete" => StateValue::Complete,
+                "downloading" => StateValue::Downloading,
+                "truncated" => StateValue::Truncated,
+                "incomplete" => StateValue::Incomplete,
+                _ => return Err(bad(" complete、downloading、truncated 或 incomplete")),
+            }),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(super) enum StateValue {
+    Complete,
+    Downloading,
+    Truncated,
+    /// 仅用于筛选：未完成（下载中或不完整）
+    Incomplete,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) enum Value {
+    /// 小写文本
+    Text(String),
+    Num(u64),
+    /// 清晰度排序值与小写标签
+    Quality(u32, String),
+    /// YYYY-MM-DD，按字符串比较即按日期比较
+    Date(String),
+    State(StateValue),
+}
+
+impl Value {
+    /// 同类型值的排序
+    pub(super) fn compare(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Self::Text(a), Self::Text(b)) | (Self::Date(a), Self::Date(b)) => a.cmp(b),
+            (Self::Num(a), Self::Num(b)) => a.cmp(b),
+            (Self::Quality(a, _), Self::Quality(b, _)) => a.cmp(b),
+            (Self::State(a), Self::State(b)) => a.cmp(b),
+            _ => Ordering::Equal,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Eq,
+    Ne,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+    Contains,
+    NotContains,
+}
+
+impl Op {
+    fn symbol(self) -> &'static str {
+        match self {
+            Self::Eq => "=",
+            Self::Ne => "!=",
+            Self::Gt => ">",
+            Self::Ge => ">=",
+            Self::Lt => "<",
+            Self::Le => "<=",
+            Self::Contains => "~",
+            Self::NotContains => "!~",
+        }
+    }
+
+    fn supported_by(self, field: Field) -> bool {
+        match field {
+            Field::Title | Field::Uploader | Field::Bvid | Field::Dir => {
+                matches!(self, Self::Eq | Self::Ne | Self::Contains | Self::NotContains)
+            }
+            Field::Quality => true,
+            Field::State => matches!(self, Self::Eq | Self::Ne),
+            _ => !matches!(self, Self::Contains | Self::NotContains),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Expr {
+    Compare(Field, Op, Value),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+}
+
+impl Expr {
+    fn eval(&self, v: &VideoInfo) -> bool {
+        match self {
+            Self::And(a, b) => a.eval(v) && b.eval(v),
+            Self::Or(a, b) => a.eval(v) || b.eval(v),
+            Self::Not(e) => !e.eval(v),
+            Self::Compare(field, op, expected) => match field.value(v) {
+                Some(actual) => compare(&actual, *op, expected),
+                // 缺失的字段只满足「不等于」与「不包含」
+                None => matches!(op, Op::Ne | Op::NotContains),
+            },
+        }
+    }
+}
+
+fn compare(actual: &Value, op: Op, expected: &Value) -> bool {
+    match (actual, expected) {
+        (Value::Text(a), Value::Text(b)) | (Value::Quality(_, a), Value::Quality(_, b))
+            if matches!(op, Op::Contains | Op::NotContains) =>
+        {
+            a.contains(b.as_str()) == (op == Op::Contains)
+        }
+        (Value::State(a), Value::State(StateValue::Incomplete)) => (*a != StateValue::Complete) == (op == Op::Eq),
+        _ => {
+            let ord = actual.compare(expected);
+            match op {
+                Op::Eq => ord == Ordering::Equal,
+                Op::Ne => ord != Ordering::Equal,
+                Op::Gt => ord == Ordering::Greater,
+                Op::Ge => ord != Ordering::Less,
+                Op::Lt => ord == Ordering::Less,
+                Op::Le => ord != Ordering::Greater,
+                Op::Contains | Op::NotContains => false,
+            }
+        }
+    }
+}
+
+/// 大小：数字加可选单位 B / K(B) / M(B) / G(B) / T(B)，按 1024 换算
+pub fn parse_size(raw: &str) -> Option<u64> {
+    let split = raw.find(|c: char| !(c.is_ascii_digit() || c == '.')).unwrap_or(raw.len());
+    let (num, unit) = raw.split_at(split);
+    let num: f64 = num.parse().ok()?;
+    let power = match unit.to_ascii_uppercase().as_str() {
+        "" | "B" => 0,
+        "K" | "KB" => 1,
+        "M" | "MB" => 2,
+        "G" | "GB" => 3,
+        "T" | "TB" => 4,
+        _ => return None,
+    };
+    Some((num * 1024f64.powi(power)) as u64)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    /// 未加引号的词：字段名、关键字或值
+    Word(String),
+    /// 双引号字符串
+    Str(String),
+    Op(Op),
+    LParen,
+    RParen,
+}
+
+/// 切分为 (起始字符位置, 记号)
+fn tokenize(input: &str) -> Result<Vec<(usize, Token)>, FilterError> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    let is_special = |c: char| c.is_whitespace() || "()\"<>=!~".contains(c);
+    while i < chars.len() {
+        let c = chars[i];
+        let start = i;
+        let next = chars.get(i + 1).copied();
+        let token = match c {
+            c if c.is_whitespace() => {
+                i += 1;
+                continue;
+            }
+            '(' => Token::LParen,
+            ')' => Token::RParen,
+            '"' => {
+                let mut s = String::new();
+                i += 1;
+                loop {
+                    match chars.get(i) {
+                        None => return Err(FilterError::Syntax { pos: start + 1, msg: "引号未闭合".into() }),
+                        Some('"') => break,
+                        Some('\\') if chars.get(i + 1).is_some() => {
+                            s.push(chars[i + 1]);
+                            i += 2;
+                        }
+                        Some(&ch) => {
+                            s.push(ch);
+                            i += 1;
+                        }
+                    }
+                }
+                Token::Str(s)
+            }
+            '=' if next == Some('=') => {
+                i += 1;
+                Token::Op(Op::Eq)
+            }
+            '=' => Token::Op(Op::Eq),
+            '!' if next == Some('=') => {
+                i += 1;
+                Token::Op(Op::Ne)
+            }
+            '!' if next == Some('~') => {
+                i += 1;
+                Token::Op(Op::NotContains)
+            }
+            '>' | '<' if next == Some('=') => {
+                i += 1;
+                Token::Op(if c == '>' { Op::Ge } else { Op::Le })
+            }
+            '>' => Token::Op(Op::Gt),
+            '<' => Token::Op(Op::Lt),
+            '~' => Token::Op(Op::Contains),
+            '!' => return Err(FilterError::Syntax { pos: start + 1, msg: "「!」后应为 = 或 ~，取反请用 not".into() }),
+            _ => {
+                while i < chars.len() && !is_special(chars[i]) {
+                    i += 1;
+                }
+                tokens.push((start, Token::Word(chars[start..i].iter().collect())));
+                continue;
+            }
+        };
+        tokens.push((start, token));
+        i += 1;
+    }
+    Ok(tokens)
+}
+
+/// not 与括号的最大嵌套层数，避免过深的表达式耗尽栈空间
+const MAX_NESTING: usize = 64;
+
+/// 递归下降分析：or 优先级最低，其次 and，再次 not
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    pos: usize,
+    /// 输入长度，用于报告末尾处的错误
+    len: usize,
+    /// 当前 not 与括号的嵌套层数
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(_, t)| t)
+    }
+
+    fn here(&self) -> usize {
+        self.tokens.get(self.pos).map(|(p, _)| *p).unwrap_or(self.len) + 1
+    }
+
+    fn error(&self, msg: impl Into<String>) -> FilterError {
+        FilterError::Syntax { pos: self.here(), msg: msg.into() }
+    }
+
+    fn keyword(&self, kw: &str) -> bool {
+        matches!(self.peek(), Some(Token::Word(w)) if w.eq_ignore_ascii_case(kw))
+    }
+
+    fn or(&mut self) -> Result<Expr, FilterError> {
+        let mut left = self.and()?;
+        while self.keyword("or") {
+            self.pos += 1;
+            left = Expr::Or(Box::new(left), Box::new(self.and()?));
+        }
+        Ok(left)
+    }
+
+    fn and(&mut self) -> Result<Expr, FilterError> {
+        let mut left = self.unary()?;
+        while self.keyword("and") {
+            self.pos += 1;
+            left = Expr::And(Box::new(left), Box::new(self.unary()?));
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Expr, FilterError> {
+        let nested = self.keyword("not") || self.peek() == Some(&Token::LParen);
+        if !nested {
+            return self.comparison();
+        }
+        if self.depth >= MAX_NESTING {
+            return Err(self.error(format!("not 与括号嵌套超过 {} 层", MAX_NESTING)));
+        }
+        self.depth += 1;
+        let expr = if self.keyword("not") {
+            self.pos += 1;
+            self.unary().map(|e| Expr::Not(Box::new(e)))
+        } else {
+            self.pos += 1;
+            self.or().and_then(|inner| {
+                if self.peek() != Some(&Token::RParen) {
+                    return Err(self.error("缺少右括号"));
+                }
+                self.pos += 1;
+                Ok(inner)
+            })
+        };
+        self.depth -= 1;
+        expr
+    }
+
+    fn comparison(&mut self) -> Result<Expr, FilterError> {
+        let field = match self.peek() {
+            Some(Token::Word(w)) => Field::parse(w)?,
+            _ => return Err(self.error("应为字段名")),
+        };
+        self.pos += 1;
+        let op = match self.peek() {
+            Some(Token::Op(op)) => *op,
+            _ => return Err(self.error("应为运算符，如 =、>=、~")),
+        };
+        if !op.supported_by(field) {
+            return Err(FilterError::UnsupportedOp { field: field.name(), op: op.symbol() });
+        }
+        self.pos += 1;
+        let raw = match self.peek() {
+            Some(Token::Word(w)) | Some(Token::Str(w)) => w.clone(),
+            _ => return Err(self.error("应为值")),
+        };
+        self.pos += 1;
+        Ok(Expr::Compare(field, op, field.literal(&raw)?))
+    }
+}
+
+/// 解析后的筛选表达式
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Filter(Expr);
+
+impl Filter {
+    pub fn parse(input: &str) -> Result<Self, FilterError> {
+        let mut parser = Parser { tokens: tokenize(input)?, pos: 0, len: input.chars().count(), depth: 0 };
+        if parser.tokens.is_empty() {
+            return Err(parser.error("表达式为空"));
+        }
+        let expr = parser.or()?;
+        if parser.pos < parser.tokens.len() {
+            return Err(parser.error("多余的内容，条件之间应以 and / or 连接"));
+        }
+        Ok(Self(expr))
+    }
+
+    /// 单个条件 `字段 = 值`（`exact`）或 `字段 ~ 值`，值不经表达式语法解析，供界面的搜索框等使用
+    pub fn field(name: &str, value: &str, exact: bool) -> Result<Self, FilterError> {
+        let field = Field::parse(name)?;
+        let op = if exact { Op::Eq } else { Op::Contains };
+        if !op.supported_by(field) {
+            return Err(FilterError::UnsupportedOp { field: field.name(), op: op.symbol() });
+        }
+        Ok(Self(Expr::Compare(field, op, field.literal(value)?)))
+    }
+
+    /// 同时满足两个条件
+    pub fn and(self, other: Self) -> Self {
+        Self(Expr::And(Box::new(self.0), Box::new(other.0)))
+    }
+
+    pub fn matches(&self, video: &VideoInfo) -> bool {
+        self.0.eval(video)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn video(title: &str, quality: &str, uploader: Option<&str>, cached_at: &str, size: u64) -> VideoInfo {
+        VideoInfo {
+            quality: quality.into(),
+            size_bytes: size,
+            cached_at: Some(cached_at.into()),
+            uploader: uploader.map(String::from),
+            ..VideoInfo::fixture(Path::new("/c"), title)
+        }
+    }
+
+    #[test]
+    fn test_parse_and_match() {
+        const GB: u64 = 1024 * 1024 * 1024;
+        let f = Filter::parse(r#"quality>=1080P and uploader~"某UP" and cached_at>2025-01-01 and size<2GB"#).unwrap();
+        assert!(f.matches(&video("a", "1080P60", Some("某UP主"), "2025-03-01", GB)));
+        assert!(!f.matches(&video("a", "720P", Some("某UP主"), "2025-03-01", GB)));
+        assert!(!f.matches(&video("a", "1080P", None, "2025-03-01", GB)));
+        assert!(!f.matches(&video("a", "1080P", Some("某UP主"), "2025-01-01", GB)));
+        assert!(!f.matches(&video("a", "1080P", Some("某UP主"), "2025-03-01", 3 * GB)));
+
+        let f = Filter::parse("not (title~OP or title~ED) and state=complete").unwrap();
+        assert!(f.matches(&video("正片", "1080P", None, "2025-01-01", 1)));
+        assert!(!f.matches(&video("op 合集", "1080P", None, "2025-01-01", 1)));
+        assert!(Filter::parse("uploader!=x").unwrap().matches(&video("a", "1080P", None, "2025-01-01", 1)));
+
+        assert_eq!(Filter::parse("size>1.5G"), Filter::parse("size>1536MB"));
+        assert!(matches!(Filter::parse("bogus=1"), Err(FilterError::UnknownField(_))));
+        assert!(matches!(Filter::parse("size~1"), Err(FilterError::UnsupportedOp { .. })));
+        assert!(matches!(Filter::parse("cached_at>yesterday"), Err(FilterError::BadValue { .. })));
+        assert_eq!(
+            Filter::parse("title=a title=b"),
+            Err(FilterError::Syntax { pos: 9, msg: "多余的内容，条件之间应以 and / or 连接".into() })
+        );
+        assert!(matches!(Filter::parse(r#"title="a"#), Err(FilterError::Syntax { pos: 7, .. })));
+
+        let deep = format!("{}title=a{}", "not (".repeat(40), ")".repeat(40));
+        assert!(matches!(Filter::parse(&deep), Err(FilterError::Syntax { pos: 161, .. })));
+        assert!(Filter::parse(&format!("{}title=a", "not ".repeat(64))).is_ok());
+
+        let f = Filter::field("title", "OP \"1\"", false).unwrap().and(Filter::field("quality", "1080P", true).unwrap());
+        assert!(f.matches(&video("OP \"1\" 合集", "1080P", None, "2025-01-01", 1)));
+        assert!(!f.matches(&video("OP \"1\" 合集", "720P", None, "2025-01-01", 1)));
+        assert!(matches!(Filter::field("size", "1", false), Err(FilterError::UnsupportedOp { .. })));
+    }
+}
@@ -0,0 +1,5 @@
+mod expr;
+mod select;
+
+pub use expr::{parse_size, Filter, FilterError};
+pub use select::{select, Page, SortKey};
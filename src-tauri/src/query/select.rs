@@ -0,0 +1,113 @@
+//! 按表达式筛选、排序并分页，界面与 CLI 无需一次取回全部扫描结果
+
+use super::expr::{Field, Filter, FilterError};
+use crate::cache::VideoInfo;
+use serde::Serialize;
+use std::cmp::Ordering;
+
+/// 排序键：字段与方向，如 `size desc`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SortKey {
+    field: Field,
+    descending: bool,
+}
+
+impl SortKey {
+    /// 逗号分隔的多个排序键，如 `quality desc, cached_at`；默认升序，出错时报告所在字符位置
+    pub fn parse_list(input: &str) -> Result<Vec<Self>, FilterError> {
+        // 各部分都是 input 的子串，按字节偏移换算为从 1 开始的字符位置
+        let pos = |word: &str| input[..word.as_ptr() as usize - input.as_ptr() as usize].chars().count() + 1;
+        input
+            .split(',')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(|part| {
+                let mut words = part.split_whitespace();
+                let field = Field::parse(words.next().unwrap_or_default())?;
+                let descending = match words.next() {
+                    None => false,
+                    Some(word) if word.eq_ignore_ascii_case("asc") => false,
+                    Some(word) if word.eq_ignore_ascii_case("desc") => true,
+                    Some(other) => {
+                        return Err(FilterError::Syntax {
+                            pos: pos(other),
+                            msg: format!("排序方向应为 asc 或 desc: {}", other),
+                        })
+                    }
+                };
+                match words.next() {
+                    Some(extra) => {
+                        Err(FilterError::Syntax { pos: pos(extra), msg: format!("排序键多余的内容: {}", extra) })
+                    }
+                    None => Ok(Self { field, descending }),
+                }
+            })
+            .collect()
+    }
+
+    /// 缺失值（如没有缓存日期）总排在最后
+    fn compare(&self, a: &VideoInfo, b: &VideoInfo) -> Ordering {
+        match (self.field.value(a), self.field.value(b)) {
+            (Some(x), Some(y)) => {
+                let ord = x.compare(&y);
+                if self.descending {
+                    ord.reverse()
+                } else {
+                    ord
+                }
+            }
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        }
+    }
+}
+
+/// 一页结果
+#[derive(Debug, Clone, Serialize)]
+pub struct Page {
+    /// 筛选后的总数
+    pub total: usize,
+    /// 筛选后全部视频的总大小
+    pub total_bytes: u64,
+    pub offset: usize,
+    pub items: Vec<VideoInfo>,
+}
+
+/// 筛选、排序后取 `offset` 起的 `limit` 条；`limit` 为空时取到末尾
+pub fn select(
+    videos: &[VideoInfo],
+    filter: Option<&Filter>,
+    sort: &[SortKey],
+    offset: usize,
+    limit: Option<usize>,
+) -> Page {
+    let mut matched: Vec<&VideoInfo> = videos.iter().filter(|v| filter.is_none_or(|f| f.matches(v))).collect();
+    // 稳定排序，未指定排序键时保持扫描顺序
+    matched.sort_by(|a, b| sort.iter().map(|k| k.compare(a, b)).find(|o| o.is_ne()).unwrap_or(Ordering::Equal));
+    let total_bytes = matched.iter().map(|v| v.size_bytes).sum();
+    let items = matched
+        .iter()
+        .skip(offset)
+        .take(limit.unwrap_or(usize::MAX))
+        .map(|v| (*v).clone())
+        .collect();
+    Page { total: matched.len(), total_bytes, offset, items }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sort_key_errors() {
+        assert_eq!(SortKey::parse_list(" size desc, title").unwrap().len(), 2);
+        assert_eq!(
+            SortKey::parse_list("size desc, 标题 down").unwrap_err(),
+            FilterError::UnknownField("标题".into())
+        );
+        assert!(matches!(SortKey::parse_list("size desc, title down"), Err(FilterError::Syntax { pos: 18, .. })));
+        assert!(matches!(SortKey::parse_list("页, title asc x"), Err(FilterError::UnknownField(_))));
+        assert!(matches!(SortKey::parse_list("page, title asc x"), Err(FilterError::Syntax { pos: 17, .. })));
+    }
+}
@@ -0,0 +1,131 @@
+//! 转换任务定义
+
+use crate::cache::VideoInfo;
+use crate::convert::{merged_video, TranscodeSettings, TrimRange};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+
+/// 任务 ID，在进程生命周期内单调递增、不复用
+pub type JobId = u64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    /// 等待执行
+    Queued,
+    /// 已暂停，恢复前不会被调度
+    Paused,
+    /// 正在转换
+    Running,
+    /// 转换成功
+    Succeeded,
+    /// 冲突策略跳过
+    Skipped,
+    /// 转换失败
+    Failed,
+    /// 已取消
+    Cancelled,
+}
+
+impl JobStatus {
+    /// 是否已结束（不会再被调度）
+    pub fn is_finished(self) -> bool {
+        matches!(
+            self,
+            JobStatus::Succeeded | JobStatus::Skipped | JobStatus::Failed | JobStatus::Cancelled
+        )
+    }
+}
+
+/// 转换请求中的一项：缓存视频及可选的截取范围与转码参数，JSON 中 `trim`、`transcode` 与视频字段平级
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConvertItem {
+    #[serde(flatten)]
+    pub video: VideoInfo,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trim: Option<TrimRange>,
+    /// 指定时转码，优先于方案的转码设置
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transcode: Option<TranscodeSettings>,
+    /// 多 P 合并时的全部分 P，由 `merge_parts` 命令设置，不从请求中读取
+    #[serde(skip)]
+    pub merge: Vec<VideoInfo>,
+}
+
+impl ConvertItem {
+    /// 多 P 合并：`parts` 须已按分 P 排序，视频信息取合并后的整个视频
+    pub fn merged(parts: Vec<VideoInfo>) -> Self {
+        let refs: Vec<&VideoInfo> = parts.iter().collect();
+        Self { video: merged_video(&refs), trim: None, transcode: None, merge: parts }
+    }
+}
+
+impl From<VideoInfo> for ConvertItem {
+    fn from(video: VideoInfo) -> Self {
+        Self { video, trim: None, transcode: None, merge: Vec::new() }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Job {
+    pub id: JobId,
+    pub video: VideoInfo,
+    /// 请求的截取范围，为空时转换整个视频
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trim: Option<TrimRange>,
+    /// 单项指定的转码参数
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transcode: Option<TranscodeSettings>,
+    /// 多 P 合并任务的全部分 P（按分 P 排序），此时 `video` 为合并后的视频信息
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub merge: Vec<VideoInfo>,
+    /// 输出目录
+    pub out_dir: String,
+    /// 转换方案名，为空时使用默认方案
+    pub profile: Option<String>,
+    pub status: JobStatus,
+    /// 成功时的输出文件路径；分段时为第一段
+    pub output_path: Option<String>,
+    /// 输出超过分段上限时切成的各段
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub parts: Vec<String>,
+    /// 失败原因
+    pub error: Option<String>,
+    /// 单任务取消标记
+    #[serde(skip)]
+    pub(crate) cancel: Arc<AtomicBool>,
+}
+
+impl Job {
+    pub(crate) fn new(id: JobId, item: ConvertItem, out_dir: String, profile: Option<String>) -> Self {
+        Self {
+            id,
+            video: item.video,
+            trim: item.trim,
+            transcode: item.transcode,
+            merge: item.merge,
+            out_dir,
+            profile,
+            status: JobStatus::Queued,
+            output_path: None,
+            parts: Vec::new(),
+            error: None,
+            cancel: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// 成功时的全部输出文件：分段时为各段，否则为输出路径
+    pub fn outputs(&self) -> Vec<String> {
+        if self.parts.is_empty() {
+            self.output_path.iter().cloned().collect()
+        } else {
+            self.parts.clone()
+        }
+    }
+
+    /// 供转换函数轮询的取消标记
+    pub fn cancel_flag(&self) -> &AtomicBool {
+        &self.cancel
+    }
+}
@@ -0,0 +1,435 @@
+//! 任务队列：后台单线程按顺序执行，支持增删、暂停、排序与单项取消
+
+use super::job::{ConvertItem, Job, JobId, JobStatus};
+use crate::convert::ConvertError;
+use std::path::PathBuf;
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use thiserror::Error;
+
+/// 执行单个任务；参数为任务及其在当前队列中的位置 (序号, 总数)，返回输出文件（分段时为多个）
+type Runner = Box<dyn Fn(&Job, (usize, usize)) -> Result<Vec<PathBuf>, ConvertError> + Send + Sync>;
+/// 队列变化回调
+type Listener = Box<dyn Fn(QueueChange<'_>) + Send + Sync>;
+
+/// 队列变化，供界面同步任务列表
+#[derive(Debug, Clone, Copy)]
+pub enum QueueChange<'a> {
+    /// 任务加入或状态变化
+    Job(&'a Job),
+    /// 任务已从队列中移除
+    Removed(&'a [JobId]),
+    /// 执行顺序变化，参数为全部任务按新顺序排列的 ID
+    Reordered(&'a [JobId]),
+}
+
+#[derive(Debug, Error)]
+pub enum QueueError {
+    #[error("任务不存在: {0}")]
+    NotFound(JobId),
+    #[error("任务 {0} 当前状态不允许该操作: {1:?}")]
+    InvalidState(JobId, JobStatus),
+}
+
+#[derive(Default)]
+struct QueueState {
+    jobs: Vec<Job>,
+    next_id: JobId,
+}
+
+impl QueueState {
+    fn find_mut(&mut self, id: JobId) -> Result<&mut Job, QueueError> {
+        self.jobs
+            .iter_mut()
+            .find(|j| j.id == id)
+            .ok_or(QueueError::NotFound(id))
+    }
+}
+
+pub struct JobQueue {
+    state: Mutex<QueueState>,
+    changed: Condvar,
+    runner: Runner,
+    listener: Listener,
+}
+
+impl JobQueue {
+    /// 创建队列并启动后台工作线程
+    pub fn start(
+        runner: impl Fn(&Job, (usize, usize)) -> Result<Vec<PathBuf>, ConvertError> + Send + Sync + 'static,
+        listener: impl Fn(QueueChange<'_>) + Send + Sync + 'static,
+    ) -> Arc<Self> {
+        let queue = Arc::new(Self {
+            state: Mutex::new(QueueState { jobs: Vec::new(), next_id: 1 }),
+            changed: Condvar::new(),
+            runner: Box::new(runner),
+            listener: Box::new(listener),
+        });
+        let worker = Arc::clone(&queue);
+        std::thread::spawn(move || worker.worker_loop());
+        queue
+    }
+
+    fn lock(&self) -> MutexGuard<'_, QueueState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// 修改状态后通知工作线程与等待者，并在锁外回调 listener
+    fn notify(&self, changed: Vec<Job>) {
+        self.changed.notify_all();
+        for job in &changed {
+            (self.listener)(QueueChange::Job(job));
+        }
+    }
+
+    /// 任务被移除或重新排序后通知工作线程与等待者，并在锁外回调 listener
+    fn notify_change(&self, change: QueueChange<'_>) {
+        self.changed.notify_all();
+        (self.listener)(change);
+    }
+
+    /// 追加任务到队尾，返回新任务 ID；可直接传入视频（不截取）
+    pub fn enqueue(
+        &self,
+        items: impl IntoIterator<Item = impl Into<ConvertItem>>,
+        out_dir: &str,
+        profile: Option<&str>,
+    ) -> Vec<JobId> {
+        let added: Vec<Job> = {
+            let mut st = self.lock();
+            let start = st.jobs.len();
+            for item in items {
+                let id = st.next_id;
+                st.next_id += 1;
+                st.jobs.push(Job::new(id, item.into(), out_dir.to_string(), profile.map(String::from)));
+            }
+            st.jobs[start..].to_vec()
+        };
+        let ids = added.iter().map(|j| j.id).collect();
+        self.notify(added);
+        ids
+    }
+
+    /// 移除未在运行的任务；运行中的任务需先取消
+    pub fn remove(&self, id: JobId) -> Result<(), QueueError> {
+        let mut st = self.lock();
+        let job = st.find_mut(id)?;
+        if job.status == JobStatus::Running {
+            return Err(QueueError::InvalidState(id, job.status));
+        }
+        st.jobs.retain(|j| j.id != id);
+        drop(st);
+        self.notify_change(QueueChange::Removed(&[id]));
+        Ok(())
+    }
+
+    /// 暂停排队中的任务
+    pub fn pause_item(&self, id: JobId) -> Result<(), QueueError> {
+        self.transition(id, JobStatus::Queued, JobStatus::Paused)
+    }
+
+    /// 恢复已暂停的任务
+    pub fn resume_item(&self, id: JobId) -> Result<(), QueueError> {
+        self.transition(id, JobStatus::Paused, JobStatus::Queued)
+    }
+
+    fn transition(&self, id: JobId, from: JobStatus, to: JobStatus) -> Result<(), QueueError> {
+        let job = {
+            let mut st = self.lock();
+            let job = st.find_mut(id)?;
+            if job.status != from {
+                return Err(QueueError::InvalidState(id, job.status));
+            }
+            job.status = to;
+            job.clone()
+        };
+        self.notify(vec![job]);
+        Ok(())
+    }
+
+    /// 将任务移动到指定位置（超出范围则移到队尾）
+    pub fn reorder(&self, id: JobId, to_index: usize) -> Result<(), QueueError> {
+        let mut st = self.lock();
+        let from = st
+            .jobs
+            .iter()
+            .position(|j| j.id == id)
+            .ok_or(QueueError::NotFound(id))?;
+        let job = st.jobs.remove(from);
+        let to = to_index.min(st.jobs.len());
+        st.jobs.insert(to, job);
+        let order: Vec<JobId> = st.jobs.iter().map(|j| j.id).collect();
+        drop(st);
+        self.notify_change(QueueChange::Reordered(&order));
+        Ok(())
+    }
+
+    /// 取消单个任务：未开始的直接标记取消，运行中的置取消标记由转换函数响应
+    pub fn cancel_item(&self, id: JobId) -> Result<(), QueueError> {
+        let job = {
+            let mut st = self.lock();
+            let job = st.find_mut(id)?;
+            match job.status {
+                JobStatus::Queued | JobStatus::Paused => {
+                    job.status = JobStatus::Cancelled;
+                    job.clone()
+                }
+                JobStatus::Running => {
+                    job.cancel.store(true, Ordering::Relaxed);
+                    return Ok(());
+                }
+                status => return Err(QueueError::InvalidState(id, status)),
+            }
+        };
+        self.notify(vec![job]);
+        Ok(())
+    }
+
+    /// 取消全部未结束的任务
+    pub fn cancel_all(&self) {
+        let changed: Vec<Job> = {
+            let mut st = self.lock();
+            st.jobs
+                .iter_mut()
+                .filter_map(|job| match job.status {
+                    JobStatus::Queued | JobStatus::Paused => {
+                        job.status = JobStatus::Cancelled;
+                        Some(job.clone())
+                    }
+                    JobStatus::Running => {
+                        job.cancel.store(true, Ordering::Relaxed);
+                        None
+                    }
+                    _ => None,
+                })
+                .collect()
+        };
+        self.notify(changed);
+    }
+
+    /// 当前全部任务快照（按执行顺序）
+    pub fn list_jobs(&self) -> Vec<Job> {
+        self.lock().jobs.clone()
+    }
+
+    /// 清除已结束的任务，返回清除数量
+    pub fn clear_finished(&self) -> usize {
+        let removed: Vec<JobId> = {
+            let mut st = self.lock();
+            let removed = st.jobs.iter().filter(|j| j.status.is_finished()).map(|j| j.id).collect();
+            st.jobs.retain(|j| !j.status.is_finished());
+            removed
+        };
+        if !removed.is_empty() {
+            self.notify_change(QueueChange::Removed(&removed));
+        }
+        removed.len()
+    }
+
+    /// 阻塞直到指定任务全部结束、被移除或处于暂停，返回其当前状态；
+    /// 暂停的任务恢复前不会执行，不再等待
+    pub fn wait_for(&self, ids: &[JobId]) -> Vec<Job> {
+        self.wait_until(ids, true)
+    }
+
+    /// 阻塞直到指定任务全部结束或被移除，暂停的任务恢复并执行完才返回
+    pub fn wait_finished(&self, ids: &[JobId]) -> Vec<Job> {
+        self.wait_until(ids, false)
+    }
+
+    fn wait_until(&self, ids: &[JobId], stop_on_pause: bool) -> Vec<Job> {
+        let mut st = self.lock();
+        loop {
+            let pending = st.jobs.iter().any(|j| {
+                ids.contains(&j.id) && !j.status.is_finished() && !(stop_on_pause && j.status == JobStatus::Paused)
+            });
+            if !pending {
+                return st.jobs.iter().filter(|j| ids.contains(&j.id)).cloned().collect();
+            }
+            st = self.changed.wait(st).unwrap_or_else(|e| e.into_inner());
+        }
+    }
+
+    fn next_job(&self) -> (Job, (usize, usize)) {
+        let mut st = self.lock();
+        loop {
+            let total = st.jobs.len();
+            let index = st.jobs.iter().filter(|j| j.status.is_finished()).count() + 1;
+            if let Some(job) = st.jobs.iter_mut().find(|j| j.status == JobStatus::Queued) {
+                job.status = JobStatus::Running;
+                return (job.clone(), (index, total));
+            }
+            st = self.changed.wait(st).unwrap_or_else(|e| e.into_inner());
+        }
+    }
+
+    fn worker_loop(&self) {
+        loop {
+            let (job, position) = self.next_job();
+            self.notify(vec![job.clone()]);
+
+            let result = if job.cancel.load(Ordering::Relaxed) {
+                Err(ConvertError::Cancelled)
+            } else {
+                (self.runner)(&job, position)
+            };
+
+            let finished = {
+                let mut st = self.lock();
+                // 运行期间任务不可移除，此处必然存在
+                let Ok(entry) = st.find_mut(job.id) else { continue };
+                match result {
+                    Ok(paths) => {
+                        let paths: Vec<String> = paths.iter().map(|p| p.display().to_string()).collect();
+                        entry.status = JobStatus::Succeeded;
+                        entry.output_path = paths.first().cloned();
+                        if paths.len() > 1 {
+                            entry.parts = paths;
+                        }
+                    }
+                    Err(ConvertError::Skipped(_)) => entry.status = JobStatus::Skipped,
+                    Err(ConvertError::Cancelled) => entry.status = JobStatus::Cancelled,
+                    Err(e) => {
+                        entry.status = JobStatus::Failed;
+                        entry.error = Some(e.to_string());
+                    }
+                }
+                entry.clone()
+            };
+            self.notify(vec![finished]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::VideoInfo;
+    use std::path::Path;
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    fn video(title: &str) -> VideoInfo {
+        VideoInfo::fixture(Path::new("/tmp"), title)
+    }
+
+    /// 运行器在收到放行信号前阻塞，便于在任务运行期间操作队列
+    fn gated_queue() -> (Arc<JobQueue>, mpsc::Sender<()>, Arc<Mutex<Vec<String>>>) {
+        let (tx, rx) = mpsc::channel::<()>();
+        let rx = Mutex::new(rx);
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let seen = Arc::clone(&order);
+        let queue = JobQueue::start(
+            move |job, _| {
+                rx.lock().unwrap().recv_timeout(Duration::from_secs(5)).ok();
+                seen.lock().unwrap().push(job.video.title.clone());
+                if job.cancel_flag().load(Ordering::Relaxed) {
+                    return Err(ConvertError::Cancelled);
+                }
+                match job.video.title.as_str() {
+                    "c" => Ok(vec![PathBuf::from("/out/c-part1.mp4"), PathBuf::from("/out/c-part2.mp4")]),
+                    title => Ok(vec![PathBuf::from(format!("/out/{}.mp4", title))]),
+                }
+            },
+            |_| {},
+        );
+        (queue, tx, order)
+    }
+
+    fn wait_running(queue: &JobQueue, id: JobId) {
+        for _ in 0..500 {
+            if queue.list_jobs().iter().any(|j| j.id == id && j.status == JobStatus::Running) {
+                return;
+            }
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        panic!("任务 {} 未开始运行", id);
+    }
+
+    #[test]
+    fn test_pause_and_reorder() {
+        let (queue, tx, order) = gated_queue();
+        let ids = queue.enqueue(vec![video("a"), video("b"), video("c"), video("d")], "/out", None);
+        wait_running(&queue, ids[0]);
+        queue.pause_item(ids[1]).unwrap();
+        queue.reorder(ids[3], 1).unwrap();
+        for _ in 0..3 {
+            tx.send(()).unwrap();
+        }
+        let jobs = queue.wait_for(&[ids[0], ids[2], ids[3]]);
+        assert!(jobs.iter().all(|j| j.status == JobStatus::Succeeded));
+        assert_eq!(jobs[2].outputs(), vec!["/out/c-part1.mp4", "/out/c-part2.mp4"]);
+        assert_eq!(jobs[0].outputs(), vec!["/out/a.mp4"]);
+        assert_eq!(*order.lock().unwrap(), vec!["a", "d", "c"]);
+
+        assert_eq!(queue.list_jobs().iter().find(|j| j.id == ids[1]).unwrap().status, JobStatus::Paused);
+        queue.resume_item(ids[1]).unwrap();
+        tx.send(()).unwrap();
+        let jobs = queue.wait_for(&[ids[1]]);
+        assert_eq!(jobs[0].output_path.as_deref(), Some("/out/b.mp4"));
+        assert_eq!(queue.clear_finished(), 4);
+        assert!(queue.list_jobs().is_empty());
+    }
+
+    #[test]
+    fn test_cancel_item_and_remove() {
+        let (queue, tx, _) = gated_queue();
+        let ids = queue.enqueue(vec![video("a"), video("b"), video("c")], "/out", None);
+        wait_running(&queue, ids[0]);
+        assert!(matches!(queue.remove(ids[0]), Err(QueueError::InvalidState(_, JobStatus::Running))));
+        queue.cancel_item(ids[0]).unwrap();
+        queue.cancel_item(ids[1]).unwrap();
+        queue.remove(ids[2]).unwrap();
+        tx.send(()).unwrap();
+        let jobs = queue.wait_for(&ids);
+        assert_eq!(jobs.len(), 2);
+        assert!(jobs.iter().all(|j| j.status == JobStatus::Cancelled));
+        assert!(matches!(queue.cancel_item(ids[1]), Err(QueueError::InvalidState(..))));
+        assert!(matches!(queue.pause_item(99), Err(QueueError::NotFound(99))));
+    }
+
+    #[test]
+    fn test_wait_paused_and_listener() {
+        let (tx, rx) = mpsc::channel::<()>();
+        let rx = Mutex::new(rx);
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let log = Arc::clone(&seen);
+        let queue = JobQueue::start(
+            move |job, _| {
+                rx.lock().unwrap().recv_timeout(Duration::from_secs(5)).ok();
+                Ok(vec![PathBuf::from(format!("/out/{}.mp4", job.video.title))])
+            },
+            move |change| match change {
+                QueueChange::Removed(ids) => log.lock().unwrap().push(format!("removed {:?}", ids)),
+                QueueChange::Reordered(ids) => log.lock().unwrap().push(format!("order {:?}", ids)),
+                QueueChange::Job(_) => {}
+            },
+        );
+        let ids = queue.enqueue(vec![video("a"), video("b"), video("c")], "/out", None);
+        wait_running(&queue, ids[0]);
+        queue.pause_item(ids[1]).unwrap();
+        queue.reorder(ids[2], 0).unwrap();
+        tx.send(()).unwrap();
+        tx.send(()).unwrap();
+        // 暂停的任务不会执行，等待在其余任务结束后返回
+        let jobs = queue.wait_for(&ids);
+        let statuses: Vec<JobStatus> = jobs.iter().map(|j| j.status).collect();
+        assert_eq!(statuses, vec![JobStatus::Succeeded, JobStatus::Succeeded, JobStatus::Paused]);
+        // 等待全部结束时暂停的任务仍在等待之列，移除后才返回
+        let waiter = {
+            let (queue, ids) = (Arc::clone(&queue), ids.clone());
+            std::thread::spawn(move || queue.wait_finished(&ids))
+        };
+        std::thread::sleep(Duration::from_millis(50));
+        assert!(!waiter.is_finished());
+        queue.remove(ids[1]).unwrap();
+        assert_eq!(waiter.join().unwrap().len(), 2);
+        assert_eq!(queue.clear_finished(), 2);
+        let expected = vec![
+            format!("order {:?}", [ids[2], ids[0], ids[1]]),
+            format!("removed {:?}", [ids[1]]),
+            format!("removed {:?}", [ids[2], ids[0]]),
+        ];
+        assert_eq!(*seen.lock().unwrap(), expected);
+    }
+}
@@ -0,0 +1,5 @@
+mod job;
+mod manager;
+
+pub use job::{ConvertItem, Job, JobId, JobStatus};
+pub use manager::{JobQueue, QueueChange, QueueError};
@@ -0,0 +1,5 @@
+mod render;
+mod store;
+
+pub use render::{ThumbnailKind, MAX_SHEET_SIDE};
+pub use store::{default_thumbnail_dir, image_data_uri, thumbnail, Thumbnail, ThumbnailError, ThumbnailSource};
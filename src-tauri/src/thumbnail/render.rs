@@ -0,0 +1,178 @@
+//! 由 ffmpeg 截取关键帧：单帧缩略图与带时间标注的联系表
+
+use super::ThumbnailError;
+use crate::convert::format_time;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::process::Command;
+
+/// 单帧缩略图宽度（像素），高度按比例
+const POSTER_WIDTH: u32 = 320;
+/// 联系表中每格的宽度
+const TILE_WIDTH: u32 = 240;
+/// 联系表每边的最大格数
+pub const MAX_SHEET_SIDE: u32 = 8;
+/// b 站缓存 m4s 开头的填充字节数，交给 ffmpeg 时跳过
+const M4S_PADDING: u32 = 9;
+
+/// 缩略图种类
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ThumbnailKind {
+    /// 有缓存封面时直接使用封面，否则取视频中有代表性的关键帧
+    Poster,
+    /// 总是从视频中截取关键帧
+    Frame,
+    /// `columns` × `rows` 张在全片均匀分布的关键帧，每格标注时间
+    ContactSheet { columns: u32, rows: u32 },
+}
+
+impl ThumbnailKind {
+    pub fn validate(&self) -> Result<(), ThumbnailError> {
+        match *self {
+            ThumbnailKind::ContactSheet { columns, rows }
+                if !(1..=MAX_SHEET_SIDE).contains(&columns) || !(1..=MAX_SHEET_SIDE).contains(&rows) =>
+            {
+                Err(ThumbnailError::Invalid(format!(
+                    "联系表行列数须在 1–{} 之间: {}×{}",
+                    MAX_SHEET_SIDE, columns, rows
+                )))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// 解析 CLI 写法：`poster`、`frame` 或 `4x3`（列×行）
+    pub fn parse(s: &str) -> Result<Self, ThumbnailError> {
+        let kind = match s.trim().to_ascii_lowercase().as_str() {
+            "poster" => ThumbnailKind::Poster,
+            "frame" => ThumbnailKind::Frame,
+            sheet => {
+                let invalid = || ThumbnailError::Invalid(format!("无法识别的缩略图种类: {}（poster、frame 或 4x3）", s));
+                let (columns, rows) = sheet.split_once(['x', '×']).ok_or_else(invalid)?;
+                ThumbnailKind::ContactSheet {
+                    columns: columns.parse().map_err(|_| invalid())?,
+                    rows: rows.parse().map_err(|_| invalid())?,
+                }
+            }
+        };
+        kind.validate()?;
+        Ok(kind)
+    }
+}
+
+/// 有代表性的一帧：取全片约 1/3 处之前最近的关键帧，避开片头；没有关键帧信息时直接取该时间
+pub(super) fn poster_time(keyframes: &[u64], duration_ms: u64) -> u64 {
+    snap(keyframes, duration_ms / 3)
+}
+
+/// 联系表各格的时间：把全片等分为 `count` 段，各取段中点之前最近的关键帧
+pub(super) fn sheet_times(keyframes: &[u64], duration_ms: u64, count: u32) -> Vec<u64> {
+    let count = count as u64;
+    (0..count).map(|i| snap(keyframes, duration_ms * (2 * i + 1) / (2 * count))).collect()
+}
+
+fn snap(keyframes: &[u64], target: u64) -> u64 {
+    keyframes.iter().rev().find(|&&k| k <= target).or(keyframes.first()).copied().unwrap_or(target)
+}
+
+fn seconds(ms: u64) -> String {
+    format!("{}.{:03}", ms / 1000, ms % 1000)
+}
+
+/// 一路输入：`-ss` 作为输入选项按关键帧快速定位；带填充的 m4s 跳过开头的填充字节
+fn input_args(input: &Path, padded: bool, time_ms: u64) -> Vec<String> {
+    let mut args = Vec::new();
+    if padded {
+        args.extend(["-skip_initial_bytes".to_string(), M4S_PADDING.to_string()]);
+    }
+    args.extend(["-ss".to_string(), seconds(time_ms), "-i".to_string(), input.to_string_lossy().into_owned()]);
+    args
+}
+
+/// 单帧参数：缩放到固定宽度，输出一张 JPEG
+pub(super) fn frame_args(input: &Path, padded: bool, time_ms: u64, output: &Path) -> Vec<String> {
+    let mut args = vec!["-y".to_string()];
+    args.extend(input_args(input, padded, time_ms));
+    args.extend(["-frames:v", "1", "-vf"].map(String::from));
+    args.push(format!("scale={}:-2", POSTER_WIDTH));
+    args.extend(["-q:v", "3"].map(String::from));
+    args.push(output.to_string_lossy().into_owned());
+    args
+}
+
+/// 联系表参数：每个时间点一路输入各取一帧，缩放并标注时间后拼接，再由 tile 排成 `columns` 列
+pub(super) fn sheet_args(input: &Path, padded: bool, times: &[u64], columns: u32, output: &Path) -> Vec<String> {
+    let mut args = vec!["-y".to_string()];
+    let mut graph = Vec::new();
+    for (i, &time) in times.iter().enumerate() {
+        args.extend(input_args(input, padded, time));
+        let label = drawtext_label(&format_time(time)[..8]);
+        graph.push(format!(
+            "[{i}:v]trim=end_frame=1,setpts=PTS-STARTPTS,scale={w}:-2,setsar=1,\
+             drawtext=text={label}:x=6:y=h-th-6:fontsize=18:fontcolor=white:box=1:boxcolor=black@0.6:boxborderw=4[t{i}]",
+            w = TILE_WIDTH,
+        ));
+    }
+    let inputs: String = (0..times.len()).map(|i| format!("[t{}]", i)).collect();
+    let rows = (times.len() as u32).div_ceil(columns);
+    graph.push(format!(
+        "{}concat=n={}:v=1:a=0,tile={}x{}:padding=4:margin=4",
+        inputs,
+        times.len(),
+        columns,
+        rows
+    ));
+    args.extend(["-filter_complex".to_string(), graph.join(";")]);
+    args.extend(["-frames:v", "1", "-q:v", "3"].map(String::from));
+    args.push(output.to_string_lossy().into_owned());
+    args
+}
+
+/// drawtext 的文本：`:` 先按滤镜选项转义为 `\:`，反斜杠再按滤镜图转义一次
+fn drawtext_label(text: &str) -> String {
+    text.replace(':', "\\:").replace('\\', "\\\\")
+}
+
+/// 执行 ffmpeg；失败时删除不完整的输出，错误取 stderr 的最后一行
+pub(super) fn run_ffmpeg(ffmpeg: &str, args: &[String], output: &Path) -> Result<(), ThumbnailError> {
+    let result = Command::new(ffmpeg).args(args).output().map_err(|_| ThumbnailError::FfmpegNotFound)?;
+    if result.status.success() && output.is_file() {
+        return Ok(());
+    }
+    let _ = std::fs::remove_file(output);
+    let stderr = String::from_utf8_lossy(&result.stderr);
+    let last = stderr.lines().rev().find(|l| !l.trim().is_empty()).unwrap_or("未生成图片");
+    Err(ThumbnailError::Ffmpeg(last.trim().to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_times_and_args() {
+        let keyframes: Vec<u64> = (0..10).map(|i| i * 60_000).collect();
+        assert_eq!(poster_time(&keyframes, 600_000), 180_000);
+        assert_eq!(poster_time(&[], 600_000), 200_000);
+        // 2×2：段中点 75、225、375、525 秒
+        assert_eq!(sheet_times(&keyframes, 600_000, 4), vec![60_000, 180_000, 360_000, 480_000]);
+
+        let args = frame_args(Path::new("/c/video.m4s"), true, 180_000, Path::new("/t/a.jpg"));
+        assert_eq!(args[1..7], ["-skip_initial_bytes", "9", "-ss", "180.000", "-i", "/c/video.m4s"]);
+        assert!(args.contains(&"scale=320:-2".to_string()));
+
+        let args = sheet_args(Path::new("/o/a.mp4"), false, &[60_000, 3_723_000, 7_200_000], 2, Path::new("/t/s.jpg"));
+        assert_eq!(args.iter().filter(|a| *a == "-i").count(), 3);
+        let graph = &args[args.iter().position(|a| a == "-filter_complex").unwrap() + 1];
+        assert!(graph.contains("text=01\\\\:02\\\\:03:"));
+        assert!(graph.ends_with("[t0][t1][t2]concat=n=3:v=1:a=0,tile=2x2:padding=4:margin=4"));
+
+        assert_eq!(ThumbnailKind::parse("4x3").unwrap(), ThumbnailKind::ContactSheet { columns: 4, rows: 3 });
+        assert_eq!(ThumbnailKind::parse("Poster").unwrap(), ThumbnailKind::Poster);
+        assert!(ThumbnailKind::parse("9x1").is_err());
+        assert!(ThumbnailKind::parse("big").is_err());
+        let json: ThumbnailKind = serde_json::from_str(r#"{"kind":"contact_sheet","columns":3,"rows":2}"#).unwrap();
+        assert_eq!(json, ThumbnailKind::ContactSheet { columns: 3, rows: 2 });
+    }
+}
@@ -0,0 +1,281 @@
+//! 缩略图缓存：按来源文件与种类生成，存放于配置目录的 thumbnails/，来源未变时直接复用
+
+use super::render::{frame_args, poster_time, run_ffmpeg, sheet_args, sheet_times, ThumbnailKind};
+use crate::cache::VideoInfo;
+use crate::config::config_dir;
+use crate::convert::{has_padding, probe_duration_ms, probe_keyframes};
+use crate::filemgr::find_cover;
+use crate::util::encode_base64;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+
+/// 缓存目录名，位于配置文件同目录
+const THUMBNAIL_DIR: &str = "thumbnails";
+/// 截图参数变化时递增，使旧缓存失效
+const THUMBNAIL_VERSION: u32 = 1;
+/// 缓存目录的大小上限，超出时删除最久未使用的缩略图
+const MAX_CACHE_BYTES: u64 = 256 * 1024 * 1024;
+
+/// 临时文件序号，与进程号一起区分并发请求
+static TEMP_SEQ: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Debug, Error)]
+pub enum ThumbnailError {
+    #[error("ffmpeg 未找到，生成缩略图需要 ffmpeg")]
+    FfmpegNotFound,
+    #[error("ffmpeg 截图失败: {0}")]
+    Ffmpeg(String),
+    #[error("{0}")]
+    Invalid(String),
+    #[error("视频文件不存在: {0}")]
+    NotFound(String),
+    #[error("无法确定缩略图缓存目录")]
+    NoCacheDir,
+    #[error("读写缩略图失败: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// 生成缩略图的来源
+#[derive(Debug, Clone, Copy)]
+pub enum ThumbnailSource<'a> {
+    /// 缓存视频：可使用缓存封面，截帧时读取 `video_path`
+    Cache(&'a VideoInfo),
+    /// 已转换的输出文件
+    Output(&'a Path),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Thumbnail {
+    pub path: PathBuf,
+    /// 取自缓存封面而非视频帧
+    pub from_cover: bool,
+    /// 请求内联时的 data URI，供界面直接显示
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data_uri: Option<String>,
+}
+
+impl Thumbnail {
+    /// 读取图片并附上 data URI
+    pub fn inline(mut self) -> Result<Self, ThumbnailError> {
+        self.data_uri = Some(image_data_uri(&self.path)?);
+        Ok(self)
+    }
+}
+
+/// 默认缓存目录
+pub fn default_thumbnail_dir() -> Option<PathBuf> {
+    config_dir().map(|d| d.join(THUMBNAIL_DIR))
+}
+
+/// 取得缩略图：缓存视频的 [`ThumbnailKind::Poster`] 优先使用缓存封面，缩放到与截帧相同的宽度；
+/// 其余情况由 ffmpeg 截取关键帧。结果按来源文件的路径、大小、修改时间与种类缓存在 `cache_dir`
+pub fn thumbnail(
+    source: ThumbnailSource,
+    kind: ThumbnailKind,
+    ffmpeg: &str,
+    cache_dir: &Path,
+) -> Result<Thumbnail, ThumbnailError> {
+    kind.validate()?;
+    if let (ThumbnailSource::Cache(video), ThumbnailKind::Poster) = (source, kind) {
+        if let Some(cover) = find_cover(&video.cache_dir) {
+            // 缩放失败（如没有 ffmpeg）时退回封面原图
+            let path = cached(&cover, kind, cache_dir, |temp| run_ffmpeg(ffmpeg, &frame_args(&cover, false, 0, temp), temp))
+                .unwrap_or(cover);
+            return Ok(Thumbnail { path, from_cover: true, data_uri: None });
+        }
+    }
+    let (input, duration_hint) = match source {
+        ThumbnailSource::Cache(video) => (video.video_path.as_path(), video.duration_ms),
+        ThumbnailSource::Output(path) => (path, None),
+    };
+    let path = cached(input, kind, cache_dir, |temp| {
+        let keyframes = probe_keyframes(input).ok().flatten().unwrap_or_default();
+        // 分片的 m4s 中 mvhd 往往没有时长，此时以最后一个关键帧近似
+        let duration_ms = duration_hint
+            .or_else(|| probe_duration_ms(input).ok().flatten())
+            .or(keyframes.last().copied())
+            .ok_or_else(|| ThumbnailError::Invalid(format!("无法读取视频时长与关键帧: {}", input.display())))?;
+        let padded = has_padding(input);
+        let args = match kind {
+            ThumbnailKind::ContactSheet { columns, rows } => {
+                let times = sheet_times(&keyframes, duration_ms, columns * rows);
+                sheet_args(input, padded, &times, columns, temp)
+            }
+            _ => frame_args(input, padded, poster_time(&keyframes, duration_ms), temp),
+        };
+        run_ffmpeg(ffmpeg, &args, temp)
+    })?;
+    Ok(Thumbnail { path, from_cover: false, data_uri: None })
+}
+
+/// 查找 `input` 对应的缓存图片；未命中时由 `render` 写入临时文件后改名，再按上限清理缓存目录。
+/// 命中时更新修改时间，清理时据此保留最近使用的图片
+fn cached(
+    input: &Path,
+    kind: ThumbnailKind,
+    cache_dir: &Path,
+    render: impl FnOnce(&Path) -> Result<(), ThumbnailError>,
+) -> Result<PathBuf, ThumbnailError> {
+    let key = cache_key(input, kind).ok_or_else(|| ThumbnailError::NotFound(input.display().to_string()))?;
+    let path = cache_dir.join(format!("{:016x}.jpg", key));
+    if path.is_file() {
+        let _ = std::fs::File::options().write(true).open(&path).and_then(|f| f.set_modified(SystemTime::now()));
+        return Ok(path);
+    }
+
+    std::fs::create_dir_all(cache_dir)?;
+    // 每个请求使用独有的临时文件，写完再改名，并发请求不会互相覆盖或读到不完整的图片
+    let seq = TEMP_SEQ.fetch_add(1, Ordering::Relaxed);
+    let temp = cache_dir.join(format!("{:016x}.{}-{}.partial.jpg", key, std::process::id(), seq));
+    render(&temp)?;
+    if let Err(e) = std::fs::rename(&temp, &path) {
+        let _ = std::fs::remove_file(&temp);
+        return Err(e.into());
+    }
+    evict(cache_dir, MAX_CACHE_BYTES, &path);
+    Ok(path)
+}
+
+/// 缓存目录中的图片总大小超过 `max_bytes` 时按修改时间从旧到新删除，`keep` 与未完成的临时文件除外
+fn evict(cache_dir: &Path, max_bytes: u64, keep: &Path) {
+    let Ok(entries) = std::fs::read_dir(cache_dir) else {
+        return;
+    };
+    let mut files: Vec<(SystemTime, u64, PathBuf)> = entries
+        .flatten()
+        .filter(|e| {
+            let name = e.file_name().to_string_lossy().into_owned();
+            name.ends_with(".jpg") && !name.ends_with(".partial.jpg")
+        })
+        .filter_map(|e| {
+            let meta = e.metadata().ok()?;
+            Some((meta.modified().unwrap_or(UNIX_EPOCH), meta.len(), e.path()))
+        })
+        .collect();
+    let mut total: u64 = files.iter().map(|(_, len, _)| len).sum();
+    files.sort();
+    for (_, len, path) in files {
+        if total <= max_bytes {
+            break;
+        }
+        if path != keep && std::fs::remove_file(&path).is_ok() {
+            total -= len;
+        }
+    }
+}
+
+/// 缓存键：对路径、大小、修改时间与种类做 FNV-1a，跨版本与重启保持不变；来源文件不存在时返回 `None`
+fn cache_key(input: &Path, kind: ThumbnailKind) -> Option<u64> {
+    let meta = std::fs::metadata(input).ok()?;
+    let mtime = meta.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    let kind = serde_json::to_string(&kind).ok()?;
+    let parts: [&[u8]; 5] = [
+        &THUMBNAIL_VERSION.to_le_bytes(),
+        input.as_os_str().as_encoded_bytes(),
+        &meta.len().to_le_bytes(),
+        &mtime.as_nanos().to_le_bytes(),
+        kind.as_bytes(),
+    ];
+    // 各部分后附长度，避免不同的拆分拼出相同的字节序列
+    Some(parts.iter().fold(FNV_OFFSET, |hash, part| fnv1a(fnv1a(hash, part), &(part.len() as u64).to_le_bytes())))
+}
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0100_0000_01b3;
+
+fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |h, &b| (h ^ b as u64).wrapping_mul(FNV_PRIME))
+}
+
+/// 读取图片为 data URI，MIME 类型按扩展名判断
+pub fn image_data_uri(path: &Path) -> std::io::Result<String> {
+    let mime = match path.extension().and_then(|e| e.to_str()).map(|e| e.to_ascii_lowercase()).as_deref() {
+        Some("png") => "image/png",
+        Some("webp") => "image/webp",
+        _ => "image/jpeg",
+    };
+    let data = std::fs::read(path)?;
+    Ok(format!("data:{};base64,{}", mime, encode_base64(&data)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn video(cache_dir: &Path) -> VideoInfo {
+        VideoInfo { duration_ms: Some(60_000), ..VideoInfo::fixture(cache_dir, "标题") }
+    }
+
+    #[test]
+    fn test_thumbnail_cover_cache_and_errors() {
+        let tmp = std::env::temp_dir().join("bili2mp4_thumbnail_test");
+        let _ = std::fs::remove_dir_all(&tmp);
+        let (cache, thumbs) = (tmp.join("c"), tmp.join("thumbs"));
+        std::fs::create_dir_all(&cache).unwrap();
+        std::fs::write(cache.join("video.m4s"), [0x30u8; 32]).unwrap();
+        let v = video(&cache);
+        let missing = "/nonexistent/ffmpeg";
+
+        // 没有封面时需要截帧；ffmpeg 不可用时报错且不留下临时文件
+        let err = thumbnail(ThumbnailSource::Cache(&v), ThumbnailKind::Poster, missing, &thumbs).unwrap_err();
+        assert!(matches!(err, ThumbnailError::FfmpegNotFound));
+        assert_eq!(std::fs::read_dir(&thumbs).unwrap().count(), 0);
+
+        std::fs::write(cache.join("cover.png"), b"abcd").unwrap();
+        let t = thumbnail(ThumbnailSource::Cache(&v), ThumbnailKind::Poster, missing, &thumbs).unwrap();
+        assert_eq!(t, Thumbnail { path: cache.join("cover.png"), from_cover: true, data_uri: None });
+        assert_eq!(t.inline().unwrap().data_uri.as_deref(), Some("data:image/png;base64,YWJjZA=="));
+
+        // 已缓存的截图直接复用；来源变化或种类不同时键也不同
+        let sheet = ThumbnailKind::ContactSheet { columns: 3, rows: 2 };
+        let key = cache_key(&v.video_path, sheet).unwrap();
+        std::fs::write(thumbs.join(format!("{:016x}.jpg", key)), b"jpg").unwrap();
+        let t = thumbnail(ThumbnailSource::Output(&v.video_path), sheet, missing, &thumbs).unwrap();
+        assert!(!t.from_cover && t.path.starts_with(&thumbs));
+        assert_ne!(cache_key(&v.video_path, ThumbnailKind::Frame), Some(key));
+        std::fs::write(&v.video_path, [0x30u8; 48]).unwrap();
+        assert_ne!(cache_key(&v.video_path, sheet), Some(key));
+
+        let bad = ThumbnailKind::ContactSheet { columns: 0, rows: 2 };
+        assert!(matches!(thumbnail(ThumbnailSource::Cache(&v), bad, missing, &thumbs), Err(ThumbnailError::Invalid(_))));
+        let gone = tmp.join("gone.mp4");
+        assert!(matches!(
+            thumbnail(ThumbnailSource::Output(&gone), ThumbnailKind::Frame, missing, &thumbs),
+            Err(ThumbnailError::NotFound(_))
+        ));
+
+        // 时长与关键帧都读不到时报错，不截取第 0 毫秒
+        let junk = tmp.join("junk.mp4");
+        std::fs::write(&junk, b"not a video").unwrap();
+        assert!(matches!(
+            thumbnail(ThumbnailSource::Output(&junk), ThumbnailKind::Frame, missing, &thumbs),
+            Err(ThumbnailError::Invalid(_))
+        ));
+        assert_eq!(fnv1a(FNV_OFFSET, b"a"), 0xaf63_dc4c_8601_ec8c);
+        std::fs::remove_dir_all(&tmp).ok();
+    }
+
+    #[test]
+    fn test_evict_oldest() {
+        let tmp = std::env::temp_dir().join("bili2mp4_thumbnail_evict_test");
+        let _ = std::fs::remove_dir_all(&tmp);
+        std::fs::create_dir_all(&tmp).unwrap();
+        let now = SystemTime::now();
+        for (i, name) in ["a.jpg", "b.jpg", "c.jpg", "d.1-0.partial.jpg"].iter().enumerate() {
+            let path = tmp.join(name);
+            std::fs::write(&path, [0u8; 100]).unwrap();
+            let age = std::time::Duration::from_secs(100 * (4 - i as u64));
+            std::fs::File::options().write(true).open(&path).unwrap().set_modified(now - age).unwrap();
+        }
+        // 保留刚写入的 a.jpg，从其余最旧的 b.jpg 开始删
+        evict(&tmp, 200, &tmp.join("a.jpg"));
+        let mut left: Vec<String> =
+            std::fs::read_dir(&tmp).unwrap().map(|e| e.unwrap().file_name().to_string_lossy().into_owned()).collect();
+        left.sort();
+        assert_eq!(left, ["a.jpg", "c.jpg", "d.1-0.partial.jpg"]);
+        std::fs::remove_dir_all(&tmp).ok();
+    }
+}
@@ -0,0 +1,15 @@
+mod probe;
+mod which;
+
+pub use probe::{is_bare_name, parse_version, probe, Capabilities, Tool, ToolInfo};
+pub use which::{find_in_path, is_executable};
+
+use crate::config::{resolve_ffmpeg_path, resolve_mp4box_path, AppConfig};
+
+/// 按当前配置解析并探测全部外部工具
+pub fn doctor(config: &AppConfig) -> Vec<ToolInfo> {
+    vec![
+        probe(Tool::Mp4Box, &resolve_mp4box_path(config)),
+        probe(Tool::Ffmpeg, &resolve_ffmpeg_path(config)),
+    ]
+}
@@ -0,0 +1,291 @@
+//! 外部工具探测：运行 `-version` 解析版本，按工具输出判断封装能力，结果按路径与修改时间缓存
+
+use super::which::find_in_path;
+use crate::convert::Codec;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::{Mutex, OnceLock};
+use std::time::SystemTime;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Tool {
+    #[serde(rename = "mp4box")]
+    Mp4Box,
+    Ffmpeg,
+}
+
+impl Tool {
+    pub fn label(self) -> &'static str {
+        match self {
+            Tool::Mp4Box => "MP4Box",
+            Tool::Ffmpeg => "ffmpeg",
+        }
+    }
+
+    /// 未找到时的安装提示，按当前平台给出
+    pub fn install_hint(self) -> &'static str {
+        #[cfg(target_os = "macos")]
+        {
+            match self {
+                Tool::Mp4Box => "请安装: brew install gpac",
+                Tool::Ffmpeg => "请安装: brew install ffmpeg",
+            }
+        }
+        #[cfg(target_os = "windows")]
+        {
+            match self {
+                Tool::Mp4Box => "请安装 GPAC 或在设置中指定 MP4Box 路径",
+                Tool::Ffmpeg => "请安装 ffmpeg 或在设置中指定 ffmpeg 路径",
+            }
+        }
+        #[cfg(target_os = "linux")]
+        {
+            match self {
+                Tool::Mp4Box => "请安装 gpac，如 sudo apt install gpac",
+                Tool::Ffmpeg => "请安装 ffmpeg，如 sudo apt install ffmpeg",
+            }
+        }
+        #[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "linux")))]
+        {
+            match self {
+                Tool::Mp4Box => "请安装 GPAC",
+                Tool::Ffmpeg => "请安装 ffmpeg",
+            }
+        }
+    }
+}
+
+/// 无损封装进 MP4 的能力；`None` 表示工具输出中没有相关信息，按支持处理
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct Capabilities {
+    pub hevc: Option<bool>,
+    pub av1: Option<bool>,
+    pub flac: Option<bool>,
+}
+
+impl Capabilities {
+    /// 由 `ffmpeg -codecs` 的输出判断：列表中出现该编码即可无损封装
+    fn from_ffmpeg_codecs(output: &str) -> Self {
+        // 每行形如 ` DEV.L. hevc  H.265 / HEVC ...`，前 6 个字符为能力标记
+        let names: Vec<&str> = output
+            .lines()
+            .filter_map(|l| {
+                let mut words = l.split_whitespace();
+                let flags = words.next()?;
+                (flags.len() == 6 && flags.starts_with(['D', '.'])).then(|| words.next()).flatten()
+            })
+            .collect();
+        if names.is_empty() {
+            return Self::default();
+        }
+        let has = |name: &str| Some(names.contains(&name));
+        Self { hevc: has("hevc"), av1: has("av1"), flac: has("flac") }
+    }
+
+    /// 由 `MP4Box -version` 的 `Disabled features:` 行判断：编译时关闭的编码不支持；
+    /// 没有该行的旧版本无法判断
+    fn from_gpac_features(output: &str) -> Self {
+        let Some(disabled) = output.lines().find_map(|l| l.trim().strip_prefix("Disabled features:")) else {
+            return Self::default();
+        };
+        let flags: Vec<&str> = disabled.split_whitespace().collect();
+        let has = |flag: &str| Some(!flags.contains(&flag));
+        Self { hevc: has("GPAC_DISABLE_HEVC"), av1: has("GPAC_DISABLE_AV1"), flac: None }
+    }
+
+    /// 不支持的编码（仅在确定不支持时返回）
+    pub fn unsupported<'a>(&self, codecs: &'a [Codec]) -> Vec<&'a Codec> {
+        codecs
+            .iter()
+            .filter(|c| {
+                let flag = match c {
+                    Codec::Hevc => self.hevc,
+                    Codec::Av1 => self.av1,
+                    Codec::Flac => self.flac,
+                    _ => None,
+                };
+                flag == Some(false)
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ToolInfo {
+    pub tool: Tool,
+    /// 解析后的路径（可能为裸命令名）
+    pub path: String,
+    /// 能否成功启动
+    pub runs: bool,
+    /// 版本号，如 2.2.1、6.1
+    pub version: Option<String>,
+    pub capabilities: Capabilities,
+    /// 启动失败原因
+    pub error: Option<String>,
+}
+
+impl ToolInfo {
+    /// 不可用于转换的原因；可用时返回 None
+    pub fn unavailable_reason(&self, codecs: &[Codec]) -> Option<String> {
+        if !self.runs {
+            return Some(format!(
+                "{} 无法运行: {}",
+                self.tool.label(),
+                self.error.as_deref().unwrap_or("未知错误")
+            ));
+        }
+        let unsupported = self.capabilities.unsupported(codecs);
+        if unsupported.is_empty() {
+            return None;
+        }
+        let names: Vec<&str> = unsupported.iter().map(|c| c.label()).collect();
+        Some(format!(
+            "{} {} 不支持封装 {}",
+            self.tool.label(),
+            self.version.as_deref().unwrap_or(""),
+            names.join("/")
+        ))
+    }
+}
+
+type CacheKey = (PathBuf, SystemTime);
+
+fn cache() -> &'static Mutex<HashMap<CacheKey, ToolInfo>> {
+    static CACHE: OnceLock<Mutex<HashMap<CacheKey, ToolInfo>>> = OnceLock::new();
+    CACHE.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// 缓存键：裸命令名先在 PATH 中解析为实际文件；找不到文件时不缓存
+fn cache_key(path: &str) -> Option<CacheKey> {
+    let file = if is_bare_name(path) { PathBuf::from(find_in_path(path)?) } else { PathBuf::from(path) };
+    let mtime = std::fs::metadata(&file).and_then(|m| m.modified()).ok()?;
+    Some((file, mtime))
+}
+
+/// 探测工具；同一文件未变化时直接返回缓存
+pub fn probe(tool: Tool, path: &str) -> ToolInfo {
+    let key = cache_key(path);
+    if let Some(info) = key.as_ref().and_then(|k| cache().lock().ok()?.get(k).cloned()) {
+        return info;
+    }
+    let info = probe_uncached(tool, path);
+    if let (Some(key), Ok(mut c)) = (key, cache().lock()) {
+        c.insert(key, info.clone());
+    }
+    info
+}
+
+fn probe_uncached(tool: Tool, path: &str) -> ToolInfo {
+    let output = Command::new(path)
+        .arg("-version")
+        .stdin(Stdio::null())
+        .output();
+    let (runs, version, error) = match output {
+        Ok(out) => {
+            // MP4Box 输出到 stderr，ffmpeg 输出到 stdout
+            let text = format!(
+                "{}\n{}",
+                String::from_utf8_lossy(&out.stdout),
+                String::from_utf8_lossy(&out.stderr)
+            );
+            (true, Some(text), None)
+        }
+        Err(e) => (false, None, Some(e.to_string())),
+    };
+    let capabilities = match (tool, &version) {
+        (Tool::Mp4Box, Some(text)) => Capabilities::from_gpac_features(text),
+        (Tool::Ffmpeg, Some(_)) => Capabilities::from_ffmpeg_codecs(&ffmpeg_codecs(path)),
+        _ => Capabilities::default(),
+    };
+    let version = version.and_then(|text| parse_version(tool, &text));
+    ToolInfo {
+        tool,
+        path: path.to_string(),
+        runs,
+        version,
+        capabilities,
+        error,
+    }
+}
+
+/// 从 `-version` 输出中提取版本号
+///
+/// - MP4Box：`MP4Box - GPAC version 2.2.1-rev0-gb34e3851-release-2.2`
+/// - ffmpeg：`ffmpeg version 6.1.1 Copyright ...`、`ffmpeg version n6.0 ...`
+pub fn parse_version(tool: Tool, output: &str) -> Option<String> {
+    let marker = match tool {
+        Tool::Mp4Box => "GPAC version ",
+        Tool::Ffmpeg => "ffmpeg version ",
+    };
+    let rest = output.lines().find_map(|l| l.find(marker).map(|i| &l[i + marker.len()..]))?;
+    let word = rest.split_whitespace().next()?;
+    let word = word.trim_start_matches(['n', 'v']);
+    let version: String = word
+        .chars()
+        .take_while(|c| c.is_ascii_digit() || *c == '.')
+        .collect();
+    let version = version.trim_end_matches('.');
+    (!version.is_empty() && version.contains('.')).then(|| version.to_string())
+}
+
+/// `ffmpeg -codecs` 的输出；无法运行时为空
+fn ffmpeg_codecs(path: &str) -> String {
+    Command::new(path)
+        .args(["-hide_banner", "-codecs"])
+        .stdin(Stdio::null())
+        .output()
+        .map(|out| String::from_utf8_lossy(&out.stdout).into_owned())
+        .unwrap_or_default()
+}
+
+/// 探测工具时用于判断是否为裸命令名（未在任何位置找到）
+pub fn is_bare_name(path: &str) -> bool {
+    Path::new(path).components().count() == 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_version() {
+        assert_eq!(
+            parse_version(Tool::Mp4Box, "MP4Box - GPAC version 2.2.1-rev0-gb34e3851-release-2.2\n(c) 2000-2023"),
+            Some("2.2.1".into())
+        );
+        assert_eq!(parse_version(Tool::Ffmpeg, "ffmpeg version 6.1.1 Copyright (c) 2000-2023"), Some("6.1.1".into()));
+        assert_eq!(parse_version(Tool::Ffmpeg, "ffmpeg version n5.1.2-static"), Some("5.1.2".into()));
+        assert_eq!(parse_version(Tool::Ffmpeg, "ffmpeg version N-109876-gabcdef"), None);
+        assert_eq!(parse_version(Tool::Ffmpeg, "command not found"), None);
+    }
+
+    #[test]
+    fn test_capabilities_from_output() {
+        let codecs = "Codecs:\n D..... = Decoding supported\n -------\n DEV.L. h264  H.264 / AVC\n \
+                      DEV.L. hevc  H.265 / HEVC\n DEAIL. aac   AAC (Advanced Audio Coding)\n";
+        let ffmpeg = Capabilities::from_ffmpeg_codecs(codecs);
+        assert_eq!(ffmpeg.unsupported(&[Codec::Hevc, Codec::Av1, Codec::Flac]), vec![&Codec::Av1, &Codec::Flac]);
+        assert_eq!(Capabilities::from_ffmpeg_codecs(""), Capabilities::default());
+
+        let gpac = "MP4Box - GPAC version 2.2.1\nFeatures: GPAC_CONFIG_LINUX GPAC_64_BITS\n\
+                    Disabled features: GPAC_DISABLE_3D GPAC_DISABLE_AV1\n";
+        let gpac = Capabilities::from_gpac_features(gpac);
+        assert_eq!(gpac.unsupported(&[Codec::Av1, Codec::Hevc, Codec::Flac]), vec![&Codec::Av1]);
+        let old = Capabilities::from_gpac_features("MP4Box - GPAC version 0.7.1");
+        assert!(old.unsupported(&[Codec::Av1, Codec::Flac]).is_empty());
+    }
+
+    #[test]
+    fn test_probe_missing_tool() {
+        let info = probe(Tool::Ffmpeg, "/nonexistent/bili2mp4/ffmpeg");
+        assert!(!info.runs);
+        assert!(info.unavailable_reason(&[]).unwrap().contains("无法运行"));
+        // 裸命令名按 PATH 中的实际文件缓存，找不到时不缓存
+        assert!(cache_key("bili2mp4-no-such-tool").is_none());
+        #[cfg(unix)]
+        assert_eq!(cache_key("sh").map(|k| k.0), find_in_path("sh").map(PathBuf::from));
+    }
+}
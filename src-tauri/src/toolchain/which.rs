@@ -0,0 +1,67 @@
+//! 纯 Rust 实现的 PATH 查找，替代 which / where 子进程
+
+use std::path::{Path, PathBuf};
+
+/// 在 PATH 中查找可执行文件，返回首个匹配的绝对路径
+///
+/// Windows 下名称不含扩展名时按 PATHEXT 依次尝试
+pub fn find_in_path(name: &str) -> Option<String> {
+    let path = std::env::var_os("PATH")?;
+    std::env::split_paths(&path)
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .flat_map(|dir| candidates(&dir, name))
+        .find(|p| is_executable(p))
+        .and_then(|p| p.to_str().map(String::from))
+}
+
+#[cfg(windows)]
+fn candidates(dir: &Path, name: &str) -> Vec<PathBuf> {
+    if Path::new(name).extension().is_some() {
+        return vec![dir.join(name)];
+    }
+    let exts = std::env::var("PATHEXT").unwrap_or_else(|_| ".COM;.EXE;.BAT;.CMD".to_string());
+    exts.split(';')
+        .filter(|e| !e.is_empty())
+        .map(|e| dir.join(format!("{}{}", name, e.to_ascii_lowercase())))
+        .collect()
+}
+
+#[cfg(not(windows))]
+fn candidates(dir: &Path, name: &str) -> Vec<PathBuf> {
+    vec![dir.join(name)]
+}
+
+/// 是否为可执行文件；Unix 下需有执行权限
+#[cfg(unix)]
+pub fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::metadata(path)
+        .map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+pub fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_in_path_finds_sh() {
+        let sh = find_in_path("sh").expect("PATH 中应有 sh");
+        assert!(Path::new(&sh).is_absolute());
+        assert!(find_in_path("bili2mp4-no-such-tool").is_none());
+
+        // 没有执行权限的普通文件不算
+        let tmp = std::env::temp_dir().join("bili2mp4_which_test");
+        std::fs::create_dir_all(&tmp).unwrap();
+        let plain = tmp.join("tool");
+        std::fs::write(&plain, b"#!/bin/sh\n").unwrap();
+        assert!(!is_executable(&plain));
+        assert!(is_executable(Path::new(&sh)));
+        std::fs::remove_dir_all(&tmp).ok();
+    }
+}
@@ -0,0 +1,9 @@
+//! HTML / XML 文本转义，供 HTML 清单与 NFO 共用
+
+/// 转义 `&`、`<`、`>` 与双引号，结果可用于元素内容与双引号属性值
+pub fn escape_markup(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,5 @@
+mod base64;
+mod markup;
+
+pub use base64::encode_base64;
+pub use markup::escape_markup;
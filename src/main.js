@@ -1,10 +1,23 @@
 import { invoke } from '@tauri-apps/api/core';
 import { listen } from '@tauri-apps/api/event';
-import { open } from '@tauri-apps/plugin-dialog';
+import { open, save } from '@tauri-apps/plugin-dialog';
 
 let cacheDir = null;
+// 已加载的几页视频，按后端排序；完整的扫描结果只保存在后端
 let videos = [];
+// 当前筛选条件下的视频总数
+let listTotal = 0;
+// 最近一次扫描的概要：总数、清晰度等
+let scanInfo = { total: 0, qualities: [] };
+let rejected = [];
 let converting = false;
+const PAGE_SIZE = 200;
+// 同一视频的重复缓存排序后相邻
+const LIST_SORT = 'page, bvid, cid';
+// 按缓存目录记录的截取范围 { start_ms, end_ms }，转换时随视频一起提交
+const trims = new Map();
+// cache_dir → 缩略图 data URI；生成失败时为 null，不再重试
+const thumbs = new Map();
 
 function fmtSize(bytes) {
   if (bytes < 1024) return bytes + ' B';
@@ -14,39 +27,120 @@ function fmtSize(bytes) {
 
 async function doScan(dir) {
   try {
-    const list = await invoke('scan_cache', { dir });
-    videos = list || [];
+    const report = await invoke('scan_changes', { dir });
+    scanInfo = report;
     cacheDir = dir;
-    renderList();
-    return list;
+    rejected = report.rejected || [];
+    renderRejected();
+    renderFilterOptions();
+    await loadList();
+    // 再次扫描同一目录时提示与上次的差异
+    if (!report.first_scan) {
+      const { added, removed, changed } = report;
+      if (added || removed || changed) {
+        appendLog('info', `与上次扫描相比：新增 ${added}，移除 ${removed}，变化 ${changed}`);
+      }
+    }
+    return report.total;
   } catch (e) {
     alert('扫描失败: ' + e);
-    return [];
+    return 0;
   }
 }
 
+// 无法识别的缓存：工具栏显示数量，点击在日志中列出并可导出诊断报告
+function renderRejected() {
+  const btn = document.getElementById('btn-rejected');
+  btn.style.display = rejected.length ? '' : 'none';
+  btn.textContent = `⚠ ${rejected.length} 个缓存无法识别`;
+}
+
+document.getElementById('btn-rejected').addEventListener('click', async () => {
+  const logMode = document.getElementById('log-mode');
+  if (!logMode.checked) {
+    logMode.checked = true;
+    logMode.dispatchEvent(new Event('change'));
+  }
+  for (const r of rejected) appendLog('warn', `无法识别: ${r.cache_dir}（${r.detail}）`);
+  if (!confirm(`${rejected.length} 个缓存目录无法识别，已列在日志中。\n是否导出诊断报告用于问题反馈？`)) return;
+  try {
+    const path = await save({
+      title: '导出诊断报告',
+      defaultPath: 'bili2mp4-scan-report.json',
+      filters: [{ name: 'JSON', extensions: ['json'] }]
+    });
+    if (!path) return;
+    await invoke('export_diagnostics', { dir: cacheDir, path });
+    appendLog('info', '诊断报告已导出: ' + path);
+  } catch (e) {
+    alert('导出失败: ' + e);
+  }
+});
+
+// 导出缓存清单；填写了输出目录时一并检查每个视频是否已转换
+document.getElementById('btn-catalog').addEventListener('click', async () => {
+  try {
+    const path = await save({
+      title: '导出缓存清单',
+      defaultPath: 'bili2mp4-catalog.html',
+      filters: [
+        { name: 'HTML', extensions: ['html'] },
+        { name: 'CSV', extensions: ['csv'] },
+        { name: 'JSON Lines', extensions: ['jsonl'] }
+      ]
+    });
+    if (!path) return;
+    const outDir = document.getElementById('output-path').value?.trim() || null;
+    const count = await invoke('export_catalog', { dir: cacheDir, path, outDir });
+    appendLog('info', `缓存清单已导出（${count} 个视频）: ${path}`);
+  } catch (e) {
+    alert('导出失败: ' + e);
+  }
+});
+
+/** 全选时是否勾选：重复组（由后端按配置的策略标注）中未被保留的不勾选 */
+function isDefaultSelected(v) {
+  return (!v.duplicate || v.duplicate.keep) && isComplete(v);
+}
+
+// 未下载完成的缓存默认不勾选，转换前需确认
+function isComplete(v) {
+  return !v.download || v.download.state === 'complete';
+}
+
 async function scanDefault() {
   return doScan(null);
 }
 
 const QUALITY_ORDER = ['1080P+', '1080P60', '1080P', '720P60', '720P', '480P', '360P', '240P', '未知'];
 
-function getFilteredVideos() {
-  const search = document.getElementById('search-input').value?.trim().toLowerCase() || '';
-  const qualityFilter = document.getElementById('filter-quality').value || '';
-  let list = videos;
-  if (search) {
-    list = list.filter(v => (v.title || '').toLowerCase().includes(search));
+// 由后端按筛选表达式、标题关键字与清晰度筛选并分页；append 时加载下一页，表达式有误时在输入框提示
+async function loadList(append = false) {
+  const input = document.getElementById('where-input');
+  const expr = input.value.trim();
+  const search = document.getElementById('search-input').value?.trim() || '';
+  const quality = document.getElementById('filter-quality').value || '';
+  input.classList.remove('invalid');
+  let page;
+  try {
+    const offset = append ? videos.length : 0;
+    page = await invoke('filter_videos', { expr, search, quality, sort: LIST_SORT, offset, limit: PAGE_SIZE });
+  } catch (e) {
+    input.classList.add('invalid');
+    input.title = String(e);
+    return;
   }
-  if (qualityFilter) list = list.filter(v => v.quality === qualityFilter);
-  return list;
+  input.title = expr ? `匹配 ${page.total} 个视频，共 ${fmtSize(page.total_bytes)}` : input.dataset.hint;
+  videos = append ? videos.concat(page.items) : page.items;
+  listTotal = page.total;
+  renderList(append);
 }
 
 function renderFilterOptions() {
   const qualityEl = document.getElementById('filter-quality');
   const prevQuality = qualityEl.value;
 
-  const qualities = [...new Set(videos.map(v => v.quality).filter(Boolean))];
+  const qualities = [...(scanInfo.qualities || [])];
   qualities.sort((a, b) => {
     const ia = QUALITY_ORDER.indexOf(a);
     const ib = QUALITY_ORDER.indexOf(b);
@@ -62,22 +156,22 @@ function renderFilterOptions() {
   if (qualities.includes(prevQuality)) qualityEl.value = prevQuality;
 }
 
-function renderList() {
-  const filtered = getFilteredVideos();
+// append 为 true 时保留已勾选的行
+function renderList(append = false) {
   const tbody = document.getElementById('video-tbody');
   const table = document.getElementById('video-table');
   const empty = document.getElementById('list-empty');
 
-  if (videos.length === 0) {
+  if (!scanInfo.total) {
     table.style.display = 'none';
     empty.style.display = 'flex';
     return;
   }
-  renderFilterOptions();
-  if (filtered.length === 0) {
+  if (videos.length === 0) {
     table.style.display = 'table';
     empty.style.display = 'none';
     tbody.innerHTML = '<tr><td colspan="5" class="no-results">无匹配视频</td></tr>';
+    tbody._flatList = [];
     document.getElementById('check-all').checked = false;
     document.getElementById('check-all').indeterminate = false;
     updateConvertState();
@@ -86,40 +180,175 @@ function renderList() {
   empty.style.display = 'none';
   table.style.display = 'table';
 
-  const sorted = [...filtered].sort((a, b) => (a.page || 1) - (b.page || 1));
-
-  tbody.innerHTML = sorted.map((v, i) => `
-    <tr data-idx="${i}" class="video-row">
-      <td><input type="checkbox" class="row-check" data-idx="${i}"></td>
-      <td title="${escapeHtml(v.title)}">${escapeHtml(v.title)}</td>
+  const checked = append ? new Set(checkedIndexes()) : new Set();
+  const more = videos.length < listTotal
+    ? `<tr class="more-row"><td colspan="5"><button type="button" id="btn-more">加载更多（已显示 ${videos.length} / ${listTotal}）</button></td></tr>`
+    : '';
+  tbody.innerHTML = videos.map((v, i) => `
+    <tr data-idx="${i}" class="video-row${v.duplicate ? ' dup-row' : ''}">
+      <td><input type="checkbox" class="row-check" data-idx="${i}"${checked.has(i) ? ' checked' : ''}></td>
+      <td title="${escapeHtml(v.title)}">${thumbImage(v, i)}${trimButton(v, i)}${escapeHtml(v.title)}${dupTag(v)}${downloadTag(v)}</td>
       <td>${escapeHtml(v.quality)}</td>
       <td>${fmtSize(v.size_bytes)}</td>
       <td>${v.cached_at || '-'}</td>
     </tr>
-  `).join('');
-  tbody._flatList = sorted;
+  `).join('') + more;
+  tbody._flatList = videos;
 
   document.querySelectorAll('.row-check').forEach(cb => {
     cb.addEventListener('change', () => { updateConvertState(); });
   });
-  document.getElementById('check-all').checked = false;
-  document.getElementById('check-all').indeterminate = false;
+  document.querySelectorAll('.trim-btn').forEach(btn => {
+    btn.addEventListener('click', () => editTrim(videos[parseInt(btn.dataset.idx, 10)], btn));
+  });
+  observeThumbs(videos);
+  if (!append) {
+    document.getElementById('check-all').checked = false;
+    document.getElementById('check-all').indeterminate = false;
+  }
   updateConvertState();
 }
 
+function thumbImage(v, idx) {
+  const src = thumbs.get(v.cache_dir);
+  return `<img class="thumb" data-idx="${idx}" alt=""${src ? ` src="${src}"` : ''} title="点击查看联系表">`;
+}
+
+// 行进入可视区域后才请求缩略图，逐个生成，避免同时启动多个 ffmpeg
+let thumbObserver = null;
+const thumbQueue = [];
+let thumbLoading = false;
+
+function observeThumbs(list) {
+  thumbObserver?.disconnect();
+  thumbQueue.length = 0;
+  thumbObserver = new IntersectionObserver((entries) => {
+    for (const entry of entries) {
+      if (!entry.isIntersecting) continue;
+      thumbObserver.unobserve(entry.target);
+      thumbQueue.push({ img: entry.target, video: list[parseInt(entry.target.dataset.idx, 10)] });
+    }
+    loadThumbs();
+  });
+  document.querySelectorAll('.thumb:not([src])').forEach(img => {
+    if (!thumbs.has(list[parseInt(img.dataset.idx, 10)]?.cache_dir)) thumbObserver.observe(img);
+  });
+}
+
+async function loadThumbs() {
+  if (thumbLoading) return;
+  thumbLoading = true;
+  while (thumbQueue.length) {
+    const { img, video } = thumbQueue.shift();
+    if (!video || thumbs.has(video.cache_dir)) continue;
+    try {
+      const t = await invoke('get_thumbnail', { video, kind: { kind: 'poster' }, inline: true });
+      thumbs.set(video.cache_dir, t.data_uri);
+      if (img.isConnected) img.src = t.data_uri;
+    } catch {
+      thumbs.set(video.cache_dir, null);
+    }
+  }
+  thumbLoading = false;
+}
+
+async function showContactSheet(v) {
+  const overlay = document.getElementById('sheet-overlay');
+  const img = document.getElementById('sheet-image');
+  const caption = document.getElementById('sheet-caption');
+  img.removeAttribute('src');
+  caption.textContent = `正在生成联系表: ${v.title}`;
+  overlay.style.display = 'flex';
+  try {
+    const t = await invoke('get_thumbnail', { video: v, kind: { kind: 'contact_sheet', columns: 4, rows: 4 }, inline: true });
+    img.src = t.data_uri;
+    caption.textContent = v.title;
+  } catch (e) {
+    caption.textContent = '联系表生成失败: ' + String(e);
+  }
+}
+
+document.getElementById('sheet-overlay').addEventListener('click', (e) => {
+  e.currentTarget.style.display = 'none';
+});
+
+function trimButton(v, idx) {
+  const t = trims.get(v.cache_dir);
+  return `<button type="button" class="trim-btn${t ? ' active' : ''}" data-idx="${idx}" title="按关键帧无损截取时间段">✂${t ? ' ' + trimLabel(t) : ''}</button>`;
+}
+
+// 毫秒格式化为 时:分:秒 或 分:秒
+function fmtTime(ms) {
+  const s = Math.floor(ms / 1000);
+  const pad = n => String(n).padStart(2, '0');
+  const h = Math.floor(s / 3600);
+  const rest = `${pad(Math.floor(s / 60) % 60)}:${pad(s % 60)}`;
+  return h > 0 ? `${h}:${rest}` : rest;
+}
+
+function trimLabel(t) {
+  return `${fmtTime(t.start_ms || 0)}-${t.end_ms != null ? fmtTime(t.end_ms) : '结尾'}`;
+}
+
+// 解析 [[时:]分:]秒[.毫秒]，无法识别时返回 null
+function parseTime(s) {
+  const m = /^(?:(?:(\d+):)?(\d+):)?(\d+)(?:\.(\d{1,3}))?$/.exec(s.trim());
+  if (!m) return null;
+  const [, h = 0, min = 0, sec, frac = ''] = m;
+  return ((Number(h) * 60 + Number(min)) * 60 + Number(sec)) * 1000 + Number(frac.padEnd(3, '0'));
+}
+
+function editTrim(v, btn) {
+  if (!v) return;
+  const current = trims.has(v.cache_dir) ? trimLabel(trims.get(v.cache_dir)).replace('结尾', '') : '';
+  const input = prompt('截取时间段，如 10:05-13:00 或 1:00:00-（截到结尾）；起止会对齐到关键帧，留空则转换整个视频', current);
+  if (input === null) return;
+  if (!input.trim()) {
+    trims.delete(v.cache_dir);
+  } else {
+    const [a, b = ''] = input.split('-');
+    const start = a.trim() ? parseTime(a) : 0;
+    const end = b.trim() ? parseTime(b) : undefined;
+    if (start === null || end === null || (end !== undefined && end <= start)) {
+      alert('无法识别的时间段: ' + input);
+      return;
+    }
+    trims.set(v.cache_dir, end === undefined ? { start_ms: start } : { start_ms: start, end_ms: end });
+  }
+  const t = trims.get(v.cache_dir);
+  btn.classList.toggle('active', !!t);
+  btn.textContent = '✂' + (t ? ' ' + trimLabel(t) : '');
+}
+
+function dupTag(v) {
+  const dup = v.duplicate;
+  if (!dup) return '';
+  const note = dup.keep ? '保留' : '全选时跳过';
+  return ` <span class="dup-tag" title="同一视频共 ${dup.count} 份缓存（${escapeHtml(dup.key)}）">重复·${note}</span>`;
+}
+
+function downloadTag(v) {
+  if (isComplete(v)) return '';
+  const label = v.download.state === 'downloading' ? '下载中' : '不完整';
+  return ` <span class="incomplete-tag" title="${escapeHtml(v.download.detail || '')}">${label}</span>`;
+}
+
 function escapeHtml(s) {
   const div = document.createElement('div');
   div.textContent = s;
   return div.innerHTML;
 }
 
+function checkedIndexes() {
+  return [...document.querySelectorAll('.row-check:checked')]
+    .map(cb => parseInt(cb.dataset.idx, 10))
+    .filter(n => !isNaN(n) && n >= 0);
+}
+
 function getSelectedItems() {
   const flatList = document.getElementById('video-tbody')._flatList;
   if (!flatList) return [];
-  const checked = [...document.querySelectorAll('.row-check:checked')]
-    .map(cb => parseInt(cb.dataset.idx, 10))
-    .filter(n => !isNaN(n) && n >= 0);
-  return checked.map(i => flatList[i]).filter(Boolean);
+  return checkedIndexes().map(i => flatList[i]).filter(Boolean);
 }
 
 function updateConvertState() {
@@ -128,13 +357,23 @@ function updateConvertState() {
   btn.disabled = converting; // 不再因未勾选而禁用，点击时由 doConvert 提示
   btn.textContent = n > 0 ? `开始转换 (${n})` : '开始转换';
   const checkAll = document.getElementById('check-all');
-  const total = document.querySelectorAll('.row-check').length;
-  const checked = document.querySelectorAll('.row-check:checked').length;
-  checkAll.checked = total > 0 && checked === total;
-  checkAll.indeterminate = checked > 0 && checked < total;
+  const flatList = document.getElementById('video-tbody')._flatList || [];
+  const boxes = [...document.querySelectorAll('.row-check')];
+  const checked = boxes.filter(cb => cb.checked).length;
+  // 「全选」指按重复策略应勾选的全部视频
+  const allSelected = boxes.length > 0 && boxes.every(cb => cb.checked === isDefaultSelected(flatList[cb.dataset.idx] || {}));
+  checkAll.checked = allSelected;
+  checkAll.indeterminate = checked > 0 && !allSelected;
 }
 
 document.getElementById('btn-scan').addEventListener('click', () => scanDefault());
+
+const whereInput = document.getElementById('where-input');
+whereInput.dataset.hint = whereInput.title;
+whereInput.addEventListener('keydown', async (e) => {
+  if (e.key !== 'Enter') return;
+  await loadList();
+});
 document.getElementById('btn-refresh').addEventListener('click', () => doScan(cacheDir));
 
 document.getElementById('btn-select-dir').addEventListener('click', async () => {
@@ -174,32 +413,51 @@ document.getElementById('btn-browse').addEventListener('click', async () => {
 });
 
 document.getElementById('check-all').addEventListener('change', (e) => {
+  const flatList = document.getElementById('video-tbody')._flatList || [];
   document.querySelectorAll('.row-check').forEach(cb => {
-    cb.checked = e.target.checked;
+    cb.checked = e.target.checked && isDefaultSelected(flatList[cb.dataset.idx] || {});
   });
   updateConvertState();
 });
 
 document.getElementById('video-tbody').addEventListener('click', (e) => {
+  if (e.target.id === 'btn-more') {
+    loadList(true);
+    return;
+  }
   const tr = e.target.closest('tr.video-row');
   if (!tr) return;
   if (e.target.classList.contains('row-check')) return;
+  if (e.target.classList.contains('thumb')) {
+    showContactSheet(document.getElementById('video-tbody')._flatList[parseInt(e.target.dataset.idx, 10)]);
+    return;
+  }
   const cb = tr.querySelector('.row-check');
   if (cb) { cb.checked = !cb.checked; updateConvertState(); }
 });
 
-document.getElementById('filter-quality').addEventListener('change', renderList);
+document.getElementById('filter-quality').addEventListener('change', () => loadList());
 document.getElementById('conflict-strategy').addEventListener('change', async (e) => {
   try {
     const config = await invoke('get_config');
     await invoke('set_config', { config: { ...config, conflict_strategy: e.target.value } });
-  } catch (_) {}
+  } catch (err) {
+    alert('保存设置失败: ' + String(err));
+  }
+});
+document.getElementById('container').addEventListener('change', async (e) => {
+  try {
+    const config = await invoke('get_config');
+    await invoke('set_config', { config: { ...config, container: e.target.value } });
+  } catch (err) {
+    alert('保存设置失败: ' + String(err));
+  }
 });
-document.getElementById('search-input').addEventListener('input', debounce(renderList, 200));
+document.getElementById('search-input').addEventListener('input', debounce(() => loadList(), 200));
 document.getElementById('search-input').addEventListener('keydown', (e) => {
   if (e.key === 'Escape') {
     document.getElementById('search-input').value = '';
-    renderList();
+    loadList();
   }
 });
 
@@ -239,23 +497,55 @@ async function doConvert() {
   try {
     const config = await invoke('get_config');
     const conflictStrategy = document.getElementById('conflict-strategy').value;
+    const container = document.getElementById('container').value;
     await invoke('set_config', {
-      config: { ...config, output_dir: outDir, conflict_strategy: conflictStrategy }
+      config: { ...config, output_dir: outDir, conflict_strategy: conflictStrategy, container }
     });
 
-    const paths = await invoke('convert', { items, outDir });
-    document.getElementById('progress-fill').style.width = '100%';
-    document.getElementById('progress-text').textContent = `完成，共 ${paths.length} 个文件`;
-    const cfg = await invoke('get_config');
-    if (cfg.on_complete === 'open_folder' || !cfg.on_complete) {
-      if (paths.length > 0) {
-        try {
-          await invoke('open_folder', { path: outDir });
-        } catch (e) {
-          appendLog('warn', '打开输出文件夹失败: ' + String(e));
-        }
+    // 磁盘空间预检：空间不足或余量偏低时需用户确认，确认后强制开始
+    const check = await invoke('preflight', { items, outDir });
+    if (check.status !== 'ok' && !confirm(check.message + '\n\n仍要开始转换吗？')) return;
+    const force = check.status === 'insufficient';
+
+    // 未下载完成的视频转换后会得到不完整的 MP4，需用户确认
+    const incomplete = items.filter(v => !isComplete(v));
+    if (incomplete.length) {
+      const list = incomplete.map(v => `${v.title}：${v.download.detail}`).join('\n');
+      if (!confirm(`${incomplete.length} 个视频未下载完成，转换结果将不完整：\n${list}\n\n仍要转换吗？`)) return;
+    }
+    const allowIncomplete = incomplete.length > 0;
+
+    // 合并多 P：同一 BV 号勾选了多个分 P 时合并为一个文件，其余照常转换；
+    // 合并只做无损封装，选择转码预设时不合并
+    const transcode = transcodeSettings();
+    const singles = [];
+    const groups = new Map();
+    for (const item of items) {
+      const trim = trims.get(item.cache_dir);
+      const v = { ...item, ...(trim && { trim }), ...(transcode && { transcode }) };
+      if (document.getElementById('merge-parts').checked && v.bvid && !trim && !transcode) {
+        if (!groups.has(v.bvid)) groups.set(v.bvid, []);
+        groups.get(v.bvid).push(v);
+      } else {
+        singles.push(v);
+      }
+    }
+    const paths = [];
+    for (const parts of groups.values()) {
+      if (parts.length < 2) {
+        singles.push(...parts);
+        continue;
+      }
+      try {
+        paths.push(...await invoke('merge_parts', { items: parts, outDir, force, allowIncomplete }));
+      } catch (e) {
+        if (!confirm(`合并失败: ${String(e)}\n\n改为分别转换这些分 P 吗？`)) continue;
+        singles.push(...parts);
       }
     }
+    if (singles.length) paths.push(...await invoke('convert', { items: singles, outDir, force, allowIncomplete }));
+    document.getElementById('progress-fill').style.width = '100%';
+    document.getElementById('progress-text').textContent = `完成，共 ${paths.length} 个文件`;
   } catch (e) {
     if (document.getElementById('log-mode').checked) appendLog('error', '转换失败: ' + String(e));
     alert('转换失败: ' + String(e));
@@ -268,6 +558,20 @@ async function doConvert() {
   }
 }
 
+// 界面选择的转码参数；「按方案」时为 null，由方案决定是否转码
+function transcodeSettings() {
+  const preset = document.getElementById('transcode-preset').value;
+  if (!preset) return null;
+  const crf = document.getElementById('transcode-crf').value;
+  return preset !== 'audio_aac' && crf !== '' ? { preset, crf: Number(crf) } : { preset };
+}
+
+document.getElementById('transcode-preset').addEventListener('change', (e) => {
+  // 只转音频时不重新编码视频，CRF 不适用
+  const crf = document.getElementById('transcode-crf');
+  crf.style.display = e.target.value && e.target.value !== 'audio_aac' ? 'inline-block' : 'none';
+});
+
 document.getElementById('btn-convert').addEventListener('click', () => {
   if (document.getElementById('btn-convert').disabled) return;
   doConvert();
@@ -313,12 +617,12 @@ listen('convert-progress', (e) => {
 // TAURI_TEST_CONVERT=1 模式：自动执行完整转换流程（用于 CLI 验证）
 listen('run-test-convert', async () => {
   try {
-    const list = await invoke('scan_cache', { dir: null });
-    if (!list?.length) {
+    const summary = await invoke('scan_cache', { dir: null });
+    if (!summary?.total) {
       await invoke('report_test_result', { success: false, message: '无视频可转换' });
       return;
     }
-    const items = list.slice(0, 1);
+    const { items } = await invoke('filter_videos', { limit: 1 });
     const outDir = await invoke('default_output_dir');
     const out = outDir || '/tmp/bili2mp4-test';
     const config = await invoke('get_config');
@@ -333,7 +637,11 @@ listen('run-test-convert', async () => {
 // Init
 (async () => {
   try {
-    const config = await invoke('get_config');
+    // 配置文件损坏时提示，但不阻止扫描；保存设置会失败而不会覆盖原文件
+    const config = await invoke('get_config').catch((e) => {
+      alert('配置文件无效: ' + String(e));
+      return {};
+    });
     if (config.output_dir) {
       document.getElementById('output-path').value = config.output_dir;
     } else {
@@ -342,6 +650,10 @@ listen('run-test-convert', async () => {
     }
     const cs = document.getElementById('conflict-strategy');
     if (cs && config.conflict_strategy) cs.value = config.conflict_strategy;
+    if (config.container) document.getElementById('container').value = config.container;
+    // 迁移旧版配置时丢弃的取值、不存在的工具路径等只提示，不阻止使用
+    const warnings = await invoke('config_warnings').catch(() => []);
+    for (const w of warnings) appendLog('warn', '配置: ' + w);
     await scanDefault();
     updateConvertState();
   } catch (e) {